@@ -0,0 +1,2 @@
+ALTER TABLE pages DROP COLUMN IF EXISTS content_changed_at;
+ALTER TABLE pages DROP COLUMN IF EXISTS body_hash;
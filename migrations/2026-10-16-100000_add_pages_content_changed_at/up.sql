@@ -0,0 +1,2 @@
+ALTER TABLE pages ADD COLUMN body_hash VARCHAR(64);
+ALTER TABLE pages ADD COLUMN content_changed_at BIGINT;
@@ -0,0 +1,2 @@
+ALTER TABLE queue DROP COLUMN IF EXISTS priority;
+ALTER TABLE pages DROP COLUMN IF EXISTS has_rss;
@@ -0,0 +1,2 @@
+ALTER TABLE pages ADD COLUMN has_rss BOOL NOT NULL DEFAULT false;
+ALTER TABLE queue ADD COLUMN priority INT NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+CREATE TABLE pages_analytics_history (
+    id SERIAL PRIMARY KEY,
+    page_id INT NOT NULL REFERENCES pages(id) ON DELETE CASCADE,
+    clicks_delta INT NOT NULL,
+    impressions_delta INT NOT NULL,
+    timestamp BIGINT NOT NULL
+);
+
+CREATE INDEX idx_pages_analytics_history_page ON pages_analytics_history(page_id, timestamp);
@@ -0,0 +1 @@
+ALTER TABLE pages_analytics_history DROP COLUMN IF EXISTS search_id;
@@ -0,0 +1 @@
+ALTER TABLE pages_analytics_history ADD COLUMN search_id INT REFERENCES queries(id) ON DELETE SET NULL;
@@ -0,0 +1,8 @@
+CREATE TABLE positions (
+    word_id INT NOT NULL REFERENCES words(id) ON DELETE CASCADE,
+    page_id INT NOT NULL REFERENCES pages(id) ON DELETE CASCADE,
+    position INT NOT NULL,
+    PRIMARY KEY (page_id, position)
+);
+
+CREATE INDEX idx_positions_word ON positions(word_id, page_id);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_words_word_prefix;
+DROP INDEX IF EXISTS idx_queries_query_prefix;
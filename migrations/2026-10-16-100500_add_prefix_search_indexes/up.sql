@@ -0,0 +1,3 @@
+-- The default B-tree indexes cannot be used by `LIKE 'prefix%'` with a non-C collation
+CREATE INDEX idx_words_word_prefix ON words(word varchar_pattern_ops);
+CREATE INDEX idx_queries_query_prefix ON queries(query varchar_pattern_ops);
@@ -0,0 +1,3 @@
+DROP INDEX idx_links_from_page_id;
+
+ALTER TABLE pages DROP COLUMN page_rank;
@@ -0,0 +1,4 @@
+ALTER TABLE pages ADD COLUMN page_rank DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+-- The links of a page are replaced each time it is crawled
+CREATE INDEX idx_links_from_page_id ON links(from_page_id);
@@ -0,0 +1 @@
+ALTER TABLE pages ADD COLUMN language VARCHAR(10);
@@ -0,0 +1,3 @@
+DROP INDEX idx_queries_zero_results;
+
+ALTER TABLE queries DROP COLUMN zero_results;
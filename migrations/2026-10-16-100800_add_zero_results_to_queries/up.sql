@@ -0,0 +1,4 @@
+ALTER TABLE queries ADD COLUMN zero_results BOOLEAN NOT NULL DEFAULT false;
+
+-- Used by the zero-results queries analytics
+CREATE INDEX idx_queries_zero_results ON queries(query) WHERE zero_results;
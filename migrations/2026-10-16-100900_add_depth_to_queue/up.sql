@@ -0,0 +1 @@
+ALTER TABLE queue ADD COLUMN depth INT NOT NULL DEFAULT 0;
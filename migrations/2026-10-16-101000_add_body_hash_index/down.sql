@@ -0,0 +1 @@
+DROP INDEX IF EXISTS idx_pages_body_hash;
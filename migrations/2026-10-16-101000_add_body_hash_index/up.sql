@@ -0,0 +1 @@
+CREATE INDEX idx_pages_body_hash ON pages(body_hash);
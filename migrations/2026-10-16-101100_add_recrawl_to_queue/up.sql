@@ -0,0 +1 @@
+ALTER TABLE queue ADD COLUMN recrawl BOOL NOT NULL DEFAULT false;
@@ -0,0 +1,10 @@
+CREATE TABLE crawl_errors (
+    id SERIAL PRIMARY KEY,
+    url VARCHAR(2048) NOT NULL,
+    domain VARCHAR(100) NOT NULL,
+    error_type VARCHAR(50) NOT NULL,
+    error_detail TEXT,
+    timestamp BIGINT NOT NULL
+);
+
+CREATE INDEX idx_crawl_errors_timestamp ON crawl_errors(timestamp);
@@ -0,0 +1 @@
+ALTER TABLE pages ADD COLUMN canonical_url VARCHAR(2048);
@@ -0,0 +1 @@
+ALTER TABLE pages ADD COLUMN previous_hash VARCHAR;
@@ -0,0 +1,3 @@
+CREATE TABLE visited_extra (
+    url VARCHAR(2048) PRIMARY KEY
+);
@@ -0,0 +1,15 @@
+CREATE TABLE domain_stats (
+    domain VARCHAR(100) PRIMARY KEY,
+    pages_crawled INT8 NOT NULL DEFAULT 0,
+    pages_indexed INT8 NOT NULL DEFAULT 0,
+    last_crawled INT8,
+    avg_response_time INT4 NOT NULL DEFAULT 0,
+    error_count INT4 NOT NULL DEFAULT 0
+);
+
+CREATE INDEX idx_domain_stats_pages_crawled ON domain_stats(pages_crawled);
+
+INSERT INTO domain_stats (domain, pages_crawled, pages_indexed, last_crawled, avg_response_time)
+SELECT domain, COUNT(*), COUNT(last_indexed), MAX(last_crawled), AVG(response_time)::int4
+FROM pages
+GROUP BY domain;
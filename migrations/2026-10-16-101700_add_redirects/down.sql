@@ -0,0 +1,2 @@
+ALTER TABLE queue DROP COLUMN redirect_depth;
+ALTER TABLE pages DROP COLUMN final_url;
@@ -0,0 +1,2 @@
+ALTER TABLE queue ADD COLUMN redirect_depth SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE pages ADD COLUMN final_url VARCHAR(2048);
@@ -0,0 +1,2 @@
+ALTER TABLE pages DROP COLUMN meta_author;
+ALTER TABLE pages DROP COLUMN published_at;
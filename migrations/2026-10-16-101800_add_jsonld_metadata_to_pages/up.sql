@@ -0,0 +1,2 @@
+ALTER TABLE pages ADD COLUMN meta_author VARCHAR(100);
+ALTER TABLE pages ADD COLUMN published_at INT8;
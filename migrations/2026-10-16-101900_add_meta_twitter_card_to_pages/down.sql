@@ -0,0 +1 @@
+ALTER TABLE pages DROP COLUMN meta_twitter_card;
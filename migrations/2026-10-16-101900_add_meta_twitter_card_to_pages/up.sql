@@ -0,0 +1 @@
+ALTER TABLE pages ADD COLUMN meta_twitter_card VARCHAR(30);
@@ -0,0 +1,3 @@
+ALTER TABLE pages DROP COLUMN meta_og_type;
+ALTER TABLE pages DROP COLUMN meta_og_site_name;
+ALTER TABLE pages DROP COLUMN meta_og_locale;
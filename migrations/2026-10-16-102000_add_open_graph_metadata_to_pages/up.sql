@@ -0,0 +1,3 @@
+ALTER TABLE pages ADD COLUMN meta_og_type VARCHAR(50);
+ALTER TABLE pages ADD COLUMN meta_og_site_name VARCHAR(100);
+ALTER TABLE pages ADD COLUMN meta_og_locale VARCHAR(20);
@@ -0,0 +1 @@
+ALTER TABLE pages ADD COLUMN image_alts TEXT;
@@ -0,0 +1,6 @@
+CREATE TABLE page_alternates (
+    page_id INT NOT NULL REFERENCES pages(id) ON DELETE CASCADE,
+    lang VARCHAR(10) NOT NULL,
+    url VARCHAR(2048) NOT NULL,
+    PRIMARY KEY (page_id, lang)
+);
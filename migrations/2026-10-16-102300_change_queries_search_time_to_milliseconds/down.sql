@@ -0,0 +1 @@
+ALTER TABLE queries ALTER COLUMN search_time TYPE INT4 USING LEAST(search_time * 1000000, 2147483647);
@@ -0,0 +1 @@
+ALTER TABLE queries ALTER COLUMN search_time TYPE INT8 USING GREATEST(search_time, 0) / 1000000;
@@ -0,0 +1 @@
+ALTER TABLE favicons DROP COLUMN local_path;
@@ -0,0 +1 @@
+ALTER TABLE favicons ADD COLUMN local_path VARCHAR(255);
@@ -0,0 +1,7 @@
+CREATE TABLE page_links (
+    from_page_id INT NOT NULL REFERENCES pages(id) ON DELETE CASCADE,
+    to_url VARCHAR(2048) NOT NULL,
+    PRIMARY KEY (from_page_id, to_url)
+);
+
+CREATE INDEX idx_page_links_to_url ON page_links(to_url);
@@ -0,0 +1,2 @@
+ALTER TABLE pages ADD COLUMN deleted_at INT8;
+CREATE INDEX idx_pages_deleted_at ON pages(deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE pages ADD COLUMN word_count INT NOT NULL DEFAULT 0;
+
+UPDATE pages SET word_count = COALESCE((SELECT SUM(count) FROM indexes WHERE page_id = pages.id), 0)::int4;
@@ -0,0 +1,2 @@
+ALTER TABLE pages DROP CONSTRAINT IF EXISTS pages_url_fingerprint_key;
+ALTER TABLE pages DROP COLUMN url_fingerprint;
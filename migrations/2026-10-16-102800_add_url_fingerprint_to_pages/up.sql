@@ -0,0 +1,3 @@
+ALTER TABLE pages ADD COLUMN url_fingerprint VARCHAR(32);
+
+ALTER TABLE pages ADD CONSTRAINT pages_url_fingerprint_key UNIQUE (url_fingerprint);
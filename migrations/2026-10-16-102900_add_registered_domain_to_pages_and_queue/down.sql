@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_pages_registered_domain;
+ALTER TABLE queue DROP COLUMN registered_domain;
+ALTER TABLE pages DROP COLUMN registered_domain;
@@ -0,0 +1,4 @@
+ALTER TABLE pages ADD COLUMN registered_domain VARCHAR(100);
+ALTER TABLE queue ADD COLUMN registered_domain VARCHAR(100);
+
+CREATE INDEX idx_pages_registered_domain ON pages (registered_domain);
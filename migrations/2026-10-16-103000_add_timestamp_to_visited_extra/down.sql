@@ -0,0 +1 @@
+ALTER TABLE visited_extra DROP COLUMN timestamp;
@@ -0,0 +1,3 @@
+ALTER TABLE visited_extra ADD COLUMN timestamp BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_visited_extra_timestamp ON visited_extra(timestamp);
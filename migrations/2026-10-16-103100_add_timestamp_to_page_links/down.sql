@@ -0,0 +1 @@
+ALTER TABLE page_links DROP COLUMN timestamp;
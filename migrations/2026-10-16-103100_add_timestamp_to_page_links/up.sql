@@ -0,0 +1,3 @@
+ALTER TABLE page_links ADD COLUMN timestamp BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_page_links_timestamp ON page_links(timestamp);
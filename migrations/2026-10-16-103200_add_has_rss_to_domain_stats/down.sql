@@ -0,0 +1 @@
+ALTER TABLE domain_stats DROP COLUMN has_rss;
@@ -0,0 +1,4 @@
+ALTER TABLE domain_stats ADD COLUMN has_rss BOOLEAN NOT NULL DEFAULT false;
+
+UPDATE domain_stats SET has_rss = true
+WHERE domain IN (SELECT domain FROM pages WHERE has_rss);
@@ -0,0 +1,44 @@
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+
+/// Check that an API key matches the configured `API_KEY`
+pub fn is_valid_api_key(api_key: &str, expected: Option<&str>) -> bool {
+    if let Some(expected) = expected {
+        api_key == expected
+    } else {
+        eprintln!("[API] API_KEY is not set");
+        false
+    }
+}
+
+/// Get the token of an `Authorization: Bearer` header, used to send the API key
+pub fn get_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_api_key() {
+        assert!(is_valid_api_key("secret", Some("secret")));
+        assert!(!is_valid_api_key("wrong", Some("secret")));
+        assert!(!is_valid_api_key("", None));
+    }
+
+    #[test]
+    fn test_get_bearer_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(get_bearer_token(&headers), None);
+
+        headers.insert(AUTHORIZATION, "metrics key".parse().unwrap());
+        assert_eq!(get_bearer_token(&headers), None);
+
+        headers.insert(AUTHORIZATION, "Bearer metrics key".parse().unwrap());
+        assert_eq!(get_bearer_token(&headers), Some("metrics key"));
+    }
+}
@@ -1,8 +1,154 @@
-use database::DbPool;
-use std::sync::Arc;
+use crate::{
+    middleware::rate_limit::{RateLimiter, DEFAULT_RATE_LIMIT_BURST, DEFAULT_RATE_LIMIT_RPM},
+    routes::base::{SearchCache, SUGGEST_RATE_LIMIT},
+};
+use database::{DbConn, DbPool};
+use lru::LruCache;
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use utils::{crawler_metrics::CrawlerMetrics, indexer_state::IndexerState};
+
+pub const DEFAULT_SEARCH_CACHE_SIZE: usize = 1000;
+
+/// Parameters of the Okapi BM25 ranking
+pub struct Bm25Params {
+    /// Term frequency saturation
+    pub k1: f64,
+    /// Document length normalization
+    pub b: f64,
+}
+
+impl Default for Bm25Params {
+    fn default() -> Self {
+        Self { k1: 1.5, b: 0.75 }
+    }
+}
 
 pub struct Environment {
+    /// The primary database, used by the handlers which write
     pub db_pool: DbPool,
+    /// A read replica of the primary database, set from `REPLICA_DATABASE_URL`.
+    /// The read-only handlers use it when set, so the replication lag delays their results.
+    pub read_pool: Option<DbPool>,
+    /// Last time a VACUUM was started from `POST /api/admin/vacuum`
+    pub last_vacuum: Mutex<Option<Instant>>,
+    /// Number of requests received since the monitor last saved it
+    pub request_count: Arc<AtomicU64>,
+    /// Used by the `bm25` search ranking
+    pub bm25: Bm25Params,
+    /// Rate limits of all the requests
+    pub rate_limiter: RateLimiter,
+    /// Rate limits of `GET /api/suggest`
+    pub suggest_rate_limiter: RateLimiter,
+    /// Responses of the recent searches, by query and search parameters
+    pub search_cache: Arc<Mutex<SearchCache>>,
+    /// Must match the indexer setting, the search looks up the stem of the words
+    pub stemming: bool,
+    /// When the API started, for `GET /api/health`
+    pub uptime_start: Instant,
+    /// Live state of the crawler, when it runs in the same process
+    pub crawler_metrics: Option<Arc<CrawlerMetrics>>,
+    /// Pause flag of the crawler, when it runs in the same process
+    pub crawler_paused: Option<Arc<AtomicBool>>,
+    /// Live state and trigger of the indexer, when it runs in the same process
+    pub indexer_state: Option<Arc<IndexerState>>,
+    /// Exchanged for a token on `POST /api/token`, and used by the metrics scrapers
+    pub api_key: Option<String>,
+    /// Signs the tokens of the protected routes
+    pub jwt_secret: Option<String>,
+}
+
+impl Environment {
+    pub fn new(db_pool: DbPool, request_count: Arc<AtomicU64>) -> Self {
+        Self {
+            db_pool,
+            read_pool: None,
+            last_vacuum: Mutex::new(None),
+            request_count,
+            bm25: Bm25Params::default(),
+            rate_limiter: RateLimiter::default(),
+            suggest_rate_limiter: RateLimiter::new(SUGGEST_RATE_LIMIT, SUGGEST_RATE_LIMIT),
+            search_cache: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(DEFAULT_SEARCH_CACHE_SIZE).unwrap(),
+            ))),
+            stemming: true,
+            uptime_start: Instant::now(),
+            crawler_metrics: None,
+            crawler_paused: None,
+            indexer_state: None,
+            api_key: None,
+            jwt_secret: None,
+        }
+    }
+
+    /// Replace the search cache with an empty one of `size` entries
+    pub fn set_search_cache_size(&mut self, size: NonZeroUsize) {
+        self.search_cache = Arc::new(Mutex::new(LruCache::new(size)));
+    }
+
+    /// Replace the rate limiter of all the requests, `None` keeps the default value
+    pub fn set_rate_limit(&mut self, rpm: Option<u32>, burst: Option<u32>) {
+        self.rate_limiter = RateLimiter::new(
+            rpm.unwrap_or(DEFAULT_RATE_LIMIT_RPM),
+            burst.unwrap_or(DEFAULT_RATE_LIMIT_BURST),
+        );
+    }
+
+    /// A connection to the primary database
+    pub fn get_conn(&self) -> DbConn {
+        self.db_pool.get().unwrap()
+    }
+
+    /// A connection to the read replica, or to the primary database without replica
+    pub fn get_read_conn(&self) -> DbConn {
+        self.get_read_pool().get().unwrap()
+    }
+
+    fn get_read_pool(&self) -> &DbPool {
+        self.read_pool.as_ref().unwrap_or(&self.db_pool)
+    }
+
+    /// Environment with a pool that never connects, for tests that do not use the database
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        use diesel::{pg::PgConnection, r2d2::ConnectionManager};
+
+        let manager = ConnectionManager::<PgConnection>::new("postgres://localhost/epsilon_tests");
+        let db_pool = DbPool::builder().build_unchecked(manager);
+
+        Environment::new(db_pool, Arc::new(AtomicU64::new(0)))
+    }
 }
 
 pub type ApiState = Arc<Environment>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::{pg::PgConnection, r2d2::ConnectionManager};
+
+    #[test]
+    fn test_read_pool() {
+        let mut environment = Environment::for_tests();
+        assert_eq!(
+            std::ptr::eq(environment.get_read_pool(), &environment.db_pool),
+            true
+        );
+
+        let manager = ConnectionManager::<PgConnection>::new("postgres://replica/epsilon_tests");
+        environment.read_pool = Some(DbPool::builder().build_unchecked(manager));
+        assert_eq!(
+            std::ptr::eq(
+                environment.get_read_pool(),
+                environment.read_pool.as_ref().unwrap()
+            ),
+            true
+        );
+    }
+}
@@ -1,38 +1,82 @@
-use crate::environment::Environment;
-use axum::Router;
+use crate::{
+    environment::Environment,
+    middleware::{
+        auth::authenticate,
+        rate_limit::{rate_limit, RATE_LIMIT_CLEANUP_INTERVAL},
+        request_count::count_requests,
+    },
+};
+use axum::{middleware::from_fn_with_state, Router};
 use routes::{
-    analytics::create_analytics_router, base::create_base_router,
-    statistics::create_statistics_router, votes::create_votes_router,
+    admin::create_admin_router, analytics::create_analytics_router, base::create_base_router,
+    crawl::create_crawl_router, domain::create_domain_router, health::create_health_router,
+    index::create_index_router, metrics::create_metrics_router,
+    statistics::create_statistics_router, votes::create_votes_router, words::create_words_router,
 };
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Instant;
 use utoipa::OpenApi;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_swagger_ui::{Config, SwaggerUi};
 
+mod auth;
 pub mod environment;
+mod middleware;
 mod routes;
+pub mod warming;
 
 #[derive(OpenApi)]
 #[openapi()]
 struct ApiDoc;
 
-pub async fn build_api(env: Arc<Environment>, port: u16) {
+/// Serve the API until the `shutdown` future completes, the pending requests are finished first
+pub async fn build_api(
+    env: Arc<Environment>,
+    port: u16,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
     let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
         .nest("/api", create_base_router())
         .nest("/api/statistics", create_statistics_router())
         .nest("/api/analytics", create_analytics_router())
+        .nest("/api/index", create_index_router())
+        .nest("/api", create_metrics_router())
+        .nest("/api", create_health_router())
         .nest("/api/votes", create_votes_router())
-        .with_state(env)
+        .nest("/api/admin", create_admin_router())
+        .nest("/api/domain", create_domain_router())
+        .nest("/api/crawl", create_crawl_router())
+        .nest("/api", create_words_router())
+        .with_state(env.clone())
         .split_for_parts();
 
+    // Only the API routes are counted, not the docs
+    let router = router
+        .layer(from_fn_with_state(env.clone(), authenticate))
+        .layer(from_fn_with_state(env.clone(), count_requests));
+
     let router = router.merge(
         SwaggerUi::new("/docs")
             .config(Config::default())
             .url("/docs/openapi.json", api.clone()),
     );
 
-    let app = Router::new().merge(router);
+    // The favicons and the docs are not rate limited
+    let app = Router::new()
+        .merge(router)
+        .layer(from_fn_with_state(env.clone(), rate_limit));
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(RATE_LIMIT_CLEANUP_INTERVAL).await;
+
+            let now = Instant::now();
+            env.rate_limiter.remove_stale(now);
+            env.suggest_rate_limiter.remove_stale(now);
+        }
+    });
 
     let addr = format!("0.0.0.0:{port}");
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
@@ -43,6 +87,7 @@ pub async fn build_api(env: Arc<Environment>, port: u16) {
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
     )
+    .with_graceful_shutdown(shutdown)
     .await
     .unwrap();
 }
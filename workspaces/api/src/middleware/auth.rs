@@ -0,0 +1,186 @@
+use crate::environment::ApiState;
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use jsonwebtoken::{
+    decode, encode, errors::Error, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+
+/// Lifetime of the issued tokens in seconds (24 hours)
+pub const TOKEN_LIFETIME: u64 = 86_400;
+
+/// Added to the request extensions when it has a valid token.
+/// Protected routes extract it with `Option<Extension<AuthenticatedUser>>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthenticatedUser {
+    pub sub: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    /// Expiration time, in seconds since the Unix epoch
+    exp: u64,
+}
+
+/// Create a HS256 token for `sub`, expiring `TOKEN_LIFETIME` seconds after `now` (in seconds)
+pub fn create_token(sub: &str, secret: &str, now: u64) -> Result<String, Error> {
+    let claims = Claims {
+        sub: sub.to_string(),
+        exp: now + TOKEN_LIFETIME,
+    };
+
+    encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+}
+
+/// Get the user of a `Bearer` token, if it is signed with `secret` and not expired
+fn get_authenticated_user(headers: &HeaderMap, secret: &str) -> Option<AuthenticatedUser> {
+    let authorization = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let token = authorization.strip_prefix("Bearer ")?;
+
+    let claims = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::new(Algorithm::HS256),
+    )
+    .ok()?
+    .claims;
+
+    Some(AuthenticatedUser { sub: claims.sub })
+}
+
+/// Add the `AuthenticatedUser` of the request to its extensions.
+/// Requests without a valid token are not rejected here, only the protected routes do it.
+pub async fn authenticate(
+    State(state): State<ApiState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(secret) = &state.jwt_secret {
+        if let Some(user) = get_authenticated_user(request.headers(), secret) {
+            request.extensions_mut().insert(user);
+        }
+    } else if request.headers().contains_key(AUTHORIZATION) {
+        eprintln!("[API] JWT_SECRET is not set");
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use axum::{
+        body::Body, http::StatusCode, middleware::from_fn_with_state, routing::get, Extension,
+        Router,
+    };
+    use std::{
+        sync::Arc,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+    use tower::ServiceExt;
+
+    const SECRET: &str = "test secret";
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_valid_token() {
+        let token = create_token("admin", SECRET, now()).unwrap();
+
+        assert_eq!(
+            get_authenticated_user(&bearer(&token), SECRET),
+            Some(AuthenticatedUser {
+                sub: "admin".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let token = create_token("admin", SECRET, now() - 2 * TOKEN_LIFETIME).unwrap();
+        assert_eq!(get_authenticated_user(&bearer(&token), SECRET), None);
+    }
+
+    #[test]
+    fn test_wrong_secret() {
+        let token = create_token("admin", "another secret", now()).unwrap();
+        assert_eq!(get_authenticated_user(&bearer(&token), SECRET), None);
+    }
+
+    #[test]
+    fn test_missing_header() {
+        assert_eq!(get_authenticated_user(&HeaderMap::new(), SECRET), None);
+
+        // Not a bearer token
+        let token = create_token("admin", SECRET, now()).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, token.parse().unwrap());
+        assert_eq!(get_authenticated_user(&headers, SECRET), None);
+    }
+
+    #[tokio::test]
+    async fn test_protected_route_without_token() {
+        let mut env = Environment::for_tests();
+        env.jwt_secret = Some(SECRET.to_string());
+
+        let app = Router::new()
+            .route(
+                "/protected",
+                get(|user: Option<Extension<AuthenticatedUser>>| async move {
+                    if user.is_some() {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::UNAUTHORIZED
+                    }
+                }),
+            )
+            .layer(from_fn_with_state(Arc::new(env), authenticate));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/protected")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Signed with the configured secret
+        let token = create_token("admin", SECRET, now()).unwrap();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/protected")
+                    .header(AUTHORIZATION, format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod auth;
+pub mod rate_limit;
+pub mod request_count;
@@ -0,0 +1,299 @@
+use crate::environment::ApiState;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+/// Above this number of tracked IPs, the full buckets are removed
+pub const MAX_TRACKED_IPS: usize = 10_000;
+
+/// Requests per minute allowed for an IP on the rate limited routes
+pub const DEFAULT_RATE_LIMIT_RPM: u32 = 120;
+
+/// Requests an IP can send at once, a results page also loads its suggestions
+pub const DEFAULT_RATE_LIMIT_BURST: u32 = 30;
+
+/// Routes loaded several times per results page, or by the docs page, which are not rate limited
+pub const RATE_LIMIT_EXEMPT_PREFIXES: [&str; 2] = ["/api/favicon/", "/docs"];
+
+/// Delay between two removals of the full buckets
+pub const RATE_LIMIT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: f64, now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, capacity: f64, refill_per_sec: f64, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * refill_per_sec).min(capacity);
+        self.last_refill = now;
+    }
+
+    /// Take a token, returns `false` if the bucket is empty
+    pub fn try_take(&mut self, capacity: f64, refill_per_sec: f64, now: Instant) -> bool {
+        self.refill(capacity, refill_per_sec, now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Remove the full buckets, a new bucket would be the same
+fn remove_full_buckets(
+    buckets: &DashMap<IpAddr, TokenBucket>,
+    capacity: f64,
+    refill_per_sec: f64,
+    now: Instant,
+) {
+    buckets.retain(|_, bucket| {
+        bucket.refill(capacity, refill_per_sec, now);
+        bucket.tokens < capacity
+    });
+}
+
+/// Take a token from the bucket of an IP, returns `false` if it is rate limited
+pub fn check_rate_limit(
+    buckets: &DashMap<IpAddr, TokenBucket>,
+    ip: IpAddr,
+    capacity: f64,
+    refill_per_sec: f64,
+    now: Instant,
+) -> bool {
+    if buckets.len() > MAX_TRACKED_IPS {
+        remove_full_buckets(buckets, capacity, refill_per_sec, now);
+    }
+
+    buckets
+        .entry(ip)
+        .or_insert_with(|| TokenBucket::new(capacity, now))
+        .try_take(capacity, refill_per_sec, now)
+}
+
+/// A token bucket per IP, holding up to `burst` tokens refilled at `rpm` tokens per minute
+pub struct RateLimiter {
+    buckets: DashMap<IpAddr, TokenBucket>,
+    capacity: f64,
+    refill_per_sec: f64,
+}
+
+impl RateLimiter {
+    pub fn new(rpm: u32, burst: u32) -> Self {
+        Self {
+            buckets: DashMap::new(),
+            capacity: burst as f64,
+            refill_per_sec: rpm as f64 / 60.0,
+        }
+    }
+
+    /// Take a token of the IP, returns `false` if it is rate limited
+    pub fn check(&self, ip: IpAddr, now: Instant) -> bool {
+        check_rate_limit(&self.buckets, ip, self.capacity, self.refill_per_sec, now)
+    }
+
+    /// Forget the IPs which did not send requests for a while
+    pub fn remove_stale(&self, now: Instant) {
+        remove_full_buckets(&self.buckets, self.capacity, self.refill_per_sec, now);
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_RATE_LIMIT_RPM, DEFAULT_RATE_LIMIT_BURST)
+    }
+}
+
+/// Whether the requests to a path take a token of the IP
+pub fn is_rate_limited_path(path: &str) -> bool {
+    !RATE_LIMIT_EXEMPT_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+/// Reject the requests of the IPs above the rate limit with `429 Too Many Requests`.
+/// The IP is the one of the connection: behind a reverse proxy, all the clients share the bucket
+/// of the proxy, so the limits must be raised or the rate limiting done by the proxy.
+pub async fn rate_limit(
+    State(state): State<ApiState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_rate_limited_path(request.uri().path())
+        && !state.rate_limiter.check(addr.ip(), Instant::now())
+    {
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use axum::{body::Body, middleware::from_fn_with_state, routing::get, Router};
+    use std::{net::Ipv4Addr, sync::Arc};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_rate_limited_path() {
+        assert_eq!(is_rate_limited_path("/api/search"), true);
+        assert_eq!(is_rate_limited_path("/api/suggest"), true);
+        assert_eq!(is_rate_limited_path("/api/favicon/12"), false);
+        assert_eq!(is_rate_limited_path("/docs/openapi.json"), false);
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(60.0, now);
+
+        for _ in 0..60 {
+            assert_eq!(bucket.try_take(60.0, 1.0, now), true);
+        }
+        assert_eq!(bucket.try_take(60.0, 1.0, now), false);
+
+        // One token per second
+        let later = now + Duration::from_secs(1);
+        assert_eq!(bucket.try_take(60.0, 1.0, later), true);
+        assert_eq!(bucket.try_take(60.0, 1.0, later), false);
+
+        // Never more than the capacity
+        let much_later = now + Duration::from_secs(3_600);
+        for _ in 0..60 {
+            assert_eq!(bucket.try_take(60.0, 1.0, much_later), true);
+        }
+        assert_eq!(bucket.try_take(60.0, 1.0, much_later), false);
+    }
+
+    #[test]
+    fn test_check_rate_limit() {
+        let buckets = DashMap::new();
+        let now = Instant::now();
+        let first = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let second = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+
+        assert_eq!(check_rate_limit(&buckets, first, 2.0, 1.0, now), true);
+        assert_eq!(check_rate_limit(&buckets, first, 2.0, 1.0, now), true);
+        assert_eq!(check_rate_limit(&buckets, first, 2.0, 1.0, now), false);
+        assert_eq!(check_rate_limit(&buckets, second, 2.0, 1.0, now), true);
+    }
+
+    #[test]
+    fn test_rate_limiter_burst() {
+        let limiter = RateLimiter::new(60, 10);
+        let now = Instant::now();
+        let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        for _ in 0..10 {
+            assert_eq!(limiter.check(ip, now), true);
+        }
+        assert_eq!(limiter.check(ip, now), false);
+        // Other IPs have their own bucket
+        assert_eq!(
+            limiter.check(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)), now),
+            true
+        );
+    }
+
+    #[test]
+    fn test_rate_limiter_refill() {
+        // One token every 2 seconds
+        let limiter = RateLimiter::new(30, 2);
+        let now = Instant::now();
+        let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        assert_eq!(limiter.check(ip, now), true);
+        assert_eq!(limiter.check(ip, now), true);
+        assert_eq!(limiter.check(ip, now), false);
+
+        assert_eq!(limiter.check(ip, now + Duration::from_secs(1)), false);
+        assert_eq!(limiter.check(ip, now + Duration::from_secs(2)), true);
+        assert_eq!(limiter.check(ip, now + Duration::from_secs(2)), false);
+
+        // Refilled up to the burst only
+        let later = now + Duration::from_secs(60);
+        assert_eq!(limiter.check(ip, later), true);
+        assert_eq!(limiter.check(ip, later), true);
+        assert_eq!(limiter.check(ip, later), false);
+    }
+
+    #[test]
+    fn test_rate_limiter_remove_stale() {
+        let limiter = RateLimiter::new(60, 2);
+        let now = Instant::now();
+        let first = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let second = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+
+        limiter.check(first, now);
+        limiter.check(second, now + Duration::from_secs(10));
+        limiter.check(second, now + Duration::from_secs(10));
+
+        // The first bucket is full again after 1 second, the second one after 2 seconds
+        limiter.remove_stale(now + Duration::from_secs(11));
+        assert_eq!(limiter.buckets.contains_key(&first), false);
+        assert_eq!(limiter.buckets.contains_key(&second), true);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_middleware() {
+        let mut env = Environment::for_tests();
+        env.rate_limiter = RateLimiter::new(60, 2);
+        let env = Arc::new(env);
+
+        let app = Router::new()
+            .route("/ping", get(|| async { StatusCode::OK }))
+            .route("/api/favicon/{id}", get(|| async { StatusCode::OK }))
+            .layer(from_fn_with_state(env, rate_limit));
+        let request = |ip: [u8; 4]| {
+            Request::builder()
+                .uri("/ping")
+                .extension(ConnectInfo(SocketAddr::from((ip, 8080))))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for expected in [
+            StatusCode::OK,
+            StatusCode::OK,
+            StatusCode::TOO_MANY_REQUESTS,
+        ] {
+            let response = app.clone().oneshot(request([10, 0, 0, 1])).await.unwrap();
+            assert_eq!(response.status(), expected);
+        }
+
+        let response = app.clone().oneshot(request([10, 0, 0, 2])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The favicons of the results do not take tokens
+        let favicon = Request::builder()
+            .uri("/api/favicon/1")
+            .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 8080))))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(favicon).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
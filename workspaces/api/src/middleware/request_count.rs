@@ -0,0 +1,61 @@
+use crate::environment::ApiState;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::atomic::Ordering;
+
+/// Count every request received by the API.
+/// The monitor saves and resets this counter as a `StatisticType::ApiRequestCount`.
+pub async fn count_requests(
+    State(state): State<ApiState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    state.request_count.fetch_add(1, Ordering::Relaxed);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use axum::{
+        body::Body, http::StatusCode, middleware::from_fn_with_state, routing::get, Router,
+    };
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_count_requests() {
+        let env = Arc::new(Environment::for_tests());
+        let app = Router::new()
+            .route("/ping", get(|| async { StatusCode::OK }))
+            .layer(from_fn_with_state(env.clone(), count_requests));
+
+        for i in 1..=3 {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri("/ping").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(env.request_count.load(Ordering::Relaxed), i);
+        }
+
+        // Unknown routes are requests too
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/unknown")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(env.request_count.load(Ordering::Relaxed), 4);
+    }
+}
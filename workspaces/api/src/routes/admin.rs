@@ -0,0 +1,160 @@
+use crate::{
+    environment::{ApiState, Environment},
+    middleware::auth::AuthenticatedUser,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use diesel::{sql_query, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+/// Minimum delay between two VACUUM runs
+pub const VACUUM_COOLDOWN: Duration = Duration::from_secs(600);
+
+pub fn create_admin_router() -> OpenApiRouter<ApiState> {
+    OpenApiRouter::new().routes(routes!(post_vacuum_handler))
+}
+
+#[derive(Deserialize)]
+struct VacuumQuery {
+    table: String,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+struct VacuumResponse {
+    status: String,
+    table: String,
+}
+
+/// Get the VACUUM statement of a table.
+/// Only a fixed list of tables is accepted so the name is never inserted in the SQL.
+fn get_vacuum_statement(table: &str) -> Option<&'static str> {
+    match table {
+        "pages" => Some("VACUUM ANALYZE pages"),
+        "indexes" => Some("VACUUM ANALYZE indexes"),
+        "all" => Some("VACUUM ANALYZE"),
+        _ => None,
+    }
+}
+
+/// Returns `true` and saves `now` as the last vacuum if the cooldown is over
+fn try_start_vacuum(last_vacuum: &mut Option<Instant>, now: Instant) -> bool {
+    if let Some(last) = last_vacuum {
+        if now.duration_since(*last) < VACUUM_COOLDOWN {
+            return false;
+        }
+    }
+
+    *last_vacuum = Some(now);
+    true
+}
+
+/// Run `VACUUM ANALYZE` on the `pages` table, the `indexes` table, or the whole database.
+///
+/// **Security warning**: VACUUM is expensive (heavy disk I/O, and it competes with the
+/// crawler and indexer for locks). This route must never be reachable without the API_KEY,
+/// and it is limited to one run every 10 minutes.
+#[utoipa::path(
+    post,
+    path = "/vacuum",
+    description = "Run VACUUM ANALYZE on a table (once every 10 minutes). A token from /api/token is required in the 'Authorization: Bearer' header",
+    params(
+        ("table" = String, Query, description = "The table to vacuum: 'pages', 'indexes' or 'all'")
+    ),
+    responses(
+        (status = OK, body = VacuumResponse),
+        (status = BAD_REQUEST, description = "Invalid table"),
+        (status = UNAUTHORIZED),
+        (status = TOO_MANY_REQUESTS, description = "A VACUUM was already run less than 10 minutes ago"),
+    )
+)]
+#[axum::debug_handler]
+async fn post_vacuum_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    query: Query<VacuumQuery>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let statement = if let Some(statement) = get_vacuum_statement(&query.table) {
+        statement
+    } else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    {
+        let mut last_vacuum = state.last_vacuum.lock().unwrap();
+        if !try_start_vacuum(&mut last_vacuum, Instant::now()) {
+            return StatusCode::TOO_MANY_REQUESTS.into_response();
+        }
+    }
+
+    // VACUUM cannot run inside a transaction block.
+    // Pooled connections are in autocommit mode, so the statement is executed directly.
+    let db_conn = &mut state.get_conn();
+
+    if let Err(e) = sql_query(statement).execute(db_conn) {
+        eprintln!("[API] Failed to vacuum '{}': {e}", query.table);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    println!("[API] VACUUM ANALYZE done for '{}'", query.table);
+
+    Json(VacuumResponse {
+        status: "ok".into(),
+        table: query.table.clone(),
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_vacuum_statement() {
+        assert_eq!(get_vacuum_statement("pages"), Some("VACUUM ANALYZE pages"));
+        assert_eq!(
+            get_vacuum_statement("indexes"),
+            Some("VACUUM ANALYZE indexes")
+        );
+        assert_eq!(get_vacuum_statement("all"), Some("VACUUM ANALYZE"));
+        assert_eq!(get_vacuum_statement("queue"), None);
+        assert_eq!(get_vacuum_statement("pages; DROP TABLE pages"), None);
+    }
+
+    #[test]
+    fn test_try_start_vacuum() {
+        let start = Instant::now();
+        let mut last_vacuum = None;
+
+        assert_eq!(try_start_vacuum(&mut last_vacuum, start), true);
+        assert_eq!(last_vacuum, Some(start));
+
+        // Rate limited during the cooldown
+        assert_eq!(
+            try_start_vacuum(&mut last_vacuum, start + Duration::from_secs(1)),
+            false
+        );
+        assert_eq!(
+            try_start_vacuum(&mut last_vacuum, start + Duration::from_secs(599)),
+            false
+        );
+        assert_eq!(last_vacuum, Some(start));
+
+        // Allowed again after the cooldown
+        let later = start + VACUUM_COOLDOWN;
+        assert_eq!(try_start_vacuum(&mut last_vacuum, later), true);
+        assert_eq!(last_vacuum, Some(later));
+    }
+}
@@ -1,8 +1,17 @@
-use crate::environment::{ApiState, Environment};
-use axum::{extract::State, http::StatusCode, Json};
+use crate::{
+    environment::{ApiState, Environment},
+    middleware::auth::AuthenticatedUser,
+    routes::base::evict_cached_searches,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
 use database::{
-    models::{NewPageAnalytics, Statistic},
-    schema::{pages, pages_analytics, statistics},
+    models::{NewPageAnalytics, NewPageAnalyticsHistory, Statistic},
+    schema::{pages, pages_analytics, pages_analytics_history, queries, statistics},
     types::StatisticType,
     DbConn,
 };
@@ -12,6 +21,7 @@ use diesel::{
 };
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc};
+use utils::sql::get_sql_timestamp;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 pub fn create_analytics_router() -> OpenApiRouter<ApiState> {
@@ -19,9 +29,17 @@ pub fn create_analytics_router() -> OpenApiRouter<ApiState> {
         .routes(routes!(get_analytics_system_handler))
         .routes(routes!(get_analytics_database_handler))
         .routes(routes!(get_analytics_pages_handler))
+        .routes(routes!(get_analytics_page_history_handler))
         .routes(routes!(post_analytics_click_handler))
+        .routes(routes!(get_analytics_top_queries_handler))
+        .routes(routes!(get_analytics_zero_results_queries_handler))
+        .routes(routes!(get_analytics_pages_ctr_handler))
 }
 
+pub const DEFAULT_LIST_LIMIT: i64 = 20;
+
+pub const MAX_LIST_LIMIT: i64 = 100;
+
 /// Holds (value, timestamp)
 #[derive(utoipa::ToSchema, Serialize)]
 struct StatisticValue(i64, i64);
@@ -66,7 +84,7 @@ struct SystemAnalytics {
 async fn get_analytics_system_handler(
     State(state): State<Arc<Environment>>,
 ) -> Json<SystemAnalytics> {
-    let db_conn = &mut state.db_pool.get().unwrap();
+    let db_conn = &mut state.get_read_conn();
 
     let mut stats = get_statistics(
         vec![StatisticType::MemoryUsage, StatisticType::CpuUsage],
@@ -93,6 +111,24 @@ struct DatabaseAnalytics {
     word_counts: Vec<StatisticValue>,
     indexes_counts: Vec<StatisticValue>,
     favicons_counts: Vec<StatisticValue>,
+    /// Pages queued again by each re-crawl run
+    recrawl_counts: Vec<StatisticValue>,
+    /// Pages indexed per minute by each indexer run
+    indexer_throughputs: Vec<StatisticValue>,
+    /// Stale favicons downloaded again
+    favicon_refresh_counts: Vec<StatisticValue>,
+    /// Used bytes of the filesystem
+    disk_usages: Vec<StatisticValue>,
+    /// Size in bytes of the favicons directory
+    favicon_dir_sizes: Vec<StatisticValue>,
+    /// Alerts fired by the monitor
+    alert_fired_counts: Vec<StatisticValue>,
+    /// Domains with crawl statistics
+    domain_counts: Vec<StatisticValue>,
+    /// Deleted pages waiting to be purged
+    deleted_page_counts: Vec<StatisticValue>,
+    /// Median response time of the crawled pages, in milliseconds
+    average_response_times: Vec<StatisticValue>,
 }
 
 #[utoipa::path(
@@ -107,7 +143,7 @@ struct DatabaseAnalytics {
 async fn get_analytics_database_handler(
     State(state): State<Arc<Environment>>,
 ) -> Json<DatabaseAnalytics> {
-    let db_conn = &mut state.db_pool.get().unwrap();
+    let db_conn = &mut state.get_read_conn();
 
     let mut stats = get_statistics(
         vec![
@@ -120,6 +156,15 @@ async fn get_analytics_database_handler(
             StatisticType::WordCount,
             StatisticType::IndexesCount,
             StatisticType::FaviconsCount,
+            StatisticType::RecrawlCount,
+            StatisticType::IndexerThroughput,
+            StatisticType::FaviconRefreshCount,
+            StatisticType::DiskUsage,
+            StatisticType::FaviconDirSize,
+            StatisticType::AlertFiredCount,
+            StatisticType::DomainCount,
+            StatisticType::DeletedPageCount,
+            StatisticType::AverageResponseTime,
         ],
         db_conn,
     )
@@ -153,11 +198,41 @@ async fn get_analytics_database_handler(
         favicons_counts: stats
             .remove(&StatisticType::FaviconsCount)
             .unwrap_or(Vec::new()),
+        recrawl_counts: stats
+            .remove(&StatisticType::RecrawlCount)
+            .unwrap_or(Vec::new()),
+        indexer_throughputs: stats
+            .remove(&StatisticType::IndexerThroughput)
+            .unwrap_or(Vec::new()),
+        favicon_refresh_counts: stats
+            .remove(&StatisticType::FaviconRefreshCount)
+            .unwrap_or(Vec::new()),
+        disk_usages: stats
+            .remove(&StatisticType::DiskUsage)
+            .unwrap_or(Vec::new()),
+        favicon_dir_sizes: stats
+            .remove(&StatisticType::FaviconDirSize)
+            .unwrap_or(Vec::new()),
+        alert_fired_counts: stats
+            .remove(&StatisticType::AlertFiredCount)
+            .unwrap_or(Vec::new()),
+        domain_counts: stats
+            .remove(&StatisticType::DomainCount)
+            .unwrap_or(Vec::new()),
+        deleted_page_counts: stats
+            .remove(&StatisticType::DeletedPageCount)
+            .unwrap_or(Vec::new()),
+        average_response_times: stats
+            .remove(&StatisticType::AverageResponseTime)
+            .unwrap_or(Vec::new()),
     })
 }
 
 #[derive(utoipa::ToSchema, Serialize)]
 struct PagesAnalytics {
+    /// The time range in hours, `null` for all-time analytics
+    timerange: Option<i64>,
+    /// Average search time in milliseconds
     average_search_time: i64,
     total_clicks: i64,
     total_impressions: i64,
@@ -170,19 +245,43 @@ struct AvgResult {
     avg: Option<f64>,
 }
 
+#[derive(Deserialize)]
+struct PagesAnalyticsQuery {
+    timerange: Option<i64>,
+}
+
+/// Get the minimum timestamp of a time range in hours.
+/// Returns `0` (all-time) if there is no time range.
+fn get_timerange_start(timerange: Option<i64>, now: i64) -> i64 {
+    if let Some(hours) = timerange {
+        now.saturating_sub(hours.saturating_mul(3_600_000)).max(0)
+    } else {
+        0
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/pages",
-    description = "Get the pages global analytics",
+    description = "Get the pages global analytics. Clicks and impressions are always all-time totals",
+    params(
+        ("timerange" = Option<i64>, Query, description = "Only use the searches of the last `timerange` hours (default: all-time)")
+    ),
     responses(
-        (status = OK, body = PagesAnalytics)
+        (status = OK, body = PagesAnalytics),
+        (status = BAD_REQUEST, description = "Invalid time range")
     )
 )]
 #[axum::debug_handler]
 async fn get_analytics_pages_handler(
     State(state): State<Arc<Environment>>,
-) -> Json<PagesAnalytics> {
-    let db_conn = &mut state.db_pool.get().unwrap();
+    query: Query<PagesAnalyticsQuery>,
+) -> Response {
+    if query.timerange.is_some_and(|t| t < 1) {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let db_conn = &mut state.get_read_conn();
 
     let result: (Option<i64>, Option<i64>) = pages_analytics::table
         .select((
@@ -192,20 +291,344 @@ async fn get_analytics_pages_handler(
         .first(db_conn)
         .expect("Error calculating sum");
 
-    let average_result = sql_query("SELECT AVG(search_time)::float8 AS avg FROM queries")
-        .get_result::<AvgResult>(db_conn)
-        .unwrap();
+    let since = get_timerange_start(query.timerange, get_sql_timestamp());
+
+    let average_result =
+        sql_query("SELECT AVG(search_time)::float8 AS avg FROM queries WHERE timestamp > $1")
+            .bind::<diesel::sql_types::BigInt, _>(since)
+            .get_result::<AvgResult>(db_conn)
+            .unwrap();
 
     Json(PagesAnalytics {
+        timerange: query.timerange,
         average_search_time: average_result.avg.map(|t| t.floor() as i64).unwrap_or(-1),
         total_clicks: result.0.unwrap_or(-1),
         total_impressions: result.1.unwrap_or(-1),
     })
+    .into_response()
+}
+
+#[derive(utoipa::ToSchema, Serialize, QueryableByName)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct PageHistoryBucket {
+    /// Start of the bucket
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    timestamp: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    clicks: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    impressions: i64,
+}
+
+#[derive(Deserialize)]
+struct PageHistoryQuery {
+    granularity: Option<String>,
+}
+
+/// Get the bucket size in milliseconds of a history granularity
+fn get_granularity_ms(granularity: &str) -> Option<i64> {
+    match granularity {
+        "hour" => Some(3_600_000),
+        "day" => Some(86_400_000),
+        _ => None,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/pages/{page_id}/history",
+    description = "Get the clicks and impressions history of a page",
+    params(
+        ("page_id" = i32, Path, description = "The page id"),
+        ("granularity" = Option<String>, Query, description = "`hour` or `day` (default: `hour`)")
+    ),
+    responses(
+        (status = OK, body = Vec<PageHistoryBucket>),
+        (status = BAD_REQUEST, description = "Invalid granularity")
+    )
+)]
+#[axum::debug_handler]
+async fn get_analytics_page_history_handler(
+    State(state): State<Arc<Environment>>,
+    Path(page_id): Path<i32>,
+    query: Query<PageHistoryQuery>,
+) -> Response {
+    let bucket_size = match get_granularity_ms(query.granularity.as_deref().unwrap_or("hour")) {
+        Some(size) => size,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let db_conn = &mut state.get_read_conn();
+
+    let buckets = sql_query(
+        "SELECT (timestamp / $1) * $1 AS timestamp,
+            SUM(clicks_delta)::int8 AS clicks,
+            SUM(impressions_delta)::int8 AS impressions
+        FROM pages_analytics_history
+        WHERE page_id = $2
+        GROUP BY 1
+        ORDER BY 1",
+    )
+    .bind::<diesel::sql_types::BigInt, _>(bucket_size)
+    .bind::<diesel::sql_types::Integer, _>(page_id)
+    .load::<PageHistoryBucket>(db_conn)
+    .unwrap();
+
+    Json(buckets).into_response()
+}
+
+#[derive(utoipa::ToSchema, Serialize, QueryableByName)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct TopQuery {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    query: String,
+    /// Number of searches
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    count: i64,
+    /// Average search time in milliseconds
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    avg_search_time: f64,
+}
+
+#[derive(Deserialize)]
+struct TopQueriesQuery {
+    limit: Option<i64>,
+    since: Option<i64>,
+}
+
+/// Get the number of items of a list to return, `None` if it is out of range
+fn get_list_limit(limit: Option<i64>) -> Option<i64> {
+    match limit {
+        None => Some(DEFAULT_LIST_LIMIT),
+        Some(limit) if (1..=MAX_LIST_LIMIT).contains(&limit) => Some(limit),
+        _ => None,
+    }
+}
+
+/// Get the number of items of a list to skip, `None` if it is negative
+fn get_list_offset(offset: Option<i64>) -> Option<i64> {
+    match offset {
+        None => Some(0),
+        Some(offset) if offset >= 0 => Some(offset),
+        _ => None,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/queries/top",
+    description = "Get the most searched queries",
+    params(
+        ("limit" = Option<i64>, Query, description = "The number of queries, up to 100 (default: 20)"),
+        ("since" = Option<i64>, Query, description = "Only use the searches made at or after this Unix timestamp in milliseconds")
+    ),
+    responses(
+        (status = OK, body = Vec<TopQuery>),
+        (status = BAD_REQUEST, description = "Invalid limit"),
+        (status = UNAUTHORIZED)
+    )
+)]
+#[axum::debug_handler]
+async fn get_analytics_top_queries_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    query: Query<TopQueriesQuery>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let limit = match get_list_limit(query.limit) {
+        Some(limit) => limit,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let db_conn = &mut state.get_read_conn();
+
+    let top_queries = sql_query(
+        "SELECT query, COUNT(*) AS count, AVG(search_time)::float8 AS avg_search_time
+        FROM queries
+        WHERE timestamp >= $1
+        GROUP BY query
+        ORDER BY count DESC, query
+        LIMIT $2",
+    )
+    .bind::<diesel::sql_types::BigInt, _>(query.since.unwrap_or(0))
+    .bind::<diesel::sql_types::BigInt, _>(limit)
+    .load::<TopQuery>(db_conn)
+    .unwrap();
+
+    Json(top_queries).into_response()
+}
+
+#[derive(utoipa::ToSchema, Serialize, QueryableByName)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct ZeroResultsQuery {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    query: String,
+    /// Number of searches without results
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    count: i64,
+    /// Timestamp of the last search without results
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    last_seen: i64,
+}
+
+#[derive(Deserialize)]
+struct ZeroResultsQueriesQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/queries/zero-results",
+    description = "Get the queries without results, the most frequent first",
+    params(
+        ("limit" = Option<i64>, Query, description = "The number of queries, up to 100 (default: 20)"),
+        ("offset" = Option<i64>, Query, description = "The number of queries to skip (default: 0)")
+    ),
+    responses(
+        (status = OK, body = Vec<ZeroResultsQuery>),
+        (status = BAD_REQUEST, description = "Invalid limit or offset"),
+        (status = UNAUTHORIZED)
+    )
+)]
+#[axum::debug_handler]
+async fn get_analytics_zero_results_queries_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    query: Query<ZeroResultsQueriesQuery>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let limit = match get_list_limit(query.limit) {
+        Some(limit) => limit,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let offset = match get_list_offset(query.offset) {
+        Some(offset) => offset,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let db_conn = &mut state.get_read_conn();
+
+    let zero_results_queries = sql_query(
+        "SELECT query, COUNT(*) AS count, MAX(timestamp) AS last_seen
+        FROM queries
+        WHERE zero_results
+        GROUP BY query
+        ORDER BY count DESC, last_seen DESC, query
+        LIMIT $1 OFFSET $2",
+    )
+    .bind::<diesel::sql_types::BigInt, _>(limit)
+    .bind::<diesel::sql_types::BigInt, _>(offset)
+    .load::<ZeroResultsQuery>(db_conn)
+    .unwrap();
+
+    Json(zero_results_queries).into_response()
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+struct PageCtr {
+    url: String,
+    clicks: i32,
+    impressions: i32,
+    /// Click-through rate, `clicks / impressions`
+    ctr: f32,
+}
+
+#[derive(QueryableByName)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct PageClicksResult {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    url: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    clicks: i32,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    impressions: i32,
+}
+
+#[derive(Deserialize)]
+struct PagesCtrQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Click-through rate of a page, `0` if it was never shown
+fn get_ctr(clicks: i32, impressions: i32) -> f32 {
+    if impressions > 0 {
+        clicks as f32 / impressions as f32
+    } else {
+        0.0
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/pages/ctr",
+    description = "Get the click-through rate of the pages shown in the search results, the highest first",
+    params(
+        ("limit" = Option<i64>, Query, description = "The number of pages, up to 100 (default: 20)"),
+        ("offset" = Option<i64>, Query, description = "The number of pages to skip (default: 0)")
+    ),
+    responses(
+        (status = OK, body = Vec<PageCtr>),
+        (status = BAD_REQUEST, description = "Invalid limit or offset"),
+        (status = UNAUTHORIZED)
+    )
+)]
+#[axum::debug_handler]
+async fn get_analytics_pages_ctr_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    query: Query<PagesCtrQuery>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let (limit, offset) = match (get_list_limit(query.limit), get_list_offset(query.offset)) {
+        (Some(limit), Some(offset)) => (limit, offset),
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let db_conn = &mut state.get_read_conn();
+
+    let pages = sql_query(
+        "SELECT pages.url, pages_analytics.clicks, pages_analytics.impressions
+        FROM pages_analytics
+        INNER JOIN pages ON pages.id = pages_analytics.page_id
+        WHERE pages_analytics.impressions > 0
+        ORDER BY pages_analytics.clicks::float8 / NULLIF(pages_analytics.impressions, 0) DESC,
+            pages_analytics.impressions DESC, pages.id
+        LIMIT $1 OFFSET $2",
+    )
+    .bind::<diesel::sql_types::BigInt, _>(limit)
+    .bind::<diesel::sql_types::BigInt, _>(offset)
+    .load::<PageClicksResult>(db_conn)
+    .unwrap();
+
+    let pages: Vec<PageCtr> = pages
+        .into_iter()
+        .map(|p| PageCtr {
+            ctr: get_ctr(p.clicks, p.impressions),
+            url: p.url,
+            clicks: p.clicks,
+            impressions: p.impressions,
+        })
+        .collect();
+
+    Json(pages).into_response()
 }
 
 #[derive(Deserialize)]
 struct ClickAnalyticsBody {
     page_url: String,
+    /// The `search_id` of the search response the page was clicked from
+    search_id: Option<i32>,
 }
 
 #[utoipa::path(
@@ -226,11 +649,23 @@ async fn post_analytics_click_handler(
         return StatusCode::BAD_REQUEST;
     }
 
-    let db_conn = &mut state.db_pool.get().unwrap();
+    let db_conn = &mut state.get_conn();
+
+    // Ignore unknown searches instead of failing on the foreign key
+    let search_id = if let Some(search_id) = payload.search_id {
+        queries::table
+            .select(queries::id)
+            .filter(queries::id.eq(search_id))
+            .get_result::<i32>(db_conn)
+            .optional()
+            .unwrap()
+    } else {
+        None
+    };
 
     if let Some(page_id) = pages::table
         .select(pages::id)
-        .filter(pages::url.eq(payload.page_url))
+        .filter(pages::url.eq(&payload.page_url))
         .get_result::<i32>(db_conn)
         .optional()
         .unwrap()
@@ -247,8 +682,157 @@ async fn post_analytics_click_handler(
             .execute(db_conn)
             .unwrap();
 
+        diesel::insert_into(pages_analytics_history::table)
+            .values(NewPageAnalyticsHistory {
+                page_id,
+                clicks_delta: 1,
+                impressions_delta: 0,
+                timestamp: get_sql_timestamp(),
+                search_id,
+            })
+            .execute(db_conn)
+            .unwrap();
+
+        // The cached results have outdated clicks
+        evict_cached_searches(&state.search_cache, &payload.page_url);
+
         StatusCode::OK
     } else {
         StatusCode::BAD_REQUEST
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_timerange_start() {
+        let now = 1_700_000_000_000;
+
+        assert_eq!(get_timerange_start(None, now), 0);
+        assert_eq!(get_timerange_start(Some(1), now), now - 3_600_000);
+        assert_eq!(get_timerange_start(Some(24), now), now - 86_400_000);
+        assert_eq!(get_timerange_start(Some(i64::MAX), now), 0);
+    }
+
+    #[test]
+    fn test_get_granularity_ms() {
+        assert_eq!(get_granularity_ms("hour"), Some(3_600_000));
+        assert_eq!(get_granularity_ms("day"), Some(86_400_000));
+        assert_eq!(get_granularity_ms("week"), None);
+        assert_eq!(get_granularity_ms(""), None);
+    }
+
+    #[test]
+    fn test_get_list_limit() {
+        assert_eq!(get_list_limit(None), Some(DEFAULT_LIST_LIMIT));
+        assert_eq!(get_list_limit(Some(1)), Some(1));
+        assert_eq!(get_list_limit(Some(100)), Some(100));
+        assert_eq!(get_list_limit(Some(0)), None);
+        assert_eq!(get_list_limit(Some(101)), None);
+        assert_eq!(get_list_limit(Some(-5)), None);
+    }
+
+    #[test]
+    fn test_top_query_response() {
+        let top_queries = vec![TopQuery {
+            query: "rust".to_string(),
+            count: 3,
+            avg_search_time: 1500.5,
+        }];
+
+        assert_eq!(
+            serde_json::to_string(&top_queries).unwrap(),
+            r#"[{"query":"rust","count":3,"avg_search_time":1500.5}]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_top_queries_unauthorized() {
+        let response = get_analytics_top_queries_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Query(TopQueriesQuery {
+                limit: None,
+                since: None,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_get_list_offset() {
+        assert_eq!(get_list_offset(None), Some(0));
+        assert_eq!(get_list_offset(Some(0)), Some(0));
+        assert_eq!(get_list_offset(Some(40)), Some(40));
+        assert_eq!(get_list_offset(Some(-1)), None);
+    }
+
+    #[test]
+    fn test_get_ctr() {
+        assert_eq!(get_ctr(0, 0), 0.0);
+        assert_eq!(get_ctr(5, 0), 0.0);
+        assert_eq!(get_ctr(0, 10), 0.0);
+        assert_eq!(get_ctr(1, 4), 0.25);
+        assert_eq!(get_ctr(10, 10), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_pages_ctr_unauthorized() {
+        let response = get_analytics_pages_ctr_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Query(PagesCtrQuery {
+                limit: None,
+                offset: None,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_zero_results_queries_unauthorized() {
+        let response = get_analytics_zero_results_queries_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Query(ZeroResultsQueriesQuery {
+                limit: None,
+                offset: None,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_zero_results_query_response() {
+        let queries = vec![ZeroResultsQuery {
+            query: "missing".to_string(),
+            count: 2,
+            last_seen: 1_700_000_000_000,
+        }];
+
+        assert_eq!(
+            serde_json::to_string(&queries).unwrap(),
+            r#"[{"query":"missing","count":2,"last_seen":1700000000000}]"#
+        );
+    }
+
+    #[test]
+    fn test_click_body_search_id() {
+        let body: ClickAnalyticsBody =
+            serde_json::from_str(r#"{"page_url":"https://example.com/","search_id":42}"#).unwrap();
+        assert_eq!(body.page_url, "https://example.com/");
+        assert_eq!(body.search_id, Some(42));
+
+        let body: ClickAnalyticsBody =
+            serde_json::from_str(r#"{"page_url":"https://example.com/"}"#).unwrap();
+        assert_eq!(body.search_id, None);
+    }
+}
@@ -1,30 +1,113 @@
-use crate::environment::{ApiState, Environment};
+use crate::{
+    auth::is_valid_api_key,
+    environment::{ApiState, Environment},
+    middleware::auth::{create_token, AuthenticatedUser, TOKEN_LIFETIME},
+};
+use ::favicons::get_favicons_directory;
 use axum::{
-    extract::{Query, State},
-    http::{self, header::USER_AGENT, HeaderMap, StatusCode},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, USER_AGENT},
+        HeaderMap, StatusCode,
+    },
     response::{IntoResponse, Json, Response},
+    Extension,
 };
-use base64::{engine::general_purpose::STANDARD, Engine as _};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use database::{
-    models::{NewPageAnalytics, NewQuery, NewQueuedPage, Page, PageAnalytics, Word},
-    schema::{indexes, pages, pages_analytics, queries, queue, words},
+    models::{
+        NewPageAnalytics, NewPageAnalyticsHistory, NewQuery, NewQueuedPage, Page, PageAnalytics,
+        Word,
+    },
+    schema::{
+        favicons, indexes, page_alternates, pages, pages_analytics, pages_analytics_history,
+        queries, queue, votes, words,
+    },
+    types::VoteType,
     DbConn,
 };
 use diesel::{
-    dsl::sql, prelude::QueryableByName, sql_query, BoolExpressionMethods, BoxableExpression,
-    ExpressionMethods, JoinOnDsl, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
-    TextExpressionMethods,
+    dsl::{count_star, sql, sum},
+    pg::Pg,
+    prelude::QueryableByName,
+    query_builder::QueryFragment,
+    query_dsl::{methods::ExecuteDsl, LoadQuery},
+    sql_query, BoolExpressionMethods, BoxableExpression, ExpressionMethods, JoinOnDsl,
+    NullableExpressionMethods, OptionalExtension, PgTextExpressionMethods, QueryDsl, QueryResult,
+    RunQueryDsl, SelectableHelper, TextExpressionMethods,
 };
+use lru::LruCache;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, env, fs, sync::Arc, time::Instant};
-use utils::{safe_slice, sql::get_sql_timestamp, url::normalize_url};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::SocketAddr,
+    path::{Path as FilePath, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use utils::{
+    generate_snippet, get_timestamp, levenshtein, normalize_language_tag, normalize_text,
+    safe_slice, split_words,
+    sql::get_sql_timestamp,
+    stem_word,
+    stopwords::is_stopword,
+    url::{get_registered_domain, normalize_url},
+};
 use utoipa_axum::{router::OpenApiRouter, routes};
 
+/// Pages whose content changed during this window get a freshness bonus
+pub const CONTENT_FRESHNESS_WINDOW: i64 = 86_400_000 * 7;
+
+/// Pages published during this window get a score bonus, decreasing with their age
+pub const PUBLISHED_FRESHNESS_WINDOW: i64 = 86_400_000 * 30;
+
+/// Score bonus of a page published right now
+pub const MAX_FRESHNESS_BONUS: f32 = 10.0;
+
+/// Number of results of a search results page
+pub const RESULTS_PER_PAGE: usize = 10;
+
+/// Number of characters kept around a word in the word details snippets
+pub const WORD_SNIPPET_RADIUS: usize = 60;
+
+/// Max length of the search results snippets, in characters
+pub const SEARCH_SNIPPET_MAX_CHARS: usize = 160;
+
+pub const MAX_SUGGESTIONS: usize = 10;
+
+/// Number of most frequent words of a page used to find its related pages
+pub const RELATED_PAGES_WORDS: i64 = 5;
+
+pub const MAX_RELATED_PAGES: i64 = 10;
+
+/// Below this number of results, a spelling correction is suggested
+pub const DID_YOU_MEAN_THRESHOLD: usize = 3;
+
+/// Maximum Levenshtein distance of a spelling correction
+pub const MAX_CORRECTION_DISTANCE: usize = 2;
+
+/// Words of a quoted phrase matched consecutively, each one is a join of the phrase query.
+/// The next words of a longer phrase are plain terms.
+pub const MAX_PHRASE_WORDS: usize = 8;
+
+/// Indexed words compared to a misspelled word
+pub const MAX_CORRECTION_CANDIDATES: i64 = 2000;
+
+/// Requests per minute allowed on `GET /api/suggest` for an IP
+pub const SUGGEST_RATE_LIMIT: u32 = 60;
+
 pub fn create_base_router() -> OpenApiRouter<ApiState> {
     OpenApiRouter::new()
         .routes(routes!(get_ping_handler))
         .routes(routes!(get_search_handler))
+        .routes(routes!(post_token_handler))
         .routes(routes!(post_request_url_handler))
+        .routes(routes!(post_request_urls_handler))
+        .routes(routes!(get_word_handler))
+        .routes(routes!(get_suggest_handler))
+        .routes(routes!(get_related_handler))
+        .routes(routes!(get_favicon_handler))
+        .routes(routes!(get_page_handler, delete_page_handler))
 }
 
 #[utoipa::path(
@@ -40,6 +123,52 @@ async fn get_ping_handler() -> StatusCode {
     StatusCode::OK
 }
 
+#[derive(Deserialize)]
+struct TokenBody {
+    api_key: String,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub struct TokenResponse {
+    /// To send in the 'Authorization: Bearer' header
+    token: String,
+    /// Expiration timestamp in milliseconds
+    expires_at: i64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/token",
+    description = "Get a token for the protected routes from your API_KEY. It expires after 24 hours",
+    responses(
+        (status = OK, body = TokenResponse),
+        (status = UNAUTHORIZED, description = "Invalid API key"),
+    )
+)]
+#[axum::debug_handler]
+async fn post_token_handler(
+    State(state): State<Arc<Environment>>,
+    Json(payload): Json<TokenBody>,
+) -> Response {
+    if !is_valid_api_key(&payload.api_key, state.api_key.as_deref()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let Some(secret) = &state.jwt_secret else {
+        eprintln!("[API] JWT_SECRET is not set");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let now = get_timestamp().as_secs();
+    let token = create_token("api", secret, now).unwrap();
+
+    Json(TokenResponse {
+        token,
+        expires_at: ((now + TOKEN_LIFETIME) * 1000) as i64,
+    })
+    .into_response()
+}
+
 #[derive(Deserialize)]
 struct RequestUrlBody {
     url: String,
@@ -48,7 +177,7 @@ struct RequestUrlBody {
 #[utoipa::path(
     post,
     path = "/request-url",
-    description = "Add a new url to the queue. The 'Authorization' header with your API_KEY is required",
+    description = "Add a new url to the queue. A token from /api/token is required in the 'Authorization: Bearer' header",
     responses(
         (status = PROCESSING, description = "URL already in the queue"),
         (status = CREATED, description = "URL already crawled (not necessarily indexed)"),
@@ -58,37 +187,19 @@ struct RequestUrlBody {
 )]
 #[axum::debug_handler]
 async fn post_request_url_handler(
-    headers: HeaderMap,
+    user: Option<Extension<AuthenticatedUser>>,
     State(state): State<Arc<Environment>>,
     Json(payload): Json<RequestUrlBody>,
 ) -> StatusCode {
-    if let Ok(api_key) = env::var("API_KEY") {
-        if let Some(authorization) = headers.get(http::header::AUTHORIZATION) {
-            if authorization.to_str().ok().unwrap_or("None") != api_key {
-                return StatusCode::UNAUTHORIZED;
-            }
-        } else {
-            return StatusCode::UNAUTHORIZED;
-        }
-    } else {
-        eprintln!("[API] API_KEY not found in .env");
+    if user.is_none() {
         return StatusCode::UNAUTHORIZED;
     }
 
-    let db_conn = &mut state.db_pool.get().unwrap();
-
-    if let Some((url, domain)) = normalize_url(&payload.url) {
-        if url.to_string().len() > 1024 {
-            return StatusCode::BAD_REQUEST;
-        }
-
-        let scheme = url.scheme();
-        if scheme != "http" && scheme != "https" {
-            return StatusCode::BAD_REQUEST;
-        }
+    let db_conn = &mut state.get_conn();
 
+    if let Some((url, domain)) = get_requested_url(&payload.url) {
         let in_queue = queue::table
-            .filter(queue::url.eq(url.to_string()))
+            .filter(queue::url.eq(&url))
             .select(queue::id)
             .first::<i32>(db_conn)
             .optional()
@@ -99,7 +210,7 @@ async fn post_request_url_handler(
         }
 
         let in_pages = pages::table
-            .filter(pages::url.eq(url.to_string()))
+            .filter(pages::url.eq(&url))
             .select(pages::id)
             .first::<i32>(db_conn)
             .optional()
@@ -110,9 +221,13 @@ async fn post_request_url_handler(
         }
 
         let new_element = NewQueuedPage {
-            url: url.to_string(),
-            domain: domain.clone(),
+            url,
+            registered_domain: get_registered_domain(&domain),
+            domain,
             timestamp: 0, // Old timestamp so they are processed first
+            depth: 0,
+            redirect_depth: 0,
+            recrawl: false,
         };
 
         diesel::insert_into(queue::table)
@@ -127,25 +242,284 @@ async fn post_request_url_handler(
     StatusCode::BAD_REQUEST
 }
 
+/// Get the normalized URL and domain of a requested URL, `None` if it cannot be crawled
+fn get_requested_url(url: &str) -> Option<(String, String)> {
+    let (url, domain) = normalize_url(url, true)?;
+
+    let scheme = url.scheme();
+    if url.as_str().len() > 1024 || (scheme != "http" && scheme != "https") {
+        return None;
+    }
+
+    Some((url.to_string(), domain))
+}
+
+pub const MAX_REQUESTED_URLS: usize = 100;
+
+#[derive(Deserialize)]
+struct RequestUrlsBody {
+    urls: Vec<String>,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq, Default)]
+pub struct RequestUrlsResponse {
+    /// URLs added to the queue
+    accepted: i32,
+    /// URLs already crawled (not necessarily indexed)
+    already_crawled: i32,
+    /// URLs already in the queue
+    already_queued: i32,
+    invalid: i32,
+}
+
+/// Sort the requested URLs, returns the `(url, domain)` to add to the queue.
+/// Duplicates are only counted once.
+fn sort_requested_urls(
+    urls: &[String],
+    queued: &HashSet<String>,
+    crawled: &HashSet<String>,
+) -> (Vec<(String, String)>, RequestUrlsResponse) {
+    let mut response = RequestUrlsResponse::default();
+    let mut seen = HashSet::new();
+    let mut accepted = Vec::new();
+
+    for url in urls {
+        let (url, domain) = if let Some(requested) = get_requested_url(url) {
+            requested
+        } else {
+            response.invalid += 1;
+            continue;
+        };
+
+        if !seen.insert(url.clone()) {
+            continue;
+        }
+
+        if queued.contains(&url) {
+            response.already_queued += 1;
+        } else if crawled.contains(&url) {
+            response.already_crawled += 1;
+        } else {
+            response.accepted += 1;
+            accepted.push((url, domain));
+        }
+    }
+
+    (accepted, response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/request-urls",
+    description = "Add up to 100 urls to the queue. A token from /api/token is required in the 'Authorization: Bearer' header",
+    responses(
+        (status = OK, body = RequestUrlsResponse),
+        (status = BAD_REQUEST, description = "No URLs or more than 100"),
+        (status = UNAUTHORIZED),
+    )
+)]
+#[axum::debug_handler]
+async fn post_request_urls_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    Json(payload): Json<RequestUrlsBody>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    if payload.urls.is_empty() || payload.urls.len() > MAX_REQUESTED_URLS {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let db_conn = &mut state.get_conn();
+
+    let urls: Vec<String> = payload
+        .urls
+        .iter()
+        .filter_map(|url| get_requested_url(url))
+        .map(|(url, _)| url)
+        .collect();
+
+    let queued: HashSet<String> = queue::table
+        .filter(queue::url.eq_any(&urls))
+        .select(queue::url)
+        .load::<String>(db_conn)
+        .expect("Error checking queue")
+        .into_iter()
+        .collect();
+
+    let crawled: HashSet<String> = pages::table
+        .filter(pages::url.eq_any(&urls))
+        .select(pages::url)
+        .load::<String>(db_conn)
+        .expect("Error checking pages")
+        .into_iter()
+        .collect();
+
+    let (accepted, response) = sort_requested_urls(&payload.urls, &queued, &crawled);
+
+    let new_elements: Vec<NewQueuedPage> = accepted
+        .into_iter()
+        .map(|(url, domain)| NewQueuedPage {
+            url,
+            registered_domain: get_registered_domain(&domain),
+            domain,
+            timestamp: 0, // Old timestamp so they are processed first
+            depth: 0,
+            redirect_depth: 0,
+            recrawl: false,
+        })
+        .collect();
+
+    diesel::insert_into(queue::table)
+        .values(new_elements)
+        .on_conflict(queue::url)
+        .do_nothing()
+        .execute(db_conn)
+        .unwrap();
+
+    println!("[API] {} new URLs added to the queue", response.accepted);
+    Json(response).into_response()
+}
+
 #[derive(Deserialize)]
 pub struct SearchQuery {
+    /// The search query. Words between double quotes must be consecutive,
+    /// words prefixed with `-` are excluded from the results,
+    /// `site:example.com` only returns the pages of a domain
+    /// and `inurl:keyword` / `intitle:keyword` the pages whose URL / title contain the keyword
     q: String,
     p: i32,
+    ranking: Option<String>,
+    /// Used if the query does not have a `site:` operator
+    site: Option<String>,
+    /// Only pages crawled at or after this timestamp (ms)
+    after: Option<i64>,
+    /// Only pages crawled at or before this timestamp (ms)
+    before: Option<i64>,
+    /// Only pages in this language (e.g. `en`)
+    lang: Option<String>,
+    /// The `next_cursor` of the previous page, `p` is ignored when it is set
+    cursor: Option<String>,
+    /// `relevance` (default), `newest` or `oldest`
+    sort: Option<String>,
 }
 
-#[derive(utoipa::ToSchema, Serialize)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum SearchSort {
+    Relevance,
+    /// Most recently crawled pages first
+    Newest,
+    /// Least recently crawled pages first
+    Oldest,
+}
+
+/// The cached searches are searched again after this delay, to return the newly indexed pages
+pub const SEARCH_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// The normalized query and all the search parameters, kept apart so they cannot collide
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchCacheKey {
+    query: String,
+    page: i32,
+    ranking: Option<String>,
+    site: Option<String>,
+    after: Option<i64>,
+    before: Option<i64>,
+    lang: Option<String>,
+    cursor: Option<String>,
+    sort: Option<String>,
+}
+
+pub struct CachedSearch {
+    response: SearchResponse,
+    cached_at: Instant,
+}
+
+impl CachedSearch {
+    pub fn new(response: SearchResponse, cached_at: Instant) -> Self {
+        Self {
+            response,
+            cached_at,
+        }
+    }
+}
+
+pub type SearchCache = LruCache<SearchCacheKey, CachedSearch>;
+
+fn get_search_cache_key(query: &SearchQuery, user_query: &str) -> SearchCacheKey {
+    SearchCacheKey {
+        query: user_query.to_string(),
+        page: query.p,
+        ranking: query.ranking.clone(),
+        site: query.site.clone(),
+        after: query.after,
+        before: query.before,
+        lang: query.lang.clone(),
+        cursor: query.cursor.clone(),
+        sort: query.sort.clone(),
+    }
+}
+
+/// The cached response of a search, the expired one is removed
+fn get_cached_search(
+    cache: &Mutex<SearchCache>,
+    key: &SearchCacheKey,
+    now: Instant,
+) -> Option<SearchResponse> {
+    let mut cache = cache.lock().unwrap();
+
+    let cached = cache.get(key)?;
+    if now.saturating_duration_since(cached.cached_at) < SEARCH_CACHE_TTL {
+        return Some(cached.response.clone());
+    }
+
+    cache.pop(key);
+    None
+}
+
+/// Remove the cached searches containing a page, e.g. when its analytics changed
+pub(crate) fn evict_cached_searches(cache: &Mutex<SearchCache>, page_url: &str) {
+    let mut cache = cache.lock().unwrap();
+
+    let keys: Vec<SearchCacheKey> = cache
+        .iter()
+        .filter(|(_, cached)| cached.response.results.iter().any(|r| r.url == page_url))
+        .map(|(key, _)| key.clone())
+        .collect();
+
+    for key in keys {
+        cache.pop(&key);
+    }
+}
+
+/// `after` must be strictly lower than `before` when both are set
+fn is_valid_date_range(after: Option<i64>, before: Option<i64>) -> bool {
+    if let (Some(after), Some(before)) = (after, before) {
+        after < before
+    } else {
+        true
+    }
+}
+
+#[derive(utoipa::ToSchema, Serialize, Clone)]
 pub struct ResultPageMetadata {
     title: Option<String>,
     description: Option<String>,
     theme_color: Option<String>,
     keywords: Option<String>,
     image: Option<String>,
+    /// Open Graph type of the page (e.g. `article`)
+    og_type: Option<String>,
+    og_site_name: Option<String>,
 }
 
-#[derive(utoipa::ToSchema, Serialize)]
+#[derive(utoipa::ToSchema, Serialize, Clone)]
 pub struct ResultPage {
     url: String,
-    favicon: Option<String>,
+    /// Path of the favicon, e.g. `/api/favicon/42`
+    favicon_url: Option<String>,
     score: f32,
     clicks: i32,
     impressions: i32,
@@ -153,16 +527,37 @@ pub struct ResultPage {
     dislikes: i32,
     crawled_at: i64,
     indexed_at: i64,
+    /// Publication timestamp of the article
+    published_at: Option<i64>,
+    /// Where the URL redirects to, when it redirected at its last crawl
+    final_url: Option<String>,
+    /// Primary language subtag of the page (e.g. `en`)
+    language: Option<String>,
+    /// Total number of words of the page
+    word_count: i32,
+    /// Excerpt of the page with the query words wrapped in `**...**`, or the meta description
+    snippet: Option<String>,
     metadata: ResultPageMetadata,
 }
 
-#[derive(utoipa::ToSchema, Serialize)]
+#[derive(utoipa::ToSchema, Serialize, Clone)]
 pub struct SearchResponse {
+    /// The id of the search, to send with the clicks analytics
+    search_id: i64,
     results: Vec<ResultPage>,
-    time: i32,
+    /// Search time in milliseconds
+    time: i64,
     page: i32,
+    /// Deprecated: approximate, use `next_cursor` to paginate
+    #[schema(deprecated)]
     total_pages: i32,
+    /// Deprecated: approximate, use `next_cursor` to paginate
+    #[schema(deprecated)]
     total_results: i32,
+    /// Cursor of the next page of results, if there is one
+    next_cursor: Option<String>,
+    /// Corrected query, if there are only a few results
+    did_you_mean: Option<String>,
 }
 
 #[utoipa::path(
@@ -170,11 +565,19 @@ pub struct SearchResponse {
     path = "/search",
     description = "Search the web",
     params(
-        ("q" = String, Query, description = "The search query"),
-        ("p" = String, Query, description = "The page")
+        ("q" = String, Query, description = "The search query. Supports `\"exact phrases\"` of up to 8 words, `-excluded` words, `site:example.com`, `inurl:keyword` and `intitle:keyword`. The operators can be repeated, pages must match all of them"),
+        ("p" = String, Query, description = "The page"),
+        ("ranking" = Option<String>, Query, description = "`heuristic` or `bm25` (default: `heuristic`)"),
+        ("site" = Option<String>, Query, description = "Only return the pages of this domain, if the query has no `site:` operator"),
+        ("after" = Option<i64>, Query, description = "Only return the pages crawled at or after this Unix timestamp in milliseconds"),
+        ("before" = Option<i64>, Query, description = "Only return the pages crawled at or before this Unix timestamp in milliseconds"),
+        ("lang" = Option<String>, Query, description = "Only return the pages in this language, e.g. `en`"),
+        ("cursor" = Option<String>, Query, description = "The `next_cursor` of the previous response. When set, `p` is ignored"),
+        ("sort" = Option<String>, Query, description = "`relevance`, `newest` or `oldest` (default: `relevance`). Pages sorted by crawl date have a score of 0 and no cursor")
     ),
     responses(
-        (status = OK, body = SearchResponse)
+        (status = OK, body = SearchResponse),
+        (status = BAD_REQUEST, description = "Invalid query, page, ranking, sort, date range, language or cursor, or only excluded words and operators")
     ),
 )]
 #[axum::debug_handler]
@@ -193,21 +596,154 @@ pub async fn get_search_handler(
         return StatusCode::BAD_REQUEST.into_response();
     }
 
+    let use_bm25 = match query.ranking.as_deref() {
+        None | Some("heuristic") => false,
+        Some("bm25") => true,
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let sort = match query.sort.as_deref() {
+        None | Some("relevance") => SearchSort::Relevance,
+        Some("newest") => SearchSort::Newest,
+        Some("oldest") => SearchSort::Oldest,
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    // Only the results sorted by relevance have a cursor
+    if sort != SearchSort::Relevance && query.cursor.is_some() {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    if !is_valid_date_range(query.after, query.before) {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let cursor = match query.cursor.as_deref().map(SearchCursor::decode) {
+        None => None,
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let mut parsed_query = parse_query(&user_query);
+    if parsed_query.is_empty() {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    parsed_query.after = query.after;
+    parsed_query.before = query.before;
+    parsed_query.stemming = state.stemming;
+
+    if let Some(lang) = &query.lang {
+        if let Some(lang) = normalize_language_tag(lang) {
+            parsed_query.language = Some(lang);
+        } else {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    }
+
+    if parsed_query.site.is_none() {
+        if let Some(site) = &query.site {
+            if let Some(site) = clean_site(site) {
+                parsed_query.site = Some(site);
+            } else {
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+        }
+    }
+
+    let cache_key = get_search_cache_key(&query, &user_query);
+    if let Some(cached) = get_cached_search(&state.search_cache, &cache_key, Instant::now()) {
+        // Already counted in the analytics
+        return Json(cached).into_response();
+    }
+
     let start = Instant::now();
-    let db_conn = &mut state.db_pool.get().unwrap();
+    let mut read_conn = state.get_read_conn();
+    let db_conn = &mut read_conn;
+
+    let limit = RESULTS_PER_PAGE;
+    let offset = ((page as usize) - 1) * limit;
+    // One more result is fetched to know if there are more results
+    let range = ResultsRange::new(cursor.as_ref(), offset as i64, limit as i64 + 1);
 
-    let search_results = search_pages(db_conn, user_query.clone());
-    // let scores = tf_idf(db_conn, user_query.clone());
+    let search_results = match sort {
+        SearchSort::Relevance if use_bm25 => {
+            bm25_search(db_conn, &parsed_query, state.bm25.k1, state.bm25.b, &range)
+        }
+        SearchSort::Relevance => search_pages(db_conn, &parsed_query, &range),
+        SearchSort::Newest | SearchSort::Oldest => search_pages_by_date(
+            db_conn,
+            &parsed_query,
+            sort == SearchSort::Newest,
+            range.offset,
+            range.limit,
+        ),
+    };
 
-    let limit = 10usize;
-    let offset_start = ((page as usize) - 1) * limit;
-    let offset_end = offset_start + limit;
-    let results_len = search_results.len();
-    let paginated = &search_results[offset_start..offset_end.min(results_len)];
+    let (paginated, mut next_cursor) = get_results_page(search_results, limit, range.now);
+    let results_len = get_results_count(offset, &paginated);
+    if sort != SearchSort::Relevance {
+        next_cursor = None;
+    }
     let total_pages = results_len / limit;
-    let time_taken = start.elapsed().as_nanos();
+    let time_taken = get_search_time(start.elapsed());
+
+    let page_ids: Vec<i32> = paginated.iter().map(|x| x.page.id).collect();
+    let result_pages = get_result_pages(db_conn, &paginated, &parsed_query.words());
+
+    let did_you_mean = if results_len < DID_YOU_MEAN_THRESHOLD {
+        get_did_you_mean(db_conn, &parsed_query).unwrap()
+    } else {
+        None
+    };
+    // Without a replica both connections come from the same pool
+    drop(read_conn);
+
+    // Analytics, written to the primary database
+    let db_conn = &mut state.get_conn();
+    let search_id = diesel::insert_into(queries::table)
+        .values(NewQuery {
+            query: user_query.clone(),
+            timestamp: get_sql_timestamp(),
+            search_time: time_taken,
+            result_count: results_len as i32,
+            zero_results: results_len == 0,
+            user_agent: headers
+                .get(USER_AGENT)
+                .map(|h| safe_slice(h.to_str().unwrap_or(""), 255).to_string()),
+        })
+        .returning(queries::id)
+        .get_result::<i32>(db_conn)
+        .unwrap();
+
+    increment_impressions(db_conn, page_ids, Some(search_id)).unwrap();
+
+    // Response
+    let search_response = SearchResponse {
+        search_id: search_id as i64,
+        results: result_pages,
+        time: time_taken,
+        page,
+        total_pages: total_pages as i32,
+        total_results: results_len as i32,
+        next_cursor: next_cursor.map(|c| c.encode()),
+        did_you_mean,
+    };
+
+    state.search_cache.lock().unwrap().put(
+        cache_key,
+        CachedSearch::new(search_response.clone(), Instant::now()),
+    );
 
-    let page_ids: Vec<i32> = paginated.iter().map(|x| x.0.id).collect();
+    Json(search_response).into_response()
+}
+
+/// Build the results of the pages, with their analytics and votes
+fn get_result_pages(
+    db_conn: &mut DbConn,
+    pages: &[RankedPage],
+    query_words: &[String],
+) -> Vec<ResultPage> {
+    let page_ids: Vec<i32> = pages.iter().map(|x| x.page.id).collect();
     let mut result_pages = Vec::new();
 
     let analytics = pages_analytics::table
@@ -215,9 +751,13 @@ pub async fn get_search_handler(
         .filter(pages_analytics::page_id.eq_any(page_ids.clone()))
         .get_results::<PageAnalytics>(db_conn)
         .unwrap();
-    let votes = get_vote_counts(db_conn, page_ids.clone()).unwrap();
+    let votes = get_vote_counts(db_conn, page_ids).unwrap();
+    let favicon_ids: Vec<i32> = pages.iter().map(|x| x.page.favicon_id).collect();
+    let downloaded_favicons = get_downloaded_favicons(db_conn, &favicon_ids).unwrap();
+
+    let query_words: Vec<&str> = query_words.iter().map(String::as_str).collect();
 
-    for (page, score) in paginated {
+    for RankedPage { page, score, .. } in pages {
         // Should be valid
         let last_indexed = page.last_indexed.unwrap();
 
@@ -226,241 +766,2909 @@ pub async fn get_search_handler(
 
         result_pages.push(ResultPage {
             url: page.url.clone(),
-            favicon: get_page_favicon(page.favicon_id),
-            score: score.clone(),
+            favicon_url: get_page_favicon_url(page.favicon_id, &downloaded_favicons),
+            score: *score as f32,
             clicks: page_analytics.map(|x| x.clicks).unwrap_or(0),
             impressions: page_analytics.map(|x| x.impressions).unwrap_or(0),
             likes: page_votes.map(|x| x.like_count as i32).unwrap_or(0),
             dislikes: page_votes.map(|x| x.dislike_count as i32).unwrap_or(0),
             crawled_at: page.last_crawled,
             indexed_at: last_indexed,
+            published_at: page.published_at,
+            final_url: page.final_url.clone(),
+            language: page.language.clone(),
+            word_count: page.word_count,
+            snippet: page
+                .content
+                .as_deref()
+                .and_then(|content| {
+                    generate_snippet(content, &query_words, SEARCH_SNIPPET_MAX_CHARS)
+                })
+                .or_else(|| page.meta_description.clone()),
             metadata: ResultPageMetadata {
                 title: page.title.clone(),
                 description: page.meta_description.clone(),
                 theme_color: page.meta_theme_color.clone(),
                 keywords: page.meta_keywords.clone(),
                 image: page.meta_og_image.clone(),
+                og_type: page.meta_og_type.clone(),
+                og_site_name: page.meta_og_site_name.clone(),
             },
         });
     }
 
-    // Analytics
-    increment_impressions(db_conn, page_ids).unwrap();
-
-    diesel::insert_into(queries::table)
-        .values(NewQuery {
-            query: user_query.clone(),
-            timestamp: get_sql_timestamp(),
-            search_time: time_taken as i32,
-            result_count: results_len as i32,
-            user_agent: headers
-                .get(USER_AGENT)
-                .map(|h| safe_slice(h.to_str().unwrap_or(""), 255).to_string()),
-        })
-        .execute(db_conn)
-        .unwrap();
+    result_pages
+}
 
-    // Response
-    let search_response = SearchResponse {
-        results: result_pages,
-        time: time_taken as i32,
-        page,
-        total_pages: total_pages as i32,
-        total_results: results_len as i32,
-    };
+/// A search result, with its position in the ranking
+pub(crate) struct RankedPage {
+    pub page: Page,
+    pub score: f64,
+    /// Orders the pages with the same score
+    pub url_score: f64,
+}
 
-    Json(search_response).into_response()
+impl From<(Page, f64, f64)> for RankedPage {
+    fn from((page, score, url_score): (Page, f64, f64)) -> Self {
+        Self {
+            page,
+            score,
+            url_score,
+        }
+    }
 }
 
-#[derive(QueryableByName)]
-pub struct VoteCount {
-    #[diesel(sql_type = diesel::sql_types::Integer)]
-    pub page_id: i32,
+/// Position in the ranked results, the last page of the previous results page.
+/// The ranking timestamp is kept for the scores of the next pages to be comparable.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SearchCursor {
+    page_id: i32,
+    score: f64,
+    url_score: f64,
+    now: i64,
+}
 
-    #[diesel(sql_type = diesel::sql_types::BigInt)]
-    pub like_count: i64,
+impl SearchCursor {
+    /// Base64 of `page_id:score:url_score:now`
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}:{}:{}",
+            self.page_id, self.score, self.url_score, self.now
+        ))
+    }
 
-    #[diesel(sql_type = diesel::sql_types::BigInt)]
-    pub dislike_count: i64,
-}
+    fn decode(cursor: &str) -> Option<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let mut parts = decoded.split(':');
 
-pub fn get_vote_counts(conn: &mut DbConn, ids: Vec<i32>) -> QueryResult<Vec<VoteCount>> {
-    let ids_str = ids
-        .iter()
-        .map(|id| id.to_string())
-        .collect::<Vec<_>>()
-        .join(",");
+        let cursor = Self {
+            page_id: parts.next()?.parse().ok()?,
+            score: parts.next()?.parse().ok()?,
+            url_score: parts.next()?.parse().ok()?,
+            now: parts.next()?.parse().ok()?,
+        };
+        if parts.next().is_some() || !cursor.score.is_finite() || !cursor.url_score.is_finite() {
+            return None;
+        }
 
-    if ids.is_empty() {
-        return Ok(vec![]);
+        Some(cursor)
     }
+}
 
-    let query = format!(
-        r#"
-        SELECT 
-            page_id,
-            COUNT(*) FILTER (WHERE vote_type = 1) AS like_count,
-            COUNT(*) FILTER (WHERE vote_type = 2) AS dislike_count
-        FROM votes
-        WHERE page_id IN ({})
-        GROUP BY page_id
-    "#,
-        ids_str
-    );
-
-    sql_query(query).load::<VoteCount>(conn)
+/// Slice of the ranked results to load, after the cursor or from the offset
+pub(crate) struct ResultsRange<'a> {
+    cursor: Option<&'a SearchCursor>,
+    /// Timestamp of the ranking, the one of the cursor when continuing its ranking
+    now: i64,
+    offset: i64,
+    limit: i64,
 }
 
-pub fn get_page_favicon(favicon_id: i32) -> Option<String> {
-    let filename = format!("{}-", favicon_id);
+impl<'a> ResultsRange<'a> {
+    pub(crate) fn new(cursor: Option<&'a SearchCursor>, offset: i64, limit: i64) -> Self {
+        Self {
+            cursor,
+            now: cursor.map(|c| c.now).unwrap_or_else(get_sql_timestamp),
+            offset,
+            limit,
+        }
+    }
+}
 
-    // TODO: use the const from favicons workspace
-    let base_path = env::current_dir().unwrap().join("favicons");
-    let paths = fs::read_dir(&base_path).unwrap();
+/// Results page of the first `limit` results, and the cursor of the next one if there are more results
+fn get_results_page(
+    mut results: Vec<RankedPage>,
+    limit: usize,
+    now: i64,
+) -> (Vec<RankedPage>, Option<SearchCursor>) {
+    if results.len() <= limit {
+        return (results, None);
+    }
+    results.truncate(limit);
 
-    let mut encoded_favicon = None;
+    let next_cursor = results.last().map(|result| SearchCursor {
+        page_id: result.page.id,
+        score: result.score,
+        url_score: result.url_score,
+        now,
+    });
 
-    for path in paths {
-        let path = path.unwrap().file_name().into_string().unwrap();
+    (results, next_cursor)
+}
 
-        if path.starts_with(&filename) {
-            let image_bytes = fs::read(&base_path.join(path)).unwrap();
-            let base64_string = STANDARD.encode(image_bytes);
-            encoded_favicon = Some(base64_string);
-            break;
-        }
-    }
+/// Number of results up to the end of the results page, the look-ahead result is not counted
+fn get_results_count(offset: usize, page: &[RankedPage]) -> usize {
+    offset + page.len()
+}
 
-    encoded_favicon
+#[derive(utoipa::ToSchema, Serialize)]
+pub struct WordPage {
+    url: String,
+    count: i32,
+    snippet: Option<String>,
 }
 
-pub fn increment_impressions(conn: &mut DbConn, page_ids: Vec<i32>) -> QueryResult<()> {
-    if page_ids.is_empty() {
-        return Ok(());
-    }
+#[derive(utoipa::ToSchema, Serialize)]
+pub struct WordDetails {
+    id: i32,
+    word: String,
+    /// Number of pages containing the word
+    document_frequency: i64,
+    is_stopword: bool,
+    /// The 5 pages with the most occurrences of the word
+    top_pages: Vec<WordPage>,
+}
 
-    let new_rows: Vec<NewPageAnalytics> = page_ids
-        .iter()
-        .map(|&id| NewPageAnalytics {
-            page_id: id,
-            clicks: 0,
-            impressions: 1,
-        })
-        .collect();
+#[utoipa::path(
+    get,
+    path = "/words/{word}",
+    description = "Get the index details of a word",
+    params(
+        ("word" = String, Path, description = "The word")
+    ),
+    responses(
+        (status = OK, body = WordDetails),
+        (status = BAD_REQUEST, description = "Invalid word"),
+        (status = NOT_FOUND, description = "The word is not indexed")
+    )
+)]
+#[axum::debug_handler]
+async fn get_word_handler(
+    State(state): State<Arc<Environment>>,
+    Path(word): Path<String>,
+) -> Response {
+    let word = normalize_text(word.trim()).to_lowercase();
+    if word.is_empty() || word.len() > 100 {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
 
-    diesel::insert_into(pages_analytics::table)
-        .values(&new_rows)
-        .on_conflict(pages_analytics::page_id)
-        .do_update()
-        .set(pages_analytics::impressions.eq(pages_analytics::impressions + 1))
-        .execute(conn)?;
+    let db_conn = &mut state.get_read_conn();
 
-    Ok(())
+    // Looked up like the indexer stores it
+    let index_word = if state.stemming {
+        stem_word(&word)
+    } else {
+        word.clone()
+    };
+
+    let record = words::table
+        .select(words::all_columns)
+        .filter(words::word.eq(&index_word))
+        .first::<Word>(db_conn)
+        .optional()
+        .unwrap();
+
+    let record = if let Some(record) = record {
+        record
+    } else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let document_frequency = indexes::table
+        .filter(indexes::word_id.eq(record.id))
+        .count()
+        .get_result::<i64>(db_conn)
+        .unwrap();
+
+    let top_pages = indexes::table
+        .inner_join(pages::table.on(indexes::page_id.eq(pages::id)))
+        .filter(indexes::word_id.eq(record.id))
+        .order(indexes::count.desc())
+        .select((pages::url, indexes::count, pages::content))
+        .limit(5)
+        .load::<(String, i32, Option<String>)>(db_conn)
+        .unwrap();
+
+    Json(WordDetails {
+        id: record.id,
+        is_stopword: is_stopword(&word),
+        word: record.word,
+        document_frequency,
+        top_pages: top_pages
+            .into_iter()
+            .map(|(url, count, content)| WordPage {
+                url,
+                count,
+                snippet: content.and_then(|c| {
+                    get_word_snippet(&c, &word).or_else(|| get_word_snippet(&c, &index_word))
+                }),
+            })
+            .collect(),
+    })
+    .into_response()
 }
 
-fn search_pages(conn: &mut DbConn, query: String) -> Vec<(Page, f32)> {
-    let words_vec: Vec<&str> = query.split_whitespace().collect();
+/// Get the text around the first occurrence of a whole word in a page content
+fn get_word_snippet(content: &str, word: &str) -> Option<String> {
+    let (position, _) = content.match_indices(word).find(|(i, _)| {
+        let before = content[..*i].chars().next_back();
+        let after = content[i + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })?;
+
+    let end_of_word = position + word.len();
+    let start = content[..position]
+        .char_indices()
+        .rev()
+        .nth(WORD_SNIPPET_RADIUS - 1)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let end = content[end_of_word..]
+        .char_indices()
+        .nth(WORD_SNIPPET_RADIUS)
+        .map(|(i, _)| end_of_word + i)
+        .unwrap_or(content.len());
+
+    let mut snippet = content[start..end].trim().to_string();
+    if start > 0 {
+        snippet.insert_str(0, "...");
+    }
+    if end < content.len() {
+        snippet.push_str("...");
+    }
 
-    let mut filter: Box<dyn BoxableExpression<_, _, SqlType = diesel::sql_types::Bool>> =
-        Box::new(pages::url.like(format!("%{}%", words_vec[0])));
+    Some(snippet)
+}
 
-    for w in &words_vec[1..] {
-        filter = Box::new(filter.or(pages::url.like(format!("%{}%", w))));
+#[derive(Deserialize)]
+struct SuggestQuery {
+    q: String,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub struct SuggestResponse {
+    /// Previous queries first, then indexed words
+    suggestions: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/suggest",
+    description = "Get search suggestions starting with a prefix",
+    params(
+        ("q" = String, Query, description = "The prefix, at least 2 characters")
+    ),
+    responses(
+        (status = OK, body = SuggestResponse),
+        (status = BAD_REQUEST, description = "Invalid prefix"),
+        (status = TOO_MANY_REQUESTS, description = "More than 60 requests in the last minute")
+    )
+)]
+#[axum::debug_handler]
+async fn get_suggest_handler(
+    State(state): State<Arc<Environment>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    query: Query<SuggestQuery>,
+) -> Response {
+    let prefix = normalize_text(query.q.trim()).to_lowercase();
+    if prefix.chars().count() < 2 || prefix.len() > 100 {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    if !state.suggest_rate_limiter.check(addr.ip(), Instant::now()) {
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
     }
-    let pages = pages::table
-        .select(pages::all_columns)
-        .filter(pages::last_indexed.is_not_null())
-        .filter(filter)
-        .load::<Page>(conn)
-        .expect("Error loading pages");
 
-    let mut results = Vec::new();
+    let db_conn = &mut state.get_read_conn();
+    let pattern = format!("{}%", escape_like(&prefix));
 
-    for page in pages {
-        let pathname = &page.url;
-        let pathname_len = pathname.len() as f32;
-        let domain_score = 100.0 * (1.0 + ((50.0 - pathname_len.min(50.0)) / 50.0).powf(2.0));
+    let previous_queries = queries::table
+        .select(queries::query)
+        .filter(queries::query.like(pattern))
+        .group_by(queries::query)
+        .order(count_star().desc())
+        .limit(MAX_SUGGESTIONS as i64)
+        .load::<String>(db_conn)
+        .unwrap();
 
-        let mut metadata_multiplier = 1.0;
-        if page.title.is_some() {
-            metadata_multiplier += 0.1;
+    // The stemmed words are suggested in the form they have in the pages
+    let word_prefix = if state.stemming {
+        stem_word(&prefix)
+    } else {
+        prefix
+    };
+    let mut words = words::table
+        .select(words::word)
+        .filter(words::word.like(format!("{}%", escape_like(&word_prefix))))
+        .order(words::word)
+        .limit(MAX_SUGGESTIONS as i64)
+        .load::<String>(db_conn)
+        .unwrap();
+    if state.stemming {
+        let surface_forms = get_surface_forms(db_conn, &words).unwrap();
+        for word in &mut words {
+            if let Some(surface_form) = surface_forms.get(word) {
+                *word = surface_form.clone();
+            }
         }
-        if page.meta_description.is_some() {
-            metadata_multiplier += 0.1;
+    }
+
+    Json(SuggestResponse {
+        suggestions: merge_suggestions(previous_queries, words),
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct RelatedQuery {
+    url: String,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+pub struct RelatedResponse {
+    /// Most related pages first, the score is the combined count of the shared words
+    results: Vec<ResultPage>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/related",
+    description = "Get the pages related to a page, sharing its most frequent words",
+    params(
+        ("url" = String, Query, description = "The URL of the page")
+    ),
+    responses(
+        (status = OK, body = RelatedResponse),
+        (status = BAD_REQUEST, description = "Invalid URL"),
+        (status = NOT_FOUND, description = "Page not found")
+    )
+)]
+#[axum::debug_handler]
+async fn get_related_handler(
+    State(state): State<Arc<Environment>>,
+    query: Query<RelatedQuery>,
+) -> Response {
+    let url = match normalize_url(query.url.trim(), true) {
+        Some((url, _)) => url.to_string(),
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let db_conn = &mut state.get_read_conn();
+
+    let page_id = pages::table
+        .select(pages::id)
+        .filter(pages::url.eq(&url))
+        .filter(pages::deleted_at.is_null())
+        .first::<i32>(db_conn)
+        .optional()
+        .unwrap();
+
+    let page_id = if let Some(page_id) = page_id {
+        page_id
+    } else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let top_words = indexes::table
+        .inner_join(words::table)
+        .select((indexes::word_id, words::word))
+        .filter(indexes::page_id.eq(page_id))
+        .order((indexes::count.desc(), indexes::word_id))
+        .limit(RELATED_PAGES_WORDS)
+        .load::<(i32, String)>(db_conn)
+        .unwrap();
+    let (word_ids, words): (Vec<i32>, Vec<String>) = top_words.into_iter().unzip();
+
+    let scores: HashMap<i32, i64> = get_related_pages_query(page_id, word_ids)
+        .load::<(i32, Option<i64>)>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(|(id, score)| (id, score.unwrap_or(0)))
+        .collect();
+
+    let mut related: Vec<RankedPage> = pages::table
+        .select(pages::all_columns)
+        .filter(pages::id.eq_any(scores.keys().copied().collect::<Vec<_>>()))
+        .load::<Page>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(|page| RankedPage {
+            score: scores[&page.id] as f64,
+            url_score: 0.0,
+            page,
+        })
+        .collect();
+    related.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.page.id.cmp(&b.page.id)));
+
+    Json(RelatedResponse {
+        results: get_result_pages(db_conn, &related, &words),
+    })
+    .into_response()
+}
+
+/// Other pages with the highest combined count of the words.
+/// Only the searchable pages are counted, so they are not dropped after the limit.
+fn get_related_pages_query(
+    page_id: i32,
+    word_ids: Vec<i32>,
+) -> impl LoadQuery<'static, DbConn, (i32, Option<i64>)> + QueryFragment<Pg> {
+    indexes::table
+        .filter(indexes::word_id.eq_any(word_ids))
+        .filter(indexes::page_id.ne(page_id))
+        .filter(indexes::page_id.eq_any(get_searchable_pages().select(pages::id)))
+        .group_by(indexes::page_id)
+        .select((indexes::page_id, sum(indexes::count)))
+        .order((sum(indexes::count).desc(), indexes::page_id))
+        .limit(MAX_RELATED_PAGES)
+}
+
+/// Merge the suggestions without duplicates, keeping the first `MAX_SUGGESTIONS`
+fn merge_suggestions(previous_queries: Vec<String>, words: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+
+    previous_queries
+        .into_iter()
+        .chain(words)
+        .filter(|s| seen.insert(s.clone()))
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+/// Get the query with its unknown words replaced by the closest indexed words.
+/// Returns `None` if no word was corrected.
+fn get_did_you_mean(conn: &mut DbConn, query: &ParsedQuery) -> QueryResult<Option<String>> {
+    let tokens = query.words();
+    let index_words = query.index_words();
+
+    let known_words: HashSet<String> = words::table
+        .select(words::word)
+        .filter(words::word.eq_any(&index_words))
+        .load::<String>(conn)?
+        .into_iter()
+        .collect();
+
+    let mut corrected = false;
+    let mut result = Vec::new();
+
+    for (token, index_word) in tokens.into_iter().zip(index_words) {
+        if known_words.contains(&index_word) {
+            result.push(token);
+            continue;
         }
-        if page.meta_og_image.is_some() {
-            metadata_multiplier += 0.2;
+
+        let candidates = get_correction_candidates_query(&index_word).load::<String>(conn)?;
+
+        if let Some(correction) = get_best_correction(&index_word, &candidates) {
+            corrected = true;
+            if query.stemming {
+                let surface_form =
+                    get_surface_forms(conn, std::slice::from_ref(&correction))?.remove(&correction);
+                result.push(surface_form.unwrap_or(correction));
+            } else {
+                result.push(correction);
+            }
+        } else {
+            result.push(token);
         }
-        if page.seo_score > 0 {
-            metadata_multiplier += (page.seo_score as f32) / 100.0;
+    }
+
+    Ok(corrected.then(|| result.join(" ")))
+}
+
+/// Indexed words which can be a correction of `word`: with the same first letter, typos are
+/// rarely on it, so the prefix index is used, and a close length
+fn get_correction_candidates_query(
+    word: &str,
+) -> words::BoxedQuery<'static, Pg, diesel::sql_types::Text> {
+    let len = word.chars().count() as i32;
+    let first_letter: String = word.chars().take(1).collect();
+
+    words::table
+        .select(words::word)
+        .filter(words::word.like(format!("{}%", escape_like(&first_letter))))
+        .filter(
+            sql::<diesel::sql_types::Bool>("LENGTH(word) BETWEEN ")
+                .bind::<diesel::sql_types::Integer, _>(len - MAX_CORRECTION_DISTANCE as i32)
+                .sql(" AND ")
+                .bind::<diesel::sql_types::Integer, _>(len + MAX_CORRECTION_DISTANCE as i32),
+        )
+        .limit(MAX_CORRECTION_CANDIDATES)
+        .into_boxed()
+}
+
+/// Get the forms of the stemmed words in the pages, from the page with the most occurrences of each word
+fn get_surface_forms(conn: &mut DbConn, stems: &[String]) -> QueryResult<HashMap<String, String>> {
+    let contents = indexes::table
+        .inner_join(words::table)
+        .inner_join(pages::table)
+        .filter(words::word.eq_any(stems))
+        .filter(pages::deleted_at.is_null())
+        .distinct_on(indexes::word_id)
+        .order((indexes::word_id, indexes::count.desc()))
+        .select((words::word, pages::content))
+        .load::<(String, Option<String>)>(conn)?;
+
+    Ok(contents
+        .into_iter()
+        .filter_map(|(stem, content)| {
+            let surface_form = find_surface_form(content.as_deref()?, &stem)?;
+            Some((stem, surface_form))
+        })
+        .collect())
+}
+
+/// The most frequent word of the content with this stem, cleaned like the indexer does
+fn find_surface_form(content: &str, stem: &str) -> Option<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    for word in split_words(&normalize_text(content).to_lowercase()) {
+        if stem_word(word) == stem {
+            *counts.entry(word.to_string()).or_insert(0) += 1;
         }
+    }
 
-        let bonus_score = if page.domain.contains(&query) {
-            50.0
-        } else {
-            0.0
-        };
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(word, _)| word)
+}
+
+/// Get the closest different candidate within `MAX_CORRECTION_DISTANCE`
+fn get_best_correction(token: &str, candidates: &[String]) -> Option<String> {
+    candidates
+        .iter()
+        .filter(|c| c.as_str() != token)
+        .map(|c| (levenshtein(token, c), c))
+        .filter(|(distance, _)| *distance <= MAX_CORRECTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c.clone())
+}
+
+/// Get the duration of a search in milliseconds
+fn get_search_time(elapsed: Duration) -> i64 {
+    elapsed.as_millis().try_into().unwrap_or(i64::MAX)
+}
+
+#[derive(diesel::Queryable)]
+pub struct VoteCount {
+    pub page_id: i32,
+    pub like_count: i64,
+    pub dislike_count: i64,
+}
+
+/// Get the likes and dislikes of the pages with votes
+fn get_vote_counts_query(
+    ids: &[i32],
+) -> impl LoadQuery<'_, DbConn, VoteCount> + QueryFragment<Pg> + '_ {
+    votes::table
+        .filter(votes::page_id.eq_any(ids))
+        .group_by(votes::page_id)
+        .select((
+            votes::page_id,
+            sql::<diesel::sql_types::BigInt>("COUNT(*) FILTER (WHERE vote_type = ")
+                .bind::<diesel::sql_types::Integer, _>(VoteType::Like)
+                .sql(")"),
+            sql::<diesel::sql_types::BigInt>("COUNT(*) FILTER (WHERE vote_type = ")
+                .bind::<diesel::sql_types::Integer, _>(VoteType::Dislike)
+                .sql(")"),
+        ))
+}
+
+pub fn get_vote_counts(conn: &mut DbConn, ids: Vec<i32>) -> QueryResult<Vec<VoteCount>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    get_vote_counts_query(&ids).load::<VoteCount>(conn)
+}
+
+/// Browsers can keep the favicons for a day
+pub const FAVICON_CACHE_CONTROL: &str = "public, max-age=86400";
+
+/// Get the downloaded files of favicons, as `(favicon_id, local_path)`
+fn get_downloaded_favicons_query(
+    favicon_ids: &[i32],
+) -> impl LoadQuery<'_, DbConn, (i32, String)> + QueryFragment<Pg> + '_ {
+    favicons::table
+        .filter(favicons::id.eq_any(favicon_ids))
+        .filter(favicons::local_path.is_not_null())
+        .select((favicons::id, favicons::local_path.assume_not_null()))
+}
 
-        results.push((page, domain_score * metadata_multiplier + bonus_score))
+/// Get the ids of the downloaded favicons among `favicon_ids`, in a single query
+fn get_downloaded_favicons(conn: &mut DbConn, favicon_ids: &[i32]) -> QueryResult<HashSet<i32>> {
+    if favicon_ids.is_empty() {
+        return Ok(HashSet::new());
     }
 
-    results
+    Ok(get_downloaded_favicons_query(favicon_ids)
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// Get the path of a favicon file saved by the favicons downloader,
+/// only its file name is kept so it cannot leave the favicons directory
+fn get_favicon_path(directory: &FilePath, local_path: &str) -> Option<PathBuf> {
+    let file_name = FilePath::new(local_path).file_name()?;
+    Some(directory.join(file_name))
 }
 
-/// TODO: implement
-fn _tf_idf(conn: &mut DbConn, query: String) -> HashMap<i32, f64> {
-    let words_vec: Vec<&str> = query.split_whitespace().collect();
+/// The URL of `GET /api/favicon/{favicon_id}`, if the favicon is in `downloaded`
+pub fn get_page_favicon_url(favicon_id: i32, downloaded: &HashSet<i32>) -> Option<String> {
+    downloaded
+        .contains(&favicon_id)
+        .then(|| format!("/api/favicon/{favicon_id}"))
+}
 
-    let mut filter: Box<dyn BoxableExpression<_, _, SqlType = diesel::sql_types::Bool>> =
-        Box::new(words::word.like(format!("%{}%", words_vec[0])));
+#[utoipa::path(
+    get,
+    path = "/favicon/{favicon_id}",
+    description = "Get a favicon as a 32x32 PNG image",
+    params(
+        ("favicon_id" = i32, Path, description = "The favicon id")
+    ),
+    responses(
+        (status = OK, content_type = "image/png", body = Vec<u8>),
+        (status = NOT_FOUND, description = "The favicon is not downloaded")
+    )
+)]
+#[axum::debug_handler]
+async fn get_favicon_handler(
+    State(state): State<Arc<Environment>>,
+    Path(favicon_id): Path<i32>,
+) -> Response {
+    let db_conn = &mut state.get_read_conn();
+
+    let local_path = favicons::table
+        .find(favicon_id)
+        .select(favicons::local_path)
+        .first::<Option<String>>(db_conn)
+        .optional()
+        .unwrap()
+        .flatten();
+    let path = match local_path.and_then(|x| get_favicon_path(&get_favicons_directory(), &x)) {
+        Some(path) => path,
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
 
-    for w in &words_vec[1..] {
-        filter = Box::new(filter.or(words::word.like(format!("%{}%", w))));
+    match tokio::fs::read(path).await {
+        Ok(bytes) => (
+            [
+                (CONTENT_TYPE, "image/png"),
+                (CACHE_CONTROL, FAVICON_CACHE_CONTROL),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
     }
-    let words: Vec<Word> = words::table
-        .filter(filter)
-        // distinct?
-        .limit(10)
-        .load(conn)
-        .expect("Error loading words");
+}
 
-    let page_count: i64 = pages::table
-        .count()
-        .get_result(conn)
-        .expect("Error counting pages");
+#[derive(Deserialize)]
+struct PageQuery {
+    url: String,
+}
 
-    let words_list: Vec<String> = words.iter().map(|w| w.word.clone()).collect();
+/// Everything known about a page, to understand its ranking
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+struct PageDetail {
+    id: i32,
+    domain: String,
+    url: String,
+    title: Option<String>,
+    favicon_id: i32,
+    /// URL the favicon was downloaded from
+    favicon_source_url: Option<String>,
+    /// Path of the favicon, e.g. `/api/favicon/42`
+    favicon_url: Option<String>,
+    content: Option<String>,
+    body: Option<String>,
+    body_length: i32,
+    content_type: String,
+    response_time: i32,
+    status_code: i32,
+    last_crawled: i64,
+    last_indexed: Option<i64>,
+    seo_score: i32,
+    meta_description: Option<String>,
+    meta_keywords: Option<String>,
+    meta_theme_color: Option<String>,
+    meta_og_image: Option<String>,
+    body_hash: Option<String>,
+    content_changed_at: Option<i64>,
+    has_rss: bool,
+    page_rank: f64,
+    language: Option<String>,
+    canonical_url: Option<String>,
+    previous_hash: Option<String>,
+    /// Where the URL redirected at its last crawl
+    final_url: Option<String>,
+    meta_author: Option<String>,
+    /// Publication timestamp of the structured data
+    published_at: Option<i64>,
+    meta_twitter_card: Option<String>,
+    meta_og_type: Option<String>,
+    meta_og_site_name: Option<String>,
+    meta_og_locale: Option<String>,
+    /// Alternative texts of the images, one per line
+    image_alts: Option<String>,
+    /// When the page was deleted, it is purged by the monitor later
+    deleted_at: Option<i64>,
+    word_count: i32,
+    url_fingerprint: Option<String>,
+    registered_domain: Option<String>,
+    /// URLs of the language variants of the page, by `hreflang`
+    alternates: BTreeMap<String, String>,
+    clicks: i32,
+    impressions: i32,
+    likes: i32,
+    dislikes: i32,
+}
 
-    let result = indexes::table
-        .inner_join(words::table.on(indexes::word_id.eq(words::id)))
-        .inner_join(pages::table.on(indexes::page_id.eq(pages::id)))
-        .filter(words::word.eq_any(&words_list))
+/// A page with its `(clicks, impressions)` and its favicon source URL
+type PageDetailRow = (Page, Option<(i32, i32)>, Option<String>);
+
+/// Get a page by its exact URL, with its analytics and its favicon, even when it is deleted
+fn get_page_detail_query(
+    url: &str,
+) -> impl LoadQuery<'_, DbConn, PageDetailRow> + QueryFragment<Pg> {
+    pages::table
+        .left_join(pages_analytics::table)
+        .left_join(favicons::table)
+        .filter(pages::url.eq(url))
         .select((
-            pages::id,
-            pages::url,
-            words::word,
-            indexes::count,
-            sql::<diesel::sql_types::BigInt>(
-                "(SELECT COUNT(DISTINCT page_id) FROM indexes WHERE word_id = indexes.word_id)",
-            ),
+            Page::as_select(),
+            (pages_analytics::clicks, pages_analytics::impressions).nullable(),
+            favicons::url.nullable(),
         ))
-        .limit(500)
-        .load::<(i32, String, String, i32, i64)>(conn)
+        .limit(1)
+}
+
+/// Get the language variants of a page, as `(lang, url)`
+fn get_page_alternates_query(
+    page_id: i32,
+) -> impl LoadQuery<'static, DbConn, (String, String)> + QueryFragment<Pg> {
+    page_alternates::table
+        .filter(page_alternates::page_id.eq(page_id))
+        .select((page_alternates::lang, page_alternates::url))
+}
+
+fn build_page_detail(
+    (page, analytics, favicon_source_url): PageDetailRow,
+    votes: Option<&VoteCount>,
+    favicon_url: Option<String>,
+    alternates: Vec<(String, String)>,
+) -> PageDetail {
+    // Without `..`, a new column of `Page` does not compile until it is added here
+    let Page {
+        id,
+        domain,
+        url,
+        title,
+        favicon_id,
+        content,
+        body,
+        body_length,
+        content_type,
+        response_time,
+        status_code,
+        last_crawled,
+        last_indexed,
+        seo_score,
+        meta_description,
+        meta_keywords,
+        meta_theme_color,
+        meta_og_image,
+        body_hash,
+        content_changed_at,
+        has_rss,
+        page_rank,
+        language,
+        canonical_url,
+        previous_hash,
+        final_url,
+        meta_author,
+        published_at,
+        meta_twitter_card,
+        meta_og_type,
+        meta_og_site_name,
+        meta_og_locale,
+        image_alts,
+        deleted_at,
+        word_count,
+        url_fingerprint,
+        registered_domain,
+    } = page;
+
+    PageDetail {
+        id,
+        domain,
+        url,
+        title,
+        favicon_id,
+        favicon_source_url,
+        favicon_url,
+        content,
+        body,
+        body_length,
+        content_type,
+        response_time,
+        status_code,
+        last_crawled,
+        last_indexed,
+        seo_score,
+        meta_description,
+        meta_keywords,
+        meta_theme_color,
+        meta_og_image,
+        body_hash,
+        content_changed_at,
+        has_rss,
+        page_rank,
+        language,
+        canonical_url,
+        previous_hash,
+        final_url,
+        meta_author,
+        published_at,
+        meta_twitter_card,
+        meta_og_type,
+        meta_og_site_name,
+        meta_og_locale,
+        image_alts,
+        deleted_at,
+        word_count,
+        url_fingerprint,
+        registered_domain,
+        alternates: alternates.into_iter().collect(),
+        clicks: analytics.map(|(clicks, _)| clicks).unwrap_or(0),
+        impressions: analytics.map(|(_, impressions)| impressions).unwrap_or(0),
+        likes: votes.map(|x| x.like_count as i32).unwrap_or(0),
+        dislikes: votes.map(|x| x.dislike_count as i32).unwrap_or(0),
+    }
+}
+
+fn get_page_detail_response(detail: Option<PageDetail>) -> Response {
+    match detail {
+        Some(detail) => Json(detail).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/page",
+    description = "Get all the data of a page by its exact URL, deleted pages included until they are purged. \
+        Needs a token from /api/token as a bearer token",
+    params(
+        ("url" = String, Query, description = "The URL of the page")
+    ),
+    responses(
+        (status = OK, body = PageDetail),
+        (status = UNAUTHORIZED),
+        (status = NOT_FOUND, description = "The page is not crawled")
+    )
+)]
+#[axum::debug_handler]
+async fn get_page_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    query: Query<PageQuery>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let db_conn = &mut state.get_read_conn();
+
+    let row = get_page_detail_query(&query.url)
+        .get_result::<PageDetailRow>(db_conn)
+        .optional()
         .unwrap();
 
-    println!("tf_idf RESULT: {:#?}", result);
+    let detail = row.map(|row| {
+        let votes = get_vote_counts(db_conn, vec![row.0.id]).unwrap();
+        let downloaded_favicons = get_downloaded_favicons(db_conn, &[row.0.favicon_id]).unwrap();
+        let favicon_url = get_page_favicon_url(row.0.favicon_id, &downloaded_favicons);
+        let alternates = get_page_alternates_query(row.0.id)
+            .load::<(String, String)>(db_conn)
+            .unwrap();
+
+        build_page_detail(row, votes.first(), favicon_url, alternates)
+    });
+
+    get_page_detail_response(detail)
+}
+
+/// Mark a page as deleted, it is purged with everything referencing it by the monitor
+fn get_delete_page_query(
+    url: &str,
+    now: i64,
+) -> impl RunQueryDsl<DbConn> + ExecuteDsl<DbConn> + QueryFragment<Pg> + '_ {
+    diesel::update(pages::table)
+        .filter(pages::url.eq(url))
+        .filter(pages::deleted_at.is_null())
+        .set(pages::deleted_at.eq(now))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/page",
+    description = "Delete a page, it is hidden at once and purged with its analytics, votes, indexes, links, language variants \
+        and unused favicon after 30 days. Needs a token from /api/token as a bearer token. The URL is not crawled again until the page is purged.",
+    params(
+        ("url" = String, Query, description = "The URL of the page")
+    ),
+    responses(
+        (status = NO_CONTENT),
+        (status = UNAUTHORIZED),
+        (status = NOT_FOUND, description = "The page is not crawled")
+    )
+)]
+#[axum::debug_handler]
+async fn delete_page_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    query: Query<PageQuery>,
+) -> StatusCode {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let db_conn = &mut state.get_conn();
+
+    let deleted = get_delete_page_query(&query.url, get_sql_timestamp())
+        .execute(db_conn)
+        .unwrap();
+    if deleted == 0 {
+        return StatusCode::NOT_FOUND;
+    }
 
-    let mut tf_idf_scores = HashMap::new();
+    evict_cached_searches(&state.search_cache, &query.url);
+    println!("[API] Page deleted: {}", query.url);
 
-    for (page_id, url, _word, count, doc_count) in result {
-        let tf = count as f64;
-        let idf = ((page_count + 1) as f64 / (doc_count + 1) as f64).ln() + 1.0;
-        println!("{url}: {count},{page_count},{doc_count}");
+    StatusCode::NO_CONTENT
+}
 
-        *tf_idf_scores.entry(page_id).or_insert(0.0) += tf * idf;
+pub fn increment_impressions(
+    conn: &mut DbConn,
+    page_ids: Vec<i32>,
+    search_id: Option<i32>,
+) -> QueryResult<()> {
+    if page_ids.is_empty() {
+        return Ok(());
     }
 
-    println!("td_idf SCORES: {:#?}", tf_idf_scores);
+    let new_rows: Vec<NewPageAnalytics> = page_ids
+        .iter()
+        .map(|&id| NewPageAnalytics {
+            page_id: id,
+            clicks: 0,
+            impressions: 1,
+        })
+        .collect();
+
+    diesel::insert_into(pages_analytics::table)
+        .values(&new_rows)
+        .on_conflict(pages_analytics::page_id)
+        .do_update()
+        .set(pages_analytics::impressions.eq(pages_analytics::impressions + 1))
+        .execute(conn)?;
 
-    tf_idf_scores
+    let now = get_sql_timestamp();
+    let history_rows: Vec<NewPageAnalyticsHistory> = page_ids
+        .iter()
+        .map(|&id| NewPageAnalyticsHistory {
+            page_id: id,
+            clicks_delta: 0,
+            impressions_delta: 1,
+            timestamp: now,
+            search_id,
+        })
+        .collect();
+
+    diesel::insert_into(pages_analytics_history::table)
+        .values(&history_rows)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct ParsedQuery {
+    /// Plain words
+    pub terms: Vec<String>,
+    /// Double-quoted words which must be consecutive in the page
+    pub phrases: Vec<Vec<String>>,
+    /// Words prefixed with `-`, pages containing them are excluded
+    pub excluded: Vec<String>,
+    /// Domain of the `site:` operator
+    pub site: Option<String>,
+    /// Keywords of the `inurl:` operators, all must be in the page URL
+    pub in_url: Vec<String>,
+    /// Keywords of the `intitle:` operators, all must be in the page title
+    pub in_title: Vec<String>,
+    /// Minimum crawl timestamp, from the search parameters
+    pub after: Option<i64>,
+    /// Maximum crawl timestamp, from the search parameters
+    pub before: Option<i64>,
+    /// Language of the pages, from the search parameters
+    pub language: Option<String>,
+    /// Look up the stem of the words in the index, like the indexer stores them
+    pub stemming: bool,
+}
+
+impl ParsedQuery {
+    /// All the words used to rank the pages
+    pub fn words(&self) -> Vec<String> {
+        self.terms
+            .iter()
+            .cloned()
+            .chain(self.phrases.concat())
+            .collect()
+    }
+
+    /// The words as they are stored in the words table
+    pub fn index_words(&self) -> Vec<String> {
+        self.index_form(self.words())
+    }
+
+    /// The phrases as they are stored in the words table
+    pub fn index_phrases(&self) -> Vec<Vec<String>> {
+        self.phrases
+            .iter()
+            .map(|phrase| self.index_form(phrase.clone()))
+            .collect()
+    }
+
+    fn index_form(&self, words: Vec<String>) -> Vec<String> {
+        if self.stemming {
+            words.iter().map(|w| stem_word(w)).collect()
+        } else {
+            words
+        }
+    }
+
+    /// Returns `true` if there is nothing to search, e.g. only excluded words
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.phrases.is_empty()
+    }
+}
+
+/// Parse the plain terms, double-quoted phrases, `-excluded` words, `site:`, `inurl:` and `intitle:` of a query.
+/// Words are cleaned like the indexer does, single-word phrases are plain terms.
+pub(crate) fn parse_query(q: &str) -> ParsedQuery {
+    let mut parsed = ParsedQuery::default();
+
+    let segments: Vec<&str> = q.split('"').collect();
+    // An odd number of quotes means the last one is not closed
+    let unclosed = q.matches('"').count() % 2 == 1;
+
+    for (i, segment) in segments.iter().enumerate() {
+        let quoted = i % 2 == 1 && !(unclosed && i == segments.len() - 1);
+
+        if quoted {
+            let mut words: Vec<String> = segment
+                .split_whitespace()
+                .flat_map(clean_query_words)
+                .collect();
+            if words.len() > 1 {
+                let extra_words = words.split_off(words.len().min(MAX_PHRASE_WORDS));
+                parsed.phrases.push(words);
+                parsed.terms.extend(extra_words);
+            } else {
+                parsed.terms.extend(words);
+            }
+            continue;
+        }
+
+        for token in segment.split_whitespace() {
+            if let Some(site) = token.strip_prefix("site:") {
+                parsed.site = clean_site(site);
+            } else if let Some(keyword) = token.strip_prefix("inurl:") {
+                parsed.in_url.extend(clean_operator_keyword(keyword));
+            } else if let Some(keyword) = token.strip_prefix("intitle:") {
+                parsed.in_title.extend(clean_operator_keyword(keyword));
+            } else if let Some(excluded) = token.strip_prefix('-') {
+                // Matched against the raw content, so the diacritics are kept
+                parsed.excluded.extend(clean_words(excluded));
+            } else {
+                parsed.terms.extend(clean_query_words(token));
+            }
+        }
+    }
+
+    parsed
+}
+
+/// Split a query token into words like the indexer does, e.g. `open-source` => `open`, `source`
+fn clean_query_words(token: &str) -> Vec<String> {
+    clean_words(&normalize_text(token))
+}
+
+/// Split a token into lowercase words, keeping their diacritics
+fn clean_words(token: &str) -> Vec<String> {
+    split_words(token)
+        .filter_map(|word| {
+            let clean_word = word
+                .to_lowercase()
+                .trim_matches(|c: char| !c.is_alphabetic())
+                .to_string();
+
+            (!clean_word.is_empty() && clean_word.len() <= 100).then_some(clean_word)
+        })
+        .collect()
+}
+
+fn clean_site(site: &str) -> Option<String> {
+    let site = site.trim().trim_end_matches('/').to_lowercase();
+
+    if !site.is_empty() && site.len() <= 100 {
+        Some(site)
+    } else {
+        None
+    }
+}
+
+/// Keyword of the `inurl:` and `intitle:` operators, kept as is to match URL parts like `/blog`
+fn clean_operator_keyword(keyword: &str) -> Option<String> {
+    if !keyword.is_empty() && keyword.len() <= 100 {
+        Some(keyword.to_string())
+    } else {
+        None
+    }
+}
+
+/// Escape the LIKE wildcards of a user input
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+type PageFilter = Box<dyn BoxableExpression<pages::table, Pg, SqlType = diesel::sql_types::Bool>>;
+
+/// SQL filters of the pages matching the query operators
+fn get_query_filters(query: &ParsedQuery) -> Vec<PageFilter> {
+    let mut filters: Vec<PageFilter> = Vec::new();
+
+    for term in &query.excluded {
+        // "-crème" excludes the pages containing "crème" or "creme"
+        let mut forms = vec![term.clone()];
+        let normalized = normalize_text(term);
+        if normalized != *term {
+            forms.push(normalized);
+        }
+
+        for form in forms {
+            let pattern = format!("%{}%", escape_like(&form));
+            filters.push(Box::new(
+                // The query is lowercased, so the words are matched case-insensitively
+                sql::<diesel::sql_types::Bool>("NOT (COALESCE(content, '') ILIKE ")
+                    .bind::<diesel::sql_types::Text, _>(pattern.clone())
+                    .sql(" OR url ILIKE ")
+                    .bind::<diesel::sql_types::Text, _>(pattern)
+                    .sql(")"),
+            ));
+        }
+    }
+
+    if let Some(site) = &query.site {
+        filters.push(Box::new(pages::domain.eq(site.clone())));
+    }
+    // The query is lowercased, so the keywords are matched case-insensitively
+    for keyword in &query.in_url {
+        let pattern = format!("%{}%", escape_like(keyword));
+        filters.push(Box::new(pages::url.ilike(pattern)));
+    }
+    for keyword in &query.in_title {
+        let pattern = format!("%{}%", escape_like(keyword));
+        // Pages without a title never match
+        filters.push(Box::new(pages::title.ilike(pattern).assume_not_null()));
+    }
+    if let Some(after) = query.after {
+        filters.push(Box::new(pages::last_crawled.ge(after)));
+    }
+    if let Some(before) = query.before {
+        filters.push(Box::new(pages::last_crawled.le(before)));
+    }
+    if let Some(language) = &query.language {
+        // Pages without a language never match
+        filters.push(Box::new(
+            pages::language.eq(language.clone()).assume_not_null(),
+        ));
+    }
+
+    filters
+}
+
+#[derive(QueryableByName)]
+struct PageIdResult {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    page_id: i32,
+}
+
+/// Query selecting the pages where the `$1..$len` words are consecutive
+fn build_phrase_query(len: usize) -> String {
+    let mut joins = String::from(
+        "SELECT DISTINCT p0.page_id FROM positions p0 INNER JOIN words w0 ON w0.id = p0.word_id",
+    );
+    let mut conditions = String::from("w0.word = $1");
+
+    for i in 1..len {
+        joins.push_str(&format!(
+            " INNER JOIN positions p{i} ON p{i}.page_id = p0.page_id AND p{i}.position = p0.position + {i}"
+        ));
+        joins.push_str(&format!(" INNER JOIN words w{i} ON w{i}.id = p{i}.word_id"));
+        conditions.push_str(&format!(" AND w{i}.word = ${}", i + 1));
+    }
+
+    format!("{joins} WHERE {conditions}")
+}
+
+/// Get the pages containing all the phrases, `None` if there are no phrases
+fn get_phrase_pages(
+    conn: &mut DbConn,
+    phrases: &[Vec<String>],
+) -> QueryResult<Option<HashSet<i32>>> {
+    let mut result: Option<HashSet<i32>> = None;
+
+    for phrase in phrases {
+        let mut query = sql_query(build_phrase_query(phrase.len())).into_boxed();
+        for word in phrase {
+            query = query.bind::<diesel::sql_types::Text, _>(word.clone());
+        }
+
+        let matches: HashSet<i32> = query
+            .load::<PageIdResult>(conn)?
+            .into_iter()
+            .map(|r| r.page_id)
+            .collect();
+
+        result = Some(match result {
+            Some(previous) => previous.intersection(&matches).copied().collect(),
+            None => matches,
+        });
+    }
+
+    Ok(result)
+}
+
+type RankExpression =
+    Box<dyn BoxableExpression<pages::table, Pg, SqlType = diesel::sql_types::Double>>;
+
+pub(crate) fn search_pages(
+    conn: &mut DbConn,
+    query: &ParsedQuery,
+    range: &ResultsRange,
+) -> Vec<RankedPage> {
+    let page_count: i64 = get_searchable_pages()
+        .count()
+        .get_result(conn)
+        .expect("Error counting pages");
+
+    let idfs: Vec<(i32, f64)> = get_word_doc_counts(conn, &query.index_words())
+        .expect("Error loading words")
+        .into_iter()
+        .map(|(word_id, doc_count)| (word_id, get_tf_idf_idf(page_count, doc_count)))
+        .collect();
+    let word_ids: Vec<i32> = idfs.iter().map(|(word_id, _)| *word_id).collect();
+
+    // Pages containing a word, or with a word in the URL
+    let mut matches: PageFilter = Box::new(
+        pages::id.eq_any(
+            indexes::table
+                .filter(indexes::word_id.eq_any(word_ids))
+                .select(indexes::page_id),
+        ),
+    );
+    for w in &query.words() {
+        matches = Box::new(matches.or(pages::url.like(format!("%{}%", w))));
+    }
+
+    let phrase_pages =
+        get_phrase_pages(conn, &query.index_phrases()).expect("Error matching phrases");
+
+    get_ranked_pages_query(
+        query,
+        matches,
+        get_tf_idf_score_sql(&idfs, range.now),
+        phrase_pages,
+        range,
+    )
+    .load::<(Page, f64, f64)>(conn)
+    .expect("Error loading pages")
+    .into_iter()
+    .map(RankedPage::from)
+    .collect()
+}
+
+/// Pages matching the query sorted by crawl date, without scoring them. The scores are 0.
+pub(crate) fn search_pages_by_date(
+    conn: &mut DbConn,
+    query: &ParsedQuery,
+    newest_first: bool,
+    offset: i64,
+    limit: i64,
+) -> Vec<RankedPage> {
+    let phrase_pages =
+        get_phrase_pages(conn, &query.index_phrases()).expect("Error matching phrases");
+
+    get_pages_by_date_query(query, phrase_pages, newest_first, offset, limit)
+        .load::<Page>(conn)
+        .expect("Error loading pages")
+        .into_iter()
+        .map(|page| RankedPage {
+            page,
+            score: 0.0,
+            url_score: 0.0,
+        })
+        .collect()
+}
+
+fn get_pages_by_date_query(
+    query: &ParsedQuery,
+    phrase_pages: Option<HashSet<i32>>,
+    newest_first: bool,
+    offset: i64,
+    limit: i64,
+) -> pages::BoxedQuery<'static, Pg> {
+    let words_vec = query.words();
+
+    // Same matches as the scored search: pages containing a word, or with a word in the URL
+    let content_matches = indexes::table
+        .inner_join(words::table)
+        .filter(words::word.eq_any(query.index_words()))
+        .select(indexes::page_id);
+
+    let mut filter: PageFilter = Box::new(pages::id.eq_any(content_matches));
+    for w in &words_vec {
+        filter = Box::new(filter.or(pages::url.like(format!("%{}%", w))));
+    }
+
+    let mut pages_query = pages::table
+        .filter(pages::last_indexed.is_not_null())
+        .filter(pages::deleted_at.is_null())
+        .filter(filter)
+        .into_boxed();
+
+    for query_filter in get_query_filters(query) {
+        pages_query = pages_query.filter(query_filter);
+    }
+    if let Some(phrase_pages) = phrase_pages {
+        pages_query =
+            pages_query.filter(pages::id.eq_any(phrase_pages.into_iter().collect::<Vec<_>>()));
+    }
+
+    let pages_query = if newest_first {
+        pages_query.order((pages::last_crawled.desc(), pages::id.desc()))
+    } else {
+        pages_query.order((pages::last_crawled.asc(), pages::id.asc()))
+    };
+
+    pages_query.offset(offset).limit(limit)
+}
+
+/// Load the pages matching `matches` and the query operators, sorted by the `score_sql` score.
+/// The pages with the same score are ordered by their URL score then by id, the same order
+/// for both rankings so that a cursor stays valid in either one.
+fn get_ranked_pages_query(
+    query: &ParsedQuery,
+    matches: PageFilter,
+    score_sql: String,
+    phrase_pages: Option<HashSet<i32>>,
+    range: &ResultsRange,
+) -> pages::BoxedQuery<
+    'static,
+    Pg,
+    (
+        pages::SqlType,
+        diesel::sql_types::Double,
+        diesel::sql_types::Double,
+    ),
+> {
+    let terms = query.terms.join(" ");
+    let score = || -> RankExpression { Box::new(sql::<diesel::sql_types::Double>(&score_sql)) };
+    let url_score = || get_url_score_sql(&terms, range.now);
+
+    let mut pages_query = get_searchable_pages()
+        .select((pages::all_columns, score(), url_score()))
+        .filter(matches);
+
+    for query_filter in get_query_filters(query) {
+        pages_query = pages_query.filter(query_filter);
+    }
+    if let Some(phrase_pages) = phrase_pages {
+        pages_query =
+            pages_query.filter(pages::id.eq_any(phrase_pages.into_iter().collect::<Vec<_>>()));
+    }
+
+    // Keyset pagination, `(score, url_score, id) < cursor`
+    pages_query = match range.cursor {
+        Some(cursor) => pages_query.filter(
+            score().lt(cursor.score).or(score().eq(cursor.score).and(
+                url_score().lt(cursor.url_score).or(url_score()
+                    .eq(cursor.url_score)
+                    .and(pages::id.lt(cursor.page_id))),
+            )),
+        ),
+        None => pages_query.offset(range.offset),
+    };
+
+    pages_query
+        .order((score().desc(), url_score().desc(), pages::id.desc()))
+        .limit(range.limit)
+}
+
+/// Score of the heuristic ranking: the TF-IDF score multiplied by the metadata and page rank
+/// multipliers, with the freshness bonus
+fn get_tf_idf_score_sql(idfs: &[(i32, f64)], now: i64) -> String {
+    let tf_idf = get_word_sum_sql(idfs, "i.count::float8 / GREATEST(pages.word_count, 1)");
+
+    // Pages linked by many (well ranked) pages are more relevant
+    format!(
+        "({tf_idf} * {} * (1 + LEAST(GREATEST(pages.page_rank, 0), 10)) + {})",
+        get_metadata_multiplier_sql(now),
+        get_freshness_bonus_sql(now)
+    )
+}
+
+/// Sum of the `weight_sql` weights of the query words in the page, multiplied by their IDF.
+/// `weight_sql` can use the `i` index row of the word.
+fn get_word_sum_sql(idfs: &[(i32, f64)], weight_sql: &str) -> String {
+    if idfs.is_empty() {
+        return "0::float8".to_string();
+    }
+
+    let cases: String = idfs
+        .iter()
+        .map(|(word_id, idf)| format!(" WHEN {word_id} THEN {idf}::float8"))
+        .collect();
+    let word_ids = idfs
+        .iter()
+        .map(|(word_id, _)| word_id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "COALESCE((SELECT SUM((CASE i.word_id{cases} END) * {weight_sql}) FROM indexes i \
+        WHERE i.page_id = pages.id AND i.word_id IN ({word_ids})), 0::float8)"
+    )
+}
+
+fn get_metadata_multiplier_sql(now: i64) -> String {
+    format!(
+        "(1 \
+        + CASE WHEN pages.title IS NOT NULL THEN 0.1 ELSE 0 END \
+        + CASE WHEN pages.meta_description IS NOT NULL THEN 0.1 ELSE 0 END \
+        + CASE WHEN pages.meta_og_image IS NOT NULL THEN 0.2 ELSE 0 END \
+        + GREATEST(pages.seo_score, 0) / 100.0 \
+        + CASE WHEN pages.content_changed_at > {} THEN 0.1 ELSE 0 END \
+        + CASE WHEN EXISTS (SELECT 1 FROM domain_stats d WHERE d.domain = pages.domain AND d.has_rss) \
+        THEN 0.1 ELSE 0 END)::float8",
+        // Recently updated content is considered fresher
+        now - CONTENT_FRESHNESS_WINDOW
+    )
+}
+
+/// Bonus of the recently published pages, from `MAX_FRESHNESS_BONUS` to 0 at the end of the window.
+/// Pages dated in the future are considered published now.
+fn get_freshness_bonus_sql(now: i64) -> String {
+    format!(
+        "COALESCE(GREATEST(0, {MAX_FRESHNESS_BONUS} \
+        * (1 - GREATEST({now} - pages.published_at, 0)::float8 / {PUBLISHED_FRESHNESS_WINDOW})), 0)"
+    )
+}
+
+/// Heuristic favoring short URLs and domains containing the query, multiplied by the metadata multiplier
+fn get_url_score_sql(query: &str, now: i64) -> RankExpression {
+    Box::new(
+        sql::<diesel::sql_types::Double>(
+            "((100 * (1 + ((50 - LEAST(OCTET_LENGTH(pages.url), 50)) / 50.0) ^ 2) \
+            + CASE WHEN STRPOS(pages.domain, ",
+        )
+        .bind::<diesel::sql_types::Text, _>(query.to_string())
+        .sql(&format!(
+            ") > 0 THEN 50 ELSE 0 END) * {})::float8",
+            get_metadata_multiplier_sql(now)
+        )),
+    )
+}
+
+/// Ids of the words and the number of pages containing them
+fn get_word_doc_counts(conn: &mut DbConn, words: &[String]) -> QueryResult<Vec<(i32, i64)>> {
+    words::table
+        .filter(words::word.eq_any(words.to_vec()))
+        .select((
+            words::id,
+            sql::<diesel::sql_types::BigInt>(
+                "(SELECT COUNT(*) FROM indexes i WHERE i.word_id = words.id)",
+            ),
+        ))
+        .load(conn)
+}
+
+/// IDF of a word in the TF-IDF score
+fn get_tf_idf_idf(page_count: i64, doc_count: i64) -> f64 {
+    ((page_count as f64 + 1.0) / (doc_count as f64 + 1.0)).ln() + 1.0
+}
+
+/// Rank the pages containing the query words with Okapi BM25
+pub(crate) fn bm25_search(
+    conn: &mut DbConn,
+    query: &ParsedQuery,
+    k1: f64,
+    b: f64,
+    range: &ResultsRange,
+) -> Vec<RankedPage> {
+    let page_count: i64 = get_searchable_pages()
+        .count()
+        .get_result(conn)
+        .expect("Error counting pages");
+
+    let average_length: Option<f64> = get_searchable_pages()
+        .select(
+            sql::<diesel::sql_types::Nullable<diesel::sql_types::Double>>(
+                "AVG(body_length)::float8",
+            ),
+        )
+        .get_result(conn)
+        .expect("Error calculating the average length");
+
+    let idfs: Vec<(i32, f64)> = get_word_doc_counts(conn, &query.index_words())
+        .expect("Error loading words")
+        .into_iter()
+        .map(|(word_id, doc_count)| (word_id, get_bm25_idf(page_count, doc_count)))
+        .collect();
+    let word_ids: Vec<i32> = idfs.iter().map(|(word_id, _)| *word_id).collect();
+
+    let matches: PageFilter = Box::new(
+        pages::id.eq_any(
+            indexes::table
+                .filter(indexes::word_id.eq_any(word_ids))
+                .select(indexes::page_id),
+        ),
+    );
+
+    let phrase_pages =
+        get_phrase_pages(conn, &query.index_phrases()).expect("Error matching phrases");
+
+    get_ranked_pages_query(
+        query,
+        matches,
+        get_bm25_score_sql(&idfs, average_length.unwrap_or(0.0), k1, b),
+        phrase_pages,
+        range,
+    )
+    .load::<(Page, f64, f64)>(conn)
+    .expect("Error loading pages")
+    .into_iter()
+    .map(RankedPage::from)
+    .collect()
+}
+
+/// The indexed pages which are not deleted, the pages counted by the IDF
+fn get_searchable_pages() -> pages::BoxedQuery<'static, Pg> {
+    pages::table
+        .filter(pages::last_indexed.is_not_null())
+        .filter(pages::deleted_at.is_null())
+        .into_boxed()
+}
+
+/// Score of the BM25 ranking, the sum of the BM25 values of the query words
+fn get_bm25_score_sql(idfs: &[(i32, f64)], average_length: f64, k1: f64, b: f64) -> String {
+    let average_length = average_length.max(1.0);
+
+    get_word_sum_sql(
+        idfs,
+        &format!(
+            "(i.count * {}::float8) / (i.count + {k1}::float8 \
+            * (1 - {b}::float8 + {b}::float8 * pages.body_length / {average_length}::float8))",
+            k1 + 1.0
+        ),
+    )
+}
+
+/// IDF of a word in the BM25 score
+fn get_bm25_idf(page_count: i64, doc_count: i64) -> f64 {
+    ((page_count as f64 - doc_count as f64 + 0.5) / (doc_count as f64 + 0.5) + 1.0).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_page(id: i32, url: &str) -> Page {
+        Page {
+            id,
+            domain: "example.com".to_string(),
+            url: url.to_string(),
+            last_indexed: Some(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let parsed = parse_query("rust \"open source\" Lang");
+        assert_eq!(parsed.terms, vec!["rust", "lang"]);
+        assert_eq!(parsed.phrases, vec![vec!["open", "source"]]);
+
+        let parsed = parse_query("\"single\" \"source open!\"");
+        assert_eq!(parsed.terms, vec!["single"]);
+        assert_eq!(parsed.phrases, vec![vec!["source", "open"]]);
+
+        // Unclosed quote
+        let parsed = parse_query("a \"b c");
+        assert_eq!(parsed.terms, vec!["a", "b", "c"]);
+        assert!(parsed.phrases.is_empty());
+
+        // Long phrases are truncated, the next words are plain terms
+        let parsed = parse_query("\"one two three four five six seven eight nine ten\"");
+        assert_eq!(parsed.phrases[0].len(), MAX_PHRASE_WORDS);
+        assert_eq!(parsed.phrases[0][7], "eight");
+        assert_eq!(parsed.terms, vec!["nine", "ten"]);
+    }
+
+    #[test]
+    fn test_parse_query_normalization() {
+        // Looked up like the indexer stores them
+        let parsed = parse_query("Café -crème \"l'école élémentaire\"");
+        assert_eq!(parsed.terms, vec!["cafe"]);
+        assert_eq!(parsed.excluded, vec!["crème"]);
+        assert_eq!(parsed.phrases, vec![vec!["l'ecole", "elementaire"]]);
+
+        let parsed = parse_query("open-source 東京");
+        assert_eq!(parsed.terms, vec!["open", "source", "東", "京"]);
+    }
+
+    #[test]
+    fn test_parsed_query_index_words() {
+        let mut parsed = parse_query("running \"runs fast\"");
+        assert_eq!(parsed.index_words(), vec!["running", "runs", "fast"]);
+
+        parsed.stemming = true;
+        assert_eq!(parsed.index_words(), vec!["run", "run", "fast"]);
+        assert_eq!(parsed.index_phrases(), vec![vec!["run", "fast"]]);
+        // The terms are kept for the snippets and the URL matches
+        assert_eq!(parsed.terms, vec!["running"]);
+    }
+
+    #[test]
+    fn test_parse_query_excluded() {
+        let parsed = parse_query("rust -snake");
+        assert_eq!(parsed.terms, vec!["rust"]);
+        assert_eq!(parsed.excluded, vec!["snake"]);
+
+        let parsed = parse_query("-bad good");
+        assert_eq!(parsed.terms, vec!["good"]);
+        assert_eq!(parsed.excluded, vec!["bad"]);
+
+        let parsed = parse_query("-only");
+        assert_eq!(parsed.excluded, vec!["only"]);
+        assert_eq!(parsed.is_empty(), true);
+
+        // Not an operator in a phrase
+        let parsed = parse_query("\"open -source\" -");
+        assert_eq!(parsed.phrases, vec![vec!["open", "source"]]);
+        assert!(parsed.excluded.is_empty());
+    }
+
+    #[test]
+    fn test_excluded_words_filters() {
+        let parsed = parse_query("rust -crème -snake");
+
+        let filters = get_query_filters(&parsed);
+        assert_eq!(filters.len(), 3);
+
+        let query = pages::table
+            .select(pages::id)
+            .filter(filters.into_iter().next().unwrap());
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains("NOT (COALESCE(content, '') ILIKE $1 OR url ILIKE $2)"));
+        assert!(sql.contains(r#"binds: ["%crème%", "%crème%"]"#));
+
+        // The accented word also excludes its form without diacritics
+        let query = pages::table
+            .select(pages::id)
+            .filter(get_query_filters(&parsed).into_iter().nth(1).unwrap());
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#"binds: ["%creme%", "%creme%"]"#));
+    }
+
+    #[test]
+    fn test_parse_query_site() {
+        let parsed = parse_query("rust site:Example.com");
+        assert_eq!(parsed.terms, vec!["rust"]);
+        assert_eq!(parsed.site, Some("example.com".to_string()));
+
+        let parsed = parse_query("site: rust");
+        assert_eq!(parsed.terms, vec!["rust"]);
+        assert_eq!(parsed.site, None);
+
+        let parsed = parse_query("\"site:example.com\" rust");
+        assert_eq!(parsed.site, None);
+    }
+
+    #[test]
+    fn test_parse_query_inurl() {
+        let parsed = parse_query("inurl:/blog");
+        assert_eq!(parsed.in_url, vec!["/blog"]);
+        assert!(parsed.terms.is_empty());
+        assert_eq!(parsed.is_empty(), true);
+
+        let parsed = parse_query("inurl:docs inurl:v2");
+        assert_eq!(parsed.in_url, vec!["docs", "v2"]);
+
+        let parsed = parse_query("inurl: rust");
+        assert!(parsed.in_url.is_empty());
+        assert_eq!(parsed.terms, vec!["rust"]);
+    }
+
+    #[test]
+    fn test_parse_query_intitle() {
+        let parsed = parse_query("intitle:rust");
+        assert_eq!(parsed.in_title, vec!["rust"]);
+        assert!(parsed.terms.is_empty());
+        assert!(parsed.in_url.is_empty());
+    }
+
+    #[test]
+    fn test_parse_query_inurl_and_intitle() {
+        let parsed = parse_query("intitle:guide inurl:docs");
+        assert_eq!(parsed.in_title, vec!["guide"]);
+        assert_eq!(parsed.in_url, vec!["docs"]);
+        assert!(parsed.terms.is_empty());
+    }
+
+    #[test]
+    fn test_parse_query_operators_with_terms() {
+        let parsed = parse_query("rust intitle:guide async inurl:docs");
+        assert_eq!(parsed.terms, vec!["rust", "async"]);
+        assert_eq!(parsed.words(), vec!["rust", "async"]);
+        assert_eq!(parsed.in_title, vec!["guide"]);
+        assert_eq!(parsed.in_url, vec!["docs"]);
+
+        let mut query = pages::table.select(pages::id).into_boxed();
+        for filter in get_query_filters(&parsed) {
+            query = query.filter(filter);
+        }
+
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#""pages"."url" ILIKE $1"#));
+        assert!(sql.contains(r#""pages"."title" ILIKE $2"#));
+        assert!(sql.contains(r#"binds: ["%docs%", "%guide%"]"#));
+    }
+
+    #[test]
+    fn test_site_filter() {
+        let parsed = parse_query("rust site:example.com");
+
+        let mut query = pages::table.select(pages::id).into_boxed();
+        for filter in get_query_filters(&parsed) {
+            query = query.filter(filter);
+        }
+
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#""pages"."domain" = $1"#));
+        assert!(sql.contains(r#"binds: ["example.com"]"#));
+    }
+
+    #[test]
+    fn test_is_valid_date_range() {
+        assert_eq!(is_valid_date_range(None, None), true);
+        assert_eq!(is_valid_date_range(Some(1_000), None), true);
+        assert_eq!(is_valid_date_range(None, Some(1_000)), true);
+        assert_eq!(is_valid_date_range(Some(1_000), Some(2_000)), true);
+        assert_eq!(is_valid_date_range(Some(2_000), Some(1_000)), false);
+        assert_eq!(is_valid_date_range(Some(1_000), Some(1_000)), false);
+    }
+
+    #[test]
+    fn test_date_filters() {
+        let debug_filters = |query: &ParsedQuery| {
+            let mut sql_query = pages::table.select(pages::id).into_boxed();
+            for filter in get_query_filters(query) {
+                sql_query = sql_query.filter(filter);
+            }
+            diesel::debug_query::<Pg, _>(&sql_query).to_string()
+        };
+
+        let mut parsed = parse_query("rust");
+        parsed.after = Some(1_000);
+        let sql = debug_filters(&parsed);
+        assert!(sql.contains(r#""pages"."last_crawled" >= $1"#));
+        assert!(!sql.contains("<="));
+
+        parsed.after = None;
+        parsed.before = Some(2_000);
+        let sql = debug_filters(&parsed);
+        assert!(sql.contains(r#""pages"."last_crawled" <= $1"#));
+        assert!(!sql.contains(">="));
+
+        parsed.after = Some(1_000);
+        let sql = debug_filters(&parsed);
+        assert!(sql.contains(r#""pages"."last_crawled" >= $1"#));
+        assert!(sql.contains(r#""pages"."last_crawled" <= $2"#));
+        assert!(sql.contains("binds: [1000, 2000]"));
+    }
+
+    #[tokio::test]
+    async fn test_search_invalid_date_range() {
+        let response = get_search_handler(
+            HeaderMap::new(),
+            State(Arc::new(Environment::for_tests())),
+            Query(SearchQuery {
+                q: "rust".to_string(),
+                p: 1,
+                ranking: None,
+                site: None,
+                after: Some(2_000),
+                before: Some(1_000),
+                lang: None,
+                cursor: None,
+                sort: None,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_merge_suggestions() {
+        let to_strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            merge_suggestions(
+                to_strings(&["rust lang", "rust"]),
+                to_strings(&["rust", "rustic", "rusty"])
+            ),
+            vec!["rust lang", "rust", "rustic", "rusty"]
+        );
+
+        let words: Vec<String> = (0..20).map(|i| format!("word{i}")).collect();
+        assert_eq!(merge_suggestions(vec![], words).len(), MAX_SUGGESTIONS);
+    }
+
+    #[tokio::test]
+    async fn test_suggest_short_prefix() {
+        let response = get_suggest_handler(
+            State(Arc::new(Environment::for_tests())),
+            ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8085))),
+            Query(SuggestQuery {
+                q: " r ".to_string(),
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("a%b_c\\d"), "a\\%b\\_c\\\\d");
+        assert_eq!(escape_like("rust"), "rust");
+    }
+
+    #[test]
+    fn test_search_cursor_encoding() {
+        let cursor = SearchCursor {
+            page_id: 42,
+            score: 0.1 + 0.2,
+            url_score: 150.0,
+            now: 1_700_000_000_000,
+        };
+        assert_eq!(SearchCursor::decode(&cursor.encode()), Some(cursor));
+
+        assert_eq!(SearchCursor::decode("not base64!"), None);
+        assert_eq!(SearchCursor::decode(&URL_SAFE_NO_PAD.encode("42")), None);
+        assert_eq!(
+            SearchCursor::decode(&URL_SAFE_NO_PAD.encode("1:1.0:1.0")),
+            None
+        );
+        assert_eq!(
+            SearchCursor::decode(&URL_SAFE_NO_PAD.encode("a:1.0:1.0:0")),
+            None
+        );
+        assert_eq!(
+            SearchCursor::decode(&URL_SAFE_NO_PAD.encode("1:NaN:1.0:0")),
+            None
+        );
+        assert_eq!(
+            SearchCursor::decode(&URL_SAFE_NO_PAD.encode("1:1.0:inf:0")),
+            None
+        );
+        assert_eq!(
+            SearchCursor::decode(&URL_SAFE_NO_PAD.encode("1:1.0:1.0:0:0")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_results_range_keeps_the_cursor_timestamp() {
+        let cursor = SearchCursor {
+            page_id: 1,
+            score: 1.0,
+            url_score: 1.0,
+            now: 1_000,
+        };
+
+        assert_eq!(ResultsRange::new(Some(&cursor), 0, 11).now, 1_000);
+        assert!(ResultsRange::new(None, 0, 11).now > 1_000);
+    }
+
+    fn ranked_pages(ids: std::ops::RangeInclusive<i32>) -> Vec<RankedPage> {
+        ids.map(|id| RankedPage {
+            page: test_page(id, "https://example.com/"),
+            score: 100.0 - id as f64,
+            url_score: 200.0,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_results_page() {
+        // The look-ahead result is not returned
+        let (page, next_cursor) = get_results_page(ranked_pages(1..=11), 10, 1_000);
+        assert_eq!(
+            page.iter().map(|r| r.page.id).collect::<Vec<_>>(),
+            (1..=10).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            next_cursor,
+            Some(SearchCursor {
+                page_id: 10,
+                score: 90.0,
+                url_score: 200.0,
+                now: 1_000,
+            })
+        );
+
+        let (page, next_cursor) = get_results_page(ranked_pages(1..=10), 10, 1_000);
+        assert_eq!(page.len(), 10);
+        assert_eq!(next_cursor, None);
+
+        let (page, next_cursor) = get_results_page(Vec::new(), 10, 1_000);
+        assert_eq!(page.len(), 0);
+        assert_eq!(next_cursor, None);
+    }
+
+    #[test]
+    fn test_results_page_by_date() {
+        // Rows of the newest first query, with the look-ahead one
+        let rows: Vec<RankedPage> = (1..=11)
+            .map(|id| RankedPage {
+                page: Page {
+                    last_crawled: 10_000 - id as i64 * 100,
+                    ..test_page(id, &format!("https://example.com/{}", id))
+                },
+                score: 0.0,
+                url_score: 0.0,
+            })
+            .collect();
+
+        let (page, _) = get_results_page(rows, 10, 1_000);
+        let crawled: Vec<i64> = page.iter().map(|r| r.page.last_crawled).collect();
+        assert_eq!(
+            crawled,
+            (1..=10).map(|id| 10_000 - id * 100).collect::<Vec<_>>()
+        );
+        assert_eq!(get_results_count(20, &page), 30);
+
+        // Last results page
+        let (page, _) = get_results_page(ranked_pages(1..=4), 10, 1_000);
+        assert_eq!(get_results_count(20, &page), 24);
+    }
+
+    #[test]
+    fn test_pages_by_date_query() {
+        let parsed = parse_query("rust");
+
+        let query = get_pages_by_date_query(&parsed, None, true, 20, 11);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#"ORDER BY "pages"."last_crawled" DESC, "pages"."id" DESC"#));
+        assert!(sql.contains("LIMIT $3 OFFSET $4"));
+        assert!(sql.contains(r#"binds: [["rust"], "%rust%", 11, 20]"#));
+
+        let query = get_pages_by_date_query(&parsed, None, false, 0, 11);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#"ORDER BY "pages"."last_crawled" ASC, "pages"."id" ASC"#));
+
+        let query = get_pages_by_date_query(&parsed, Some(HashSet::from([7])), true, 0, 11);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#""pages"."id" = ANY($3)"#));
+        assert!(sql.contains(r#"("pages"."deleted_at" IS NULL)"#));
+    }
+
+    #[test]
+    fn test_ranked_pages_query() {
+        let parsed = parse_query("rust site:example.com");
+        let matches: PageFilter = Box::new(pages::id.eq_any(vec![1, 2]));
+        let range = ResultsRange::new(None, 20, 11);
+
+        let query = get_ranked_pages_query(&parsed, matches, "1::float8".to_string(), None, &range);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#"("pages"."last_indexed" IS NOT NULL)"#));
+        assert!(sql.contains(r#"("pages"."deleted_at" IS NULL)"#));
+        assert!(sql.contains(r#""pages"."id" = ANY($2)"#));
+        assert!(sql.contains(r#"("pages"."domain" = $3)"#));
+        assert!(sql.contains(r#"ORDER BY 1::float8 DESC, "#));
+        assert!(sql.contains(r#"::float8 DESC, "pages"."id" DESC LIMIT $5 OFFSET $6"#));
+        assert!(sql.contains(r#"binds: ["rust", [1, 2], "example.com", "rust", 11, 20]"#));
+
+        // Continues after the cursor instead of skipping the first results
+        let cursor = SearchCursor {
+            page_id: 7,
+            score: 2.5,
+            url_score: 150.0,
+            now: 1_000,
+        };
+        let matches: PageFilter = Box::new(pages::id.eq_any(vec![1, 2]));
+        let range = ResultsRange::new(Some(&cursor), 20, 11);
+        let query = get_ranked_pages_query(
+            &parsed,
+            matches,
+            "1::float8".to_string(),
+            Some(HashSet::from([3])),
+            &range,
+        );
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#""pages"."id" = ANY($4)"#));
+        assert!(sql.contains(r#"((1::float8 < $5) OR ((1::float8 = $6) AND ("#));
+        assert!(sql.contains(r#"AND ("pages"."id" < $11)"#));
+        assert!(!sql.contains("OFFSET"));
+        assert!(sql.contains(r#"[3], 2.5, 2.5, "rust", 150.0, "rust", 150.0, 7, "rust", 11]"#));
+        assert!(sql.contains("pages.content_changed_at > -604799000"));
+
+        // The deleted pages are not counted by the IDF and the average length
+        let query = get_searchable_pages().count();
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#"("pages"."deleted_at" IS NULL)"#));
+    }
+
+    #[test]
+    fn test_word_sum_sql() {
+        assert_eq!(get_word_sum_sql(&[], "i.count"), "0::float8");
+        assert_eq!(
+            get_word_sum_sql(&[(1, 1.5), (2, 0.25)], "i.count"),
+            "COALESCE((SELECT SUM((CASE i.word_id WHEN 1 THEN 1.5::float8 WHEN 2 THEN 0.25::float8 END) \
+            * i.count) FROM indexes i WHERE i.page_id = pages.id AND i.word_id IN (1, 2)), 0::float8)"
+        );
+    }
+
+    #[test]
+    fn test_related_pages_query() {
+        let query = get_related_pages_query(1, vec![10, 20]);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains(r#"SELECT "indexes"."page_id", sum("indexes"."count")"#));
+        assert!(sql.contains(r#""indexes"."word_id" = ANY($1)"#));
+        assert!(sql.contains(r#""indexes"."page_id" != $2"#));
+        assert!(sql.contains(r#"GROUP BY "indexes"."page_id""#));
+        assert!(sql.contains(r#"ORDER BY sum("indexes"."count") DESC, "indexes"."page_id""#));
+        assert!(sql.contains("binds: [[10, 20], 1, 10]"));
+
+        // The unindexed and deleted pages are filtered before the limit
+        let filter = sql
+            .find(r#""indexes"."page_id" = ANY(SELECT "pages"."id" FROM "pages" WHERE"#)
+            .unwrap();
+        assert!(sql[filter..].contains(r#""pages"."last_indexed" IS NOT NULL"#));
+        assert!(sql[filter..].contains(r#""pages"."deleted_at" IS NULL"#));
+        assert!(filter < sql.find("GROUP BY").unwrap());
+        assert!(sql.ends_with(r#"LIMIT $3 -- binds: [[10, 20], 1, 10]"#));
+    }
+
+    #[test]
+    fn test_page_detail_query() {
+        let query = get_page_detail_query("https://example.com/");
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains(
+            r#"LEFT OUTER JOIN "pages_analytics" ON ("pages_analytics"."page_id" = "pages"."id")"#
+        ));
+        assert!(sql
+            .contains(r#"LEFT OUTER JOIN "favicons" ON ("pages"."favicon_id" = "favicons"."id")"#));
+        // The deleted pages are shown with their `deleted_at`
+        assert!(sql.contains(r#"WHERE ("pages"."url" = $1)"#));
+        assert!(!sql.contains("deleted_at\" IS NULL"));
+        assert!(sql.contains(r#"binds: ["https://example.com/", 1]"#));
+    }
+
+    #[test]
+    fn test_page_alternates_query() {
+        let query = get_page_alternates_query(7);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains(r#"WHERE ("page_alternates"."page_id" = $1)"#));
+        assert!(sql.contains("binds: [7]"));
+    }
+
+    #[test]
+    fn test_search_time() {
+        // Longer than i32::MAX nanoseconds
+        assert_eq!(get_search_time(Duration::from_secs(3)), 3000);
+        assert_eq!(get_search_time(Duration::from_micros(1500)), 1);
+        assert_eq!(get_search_time(Duration::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn test_vote_counts_query() {
+        let ids = [3, 7];
+        let query = get_vote_counts_query(&ids);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains("COUNT(*) FILTER (WHERE vote_type = $1)"));
+        assert!(sql.contains("COUNT(*) FILTER (WHERE vote_type = $2)"));
+        assert!(sql.contains(r#"WHERE ("votes"."page_id" = ANY($3))"#));
+        assert!(sql.contains(r#"GROUP BY "votes"."page_id""#));
+        // The ids are bound, the likes are counted before the dislikes
+        assert!(sql.contains("binds: [Like, Dislike, [3, 7]]"));
+    }
+
+    #[test]
+    fn test_build_page_detail() {
+        let page = Page {
+            id: 7,
+            url: "https://example.com/".to_string(),
+            favicon_id: 3,
+            has_rss: true,
+            ..Default::default()
+        };
+        let votes = VoteCount {
+            page_id: 7,
+            like_count: 4,
+            dislike_count: 1,
+        };
+
+        let detail = build_page_detail(
+            (
+                page,
+                Some((2, 10)),
+                Some("https://example.com/favicon.ico".to_string()),
+            ),
+            Some(&votes),
+            Some("/api/favicon/3".to_string()),
+            vec![
+                ("fr".to_string(), "https://example.com/fr/".to_string()),
+                ("de".to_string(), "https://example.de/".to_string()),
+            ],
+        );
+        assert_eq!(detail.id, 7);
+        assert_eq!(detail.url, "https://example.com/");
+        assert_eq!(detail.has_rss, true);
+        assert_eq!(
+            detail.favicon_source_url.as_deref(),
+            Some("https://example.com/favicon.ico")
+        );
+        assert_eq!(detail.favicon_url.as_deref(), Some("/api/favicon/3"));
+        assert_eq!((detail.clicks, detail.impressions), (2, 10));
+        assert_eq!((detail.likes, detail.dislikes), (4, 1));
+        assert_eq!(
+            detail.alternates.keys().collect::<Vec<_>>(),
+            vec!["de", "fr"]
+        );
+
+        // Never shown in a search and without votes
+        let detail = build_page_detail((Page::default(), None, None), None, None, Vec::new());
+        assert_eq!(
+            (
+                detail.clicks,
+                detail.impressions,
+                detail.likes,
+                detail.dislikes
+            ),
+            (0, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_page_detail_columns() {
+        let page = Page {
+            deleted_at: Some(1_700_000_000),
+            word_count: 120,
+            registered_domain: Some("example.com".to_string()),
+            ..Default::default()
+        };
+        let detail = build_page_detail((page, None, None), None, None, Vec::new());
+        let json = serde_json::to_value(&detail).unwrap();
+
+        assert_eq!(json["deleted_at"], 1_700_000_000);
+        assert_eq!(json["word_count"], 120);
+        assert_eq!(json["registered_domain"], "example.com");
+
+        // Every column of the pages is in the details
+        let query = pages::table.select(pages::all_columns);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        let columns = sql
+            .trim_start_matches("SELECT ")
+            .split(" FROM ")
+            .next()
+            .unwrap()
+            .split(", ")
+            .map(|column| column.trim_start_matches(r#""pages"."#).trim_matches('"'))
+            .collect::<Vec<_>>();
+        assert!(columns.len() > 30);
+        for column in columns {
+            assert!(json.get(column).is_some(), "{column} is not in PageDetail");
+        }
+    }
+
+    #[test]
+    fn test_page_detail_not_found() {
+        let response = get_page_detail_response(None);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let detail = build_page_detail((Page::default(), None, None), None, None, Vec::new());
+        let response = get_page_detail_response(Some(detail));
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_page_unauthorized() {
+        let response = get_page_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Query(PageQuery {
+                url: "https://example.com/".to_string(),
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_delete_page_unauthorized() {
+        let response = delete_page_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Query(PageQuery {
+                url: "https://example.com/".to_string(),
+            }),
+        )
+        .await;
+
+        assert_eq!(response, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_delete_page_query() {
+        let query = get_delete_page_query("https://example.com/", 1_700_000_000_000);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains(r#"UPDATE "pages" SET "deleted_at" = $1"#));
+        // Deleting again does not postpone the purge
+        assert!(sql.contains(r#"("pages"."deleted_at" IS NULL)"#));
+        assert!(sql.contains(r#"binds: [1700000000000, "https://example.com/"]"#));
+    }
+
+    #[tokio::test]
+    async fn test_related_invalid_url() {
+        let response = get_related_handler(
+            State(Arc::new(Environment::for_tests())),
+            Query(RelatedQuery {
+                url: "not an url".to_string(),
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_search_invalid_sort() {
+        let mut query = test_search_query("rust");
+        query.sort = Some("random".to_string());
+
+        let response = get_search_handler(
+            HeaderMap::new(),
+            State(Arc::new(Environment::for_tests())),
+            Query(query),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Date sorted results do not have a cursor
+        let mut query = test_search_query("rust");
+        query.sort = Some("newest".to_string());
+        query.cursor = Some(
+            SearchCursor {
+                page_id: 1,
+                score: 1.0,
+                url_score: 1.0,
+                now: 0,
+            }
+            .encode(),
+        );
+
+        let response = get_search_handler(
+            HeaderMap::new(),
+            State(Arc::new(Environment::for_tests())),
+            Query(query),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_search_invalid_cursor() {
+        let mut query = test_search_query("rust");
+        query.cursor = Some("not a cursor".to_string());
+
+        let response = get_search_handler(
+            HeaderMap::new(),
+            State(Arc::new(Environment::for_tests())),
+            Query(query),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_token_invalid_api_key() {
+        let mut env = Environment::for_tests();
+        env.api_key = Some("secret".to_string());
+        env.jwt_secret = Some("jwt secret".to_string());
+        let env = Arc::new(env);
+
+        let response = post_token_handler(
+            State(env.clone()),
+            Json(TokenBody {
+                api_key: "wrong key".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = post_token_handler(
+            State(env),
+            Json(TokenBody {
+                api_key: "secret".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_get_requested_url() {
+        assert_eq!(
+            get_requested_url("https://Example.com/page"),
+            Some((
+                "https://example.com/page".to_string(),
+                "example.com".to_string()
+            ))
+        );
+        assert_eq!(get_requested_url("ftp://example.com/"), None);
+        assert_eq!(get_requested_url("not an url"), None);
+        assert_eq!(
+            get_requested_url(&format!("https://example.com/{}", "a".repeat(1024))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sort_requested_urls() {
+        let urls = [
+            "https://example.com/new",
+            "https://example.com/new",
+            "https://example.com/crawled",
+            "https://example.com/queued",
+            "not an url",
+            "ftp://example.com/",
+        ]
+        .map(String::from);
+        let queued = HashSet::from(["https://example.com/queued".to_string()]);
+        let crawled = HashSet::from(["https://example.com/crawled".to_string()]);
+
+        let (accepted, response) = sort_requested_urls(&urls, &queued, &crawled);
+
+        assert_eq!(
+            accepted,
+            vec![(
+                "https://example.com/new".to_string(),
+                "example.com".to_string()
+            )]
+        );
+        assert_eq!(
+            response,
+            RequestUrlsResponse {
+                accepted: 1,
+                already_crawled: 1,
+                already_queued: 1,
+                invalid: 2,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_urls_limit() {
+        let response = post_request_urls_handler(
+            Some(Extension(AuthenticatedUser {
+                sub: "api".to_string(),
+            })),
+            State(Arc::new(Environment::for_tests())),
+            Json(RequestUrlsBody {
+                urls: vec!["https://example.com/".to_string(); MAX_REQUESTED_URLS + 1],
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_request_urls_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Json(RequestUrlsBody { urls: Vec::new() }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_request_url_without_token() {
+        let status = post_request_url_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Json(RequestUrlBody {
+                url: "https://example.com/".to_string(),
+            }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_search_only_excluded_words() {
+        let response = get_search_handler(
+            HeaderMap::new(),
+            State(Arc::new(Environment::for_tests())),
+            Query(SearchQuery {
+                q: "-only".to_string(),
+                p: 1,
+                ranking: None,
+                site: None,
+                after: None,
+                before: None,
+                lang: None,
+                cursor: None,
+                sort: None,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Needs a database with the migrations run, e.g.
+    /// `TEST_DATABASE_URL=postgres://localhost/epsilon_tests cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_search_single_connection_pool() {
+        use database::DbPool;
+        use diesel::{pg::PgConnection, r2d2::ConnectionManager};
+        use std::sync::atomic::AtomicU64;
+
+        let manager =
+            ConnectionManager::<PgConnection>::new(std::env::var("TEST_DATABASE_URL").unwrap());
+        let db_pool = DbPool::builder()
+            .max_size(1)
+            .connection_timeout(Duration::from_secs(5))
+            .build(manager)
+            .unwrap();
+        let state = Environment::new(db_pool, Arc::new(AtomicU64::new(0)));
+
+        // The read connection is released before the analytics are written
+        let response = get_search_handler(
+            HeaderMap::new(),
+            State(Arc::new(state)),
+            Query(SearchQuery {
+                q: "rust".to_string(),
+                p: 1,
+                ranking: None,
+                site: None,
+                after: None,
+                before: None,
+                lang: None,
+                cursor: None,
+                sort: None,
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_build_phrase_query() {
+        assert_eq!(
+            build_phrase_query(2),
+            "SELECT DISTINCT p0.page_id FROM positions p0 \
+            INNER JOIN words w0 ON w0.id = p0.word_id \
+            INNER JOIN positions p1 ON p1.page_id = p0.page_id AND p1.position = p0.position + 1 \
+            INNER JOIN words w1 ON w1.id = p1.word_id \
+            WHERE w0.word = $1 AND w1.word = $2"
+        );
+        assert!(build_phrase_query(3).ends_with("AND w1.word = $2 AND w2.word = $3"));
+    }
+
+    #[test]
+    fn test_tf_idf_idf() {
+        // Rare words weigh more than the words of every page
+        assert!((get_tf_idf_idf(9, 1) - ((10.0f64 / 2.0).ln() + 1.0)).abs() < 1e-9);
+        assert!((get_tf_idf_idf(9, 9) - 1.0).abs() < 1e-9);
+        assert_eq!(get_tf_idf_idf(0, 0), 1.0);
+    }
+
+    #[test]
+    fn test_tf_idf_score_sql() {
+        let now = PUBLISHED_FRESHNESS_WINDOW * 2;
+        let sql = get_tf_idf_score_sql(&[(1, 1.5)], now);
+
+        // The term frequency is relative to the length of the page
+        assert!(sql.contains("* i.count::float8 / GREATEST(pages.word_count, 1)"));
+        assert!(sql.contains("CASE WHEN pages.title IS NOT NULL THEN 0.1 ELSE 0 END"));
+        assert!(sql.contains("CASE WHEN pages.meta_og_image IS NOT NULL THEN 0.2 ELSE 0 END"));
+        assert!(sql.contains(&format!(
+            "pages.content_changed_at > {}",
+            now - CONTENT_FRESHNESS_WINDOW
+        )));
+        // The RSS flag is looked up by the primary key of the domain stats
+        assert!(sql.contains("FROM domain_stats d WHERE d.domain = pages.domain AND d.has_rss"));
+        // The page rank multiplier is capped
+        assert!(sql.contains("* (1 + LEAST(GREATEST(pages.page_rank, 0), 10))"));
+        assert!(sql.contains(&format!(
+            "GREATEST(0, 10 * (1 - GREATEST({now} - pages.published_at, 0)::float8 / {PUBLISHED_FRESHNESS_WINDOW}))"
+        )));
+    }
+
+    #[test]
+    fn test_url_score_sql() {
+        let query = pages::table.select(get_url_score_sql("rust", 0));
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains("LEAST(OCTET_LENGTH(pages.url), 50)"));
+        assert!(sql.contains("CASE WHEN STRPOS(pages.domain, $1) > 0 THEN 50 ELSE 0 END"));
+        assert!(sql.contains(r#"binds: ["rust"]"#));
+    }
+
+    #[test]
+    fn test_bm25_idf() {
+        assert!(get_bm25_idf(10, 1) > get_bm25_idf(10, 5));
+        assert!(get_bm25_idf(10, 10) > 0.0);
+        assert!((get_bm25_idf(10, 2) - (8.5f64 / 2.5 + 1.0).ln()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bm25_score_sql() {
+        let sql = get_bm25_score_sql(&[(1, 1.5)], 1000.0, 1.5, 0.75);
+        assert!(sql.contains(
+            "* (i.count * 2.5::float8) / (i.count + 1.5::float8 \
+            * (1 - 0.75::float8 + 0.75::float8 * pages.body_length / 1000::float8))"
+        ));
+
+        // Without indexed pages, the average length is not a divisor of 0
+        let sql = get_bm25_score_sql(&[(1, 1.5)], 0.0, 1.5, 0.75);
+        assert!(sql.contains("pages.body_length / 1::float8"));
+    }
+
+    #[test]
+    fn test_search_response_has_search_id() {
+        let response = SearchResponse {
+            search_id: 7,
+            results: Vec::new(),
+            time: 0,
+            page: 1,
+            total_pages: 0,
+            total_results: 0,
+            next_cursor: None,
+            did_you_mean: None,
+        };
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["search_id"], 7);
+        assert_eq!(json["did_you_mean"], serde_json::Value::Null);
+    }
+
+    fn test_search_response(urls: &[&str]) -> SearchResponse {
+        SearchResponse {
+            search_id: 1,
+            results: urls
+                .iter()
+                .map(|url| ResultPage {
+                    url: url.to_string(),
+                    favicon_url: None,
+                    score: 1.0,
+                    clicks: 0,
+                    impressions: 0,
+                    likes: 0,
+                    dislikes: 0,
+                    crawled_at: 0,
+                    indexed_at: 0,
+                    published_at: None,
+                    final_url: None,
+                    language: None,
+                    word_count: 0,
+                    snippet: None,
+                    metadata: ResultPageMetadata {
+                        title: None,
+                        description: None,
+                        theme_color: None,
+                        keywords: None,
+                        image: None,
+                        og_type: None,
+                        og_site_name: None,
+                    },
+                })
+                .collect(),
+            time: 0,
+            page: 1,
+            total_pages: 1,
+            total_results: urls.len() as i32,
+            next_cursor: None,
+            did_you_mean: None,
+        }
+    }
+
+    fn test_search_query(q: &str) -> SearchQuery {
+        SearchQuery {
+            q: q.to_string(),
+            p: 1,
+            ranking: None,
+            site: None,
+            after: None,
+            before: None,
+            lang: None,
+            cursor: None,
+            sort: None,
+        }
+    }
+
+    #[test]
+    fn test_get_search_cache_key() {
+        assert_eq!(
+            get_search_cache_key(&test_search_query("Rust"), "rust"),
+            get_search_cache_key(&test_search_query("rust"), "rust")
+        );
+
+        let mut query = test_search_query("rust");
+        query.site = Some("example.com".to_string());
+        assert_ne!(
+            get_search_cache_key(&query, "rust"),
+            get_search_cache_key(&test_search_query("rust"), "rust")
+        );
+
+        // The query can contain the separators of the other parameters
+        let mut query = test_search_query("x");
+        query.ranking = Some("bm25".to_string());
+        query.site = Some("1".to_string());
+        assert_ne!(
+            get_search_cache_key(&query, "x"),
+            get_search_cache_key(&test_search_query("x:1:bm25"), "x:1:bm25")
+        );
+    }
+
+    #[test]
+    fn test_cached_search_expiration() {
+        let cache = Mutex::new(LruCache::new(std::num::NonZeroUsize::new(10).unwrap()));
+        let key = get_search_cache_key(&test_search_query("rust"), "rust");
+        let now = Instant::now();
+        cache.lock().unwrap().put(
+            key.clone(),
+            CachedSearch::new(test_search_response(&["https://a.com/"]), now),
+        );
+
+        let cached = get_cached_search(&cache, &key, now + SEARCH_CACHE_TTL / 2);
+        assert_eq!(cached.unwrap().results[0].url, "https://a.com/");
+
+        // Searched again to return the newly indexed pages
+        assert!(get_cached_search(&cache, &key, now + SEARCH_CACHE_TTL).is_none());
+        assert_eq!(cache.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_language_filter() {
+        let mut parsed = parse_query("rust");
+        parsed.language = Some("fr".to_string());
+
+        let mut sql_query = pages::table.select(pages::id).into_boxed();
+        for filter in get_query_filters(&parsed) {
+            sql_query = sql_query.filter(filter);
+        }
+        let sql = diesel::debug_query::<Pg, _>(&sql_query).to_string();
+
+        assert!(sql.contains(r#""pages"."language" = $1"#));
+        assert!(sql.contains(r#"binds: ["fr"]"#));
+    }
+
+    #[tokio::test]
+    async fn test_search_cache_hit() {
+        let env = Arc::new(Environment::for_tests());
+        env.search_cache.lock().unwrap().put(
+            get_search_cache_key(&test_search_query("rust"), "rust"),
+            CachedSearch::new(
+                test_search_response(&["https://www.rust-lang.org/"]),
+                Instant::now(),
+            ),
+        );
+
+        // The test pool cannot connect, so the response comes from the cache
+        let response = get_search_handler(
+            HeaderMap::new(),
+            State(env.clone()),
+            Query(test_search_query("Rust")),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(env.search_cache.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_search_cache_population() {
+        let env = Environment::for_tests();
+        let query = test_search_query("rust");
+
+        env.search_cache.lock().unwrap().put(
+            get_search_cache_key(&query, "rust"),
+            CachedSearch::new(test_search_response(&["https://a.com/"]), Instant::now()),
+        );
+
+        let cached = get_cached_search(
+            &env.search_cache,
+            &get_search_cache_key(&test_search_query("Rust"), "rust"),
+            Instant::now(),
+        );
+        assert_eq!(cached.unwrap().results[0].url, "https://a.com/");
+    }
+
+    #[test]
+    fn test_evict_cached_searches() {
+        let cache = Mutex::new(LruCache::new(std::num::NonZeroUsize::new(10).unwrap()));
+        let key = |q: &str| get_search_cache_key(&test_search_query(q), q);
+        let cached = |urls: &[&str]| CachedSearch::new(test_search_response(urls), Instant::now());
+        {
+            let mut cache = cache.lock().unwrap();
+            cache.put(key("a"), cached(&["https://a.com/"]));
+            cache.put(key("ab"), cached(&["https://a.com/", "https://b.com/"]));
+            cache.put(key("b"), cached(&["https://b.com/"]));
+        }
+
+        evict_cached_searches(&cache, "https://a.com/");
+
+        let cache = cache.lock().unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.contains(&key("b")), true);
+    }
+
+    #[test]
+    fn test_find_surface_form() {
+        let content = "Running is fun. The runners run every day, they love running.";
+
+        assert_eq!(
+            find_surface_form(content, "run"),
+            Some("running".to_string())
+        );
+        assert_eq!(find_surface_form(content, "day"), Some("day".to_string()));
+        assert_eq!(
+            find_surface_form("Universités et université", &stem_word("universite")),
+            Some("universite".to_string())
+        );
+        assert_eq!(find_surface_form(content, "walk"), None);
+    }
+
+    #[test]
+    fn test_correction_candidates_query() {
+        let sql =
+            diesel::debug_query::<Pg, _>(&get_correction_candidates_query("rsut")).to_string();
+
+        assert!(sql.contains(r#""words"."word" LIKE $1"#));
+        assert!(sql.contains("LENGTH(word) BETWEEN $2 AND $3"));
+        assert!(sql.contains("LIMIT $4"));
+        assert!(sql.contains(r#"binds: ["r%", 2, 6, 2000]"#));
+
+        // The wildcards are escaped
+        let sql = diesel::debug_query::<Pg, _>(&get_correction_candidates_query("_a")).to_string();
+        assert!(sql.contains(r#"binds: ["\\_%""#));
+    }
+
+    #[test]
+    fn test_get_best_correction() {
+        let candidates: Vec<String> = ["rust", "rusty", "trust", "ruts", "linux"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        assert_eq!(
+            get_best_correction("rustt", &candidates),
+            Some("rust".to_string())
+        );
+        assert_eq!(
+            get_best_correction("rsut", &candidates),
+            Some("rust".to_string())
+        );
+        // Only the word itself
+        assert_eq!(get_best_correction("rust", &["rust".to_string()]), None);
+        // Too far
+        assert_eq!(get_best_correction("python", &candidates), None);
+    }
+
+    #[test]
+    fn test_get_word_snippet() {
+        assert_eq!(
+            get_word_snippet("the rust crawler", "rust"),
+            Some("the rust crawler".into())
+        );
+        assert_eq!(get_word_snippet("the rust crawler", "python"), None);
+
+        // Only whole words are matched
+        assert_eq!(get_word_snippet("trusted sources", "rust"), None);
+        assert_eq!(
+            get_word_snippet("trusted rust", "rust"),
+            Some("trusted rust".into())
+        );
+
+        // Long contents are cut around the word
+        let content = format!("{} rust {}", "a ".repeat(100), "b ".repeat(100));
+        let snippet = get_word_snippet(&content, "rust").unwrap();
+        assert!(snippet.starts_with("..."));
+        assert!(snippet.ends_with("..."));
+        assert!(snippet.contains(" rust "));
+        assert!(snippet.chars().count() <= WORD_SNIPPET_RADIUS * 2 + "rust".len() + 6);
+
+        // Multi-bytes characters
+        let content = format!("{} rust {}", "é ".repeat(100), "ü ".repeat(100));
+        assert!(get_word_snippet(&content, "rust").is_some());
+    }
+
+    #[test]
+    fn test_downloaded_favicons_query() {
+        let favicon_ids = [3, 4];
+        let query = get_downloaded_favicons_query(&favicon_ids);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains(r#""favicons"."id" = ANY($1)"#));
+        assert!(sql.contains(r#""favicons"."local_path" IS NOT NULL"#));
+        assert!(sql.contains("binds: [[3, 4]]"));
+    }
+
+    #[test]
+    fn test_get_page_favicon_url() {
+        let downloaded = HashSet::from([4]);
+
+        assert_eq!(
+            get_page_favicon_url(4, &downloaded),
+            Some("/api/favicon/4".to_string())
+        );
+        assert_eq!(get_page_favicon_url(5, &downloaded), None);
+    }
+
+    #[test]
+    fn test_get_favicon_path() {
+        let directory = FilePath::new("/srv/favicons");
+
+        assert_eq!(
+            get_favicon_path(directory, "4-1700000000000.png"),
+            Some(directory.join("4-1700000000000.png"))
+        );
+        assert_eq!(
+            get_favicon_path(directory, "../../etc/passwd"),
+            Some(directory.join("passwd"))
+        );
+        assert_eq!(get_favicon_path(directory, ".."), None);
+    }
 }
@@ -0,0 +1,224 @@
+use crate::{
+    environment::{ApiState, Environment},
+    middleware::auth::AuthenticatedUser,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use database::schema::queue;
+use diesel::{QueryDsl, RunQueryDsl};
+use serde::Serialize;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use utils::crawler_metrics::CrawlerMetrics;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn create_crawl_router() -> OpenApiRouter<ApiState> {
+    OpenApiRouter::new()
+        .routes(routes!(get_crawl_status_handler))
+        .routes(routes!(post_crawl_pause_handler))
+        .routes(routes!(post_crawl_resume_handler))
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+struct PauseResponse {
+    paused: bool,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+struct CrawlStatus {
+    /// Approximate number of crawled, queued and failed URLs
+    visited_count: usize,
+    /// The domains crawled most recently, the last one first
+    active_domains: Vec<String>,
+    /// Number of URLs in the queue
+    queue_depth: i64,
+    /// Crawled pages per second, averaged on the last 10 seconds
+    pages_per_second: f32,
+    /// Number of websites kept in memory by the crawler
+    websites_tracked: usize,
+}
+
+fn build_crawl_status(metrics: &CrawlerMetrics, queue_depth: i64) -> CrawlStatus {
+    CrawlStatus {
+        visited_count: metrics.visited_count.load(Ordering::Relaxed),
+        active_domains: metrics.active_domains.lock().unwrap().clone(),
+        queue_depth,
+        pages_per_second: metrics.get_pages_per_second(),
+        websites_tracked: metrics.websites_tracked.load(Ordering::Relaxed),
+    }
+}
+
+/// Set the pause flag, the workers see it before they dequeue their next URL
+fn set_crawler_paused(flag: &AtomicBool, paused: bool) -> PauseResponse {
+    flag.store(paused, Ordering::SeqCst);
+
+    PauseResponse {
+        paused: flag.load(Ordering::SeqCst),
+    }
+}
+
+/// Set the pause flag of the crawler, if it runs in the API process
+fn update_crawler_pause(
+    state: &Environment,
+    user: Option<Extension<AuthenticatedUser>>,
+    paused: bool,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match &state.crawler_paused {
+        Some(flag) => {
+            let response = set_crawler_paused(flag, paused);
+            println!(
+                "[API] Crawler {}",
+                if paused { "paused" } else { "resumed" }
+            );
+            Json(response).into_response()
+        }
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/pause",
+    description = "Stop the crawler from dequeuing URLs, the pages being crawled are finished. Requires a token from /api/token in the `Authorization: Bearer` header.",
+    responses(
+        (status = OK, body = PauseResponse),
+        (status = UNAUTHORIZED),
+        (status = SERVICE_UNAVAILABLE, description = "The crawler does not run in the API process")
+    )
+)]
+#[axum::debug_handler]
+async fn post_crawl_pause_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+) -> Response {
+    update_crawler_pause(&state, user, true)
+}
+
+#[utoipa::path(
+    post,
+    path = "/resume",
+    description = "Resume a paused crawler. Requires a token from /api/token in the `Authorization: Bearer` header.",
+    responses(
+        (status = OK, body = PauseResponse),
+        (status = UNAUTHORIZED),
+        (status = SERVICE_UNAVAILABLE, description = "The crawler does not run in the API process")
+    )
+)]
+#[axum::debug_handler]
+async fn post_crawl_resume_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+) -> Response {
+    update_crawler_pause(&state, user, false)
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    description = "Get the live state of the crawler. Requires a token from /api/token in the `Authorization: Bearer` header.",
+    responses(
+        (status = OK, body = CrawlStatus),
+        (status = UNAUTHORIZED),
+        (status = SERVICE_UNAVAILABLE, description = "The crawler does not run in the API process")
+    )
+)]
+#[axum::debug_handler]
+async fn get_crawl_status_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let metrics = match &state.crawler_metrics {
+        Some(metrics) => metrics,
+        None => return StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    };
+
+    let db_conn = &mut state.get_read_conn();
+    let queue_depth = queue::table.count().get_result::<i64>(db_conn).unwrap();
+
+    Json(build_crawl_status(metrics, queue_depth)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_crawl_status() {
+        let metrics = CrawlerMetrics::default();
+        metrics.visited_count.store(1200, Ordering::Relaxed);
+        metrics.websites_tracked.store(3, Ordering::Relaxed);
+        metrics.set_pages_per_second(2.5);
+        *metrics.active_domains.lock().unwrap() = vec!["example.com".to_string()];
+
+        assert_eq!(
+            build_crawl_status(&metrics, 42),
+            CrawlStatus {
+                visited_count: 1200,
+                active_domains: vec!["example.com".to_string()],
+                queue_depth: 42,
+                pages_per_second: 2.5,
+                websites_tracked: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_crawler_paused() {
+        let flag = Arc::new(AtomicBool::new(false));
+        // Shared like the flag of the crawler
+        let crawler_flag = flag.clone();
+
+        for _ in 0..3 {
+            assert_eq!(
+                set_crawler_paused(&flag, true),
+                PauseResponse { paused: true }
+            );
+            assert_eq!(crawler_flag.load(Ordering::SeqCst), true);
+
+            assert_eq!(
+                set_crawler_paused(&flag, false),
+                PauseResponse { paused: false }
+            );
+            assert_eq!(crawler_flag.load(Ordering::SeqCst), false);
+        }
+
+        // Pausing twice keeps it paused
+        set_crawler_paused(&flag, true);
+        assert_eq!(
+            set_crawler_paused(&flag, true),
+            PauseResponse { paused: true }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_crawl_pause_unauthorized() {
+        let state = Arc::new(Environment::for_tests());
+
+        let response = post_crawl_pause_handler(None, State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = post_crawl_resume_handler(None, State(state)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_crawl_status_unauthorized() {
+        let response =
+            get_crawl_status_handler(None, State(Arc::new(Environment::for_tests()))).await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
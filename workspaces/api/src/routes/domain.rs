@@ -0,0 +1,292 @@
+use crate::{
+    environment::{ApiState, Environment},
+    middleware::auth::AuthenticatedUser,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use database::{schema::pages, DbConn};
+use diesel::{
+    pg::Pg, prelude::QueryableByName, query_builder::QueryFragment, query_dsl::LoadQuery,
+    sql_query, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn create_domain_router() -> OpenApiRouter<ApiState> {
+    OpenApiRouter::new().routes(routes!(get_domain_handler))
+}
+
+/// Number of best pages of a domain summary
+pub const DOMAIN_TOP_PAGES: i64 = 5;
+
+/// Aggregates of the pages of a domain, computed in one query
+#[derive(QueryableByName, Debug, PartialEq)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct DomainAggregates {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    page_count: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    indexed_page_count: i64,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Double>)]
+    average_seo_score: Option<f64>,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::BigInt>)]
+    last_crawled: Option<i64>,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    inbound_link_count: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    clicks: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    impressions: i64,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+struct DomainTopPage {
+    url: String,
+    title: Option<String>,
+    seo_score: i32,
+}
+
+/// The crawl coverage of a domain
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+struct DomainSummary {
+    domain: String,
+    /// Number of crawled pages
+    page_count: i64,
+    /// Number of crawled pages which are indexed
+    indexed_page_count: i64,
+    /// Average SEO score of the pages, from 0 to 100
+    average_seo_score: f64,
+    /// Timestamp of the most recent crawl of a page
+    last_crawled: i64,
+    /// The pages with the best SEO scores
+    top_pages: Vec<DomainTopPage>,
+    /// Links to the pages of the domain from other domains
+    inbound_link_count: i64,
+    /// Clicks on the pages in the search results
+    clicks: i64,
+    /// Impressions of the pages in the search results
+    impressions: i64,
+}
+
+/// Normalize the domain of the path, `None` if it cannot be a domain
+fn clean_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().to_lowercase();
+    let is_valid = !domain.is_empty()
+        && domain.len() <= 100
+        && domain
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == ':');
+
+    is_valid.then_some(domain)
+}
+
+fn get_domain_aggregates(conn: &mut DbConn, domain: &str) -> QueryResult<DomainAggregates> {
+    sql_query(
+        "WITH domain_pages AS (
+            SELECT id, seo_score, last_crawled, last_indexed FROM pages WHERE domain = $1
+        ),
+        inbound_links AS (
+            SELECT COUNT(*) AS count FROM links
+            WHERE to_page_id IN (SELECT id FROM domain_pages)
+                AND from_page_id NOT IN (SELECT id FROM domain_pages)
+        ),
+        analytics AS (
+            SELECT COALESCE(SUM(clicks), 0)::int8 AS clicks,
+                COALESCE(SUM(impressions), 0)::int8 AS impressions
+            FROM pages_analytics
+            WHERE page_id IN (SELECT id FROM domain_pages)
+        )
+        SELECT COUNT(*) AS page_count,
+            COUNT(last_indexed) AS indexed_page_count,
+            AVG(seo_score)::float8 AS average_seo_score,
+            MAX(last_crawled) AS last_crawled,
+            (SELECT count FROM inbound_links) AS inbound_link_count,
+            (SELECT clicks FROM analytics) AS clicks,
+            (SELECT impressions FROM analytics) AS impressions
+        FROM domain_pages",
+    )
+    .bind::<diesel::sql_types::Text, _>(domain)
+    .get_result::<DomainAggregates>(conn)
+}
+
+fn get_top_pages_query(
+    domain: &str,
+) -> impl LoadQuery<'_, DbConn, (String, Option<String>, i32)> + QueryFragment<Pg> {
+    pages::table
+        .filter(pages::domain.eq(domain))
+        .select((pages::url, pages::title, pages::seo_score))
+        .order((pages::seo_score.desc(), pages::id))
+        .limit(DOMAIN_TOP_PAGES)
+}
+
+/// `None` when the domain has no crawled page
+fn build_domain_summary(
+    domain: String,
+    aggregates: DomainAggregates,
+    top_pages: Vec<(String, Option<String>, i32)>,
+) -> Option<DomainSummary> {
+    if aggregates.page_count == 0 {
+        return None;
+    }
+
+    Some(DomainSummary {
+        domain,
+        page_count: aggregates.page_count,
+        indexed_page_count: aggregates.indexed_page_count,
+        average_seo_score: aggregates.average_seo_score.unwrap_or(0.0),
+        last_crawled: aggregates.last_crawled.unwrap_or(0),
+        top_pages: top_pages
+            .into_iter()
+            .map(|(url, title, seo_score)| DomainTopPage {
+                url,
+                title,
+                seo_score,
+            })
+            .collect(),
+        inbound_link_count: aggregates.inbound_link_count,
+        clicks: aggregates.clicks,
+        impressions: aggregates.impressions,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/{domain}",
+    description = "Get a summary of the crawled pages of a domain, needs a token from /api/token as a bearer token",
+    params(
+        ("domain" = String, Path, description = "The domain, e.g. `example.com`")
+    ),
+    responses(
+        (status = OK, body = DomainSummary),
+        (status = BAD_REQUEST, description = "Invalid domain"),
+        (status = UNAUTHORIZED),
+        (status = NOT_FOUND, description = "No page of the domain is crawled")
+    )
+)]
+#[axum::debug_handler]
+async fn get_domain_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    Path(domain): Path<String>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let domain = match clean_domain(&domain) {
+        Some(domain) => domain,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let db_conn = &mut state.get_read_conn();
+
+    let aggregates = get_domain_aggregates(db_conn, &domain).unwrap();
+    let top_pages = get_top_pages_query(&domain)
+        .load::<(String, Option<String>, i32)>(db_conn)
+        .unwrap();
+
+    match build_domain_summary(domain, aggregates, top_pages) {
+        Some(summary) => Json(summary).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded_aggregates() -> DomainAggregates {
+        DomainAggregates {
+            page_count: 3,
+            indexed_page_count: 2,
+            average_seo_score: Some(70.0),
+            last_crawled: Some(1_700_000_000_000),
+            inbound_link_count: 12,
+            clicks: 5,
+            impressions: 40,
+        }
+    }
+
+    #[test]
+    fn test_clean_domain() {
+        assert_eq!(clean_domain("Example.COM"), Some("example.com".to_string()));
+        assert_eq!(
+            clean_domain("localhost:8080"),
+            Some("localhost:8080".to_string())
+        );
+        assert_eq!(clean_domain(""), None);
+        assert_eq!(clean_domain("example.com/path"), None);
+        assert_eq!(clean_domain(&"a".repeat(101)), None);
+    }
+
+    #[test]
+    fn test_top_pages_query() {
+        let query = get_top_pages_query("example.com");
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains(r#"WHERE ("pages"."domain" = $1)"#));
+        assert!(sql.contains(r#"ORDER BY "pages"."seo_score" DESC, "pages"."id""#));
+        assert!(sql.contains(r#"binds: ["example.com", 5]"#));
+    }
+
+    #[test]
+    fn test_build_domain_summary() {
+        let top_pages = vec![
+            (
+                "https://example.com/".to_string(),
+                Some("Home".to_string()),
+                90,
+            ),
+            ("https://example.com/about".to_string(), None, 70),
+        ];
+
+        let summary =
+            build_domain_summary("example.com".to_string(), seeded_aggregates(), top_pages)
+                .unwrap();
+        assert_eq!(summary.page_count, 3);
+        assert_eq!(summary.indexed_page_count, 2);
+        assert_eq!(summary.average_seo_score, 70.0);
+        assert_eq!(summary.inbound_link_count, 12);
+        assert_eq!((summary.clicks, summary.impressions), (5, 40));
+        assert_eq!(
+            summary.top_pages[0],
+            DomainTopPage {
+                url: "https://example.com/".to_string(),
+                title: Some("Home".to_string()),
+                seo_score: 90,
+            }
+        );
+
+        // Unknown domain
+        let aggregates = DomainAggregates {
+            page_count: 0,
+            indexed_page_count: 0,
+            average_seo_score: None,
+            last_crawled: None,
+            inbound_link_count: 0,
+            clicks: 0,
+            impressions: 0,
+        };
+        assert_eq!(
+            build_domain_summary("example.org".to_string(), aggregates, Vec::new()),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_domain_unauthorized() {
+        let response = get_domain_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Path("example.com".to_string()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::environment::{ApiState, Environment};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use database::count_available_connections;
+use serde::Serialize;
+use std::sync::Arc;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn create_health_router() -> OpenApiRouter<ApiState> {
+    OpenApiRouter::new().routes(routes!(get_health_handler))
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum HealthStatus {
+    Ok,
+    /// All the connections of the pool are in use
+    Degraded,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+struct Health {
+    status: HealthStatus,
+    /// Maximum number of connections of the pool
+    db_pool_size: u32,
+    /// Open connections which are not in use
+    db_pool_idle: u32,
+    /// Connections which can be used without waiting, idle or not opened yet
+    db_pool_available: u32,
+    /// Milliseconds since the API started
+    uptime_ms: i64,
+}
+
+fn build_health(pool_size: u32, connections: u32, idle: u32, uptime_ms: i64) -> Health {
+    let available = count_available_connections(pool_size, connections, idle);
+
+    Health {
+        status: if available == 0 {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Ok
+        },
+        db_pool_size: pool_size,
+        db_pool_idle: idle,
+        db_pool_available: available,
+        uptime_ms,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    description = "Get the state of the API and of its database pool",
+    responses(
+        (status = OK, body = Health),
+        (status = SERVICE_UNAVAILABLE, body = Health, description = "All the connections of the pool are in use")
+    )
+)]
+#[axum::debug_handler]
+async fn get_health_handler(State(state): State<Arc<Environment>>) -> Response {
+    let pool_state = state.db_pool.state();
+    let health = build_health(
+        state.db_pool.max_size(),
+        pool_state.connections,
+        pool_state.idle_connections,
+        state.uptime_start.elapsed().as_millis() as i64,
+    );
+
+    let status = match health.status {
+        HealthStatus::Ok => StatusCode::OK,
+        HealthStatus::Degraded => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (status, Json(health)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_health() {
+        assert_eq!(
+            build_health(40, 10, 3, 1_000),
+            Health {
+                status: HealthStatus::Ok,
+                db_pool_size: 40,
+                db_pool_idle: 3,
+                db_pool_available: 33,
+                uptime_ms: 1_000,
+            }
+        );
+
+        let health = build_health(40, 40, 0, 1_000);
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert_eq!(health.db_pool_available, 0);
+    }
+
+    #[tokio::test]
+    async fn test_health_without_connection() {
+        // The pool of the tests never connects, so all its connections are available
+        let response = get_health_handler(State(Arc::new(Environment::for_tests()))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{
+    environment::{ApiState, Environment},
+    middleware::auth::AuthenticatedUser,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use database::{
+    get_indexed_pages_filter, get_pages_to_index_filter,
+    schema::{pages, statistics},
+    types::StatisticType,
+    DbConn,
+};
+use diesel::{
+    dsl::count_star, pg::Pg, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult,
+    RunQueryDsl,
+};
+use serde::Serialize;
+use std::sync::{atomic::Ordering, Arc};
+use utils::indexer_state::IndexerState;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn create_index_router() -> OpenApiRouter<ApiState> {
+    OpenApiRouter::new()
+        .routes(routes!(get_index_status_handler))
+        .routes(routes!(post_index_trigger_handler))
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq)]
+struct IndexerRunStatus {
+    /// Timestamp of the end of the last run, 0 before the first one
+    last_run_at: i64,
+    /// Pages indexed by the last run
+    last_indexed_count: usize,
+    is_running: bool,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+struct IndexStatus {
+    indexed_page_count: i64,
+    /// Pages crawled since their last indexing
+    pending_page_count: i64,
+    /// Pages indexed per minute by the last indexer run
+    last_throughput: Option<i64>,
+    /// Unchanged pages skipped by the last indexer run
+    last_skipped_count: Option<i64>,
+    /// Timestamp of the last indexer run
+    last_run: Option<i64>,
+    /// Live state of the indexer, when it runs in the API process
+    #[serde(flatten)]
+    live: Option<IndexerRunStatus>,
+}
+
+fn build_indexer_run_status(state: &IndexerState) -> IndexerRunStatus {
+    IndexerRunStatus {
+        last_run_at: state.last_run_at.load(Ordering::Relaxed),
+        last_indexed_count: state.last_indexed_count.load(Ordering::Relaxed),
+        is_running: state.is_running.load(Ordering::Relaxed),
+    }
+}
+
+/// Count the indexed pages, or the pages the indexer has to index, as the indexer selects them
+fn get_page_count_query(
+    pending: bool,
+) -> pages::BoxedQuery<'static, Pg, diesel::sql_types::BigInt> {
+    let filter = if pending {
+        get_pages_to_index_filter()
+    } else {
+        get_indexed_pages_filter()
+    };
+
+    pages::table
+        .select(count_star())
+        .filter(filter)
+        .into_boxed()
+}
+
+/// Get the last `(value, timestamp)` of a statistic
+fn get_last_statistic(
+    conn: &mut DbConn,
+    statistic_type: StatisticType,
+) -> QueryResult<Option<(i64, i64)>> {
+    statistics::table
+        .filter(statistics::statistic_type.eq(statistic_type))
+        .select((statistics::value, statistics::timestamp))
+        .order(statistics::timestamp.desc())
+        .first::<(i64, i64)>(conn)
+        .optional()
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    description = "Get the progress of the indexer, with its live state when it runs in the API process. Requires a token from /api/token in the `Authorization: Bearer` header.",
+    responses(
+        (status = OK, body = IndexStatus),
+        (status = UNAUTHORIZED)
+    )
+)]
+#[axum::debug_handler]
+async fn get_index_status_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let db_conn = &mut state.get_read_conn();
+
+    let indexed_page_count = get_page_count_query(false)
+        .get_result::<i64>(db_conn)
+        .unwrap();
+    let pending_page_count = get_page_count_query(true)
+        .get_result::<i64>(db_conn)
+        .unwrap();
+
+    let throughput = get_last_statistic(db_conn, StatisticType::IndexerThroughput).unwrap();
+    // Saved after every run which had pages to index
+    let skipped = get_last_statistic(db_conn, StatisticType::IndexerSkippedCount).unwrap();
+
+    Json(IndexStatus {
+        indexed_page_count,
+        pending_page_count,
+        last_throughput: throughput.map(|(value, _)| value),
+        last_skipped_count: skipped.map(|(value, _)| value),
+        last_run: skipped.map(|(_, timestamp)| timestamp),
+        live: state.indexer_state.as_deref().map(build_indexer_run_status),
+    })
+    .into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/trigger",
+    description = "Start an indexer run without waiting for the end of its idle delay. Requires a token from /api/token in the `Authorization: Bearer` header.",
+    responses(
+        (status = ACCEPTED),
+        (status = UNAUTHORIZED),
+        (status = SERVICE_UNAVAILABLE, description = "The indexer does not run in the API process")
+    )
+)]
+#[axum::debug_handler]
+async fn post_index_trigger_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+) -> StatusCode {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    match &state.indexer_state {
+        Some(indexer_state) => {
+            // Kept until the indexer waits, when it is running
+            indexer_state.trigger.notify_one();
+            println!("[API] Indexer run triggered");
+            StatusCode::ACCEPTED
+        }
+        None => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_count_query() {
+        let sql = diesel::debug_query::<Pg, _>(&get_page_count_query(false)).to_string();
+        assert!(sql.contains(r#"("pages"."last_indexed" IS NOT NULL)"#));
+        assert!(sql.contains(r#"("pages"."deleted_at" IS NULL)"#));
+
+        // The duplicates skipped by the indexer are not pending
+        let sql = diesel::debug_query::<Pg, _>(&get_page_count_query(true)).to_string();
+        assert!(sql.contains(r#"("pages"."deleted_at" IS NULL)"#));
+        assert!(sql.contains(r#"NOT (EXISTS (SELECT "indexed_pages"."#));
+        assert!(sql.contains(r#"("indexed_pages"."body_hash" = "pages"."body_hash")"#));
+    }
+
+    #[test]
+    fn test_build_indexer_run_status() {
+        let state = IndexerState::default();
+        assert_eq!(
+            build_indexer_run_status(&state),
+            IndexerRunStatus {
+                last_run_at: 0,
+                last_indexed_count: 0,
+                is_running: false,
+            }
+        );
+
+        state.start_run();
+        assert!(build_indexer_run_status(&state).is_running);
+
+        state.finish_run(7);
+        let status = build_indexer_run_status(&state);
+        assert!(!status.is_running);
+        assert_eq!(status.last_indexed_count, 7);
+        assert!(status.last_run_at > 0);
+    }
+
+    #[tokio::test]
+    async fn test_index_endpoints_unauthorized() {
+        let state = Arc::new(Environment::for_tests());
+
+        let response = get_index_status_handler(None, State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let status = post_index_trigger_handler(None, State(state)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{
+    auth::{get_bearer_token, is_valid_api_key},
+    environment::{ApiState, Environment},
+};
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use database::{schema::statistics, types::StatisticType};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use std::{fmt::Write, sync::Arc};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn create_metrics_router() -> OpenApiRouter<ApiState> {
+    OpenApiRouter::new().routes(routes!(get_metrics_handler))
+}
+
+/// Content type of the Prometheus text exposition format
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Prefix of the metric names
+pub const METRICS_PREFIX: &str = "epsilon";
+
+/// Build a response in the Prometheus text exposition format
+#[derive(Default)]
+pub struct PrometheusExporter {
+    output: String,
+}
+
+impl PrometheusExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a gauge named `epsilon_{name}`
+    pub fn gauge(&mut self, name: &str, help: &str, value: i64) -> &mut Self {
+        let name = format!("{METRICS_PREFIX}_{name}");
+
+        writeln!(self.output, "# HELP {name} {help}").unwrap();
+        writeln!(self.output, "# TYPE {name} gauge").unwrap();
+        writeln!(self.output, "{name} {value}").unwrap();
+        self
+    }
+
+    /// Add the gauge of a statistic
+    pub fn statistic(&mut self, statistic_type: StatisticType, value: i64) -> &mut Self {
+        let (name, help) = get_statistic_metric(statistic_type);
+        self.gauge(name, help, value)
+    }
+
+    pub fn render(&self) -> String {
+        self.output.clone()
+    }
+}
+
+/// Name and description of the metric of a statistic
+fn get_statistic_metric(statistic_type: StatisticType) -> (&'static str, &'static str) {
+    match statistic_type {
+        StatisticType::CrawledPageCount => ("crawled_page_count", "Number of crawled pages"),
+        StatisticType::IndexedPageCount => ("indexed_page_count", "Number of indexed pages"),
+        StatisticType::ApiRequestCount => (
+            "api_request_count",
+            "API requests received between the last two saves",
+        ),
+        StatisticType::UserSearchCount => ("user_search_count", "Number of saved searches"),
+        StatisticType::DatabaseSize => ("database_size_bytes", "Size of the database"),
+        StatisticType::MemoryUsage => ("memory_usage_bytes", "Memory used by the process"),
+        StatisticType::CpuUsage => (
+            "cpu_usage",
+            "CPU usage of the process, in ten-thousandths of a percent",
+        ),
+        StatisticType::QueueSize => ("queue_size", "Number of queued pages"),
+        StatisticType::WordCount => ("word_count", "Number of indexed words"),
+        StatisticType::IndexesCount => ("indexes_count", "Number of word indexes"),
+        StatisticType::FaviconsCount => ("favicons_count", "Number of favicons"),
+        StatisticType::RecrawlCount => ("recrawl_count", "Pages queued by the last re-crawl"),
+        StatisticType::IndexerThroughput => (
+            "indexer_throughput",
+            "Pages indexed per minute by the last indexer run",
+        ),
+        StatisticType::IndexerSkippedCount => (
+            "indexer_skipped_count",
+            "Unchanged pages skipped by the last indexer run",
+        ),
+        StatisticType::FaviconRefreshCount => (
+            "favicon_refresh_count",
+            "Favicons refreshed between the last two saves",
+        ),
+        StatisticType::DiskUsage => ("disk_usage_bytes", "Used bytes of the filesystem"),
+        StatisticType::FaviconDirSize => {
+            ("favicon_dir_size_bytes", "Size of the favicons directory")
+        }
+        StatisticType::AlertFiredCount => (
+            "alert_fired_count",
+            "Alerts fired by the monitor at the last check",
+        ),
+        StatisticType::DomainCount => ("domain_count", "Number of crawled domains"),
+        StatisticType::DeletedPageCount => (
+            "deleted_page_count",
+            "Number of deleted pages waiting to be purged",
+        ),
+        StatisticType::AverageResponseTime => (
+            "average_response_time_ms",
+            "Median response time of the crawled pages, in milliseconds",
+        ),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    description = "Get the last statistics in the Prometheus text format, needs the API key as a bearer token",
+    responses(
+        (status = OK, content_type = "text/plain", body = String),
+        (status = UNAUTHORIZED)
+    )
+)]
+#[axum::debug_handler]
+async fn get_metrics_handler(
+    State(state): State<Arc<Environment>>,
+    headers: HeaderMap,
+) -> Response {
+    // Scrapers cannot renew the tokens of /api/token, so the API key is used directly
+    if !get_bearer_token(&headers)
+        .is_some_and(|api_key| is_valid_api_key(api_key, state.api_key.as_deref()))
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let db_conn = &mut state.get_read_conn();
+
+    // The last value of each statistic
+    let statistics = statistics::table
+        .distinct_on(statistics::statistic_type)
+        .select((statistics::statistic_type, statistics::value))
+        .order((statistics::statistic_type, statistics::timestamp.desc()))
+        .load::<(StatisticType, i64)>(db_conn)
+        .unwrap();
+
+    let mut exporter = PrometheusExporter::new();
+    for (statistic_type, value) in statistics {
+        exporter.statistic(statistic_type, value);
+    }
+
+    let pool_state = state.db_pool.state();
+    exporter
+        .gauge(
+            "db_pool_active_connections",
+            "Connections of the API pool in use",
+            (pool_state.connections - pool_state.idle_connections) as i64,
+        )
+        .gauge(
+            "db_pool_idle_connections",
+            "Idle connections of the API pool",
+            pool_state.idle_connections as i64,
+        );
+
+    ([(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], exporter.render()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_exporter() {
+        let mut exporter = PrometheusExporter::new();
+        exporter
+            .statistic(StatisticType::CrawledPageCount, 42)
+            .statistic(StatisticType::CpuUsage, 125_000)
+            .statistic(StatisticType::QueueSize, 7)
+            .gauge("db_pool_idle_connections", "Idle connections", 3);
+        let output = exporter.render();
+
+        assert_eq!(
+            output.contains(
+                "# HELP epsilon_crawled_page_count Number of crawled pages\n\
+                # TYPE epsilon_crawled_page_count gauge\n\
+                epsilon_crawled_page_count 42\n"
+            ),
+            true
+        );
+        // 12.5%, saved as `percent * 10000` by the monitor
+        assert_eq!(output.contains("epsilon_cpu_usage 125000\n"), true);
+        assert_eq!(output.contains("epsilon_queue_size 7\n"), true);
+        assert_eq!(
+            output.contains("epsilon_db_pool_idle_connections 3\n"),
+            true
+        );
+    }
+
+    #[test]
+    fn test_statistic_metric_names() {
+        let (name, _) = get_statistic_metric(StatisticType::DiskUsage);
+        assert_eq!(name, "disk_usage_bytes");
+
+        // Valid Prometheus metric names
+        for statistic_type in [
+            StatisticType::IndexedPageCount,
+            StatisticType::IndexerThroughput,
+            StatisticType::FaviconDirSize,
+        ] {
+            let (name, _) = get_statistic_metric(statistic_type);
+            assert_eq!(
+                name.chars().all(|c| c.is_ascii_lowercase() || c == '_'),
+                true
+            );
+        }
+    }
+}
@@ -1,4 +1,11 @@
+pub mod admin;
 pub mod analytics;
 pub mod base;
+pub mod crawl;
+pub mod domain;
+pub mod health;
+pub mod index;
+pub mod metrics;
 pub mod statistics;
 pub mod votes;
+pub mod words;
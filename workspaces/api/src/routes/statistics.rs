@@ -1,15 +1,41 @@
 use crate::environment::{ApiState, Environment};
 use axum::{extract::State, Json};
-use database::{get_database_size, get_table_sizes};
-use diesel::{prelude::QueryableByName, RunQueryDsl};
+use database::{
+    get_database_size, get_table_sizes,
+    models::{CrawlError, DomainStat},
+    schema::{crawl_errors, domain_stats, pages},
+    DbConn,
+};
+use diesel::{
+    dsl::{count_star, max, sql},
+    pg::Pg,
+    prelude::QueryableByName,
+    query_builder::QueryFragment,
+    query_dsl::LoadQuery,
+    sql_types::{Integer, Nullable},
+    ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper,
+};
 use serde::Serialize;
 use std::sync::Arc;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 pub fn create_statistics_router() -> OpenApiRouter<ApiState> {
-    OpenApiRouter::new().routes(routes!(get_statistics_database_handler))
+    OpenApiRouter::new()
+        .routes(routes!(get_statistics_database_handler))
+        .routes(routes!(get_statistics_crawl_errors_handler))
+        .routes(routes!(get_statistics_domains_handler))
+        .routes(routes!(get_statistics_slow_domains_handler))
 }
 
+/// Number of errors listed in `recent`
+pub const RECENT_CRAWL_ERRORS: i64 = 20;
+
+/// Number of domains listed by `GET /api/statistics/domains`
+pub const TOP_DOMAINS: i64 = 100;
+
+/// Number of domains listed by `GET /api/statistics/slow-domains`
+pub const TOP_SLOW_DOMAINS_COUNT: i64 = 10;
+
 #[derive(utoipa::ToSchema, Serialize)]
 struct TableSize {
     name: String,
@@ -73,7 +99,7 @@ struct Statistics {
 async fn get_statistics_database_handler(
     State(state): State<Arc<Environment>>,
 ) -> Json<Statistics> {
-    let db_conn = &mut state.db_pool.get().unwrap();
+    let db_conn = &mut state.get_read_conn();
 
     let stats = diesel::sql_query(
         "SELECT 
@@ -114,3 +140,214 @@ async fn get_statistics_database_handler(
         query_count: stats.query_count,
     })
 }
+
+#[derive(utoipa::ToSchema, Serialize)]
+struct CrawlErrorGroup {
+    error_type: String,
+    count: i64,
+    /// Timestamp of the last error of this type
+    last_seen: Option<i64>,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+struct CrawlErrorEntry {
+    url: String,
+    domain: String,
+    error_type: String,
+    error_detail: Option<String>,
+    timestamp: i64,
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+struct CrawlErrorsStatistics {
+    /// Error counts per type, most frequent first
+    groups: Vec<CrawlErrorGroup>,
+    /// The last errors
+    recent: Vec<CrawlErrorEntry>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/crawl-errors",
+    description = "Get the crawl errors of the last 7 days, grouped by type",
+    responses(
+        (status = OK, body = CrawlErrorsStatistics)
+    )
+)]
+#[axum::debug_handler]
+async fn get_statistics_crawl_errors_handler(
+    State(state): State<Arc<Environment>>,
+) -> Json<CrawlErrorsStatistics> {
+    let db_conn = &mut state.get_read_conn();
+
+    let groups = crawl_errors::table
+        .group_by(crawl_errors::error_type)
+        .select((
+            crawl_errors::error_type,
+            count_star(),
+            max(crawl_errors::timestamp),
+        ))
+        .order(count_star().desc())
+        .load::<(String, i64, Option<i64>)>(db_conn)
+        .unwrap();
+
+    let recent = crawl_errors::table
+        .select(CrawlError::as_select())
+        .order(crawl_errors::timestamp.desc())
+        .limit(RECENT_CRAWL_ERRORS)
+        .load::<CrawlError>(db_conn)
+        .unwrap();
+
+    Json(CrawlErrorsStatistics {
+        groups: groups
+            .into_iter()
+            .map(|(error_type, count, last_seen)| CrawlErrorGroup {
+                error_type,
+                count,
+                last_seen,
+            })
+            .collect(),
+        recent: recent
+            .into_iter()
+            .map(|e| CrawlErrorEntry {
+                url: e.url,
+                domain: e.domain,
+                error_type: e.error_type,
+                error_detail: e.error_detail,
+                timestamp: e.timestamp,
+            })
+            .collect(),
+    })
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+struct DomainStatistics {
+    domain: String,
+    /// Crawled pages, re-crawls included
+    pages_crawled: i64,
+    /// Pages indexed at least once
+    pages_indexed: i64,
+    /// Timestamp of the last crawled page, `null` if only errors were met
+    last_crawled: Option<i64>,
+    /// Average response time in milliseconds
+    avg_response_time: i32,
+    /// Non-recoverable crawl errors, e.g. connection failures
+    error_count: i32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/domains",
+    description = "Get the crawl statistics of the domains with the most crawled pages",
+    responses(
+        (status = OK, body = Vec<DomainStatistics>)
+    )
+)]
+#[axum::debug_handler]
+async fn get_statistics_domains_handler(
+    State(state): State<Arc<Environment>>,
+) -> Json<Vec<DomainStatistics>> {
+    let db_conn = &mut state.get_read_conn();
+
+    let domains = domain_stats::table
+        .select(DomainStat::as_select())
+        .order((domain_stats::pages_crawled.desc(), domain_stats::domain))
+        .limit(TOP_DOMAINS)
+        .load::<DomainStat>(db_conn)
+        .unwrap();
+
+    Json(
+        domains
+            .into_iter()
+            .map(|d| DomainStatistics {
+                domain: d.domain,
+                pages_crawled: d.pages_crawled,
+                pages_indexed: d.pages_indexed,
+                last_crawled: d.last_crawled,
+                avg_response_time: d.avg_response_time,
+                error_count: d.error_count,
+            })
+            .collect(),
+    )
+}
+
+#[derive(utoipa::ToSchema, Serialize)]
+struct SlowDomain {
+    domain: String,
+    /// Average response time of the crawled pages, in milliseconds
+    avg_response_time: i32,
+    page_count: i64,
+}
+
+/// Get the `(domain, avg_response_time, page_count)` of the domains with the slowest pages
+fn get_slow_domains_query(
+) -> impl LoadQuery<'static, DbConn, (String, Option<i32>, i64)> + QueryFragment<Pg> {
+    pages::table
+        .filter(pages::deleted_at.is_null())
+        .group_by(pages::domain)
+        .select((
+            pages::domain,
+            sql::<Nullable<Integer>>("ROUND(AVG(response_time))::int4"),
+            count_star(),
+        ))
+        .order((sql::<Integer>("AVG(response_time) DESC"), pages::domain))
+        .limit(TOP_SLOW_DOMAINS_COUNT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/slow-domains",
+    description = "Get the domains with the highest average response time of their crawled pages",
+    responses(
+        (status = OK, body = Vec<SlowDomain>)
+    )
+)]
+#[axum::debug_handler]
+async fn get_statistics_slow_domains_handler(
+    State(state): State<Arc<Environment>>,
+) -> Json<Vec<SlowDomain>> {
+    let db_conn = &mut state.get_read_conn();
+
+    let domains = get_slow_domains_query()
+        .load::<(String, Option<i32>, i64)>(db_conn)
+        .unwrap();
+
+    Json(
+        domains
+            .into_iter()
+            .map(|(domain, avg_response_time, page_count)| SlowDomain {
+                domain,
+                avg_response_time: avg_response_time.unwrap_or(0),
+                page_count,
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::debug_query;
+
+    #[test]
+    fn test_slow_domains_query() {
+        let sql = debug_query::<Pg, _>(&get_slow_domains_query()).to_string();
+
+        assert_eq!(
+            sql.contains(
+                r#"SELECT "pages"."domain", ROUND(AVG(response_time))::int4, COUNT(*) FROM "pages""#
+            ),
+            true
+        );
+        assert_eq!(
+            sql.contains(r#"WHERE ("pages"."deleted_at" IS NULL)"#),
+            true
+        );
+        assert_eq!(sql.contains(r#"GROUP BY "pages"."domain""#), true);
+        assert_eq!(
+            sql.contains(r#"ORDER BY AVG(response_time) DESC, "pages"."domain" LIMIT $1"#),
+            true
+        );
+        assert_eq!(sql.contains("binds: [10]"), true);
+    }
+}
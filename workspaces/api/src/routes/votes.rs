@@ -46,7 +46,7 @@ async fn post_vote_handler(
         return StatusCode::BAD_REQUEST;
     }
 
-    let db_conn = &mut state.db_pool.get().unwrap();
+    let db_conn = &mut state.get_conn();
 
     if let Some(page_id) = pages::table
         .select(pages::id)
@@ -0,0 +1,217 @@
+use crate::{
+    environment::{ApiState, Environment},
+    middleware::auth::AuthenticatedUser,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use diesel::{prelude::QueryableByName, sql_query, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn create_words_router() -> OpenApiRouter<ApiState> {
+    OpenApiRouter::new()
+        .routes(routes!(get_words_handler))
+        .routes(routes!(get_rare_words_handler))
+}
+
+pub const DEFAULT_WORDS_LIMIT: i64 = 50;
+
+pub const MAX_WORDS_LIMIT: i64 = 500;
+
+const TOP_WORDS_SQL: &str =
+    "SELECT w.word, SUM(i.count)::int8 AS total_count, COUNT(*) AS page_count
+    FROM words w
+    INNER JOIN indexes i ON i.word_id = w.id
+    GROUP BY w.id, w.word
+    HAVING SUM(i.count) >= $1
+    ORDER BY total_count DESC, w.word
+    LIMIT $2";
+
+const RARE_WORDS_SQL: &str =
+    "SELECT w.word, SUM(i.count)::int8 AS total_count, COUNT(*) AS page_count
+    FROM words w
+    INNER JOIN indexes i ON i.word_id = w.id
+    GROUP BY w.id, w.word
+    HAVING COUNT(*) = 1
+    ORDER BY total_count DESC, w.word
+    LIMIT $1";
+
+#[derive(utoipa::ToSchema, Serialize, QueryableByName)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct WordFrequency {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    word: String,
+    /// Occurrences of the word in all the indexed pages
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    total_count: i64,
+    /// Number of indexed pages containing the word
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    page_count: i64,
+}
+
+#[derive(Deserialize)]
+struct WordsQuery {
+    limit: Option<i64>,
+    min_count: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct RareWordsQuery {
+    limit: Option<i64>,
+}
+
+/// Get the number of words to return, `None` if it is out of range
+fn get_words_limit(limit: Option<i64>) -> Option<i64> {
+    match limit {
+        None => Some(DEFAULT_WORDS_LIMIT),
+        Some(limit) if (1..=MAX_WORDS_LIMIT).contains(&limit) => Some(limit),
+        _ => None,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/words",
+    description = "Get the most frequent words of the index, needs a token from /api/token as a bearer token",
+    params(
+        ("limit" = Option<i64>, Query, description = "The number of words, up to 500 (default: 50)"),
+        ("min_count" = Option<i64>, Query, description = "Only return the words with at least this number of occurrences (default: 1)")
+    ),
+    responses(
+        (status = OK, body = Vec<WordFrequency>),
+        (status = BAD_REQUEST, description = "Invalid limit or min_count"),
+        (status = UNAUTHORIZED)
+    )
+)]
+#[axum::debug_handler]
+async fn get_words_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    query: Query<WordsQuery>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let limit = match get_words_limit(query.limit) {
+        Some(limit) => limit,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let min_count = query.min_count.unwrap_or(1);
+    if min_count < 1 {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let db_conn = &mut state.get_read_conn();
+
+    let words = sql_query(TOP_WORDS_SQL)
+        .bind::<diesel::sql_types::BigInt, _>(min_count)
+        .bind::<diesel::sql_types::BigInt, _>(limit)
+        .load::<WordFrequency>(db_conn)
+        .unwrap();
+
+    Json(words).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/words-rare",
+    description = "Get the words found in a single page, which inflate the index, needs a token from /api/token as a bearer token",
+    params(
+        ("limit" = Option<i64>, Query, description = "The number of words, up to 500 (default: 50)")
+    ),
+    responses(
+        (status = OK, body = Vec<WordFrequency>),
+        (status = BAD_REQUEST, description = "Invalid limit"),
+        (status = UNAUTHORIZED)
+    )
+)]
+#[axum::debug_handler]
+async fn get_rare_words_handler(
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<Environment>>,
+    query: Query<RareWordsQuery>,
+) -> Response {
+    if user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let limit = match get_words_limit(query.limit) {
+        Some(limit) => limit,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let db_conn = &mut state.get_read_conn();
+
+    let words = sql_query(RARE_WORDS_SQL)
+        .bind::<diesel::sql_types::BigInt, _>(limit)
+        .load::<WordFrequency>(db_conn)
+        .unwrap();
+
+    Json(words).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::pg::Pg;
+
+    #[test]
+    fn test_words_limit() {
+        assert_eq!(get_words_limit(None), Some(DEFAULT_WORDS_LIMIT));
+        assert_eq!(get_words_limit(Some(500)), Some(500));
+        assert_eq!(get_words_limit(Some(0)), None);
+        assert_eq!(get_words_limit(Some(501)), None);
+    }
+
+    #[test]
+    fn test_words_queries() {
+        let query = sql_query(TOP_WORDS_SQL)
+            .bind::<diesel::sql_types::BigInt, _>(3)
+            .bind::<diesel::sql_types::BigInt, _>(50);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains("HAVING SUM(i.count) >= $1"));
+        assert!(sql.contains("ORDER BY total_count DESC"));
+        assert!(sql.contains("binds: [3, 50]"));
+
+        let query = sql_query(RARE_WORDS_SQL).bind::<diesel::sql_types::BigInt, _>(50);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains("HAVING COUNT(*) = 1"));
+    }
+
+    #[test]
+    fn test_words_paths() {
+        let (_, api) = create_words_router().split_for_parts();
+        let paths: Vec<&String> = api.paths.paths.keys().collect();
+
+        // `/words/{word}` is the details of a word
+        assert_eq!(paths, vec!["/words", "/words-rare"]);
+    }
+
+    #[tokio::test]
+    async fn test_words_unauthorized() {
+        let response = get_words_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Query(WordsQuery {
+                limit: None,
+                min_count: None,
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = get_rare_words_handler(
+            None,
+            State(Arc::new(Environment::for_tests())),
+            Query(RareWordsQuery { limit: None }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{
+    environment::Environment,
+    routes::base::{parse_query, search_pages, ResultsRange, RESULTS_PER_PAGE},
+};
+use std::time::Instant;
+
+/// Parse the newline-separated `SEARCH_WARM_QUERIES` env
+pub fn parse_warm_queries(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(|q| q.trim().to_lowercase())
+        .filter(|q| !q.is_empty() && q.len() < 256)
+        .collect()
+}
+
+/// Run queries through the search to load the frequently used index pages
+/// into the PostgreSQL shared buffers. The results are discarded.
+pub fn warm_search_index(env: &Environment, queries: &[String]) {
+    let db_conn = &mut env.get_read_conn();
+
+    println!(
+        "[API] Warming the search index with {} queries",
+        queries.len()
+    );
+
+    let start = Instant::now();
+    run_warm_queries(queries, |q| {
+        let mut parsed_query = parse_query(q);
+        parsed_query.stemming = env.stemming;
+        // The first results page, with the look-ahead result of the search handler
+        let range = ResultsRange::new(None, 0, RESULTS_PER_PAGE as i64 + 1);
+        search_pages(db_conn, &parsed_query, &range).len()
+    });
+
+    println!("[API] Search index warmed in {:?}", start.elapsed());
+}
+
+/// Run each query with `search` and log its execution time.
+/// Returns the queries that were run.
+fn run_warm_queries<F>(queries: &[String], mut search: F) -> Vec<String>
+where
+    F: FnMut(&str) -> usize,
+{
+    let mut ran = Vec::new();
+
+    for query in queries {
+        let start = Instant::now();
+        let results = search(query);
+        println!(
+            "[API] Warming query '{query}': {results} results in {:?}",
+            start.elapsed()
+        );
+
+        ran.push(query.clone());
+    }
+
+    ran
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_warm_queries() {
+        assert_eq!(parse_warm_queries(""), Vec::<String>::new());
+        assert_eq!(
+            parse_warm_queries("rust\n\n  Open Source \nweb crawler\n"),
+            vec!["rust", "open source", "web crawler"]
+        );
+        assert_eq!(parse_warm_queries(&"a".repeat(300)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_run_warm_queries() {
+        let queries = parse_warm_queries("rust\nopen source\nweb crawler");
+        let mut searched = Vec::new();
+
+        let ran = run_warm_queries(&queries, |q| {
+            searched.push(q.to_string());
+            0
+        });
+
+        assert_eq!(searched, vec!["rust", "open source", "web crawler"]);
+        assert_eq!(ran, searched);
+    }
+}
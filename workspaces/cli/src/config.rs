@@ -0,0 +1,370 @@
+use serde::{Deserialize, Serialize};
+use std::{any::type_name, env, fs, path::Path, str::FromStr};
+
+/// Env of the configuration file path
+pub const CONFIG_PATH_ENV: &str = "EPSILON_CONFIG";
+
+/// Configuration file loaded from the current directory when `EPSILON_CONFIG` is not set
+pub const DEFAULT_CONFIG_FILE: &str = "epsilon.toml";
+
+/// The configuration of all the services, read from a TOML file and overridden by the env.
+/// A `None` value keeps the default of the service.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// `DATABASE_URL`
+    pub database_url: Option<String>,
+    /// `SERVICES` (space-separated), the CLI arguments are used when not set
+    pub services: Option<Vec<String>>,
+    /// `TOKIO_WORKER_THREADS`
+    pub worker_threads: Option<usize>,
+    /// `USER_AGENT`, used by the crawler and the favicons downloader
+    pub user_agent: Option<String>,
+    /// `DB_POOL_MAX_SIZE`, the connections of the pool shared by the services
+    pub db_pool_max_size: Option<u32>,
+    /// `DB_POOL_MIN_IDLE`
+    pub db_pool_min_idle: Option<u32>,
+    /// `DB_POOL_CONNECTION_TIMEOUT_SECS`
+    pub db_pool_connection_timeout_secs: Option<u64>,
+    pub api: ApiConfig,
+    pub crawler: CrawlerConfig,
+    pub favicons: FaviconsConfig,
+    pub indexer: IndexerConfig,
+    pub monitor: MonitorConfig,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// `PORT`
+    pub port: Option<u16>,
+    /// `BM25_K1`
+    pub bm25_k1: Option<f64>,
+    /// `BM25_B`
+    pub bm25_b: Option<f64>,
+    /// `SEARCH_CACHE_SIZE`
+    pub search_cache_size: Option<usize>,
+    /// `SEARCH_WARM_ON_STARTUP`
+    pub search_warm_on_startup: Option<bool>,
+    /// `SEARCH_WARM_QUERIES` (newline-separated)
+    pub search_warm_queries: Option<String>,
+    /// `RATE_LIMIT_RPM`
+    pub rate_limit_rpm: Option<u32>,
+    /// `RATE_LIMIT_BURST`
+    pub rate_limit_burst: Option<u32>,
+    /// `REPLICA_DATABASE_URL`
+    pub replica_database_url: Option<String>,
+    /// `API_KEY`
+    pub api_key: Option<String>,
+    /// `JWT_SECRET`
+    pub jwt_secret: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrawlerConfig {
+    /// `CRAWLER_THREADS`
+    pub threads: Option<usize>,
+    /// `LOCAL_QUEUE_SIZE`
+    pub local_queue_size: Option<usize>,
+    /// `CRAWL_MAX_DEPTH`
+    pub max_depth: Option<usize>,
+    /// `CRAWLER_BLOCKLIST` (newline-separated domain patterns)
+    pub blocklist: Option<String>,
+    /// `CRAWLER_ALLOWLIST` (newline-separated domain patterns)
+    pub allowlist: Option<String>,
+    /// `CRAWLER_URL_BLACKLIST` (newline-separated URL regex patterns)
+    pub url_blacklist: Option<String>,
+    /// `CRAWLER_SEED_FILE` (path of a newline-separated list of URLs)
+    pub seed_file: Option<String>,
+    /// `CRAWLER_SEED_PRIORITY` (queue timestamp of the seed URLs)
+    pub seed_priority: Option<i64>,
+    /// `CRAWLER_DOMAIN_BUDGET`
+    pub domain_budget: Option<usize>,
+    /// `CRAWLER_MAX_REDIRECTS`
+    pub max_redirects: Option<u8>,
+    /// `CRAWLER_MAX_BODY_BYTES`
+    pub max_body_bytes: Option<usize>,
+    /// `CRAWLER_PROXY_URL`
+    pub proxy_url: Option<String>,
+    /// `CRAWLER_PROXY_USERNAME`
+    pub proxy_username: Option<String>,
+    /// `CRAWLER_PROXY_PASSWORD`
+    pub proxy_password: Option<String>,
+    /// `CRAWLER_NO_PROXY` (comma-separated domain suffixes)
+    pub no_proxy: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaviconsConfig {
+    /// `FAVICONS_TASKS`
+    pub tasks: Option<usize>,
+    /// `FAVICON_REFRESH_DAYS`
+    pub refresh_days: Option<i64>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexerConfig {
+    /// `INDEXER_CONCURRENCY`
+    pub concurrency: Option<usize>,
+    /// `INDEXER_STEMMING`, shared by the indexer and the API
+    pub stemming: Option<bool>,
+    /// `INDEXER_STOPWORDS_FILE`
+    pub stopwords_file: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonitorConfig {
+    /// `MONITOR_SYS_INTERVAL_SECS`
+    pub sys_interval_secs: Option<u64>,
+    /// `MONITOR_DB_INTERVAL_SECS`
+    pub db_interval_secs: Option<u64>,
+    /// `MONITOR_CLEANUP_INTERVAL_SECS`
+    pub cleanup_interval_secs: Option<u64>,
+    /// `MONITOR_INITIAL_DELAY_SECS`
+    pub initial_delay_secs: Option<u64>,
+    /// `RECRAWL_TTL_MS`
+    pub recrawl_ttl_ms: Option<i64>,
+    /// `ALERT_QUEUE_SIZE_MAX`
+    pub alert_queue_size_max: Option<i64>,
+    /// `ALERT_DB_SIZE_MAX_GB`
+    pub alert_db_size_max_gb: Option<i64>,
+    /// `ALERT_CPU_USAGE_MAX`
+    pub alert_cpu_usage_max: Option<i64>,
+    /// `ALERT_WEBHOOK_URL`
+    pub alert_webhook_url: Option<String>,
+}
+
+/// Apply the env variables to the config, collecting the invalid ones
+struct EnvOverrides<F> {
+    var: F,
+    errors: Vec<String>,
+}
+
+impl<F: Fn(&str) -> Option<String>> EnvOverrides<F> {
+    /// Replace `field` with the parsed value of the `name` variable, if it is set
+    fn set<T: FromStr>(&mut self, field: &mut Option<T>, name: &str) {
+        if let Some(value) = (self.var)(name) {
+            match value.trim().parse::<T>() {
+                Ok(value) => *field = Some(value),
+                Err(_) => self.errors.push(format!(
+                    "{name} must be a valid {}, got '{value}'",
+                    type_name::<T>()
+                )),
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Load the file of `EPSILON_CONFIG` (or `epsilon.toml` if it exists), then apply the env
+    pub fn load() -> Result<Self, Vec<String>> {
+        let mut config = if let Ok(path) = env::var(CONFIG_PATH_ENV) {
+            Self::from_file(Path::new(&path)).map_err(|e| vec![e])?
+        } else if Path::new(DEFAULT_CONFIG_FILE).exists() {
+            Self::from_file(Path::new(DEFAULT_CONFIG_FILE)).map_err(|e| vec![e])?
+        } else {
+            Self::default()
+        };
+
+        config.apply_env(|name| env::var(name).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+
+        Self::parse(&content).map_err(|e| format!("Invalid {}: {e}", path.display()))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    /// Override the values with the variables returned by `var`
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), Vec<String>> {
+        let mut env = EnvOverrides {
+            var,
+            errors: Vec::new(),
+        };
+
+        env.set(&mut self.database_url, "DATABASE_URL");
+        if let Some(services) = (env.var)("SERVICES") {
+            self.services = Some(services.split(' ').map(String::from).collect());
+        }
+        env.set(&mut self.worker_threads, "TOKIO_WORKER_THREADS");
+        env.set(&mut self.user_agent, "USER_AGENT");
+        env.set(&mut self.db_pool_max_size, "DB_POOL_MAX_SIZE");
+        env.set(&mut self.db_pool_min_idle, "DB_POOL_MIN_IDLE");
+        env.set(
+            &mut self.db_pool_connection_timeout_secs,
+            "DB_POOL_CONNECTION_TIMEOUT_SECS",
+        );
+
+        let api = &mut self.api;
+        env.set(&mut api.port, "PORT");
+        env.set(&mut api.bm25_k1, "BM25_K1");
+        env.set(&mut api.bm25_b, "BM25_B");
+        env.set(&mut api.search_cache_size, "SEARCH_CACHE_SIZE");
+        env.set(&mut api.search_warm_on_startup, "SEARCH_WARM_ON_STARTUP");
+        env.set(&mut api.search_warm_queries, "SEARCH_WARM_QUERIES");
+        env.set(&mut api.rate_limit_rpm, "RATE_LIMIT_RPM");
+        env.set(&mut api.rate_limit_burst, "RATE_LIMIT_BURST");
+        env.set(&mut api.replica_database_url, "REPLICA_DATABASE_URL");
+        env.set(&mut api.api_key, "API_KEY");
+        env.set(&mut api.jwt_secret, "JWT_SECRET");
+
+        let crawler = &mut self.crawler;
+        env.set(&mut crawler.threads, "CRAWLER_THREADS");
+        env.set(&mut crawler.local_queue_size, "LOCAL_QUEUE_SIZE");
+        env.set(&mut crawler.max_depth, "CRAWL_MAX_DEPTH");
+        env.set(&mut crawler.blocklist, "CRAWLER_BLOCKLIST");
+        env.set(&mut crawler.allowlist, "CRAWLER_ALLOWLIST");
+        env.set(&mut crawler.url_blacklist, "CRAWLER_URL_BLACKLIST");
+        env.set(&mut crawler.seed_file, "CRAWLER_SEED_FILE");
+        env.set(&mut crawler.seed_priority, "CRAWLER_SEED_PRIORITY");
+        env.set(&mut crawler.domain_budget, "CRAWLER_DOMAIN_BUDGET");
+        env.set(&mut crawler.max_redirects, "CRAWLER_MAX_REDIRECTS");
+        env.set(&mut crawler.max_body_bytes, "CRAWLER_MAX_BODY_BYTES");
+        env.set(&mut crawler.proxy_url, "CRAWLER_PROXY_URL");
+        env.set(&mut crawler.proxy_username, "CRAWLER_PROXY_USERNAME");
+        env.set(&mut crawler.proxy_password, "CRAWLER_PROXY_PASSWORD");
+        env.set(&mut crawler.no_proxy, "CRAWLER_NO_PROXY");
+
+        let favicons = &mut self.favicons;
+        env.set(&mut favicons.tasks, "FAVICONS_TASKS");
+        env.set(&mut favicons.refresh_days, "FAVICON_REFRESH_DAYS");
+
+        let indexer = &mut self.indexer;
+        env.set(&mut indexer.concurrency, "INDEXER_CONCURRENCY");
+        env.set(&mut indexer.stemming, "INDEXER_STEMMING");
+        env.set(&mut indexer.stopwords_file, "INDEXER_STOPWORDS_FILE");
+
+        let monitor = &mut self.monitor;
+        env.set(&mut monitor.sys_interval_secs, "MONITOR_SYS_INTERVAL_SECS");
+        env.set(&mut monitor.db_interval_secs, "MONITOR_DB_INTERVAL_SECS");
+        env.set(
+            &mut monitor.cleanup_interval_secs,
+            "MONITOR_CLEANUP_INTERVAL_SECS",
+        );
+        env.set(
+            &mut monitor.initial_delay_secs,
+            "MONITOR_INITIAL_DELAY_SECS",
+        );
+        env.set(&mut monitor.recrawl_ttl_ms, "RECRAWL_TTL_MS");
+        env.set(&mut monitor.alert_queue_size_max, "ALERT_QUEUE_SIZE_MAX");
+        env.set(&mut monitor.alert_db_size_max_gb, "ALERT_DB_SIZE_MAX_GB");
+        env.set(&mut monitor.alert_cpu_usage_max, "ALERT_CPU_USAGE_MAX");
+        env.set(&mut monitor.alert_webhook_url, "ALERT_WEBHOOK_URL");
+
+        if env.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(env.errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const FILE: &str = r#"
+        database_url = "postgres://file"
+        user_agent = "EpsilonBot"
+
+        [api]
+        port = 3000
+        bm25_k1 = 1.5
+
+        [crawler]
+        threads = 10
+        blocklist = """
+        *.example.com
+        example.org"""
+
+        [indexer]
+        stemming = false
+    "#;
+
+    fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_file_values() {
+        let config = Config::parse(FILE).unwrap();
+
+        assert_eq!(config.database_url.as_deref(), Some("postgres://file"));
+        assert_eq!(config.api.port, Some(3000));
+        assert_eq!(config.api.bm25_k1, Some(1.5));
+        assert_eq!(config.crawler.threads, Some(10));
+        assert_eq!(
+            config.crawler.blocklist.as_deref(),
+            Some("        *.example.com\n        example.org")
+        );
+        assert_eq!(config.indexer.stemming, Some(false));
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config = Config::parse(FILE).unwrap();
+        config
+            .apply_env(vars(&[
+                ("PORT", "8080"),
+                ("SERVICES", "api indexer"),
+                ("INDEXER_STEMMING", "true"),
+                ("FAVICONS_TASKS", "20"),
+            ]))
+            .unwrap();
+
+        // Overridden
+        assert_eq!(config.api.port, Some(8080));
+        assert_eq!(
+            config.services,
+            Some(vec!["api".to_string(), "indexer".to_string()])
+        );
+        assert_eq!(config.indexer.stemming, Some(true));
+        assert_eq!(config.favicons.tasks, Some(20));
+        // Kept from the file
+        assert_eq!(config.database_url.as_deref(), Some("postgres://file"));
+        assert_eq!(config.crawler.threads, Some(10));
+
+        // All the invalid values are returned
+        assert_eq!(
+            config.apply_env(vars(&[("PORT", "http"), ("CRAWLER_THREADS", "-1")])),
+            Err(vec![
+                "PORT must be a valid u16, got 'http'".to_string(),
+                "CRAWLER_THREADS must be a valid usize, got '-1'".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.api.search_warm_on_startup, None);
+        assert_eq!(config.monitor.recrawl_ttl_ms, None);
+
+        // Typos are not ignored
+        assert_eq!(Config::parse("[api]\nprot = 3000").is_err(), true);
+    }
+
+    #[test]
+    fn test_example_file() {
+        let config = Config::parse(include_str!("../../../epsilon.toml.example")).unwrap();
+
+        assert_eq!(config.api.port, Some(8085));
+        assert_eq!(config.favicons.tasks, Some(20));
+    }
+}
@@ -1,26 +1,63 @@
-use api::{build_api, environment::Environment};
-use crawler::crawler::Crawler;
-use database::{create_pool, DbPool};
+use api::{
+    build_api,
+    environment::Environment,
+    warming::{parse_warm_queries, warm_search_index},
+};
+use config::Config;
+use crawler::{
+    crawler::Crawler,
+    proxy::{validate_proxy_url, ProxyConfig},
+};
+use database::{
+    create_pool, DbPool, PoolOptions, POOL_CONNECTION_TIMEOUT_LIMIT_SECS, POOL_SIZE_LIMIT,
+};
 use dotenvy::dotenv;
 use favicons::favicons::Favicons;
-use indexer::indexer::Indexer;
-use monitor::monitor::Monitor;
-use std::{env, sync::Arc, thread, time::Duration};
-use tokio::{runtime::Runtime, time::sleep};
+use indexer::{indexer::Indexer, page_rank::PageRank};
+use monitor::{
+    alerts::AlertThresholds,
+    monitor::{Monitor, MIN_MONITOR_INTERVAL_SECS},
+};
+use reqwest::Url;
+use std::{
+    env, fs,
+    future::Future,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use tokio::{
+    runtime::{Builder, Runtime},
+    signal::{
+        self,
+        unix::{signal, SignalKind},
+    },
+};
+use utils::shutdown::{sleep_or_shutdown, wait_for_shutdown};
+use utils::{crawler_metrics::CrawlerMetrics, indexer_state::IndexerState};
+
+mod config;
 
 pub const SERVICES: [&str; 5] = ["api", "crawler", "favicons", "indexer", "monitor"];
 
-#[tokio::main]
-async fn main() {
+pub const WORKER_THREAD_NAME: &str = "epsilon-worker";
+
+fn main() {
     dotenv().ok();
 
     let version = env!("CARGO_PKG_VERSION");
     println!(r#"/// Epsilon v{version} \\\"#);
 
+    let config = Config::load().unwrap_or_else(|errors| panic!("{}", format_errors(&errors)));
+
     // Get args
-    let args: Vec<String> = if let Ok(services) = env::var("SERVICES") {
-        // Use the env if present
-        services.split(' ').map(String::from).collect::<Vec<_>>()
+    let args: Vec<String> = if let Some(services) = &config.services {
+        // Use the config if present
+        services.clone()
     } else {
         // Skip the first arg
         Vec::from(&env::args().collect::<Vec<_>>()[1..])
@@ -49,103 +86,770 @@ async fn main() {
         }
     }
 
-    start_services(services).await;
+    // Check everything before starting the services
+    if let Err(errors) = validate_environment(&config, &services) {
+        panic!("{}", format_errors(&errors));
+    }
+
+    let worker_threads = get_worker_threads(&config);
+    println!("Starting the runtime with {worker_threads} worker threads");
+
+    let runtime = build_runtime(worker_threads);
+    start_services(&runtime, services, Arc::new(config));
+}
+
+/// Check the values needed by the services, returns all the missing and invalid ones
+fn validate_environment(config: &Config, services: &[String]) -> Result<(), Vec<String>> {
+    let has = |service: &str| services.iter().any(|s| s == service);
+    let mut errors = Vec::new();
+
+    let mut require = |is_set: bool, name: &str, needed_by: &str| {
+        if !is_set {
+            errors.push(format!("{name} must be set for {needed_by}"));
+        }
+    };
+    require(
+        config.database_url.is_some(),
+        "DATABASE_URL",
+        "all services",
+    );
+    if has("api") {
+        require(config.api.port.is_some(), "PORT", "the api service");
+    }
+    if has("crawler") || has("favicons") {
+        require(
+            config.user_agent.is_some(),
+            "USER_AGENT",
+            "the crawler and favicons services",
+        );
+    }
+    if has("crawler") {
+        require(
+            config.crawler.threads.is_some(),
+            "CRAWLER_THREADS",
+            "the crawler service",
+        );
+    }
+    if has("favicons") {
+        require(
+            config.favicons.tasks.is_some(),
+            "FAVICONS_TASKS",
+            "the favicons service",
+        );
+    }
+
+    for (name, value) in [
+        ("TOKIO_WORKER_THREADS", config.worker_threads),
+        ("CRAWLER_THREADS", config.crawler.threads),
+        ("LOCAL_QUEUE_SIZE", config.crawler.local_queue_size),
+        ("CRAWLER_MAX_BODY_BYTES", config.crawler.max_body_bytes),
+        ("FAVICONS_TASKS", config.favicons.tasks),
+        ("INDEXER_CONCURRENCY", config.indexer.concurrency),
+        ("SEARCH_CACHE_SIZE", config.api.search_cache_size),
+        (
+            "RATE_LIMIT_RPM",
+            config.api.rate_limit_rpm.map(|x| x as usize),
+        ),
+        (
+            "RATE_LIMIT_BURST",
+            config.api.rate_limit_burst.map(|x| x as usize),
+        ),
+    ] {
+        if value == Some(0) {
+            errors.push(format!("{name} must be greater than 0"));
+        }
+    }
+
+    // `NaN < 0.0` is false, and NaN or inf would be formatted in the BM25 SQL
+    if config
+        .api
+        .bm25_k1
+        .is_some_and(|k1| !k1.is_finite() || k1 < 0.0)
+    {
+        errors.push("BM25_K1 must be a positive number".to_string());
+    }
+    if config.api.bm25_b.is_some_and(|b| !(0.0..=1.0).contains(&b)) {
+        errors.push("BM25_B must be between 0 and 1".to_string());
+    }
+    for (name, value) in [
+        ("API_KEY", &config.api.api_key),
+        ("JWT_SECRET", &config.api.jwt_secret),
+    ] {
+        if value
+            .as_deref()
+            .is_some_and(|value| value.trim().is_empty())
+        {
+            errors.push(format!("{name} must not be empty"));
+        }
+    }
+
+    if let Some(url) = &config.crawler.proxy_url {
+        if let Err(e) = validate_proxy_url(url) {
+            errors.push(format!("CRAWLER_PROXY_URL is invalid: {e}"));
+        }
+    }
+    if config.crawler.proxy_password.is_some() && config.crawler.proxy_username.is_none() {
+        errors.push("CRAWLER_PROXY_PASSWORD needs CRAWLER_PROXY_USERNAME".to_string());
+    }
+
+    let pool_options = get_pool_options(config);
+    if config
+        .db_pool_max_size
+        .is_some_and(|size| !(1..=POOL_SIZE_LIMIT).contains(&size))
+    {
+        errors.push(format!(
+            "DB_POOL_MAX_SIZE must be between 1 and {POOL_SIZE_LIMIT}"
+        ));
+    } else if pool_options
+        .min_idle
+        .is_some_and(|min_idle| min_idle > pool_options.max_size)
+    {
+        errors.push(format!(
+            "DB_POOL_MIN_IDLE must not be greater than the pool size ({})",
+            pool_options.max_size
+        ));
+    }
+    if config
+        .db_pool_connection_timeout_secs
+        .is_some_and(|secs| !(1..=POOL_CONNECTION_TIMEOUT_LIMIT_SECS).contains(&secs))
+    {
+        errors.push(format!(
+            "DB_POOL_CONNECTION_TIMEOUT_SECS must be between 1 and {POOL_CONNECTION_TIMEOUT_LIMIT_SECS}"
+        ));
+    }
+
+    let monitor = &config.monitor;
+    for (name, secs) in [
+        ("MONITOR_SYS_INTERVAL_SECS", monitor.sys_interval_secs),
+        ("MONITOR_DB_INTERVAL_SECS", monitor.db_interval_secs),
+        (
+            "MONITOR_CLEANUP_INTERVAL_SECS",
+            monitor.cleanup_interval_secs,
+        ),
+        ("MONITOR_INITIAL_DELAY_SECS", monitor.initial_delay_secs),
+    ] {
+        if secs.is_some_and(|secs| secs < MIN_MONITOR_INTERVAL_SECS) {
+            errors.push(format!(
+                "{name} must be at least {MIN_MONITOR_INTERVAL_SECS} seconds"
+            ));
+        }
+    }
+    if let Some(url) = &monitor.alert_webhook_url {
+        if Url::parse(url).is_err() {
+            errors.push(format!("ALERT_WEBHOOK_URL is not a valid URL: '{url}'"));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Format the configuration errors as a list
+fn format_errors(errors: &[String]) -> String {
+    let list: Vec<String> = errors.iter().map(|e| format!("  - {e}")).collect();
+    format!("Invalid configuration:\n{}", list.join("\n"))
+}
+
+/// Get the options of the db pools, the missing values keep their default
+fn get_pool_options(config: &Config) -> PoolOptions {
+    let default = PoolOptions::default();
+
+    PoolOptions {
+        max_size: config.db_pool_max_size.unwrap_or(default.max_size),
+        min_idle: config.db_pool_min_idle,
+        connection_timeout: config
+            .db_pool_connection_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(default.connection_timeout),
+    }
+}
+
+/// Get the worker threads count of the config (default: the CPU cores count)
+fn get_worker_threads(config: &Config) -> usize {
+    config.worker_threads.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    })
 }
 
-async fn start_services(services: Vec<String>) {
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL env must be set");
-    let db_pool = create_pool(&db_url);
+/// The stemming setting is shared by the indexer and the API (default: true)
+fn is_stemming_enabled(config: &Config) -> bool {
+    config.indexer.stemming.unwrap_or(true)
+}
+
+/// Build the multi-thread runtime shared by all the services
+fn build_runtime(worker_threads: usize) -> Runtime {
+    Builder::new_multi_thread()
+        .worker_threads(worker_threads)
+        .thread_name(WORKER_THREAD_NAME)
+        .enable_all()
+        .build()
+        .expect("Failed to create Tokio runtime")
+}
+
+/// Listen for SIGINT (Ctrl+C) and SIGTERM, resolves to the name of the received signal.
+/// The listeners are registered on the call, so it must be called inside the runtime.
+fn shutdown_signal() -> impl Future<Output = &'static str> {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+
+    async move {
+        tokio::select! {
+            _ = signal::ctrl_c() => "SIGINT",
+            _ = terminate.recv() => "SIGTERM",
+        }
+    }
+}
+
+fn start_services(runtime: &Runtime, services: Vec<String>, config: Arc<Config>) {
+    let db_url = config.database_url.as_deref().unwrap();
+    let db_pool = create_pool(db_url, &get_pool_options(&config));
+
+    // Incremented by the API and saved by the monitor
+    let api_request_count = Arc::new(AtomicU64::new(0));
+    let has_api = services.iter().any(|s| s == "api");
+    // Incremented by the favicons downloader and saved by the monitor
+    let favicon_refresh_count = Arc::new(AtomicU64::new(0));
+    let has_favicons = services.iter().any(|s| s == "favicons");
+    // Updated by the crawler and read by the API
+    let crawler_metrics = Arc::new(CrawlerMetrics::default());
+    // Set and cleared by the API, the crawler does not dequeue while it is set
+    let crawler_paused = Arc::new(AtomicBool::new(false));
+    let has_crawler = services.iter().any(|s| s == "crawler");
+    // Updated by the indexer, read and triggered by the API
+    let indexer_state = Arc::new(IndexerState::default());
+    let has_indexer = services.iter().any(|s| s == "indexer");
+    // Set on SIGINT or SIGTERM, the services stop after their current work
+    let shutdown = Arc::new(AtomicBool::new(false));
+
+    let signal = {
+        let _guard = runtime.enter();
+        shutdown_signal()
+    };
+    runtime.spawn({
+        let shutdown = shutdown.clone();
+        let services = services.clone();
+        async move {
+            let name = signal.await;
+            println!("Received {name}");
+
+            for s in services {
+                println!("Shutting down service: {s}");
+            }
+            shutdown.store(true, Ordering::Relaxed);
+        }
+    });
 
     let mut handles = Vec::new();
 
     for s in services {
         let db_pool = db_pool.clone();
-        let handle = thread::spawn(move || {
-            println!("Starting service: {}", s);
-            let rt = Runtime::new().expect("Failed to create Tokio runtime");
-
-            match s.as_str() {
-                "api" => rt.block_on(start_api(db_pool)),
-                "crawler" => rt.block_on(start_crawler(db_pool)),
-                "favicons" => rt.block_on(start_favicons(db_pool)),
-                "indexer" => rt.block_on(start_indexer(db_pool)),
-                "monitor" => rt.block_on(start_monitor(db_pool)),
-                _ => panic!("Invalid service: {s}"),
-            }
-        });
+        let api_request_count = api_request_count.clone();
+        let favicon_refresh_count = favicon_refresh_count.clone();
+        let crawler_metrics = crawler_metrics.clone();
+        let crawler_paused = crawler_paused.clone();
+        let indexer_state = indexer_state.clone();
+        let shutdown = shutdown.clone();
+        let config = config.clone();
+
+        println!("Starting service: {}", s);
+
+        let handle = match s.as_str() {
+            "api" => runtime.spawn(start_api(
+                db_pool,
+                config,
+                api_request_count,
+                has_crawler.then_some((crawler_metrics, crawler_paused)),
+                has_indexer.then_some(indexer_state),
+                shutdown,
+            )),
+            "crawler" => runtime.spawn(start_crawler(
+                db_pool,
+                config,
+                crawler_metrics,
+                crawler_paused,
+                shutdown,
+            )),
+            "favicons" => runtime.spawn(start_favicons(
+                db_pool,
+                config,
+                favicon_refresh_count,
+                shutdown,
+            )),
+            "indexer" => runtime.spawn(start_indexer(db_pool, config, indexer_state, shutdown)),
+            "monitor" => runtime.spawn(start_monitor(
+                db_pool,
+                config,
+                has_api.then_some(api_request_count),
+                has_favicons.then_some(favicon_refresh_count),
+                shutdown,
+            )),
+            _ => panic!("Invalid service: {s}"),
+        };
 
         handles.push(handle);
     }
 
-    for h in handles {
-        h.join().expect("A service thread panicked!");
-    }
+    runtime.block_on(async {
+        for h in handles {
+            h.await.expect("A service task panicked!");
+        }
+    });
+    println!("All services stopped");
 }
 
-async fn start_api(db_pool: DbPool) {
-    let port = env::var("PORT").expect("PORT env must be set");
-    let port = port.parse::<u16>().expect("Cannot convert port to number");
+async fn start_api(
+    db_pool: DbPool,
+    config: Arc<Config>,
+    api_request_count: Arc<AtomicU64>,
+    crawler: Option<(Arc<CrawlerMetrics>, Arc<AtomicBool>)>,
+    indexer_state: Option<Arc<IndexerState>>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let api_config = &config.api;
+    let port = api_config.port.unwrap();
 
-    let environment = Arc::new(Environment { db_pool });
-    build_api(environment, port).await;
-}
+    let mut environment = Environment::new(db_pool, api_request_count);
+    if let Some((metrics, paused)) = crawler {
+        environment.crawler_metrics = Some(metrics);
+        environment.crawler_paused = Some(paused);
+    }
+    environment.indexer_state = indexer_state;
 
-async fn start_crawler(db_pool: DbPool) {
-    let user_agent = env::var("USER_AGENT").expect("USER_AGENT env must be set");
+    if let Some(k1) = api_config.bm25_k1 {
+        environment.bm25.k1 = k1;
+    }
+    if let Some(b) = api_config.bm25_b {
+        environment.bm25.b = b;
+    }
+    if let Some(size) = api_config.search_cache_size.and_then(NonZeroUsize::new) {
+        environment.set_search_cache_size(size);
+    }
+    environment.stemming = is_stemming_enabled(&config);
+    environment.api_key = api_config.api_key.clone();
+    environment.jwt_secret = api_config.jwt_secret.clone();
+    environment.set_rate_limit(api_config.rate_limit_rpm, api_config.rate_limit_burst);
+    if let Some(replica_url) = &api_config.replica_database_url {
+        environment.read_pool = Some(create_pool(replica_url, &get_pool_options(&config)));
+        println!("API read replica enabled");
+    }
 
-    let threads = env::var("CRAWLER_THREADS").expect("CRAWLER_THREADS env must be set");
-    let threads = threads
-        .parse::<usize>()
-        .expect("Cannot convert threads count to usize");
+    let environment = Arc::new(environment);
+
+    if api_config.search_warm_on_startup.unwrap_or(false) {
+        let queries = parse_warm_queries(
+            api_config
+                .search_warm_queries
+                .as_deref()
+                .unwrap_or_default(),
+        );
+        warm_search_index(&environment, &queries);
+    }
 
-    let local_queue_size = env::var("LOCAL_QUEUE_SIZE")
-        .map(|x| {
-            Some(
-                x.parse::<usize>()
-                    .expect("Cannot convert LOCAL_QUEUE_SIZE to usize"),
-            )
-        })
-        .unwrap_or(None);
+    build_api(environment, port, async move {
+        wait_for_shutdown(&shutdown).await
+    })
+    .await;
+}
+
+async fn start_crawler(
+    db_pool: DbPool,
+    config: Arc<Config>,
+    metrics: Arc<CrawlerMetrics>,
+    paused: Arc<AtomicBool>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let crawler_config = &config.crawler;
+    let user_agent = config.user_agent.clone().unwrap();
+    let threads = crawler_config.threads.unwrap();
+
+    let mut crawler = Crawler::new(
+        db_pool,
+        user_agent,
+        crawler_config.local_queue_size,
+        crawler_config.max_depth,
+    );
+    crawler.set_domain_lists(
+        crawler_config.blocklist.as_deref().unwrap_or_default(),
+        crawler_config.allowlist.as_deref().unwrap_or_default(),
+    );
+    crawler.set_url_blacklist(crawler_config.url_blacklist.as_deref().unwrap_or_default());
+    crawler.max_pages_per_domain = crawler_config.domain_budget;
+    if let Some(max_redirects) = crawler_config.max_redirects {
+        crawler.max_redirects = max_redirects;
+    }
+    if let Some(max_body_bytes) = crawler_config.max_body_bytes {
+        crawler.max_body_bytes = max_body_bytes;
+    }
+    if let Some(proxy_url) = &crawler_config.proxy_url {
+        let proxy = ProxyConfig::new(
+            proxy_url,
+            crawler_config.proxy_username.clone(),
+            crawler_config.proxy_password.clone(),
+            crawler_config.no_proxy.as_deref(),
+        )
+        .expect("Invalid CRAWLER_PROXY_URL");
+        crawler
+            .set_proxy(&proxy)
+            .expect("Failed to build the crawler proxy");
+    }
+    crawler.shutdown = shutdown;
+    crawler.metrics = metrics;
+    crawler.paused = paused;
+    if let Some(path) = &crawler_config.seed_file {
+        crawler.import_seed_file(path, crawler_config.seed_priority.unwrap_or(0));
+    }
 
-    let crawler = Arc::new(Crawler::new(db_pool, user_agent, local_queue_size));
+    let crawler = Arc::new(crawler);
     crawler.start_crawling(crawler.clone(), threads).await;
 }
 
-async fn start_favicons(db_pool: DbPool) {
-    let user_agent = env::var("USER_AGENT").expect("USER_AGENT env must be set");
-    let tasks = env::var("FAVICONS_TASKS").expect("FAVICONS_TASKS env must be set");
-    let tasks = tasks
-        .parse::<usize>()
-        .expect("Cannot convert tasks count to number");
+async fn start_favicons(
+    db_pool: DbPool,
+    config: Arc<Config>,
+    refresh_count: Arc<AtomicU64>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let user_agent = config.user_agent.clone().unwrap();
+    let tasks = config.favicons.tasks.unwrap();
 
-    let favicons = Favicons::new(db_pool, tasks, user_agent);
+    let mut favicons = Favicons::new(db_pool, tasks, user_agent);
+    favicons.refresh_count = refresh_count;
+
+    if let Some(days) = config.favicons.refresh_days {
+        favicons.refresh_days = days;
+    }
 
-    loop {
-        sleep(Duration::from_secs(1)).await;
+    while sleep_or_shutdown(Duration::from_secs(1), &shutdown).await {
         let downloaded = favicons.download_missing_favicons().await;
 
         if downloaded == 0 {
             // Nothing to download, wait longer
-            sleep(Duration::from_secs(10)).await;
+            sleep_or_shutdown(Duration::from_secs(10), &shutdown).await;
         }
     }
 }
 
-async fn start_indexer(db_pool: DbPool) {
-    let indexer = Indexer::new(db_pool);
+async fn start_indexer(
+    db_pool: DbPool,
+    config: Arc<Config>,
+    state: Arc<IndexerState>,
+    shutdown: Arc<AtomicBool>,
+) {
+    // Compute the pages rank in the background
+    tokio::spawn(PageRank::new(db_pool.clone()).run());
+
+    let mut indexer = Indexer::new(db_pool);
+
+    if let Some(concurrency) = config.indexer.concurrency {
+        indexer.concurrency = concurrency;
+    }
+    indexer.stemming = is_stemming_enabled(&config);
+    if let Some(path) = &config.indexer.stopwords_file {
+        let extra = fs::read_to_string(path).expect("Cannot read INDEXER_STOPWORDS_FILE");
+        indexer.set_extra_stop_words(&extra);
+    }
 
-    loop {
-        sleep(Duration::from_secs(1)).await;
-        let indexed = indexer.index().await;
+    while sleep_or_shutdown(Duration::from_secs(1), &shutdown).await {
+        state.start_run();
+        let run = indexer.index().await;
+        state.finish_run(run.indexed);
 
-        if indexed == 0 {
-            // Nothing to index, wait longer
-            sleep(Duration::from_secs(10)).await;
+        if run.pages == 0 {
+            // Nothing to index, wait longer unless triggered by `POST /api/index/trigger`
+            tokio::select! {
+                _ = sleep_or_shutdown(Duration::from_secs(10), &shutdown) => {}
+                _ = state.trigger.notified() => {}
+            }
         }
     }
 }
 
-async fn start_monitor(db_pool: DbPool) {
-    let monitor = Monitor::new(db_pool);
-    Monitor::run(monitor).await;
+async fn start_monitor(
+    db_pool: DbPool,
+    config: Arc<Config>,
+    api_request_count: Option<Arc<AtomicU64>>,
+    favicon_refresh_count: Option<Arc<AtomicU64>>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let monitor_config = &config.monitor;
+
+    let mut monitor = Monitor::new(db_pool, api_request_count);
+    monitor.favicon_refresh_count = favicon_refresh_count;
+
+    // The intervals are checked by `validate_environment`
+    if let Some(secs) = monitor_config.sys_interval_secs {
+        monitor.intervals.sys = Duration::from_secs(secs);
+    }
+    if let Some(secs) = monitor_config.db_interval_secs {
+        monitor.intervals.db = Duration::from_secs(secs);
+    }
+    if let Some(secs) = monitor_config.cleanup_interval_secs {
+        monitor.intervals.cleanup = Duration::from_secs(secs);
+    }
+    if let Some(secs) = monitor_config.initial_delay_secs {
+        monitor.intervals.initial_delay = Duration::from_secs(secs);
+    }
+
+    if let Some(ttl) = monitor_config.recrawl_ttl_ms {
+        monitor.recrawl_ttl = ttl;
+    }
+
+    monitor.alert_thresholds = AlertThresholds {
+        queue_size: monitor_config.alert_queue_size_max,
+        db_size_gb: monitor_config.alert_db_size_max_gb,
+        cpu_usage: monitor_config.alert_cpu_usage_max,
+    };
+    monitor.alert_webhook_url = monitor_config.alert_webhook_url.clone();
+
+    // The monitor tasks only save analytics, they can be stopped at any time
+    tokio::select! {
+        _ = Monitor::run(monitor) => {}
+        _ = wait_for_shutdown(&shutdown) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        process::{self, Command},
+    };
+    use tokio::time::timeout;
+
+    /// Build a config from a mock environment
+    fn mock_config(vars: &[(&str, &str)]) -> Config {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        let mut config = Config::default();
+        config
+            .apply_env(|name| vars.get(name).map(|v| v.to_string()))
+            .unwrap();
+        config
+    }
+
+    fn services(services: &[&str]) -> Vec<String> {
+        services.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_validate_missing_values() {
+        let errors = validate_environment(&mock_config(&[]), &services(&["api", "crawler"]));
+
+        assert_eq!(
+            errors,
+            Err(vec![
+                "DATABASE_URL must be set for all services".to_string(),
+                "PORT must be set for the api service".to_string(),
+                "USER_AGENT must be set for the crawler and favicons services".to_string(),
+                "CRAWLER_THREADS must be set for the crawler service".to_string(),
+            ])
+        );
+
+        let config = mock_config(&[
+            ("DATABASE_URL", "postgres://localhost/epsilon"),
+            ("PORT", "8085"),
+        ]);
+        assert_eq!(validate_environment(&config, &services(&["api"])), Ok(()));
+        // The other services need more values
+        assert_eq!(
+            validate_environment(&config, &services(&["favicons"])),
+            Err(vec![
+                "USER_AGENT must be set for the crawler and favicons services".to_string(),
+                "FAVICONS_TASKS must be set for the favicons service".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_invalid_values() {
+        let config = mock_config(&[
+            ("DATABASE_URL", "postgres://localhost/epsilon"),
+            ("USER_AGENT", "EpsilonBot"),
+            ("CRAWLER_THREADS", "0"),
+            ("BM25_B", "1.5"),
+            ("MONITOR_DB_INTERVAL_SECS", "1"),
+            ("ALERT_WEBHOOK_URL", "not a url"),
+        ]);
+
+        assert_eq!(
+            validate_environment(&config, &services(&["crawler", "monitor"])),
+            Err(vec![
+                "CRAWLER_THREADS must be greater than 0".to_string(),
+                "BM25_B must be between 0 and 1".to_string(),
+                "MONITOR_DB_INTERVAL_SECS must be at least 5 seconds".to_string(),
+                "ALERT_WEBHOOK_URL is not a valid URL: 'not a url'".to_string(),
+            ])
+        );
+
+        for k1 in ["-1", "NaN", "inf"] {
+            let config = mock_config(&[
+                ("DATABASE_URL", "postgres://localhost/epsilon"),
+                ("PORT", "8085"),
+                ("BM25_K1", k1),
+            ]);
+            assert_eq!(
+                validate_environment(&config, &services(&["api"])),
+                Err(vec!["BM25_K1 must be a positive number".to_string()])
+            );
+        }
+
+        let config = mock_config(&[
+            ("DATABASE_URL", "postgres://localhost/epsilon"),
+            ("PORT", "8085"),
+            ("API_KEY", ""),
+            ("JWT_SECRET", " "),
+        ]);
+        assert_eq!(
+            validate_environment(&config, &services(&["api"])),
+            Err(vec![
+                "API_KEY must not be empty".to_string(),
+                "JWT_SECRET must not be empty".to_string(),
+            ])
+        );
+
+        // Type errors are returned by the env parsing
+        let mut config = Config::default();
+        assert_eq!(
+            config.apply_env(|name| (name == "PORT").then(|| "70000".to_string())),
+            Err(vec!["PORT must be a valid u16, got '70000'".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_validate_pool_values() {
+        let with = |vars: &[(&'static str, &'static str)]| {
+            let vars: Vec<(&str, &str)> = [("DATABASE_URL", "postgres://localhost/epsilon")]
+                .iter()
+                .chain(vars)
+                .copied()
+                .collect();
+            validate_environment(&mock_config(&vars), &services(&["indexer"]))
+        };
+
+        assert_eq!(
+            with(&[
+                ("DB_POOL_MAX_SIZE", "100"),
+                ("DB_POOL_MIN_IDLE", "10"),
+                ("DB_POOL_CONNECTION_TIMEOUT_SECS", "5"),
+            ]),
+            Ok(())
+        );
+        assert_eq!(
+            with(&[
+                ("DB_POOL_MAX_SIZE", "0"),
+                ("DB_POOL_CONNECTION_TIMEOUT_SECS", "0"),
+            ]),
+            Err(vec![
+                "DB_POOL_MAX_SIZE must be between 1 and 1000".to_string(),
+                "DB_POOL_CONNECTION_TIMEOUT_SECS must be between 1 and 3600".to_string(),
+            ])
+        );
+        assert_eq!(
+            with(&[("DB_POOL_MAX_SIZE", "4294967295")]),
+            Err(vec![
+                "DB_POOL_MAX_SIZE must be between 1 and 1000".to_string()
+            ])
+        );
+        // Compared to the default size
+        assert_eq!(
+            with(&[("DB_POOL_MIN_IDLE", "4294967295")]),
+            Err(vec![
+                "DB_POOL_MIN_IDLE must not be greater than the pool size (40)".to_string()
+            ])
+        );
+
+        let config = mock_config(&[("DB_POOL_MIN_IDLE", "5")]);
+        assert_eq!(
+            get_pool_options(&config),
+            PoolOptions {
+                max_size: 40,
+                min_idle: Some(5),
+                connection_timeout: Duration::from_secs(30),
+            }
+        );
+    }
+
+    #[test]
+    fn test_validate_proxy() {
+        let base = [
+            ("DATABASE_URL", "postgres://localhost/epsilon"),
+            ("USER_AGENT", "EpsilonBot"),
+            ("CRAWLER_THREADS", "1"),
+        ];
+        let with = |vars: &[(&'static str, &'static str)]| {
+            let vars: Vec<(&str, &str)> = base.iter().chain(vars).copied().collect();
+            validate_environment(&mock_config(&vars), &services(&["crawler"]))
+        };
+
+        assert_eq!(
+            with(&[
+                ("CRAWLER_PROXY_URL", "socks5://127.0.0.1:1080"),
+                ("CRAWLER_PROXY_USERNAME", "user"),
+                ("CRAWLER_PROXY_PASSWORD", "secret"),
+                ("CRAWLER_NO_PROXY", "localhost,.internal"),
+            ]),
+            Ok(())
+        );
+        assert_eq!(
+            with(&[
+                ("CRAWLER_PROXY_URL", "ftp://proxy.local"),
+                ("CRAWLER_PROXY_PASSWORD", "secret"),
+            ]),
+            Err(vec![
+                "CRAWLER_PROXY_URL is invalid: 'ftp://proxy.local' must use one of the schemes http, https, socks5, socks5h".to_string(),
+                "CRAWLER_PROXY_PASSWORD needs CRAWLER_PROXY_USERNAME".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_format_errors() {
+        assert_eq!(
+            format_errors(&[
+                "PORT must be set".to_string(),
+                "BM25_B is invalid".to_string()
+            ]),
+            "Invalid configuration:\n  - PORT must be set\n  - BM25_B is invalid"
+        );
+    }
+
+    #[test]
+    fn test_services_share_the_runtime() {
+        let runtime = build_runtime(2);
+        assert_eq!(runtime.metrics().num_workers(), 2);
+
+        let handles: Vec<_> = SERVICES
+            .iter()
+            .map(|_| runtime.spawn(async { thread::current().name().map(String::from) }))
+            .collect();
+
+        runtime.block_on(async {
+            for h in handles {
+                assert_eq!(h.await.unwrap().as_deref(), Some(WORKER_THREAD_NAME));
+            }
+        });
+    }
+
+    #[test]
+    fn test_shutdown_signal() {
+        let runtime = build_runtime(1);
+        let signal = {
+            let _guard = runtime.enter();
+            shutdown_signal()
+        };
+
+        // The listener replaces the default SIGTERM handler, so the test process is not killed
+        let status = Command::new("kill")
+            .args(["-TERM", &process::id().to_string()])
+            .status()
+            .unwrap();
+        assert_eq!(status.success(), true);
+
+        let name = runtime.block_on(async { timeout(Duration::from_secs(5), signal).await });
+        assert_eq!(name, Ok("SIGTERM"));
+    }
 }
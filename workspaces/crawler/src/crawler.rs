@@ -1,75 +1,240 @@
-use crate::utils::is_crawlable_url;
+use crate::proxy::ProxyConfig;
+use crate::utils::{
+    blacklist_check, get_active_domains, is_crawlable_url, is_over_budget, parse_domain_patterns,
+    parse_seed_urls, parse_url_patterns,
+};
+use crate::visited::{get_visited_bloom_path, VisitedUrls};
 use crate::website::Website;
 use crate::worker::Worker;
-use dashmap::{DashMap, DashSet};
-use database::models::QueuedPage;
-use database::schema::pages;
+use dashmap::DashMap;
+use database::models::{NewQueuedPage, QueuedPage};
+use database::schema::{pages, queue};
 use database::DbPool;
 use diesel::query_dsl::methods::SelectDsl;
 use diesel::RunQueryDsl;
+use glob::Pattern;
+use regex::Regex;
 use reqwest::redirect::Policy;
 use reqwest::Client;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::Mutex;
 use tokio::task;
 use tokio::time::sleep;
-use utils::url::normalize_url;
+use utils::crawler_metrics::{CrawlerMetrics, SpeedAverage, ACTIVE_DOMAINS_LIMIT};
+use utils::shutdown::{is_shutting_down, sleep_or_shutdown};
+use utils::url::{get_registered_domain, normalize_url};
 
 pub const DEFAULT_LOCAL_QUEUE_SIZE: usize = 1000;
 
+/// Redirects followed from a queued URL before its target is dropped
+pub const DEFAULT_MAX_REDIRECTS: u8 = 5;
+
+/// Larger response bodies are truncated, or not downloaded when their length is announced
+pub const DEFAULT_MAX_BODY_BYTES: usize = 5 << 20;
+
 #[derive(Clone)]
 pub struct Task {
     pub id: i32,
     pub domain: String,
     pub url: String,
+    pub depth: i32,
+    /// Number of redirects followed to reach this URL
+    pub redirect_depth: u8,
+    /// A stale page queued again by the monitor
+    pub recrawl: bool,
+    /// The links deeper than this are not queued (default: unlimited)
+    pub max_depth: Option<usize>,
 }
 
 pub struct Crawler {
     pub user_agent: String,
     pub web_client: Client,
     pub db_pool: DbPool,
+    pub max_depth: Option<usize>,
+    /// Domains that are never crawled
+    pub blocklist: Vec<Pattern>,
+    /// When not empty, only these domains are crawled
+    pub allowlist: Vec<Pattern>,
+    /// URLs that are never queued nor crawled, e.g. the infinite calendars
+    pub url_blacklist: Vec<Regex>,
+    /// The links to domains with this many pages are not queued (default: unlimited)
+    pub max_pages_per_domain: Option<usize>,
+    /// Redirect chains longer than this are dropped
+    pub max_redirects: u8,
+    /// Limit of the response bodies, in bytes
+    pub max_body_bytes: usize,
+    /// Number of crawled pages per domain, to check the budget without db calls
+    pub domain_page_count: DashMap<String, usize>,
+    /// Set to stop the workers after their current page
+    pub shutdown: Arc<AtomicBool>,
+    /// Live state of the crawler, updated every second and read by the API
+    pub metrics: Arc<CrawlerMetrics>,
+    /// Set from the API to stop dequeuing, until it is cleared
+    pub paused: Arc<AtomicBool>,
 
-    pub visited: DashSet<String>,
+    /// The crawled, queued and recently failed URLs, which are not queued again
+    pub visited: VisitedUrls,
+    /// Keyed by the registered domain, so the subdomains share the rate limits
     pub websites: DashMap<String, Website>,
     pub queue_channel: (Sender<Task>, Mutex<Receiver<Task>>),
 }
 
 impl Crawler {
-    pub fn new(db_pool: DbPool, user_agent: String, local_queue_size: Option<usize>) -> Self {
+    pub fn new(
+        db_pool: DbPool,
+        user_agent: String,
+        local_queue_size: Option<usize>,
+        max_depth: Option<usize>,
+    ) -> Self {
         let local_queue_size = local_queue_size.unwrap_or(DEFAULT_LOCAL_QUEUE_SIZE);
         let queue = channel(local_queue_size);
         println!("Crawler local queue size: {local_queue_size}");
 
-        let urls = Crawler::load_visited_urls(&db_pool);
-        let client = Client::builder()
-            .user_agent(&user_agent)
-            .pool_idle_timeout(Some(Duration::from_secs(30)))
-            .pool_max_idle_per_host(0)
-            .redirect(Policy::default())
-            .build()
-            .unwrap();
+        let visited = VisitedUrls::load(db_pool.clone(), &get_visited_bloom_path());
+        let domain_page_count = Crawler::load_domain_page_count(&db_pool);
+        let client = Crawler::build_client(&user_agent, None).unwrap();
 
         Self {
             user_agent,
             web_client: client,
             db_pool,
-            visited: urls,
+            max_depth,
+            blocklist: Vec::new(),
+            allowlist: Vec::new(),
+            url_blacklist: Vec::new(),
+            max_pages_per_domain: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            domain_page_count,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            metrics: Arc::new(CrawlerMetrics::default()),
+            paused: Arc::new(AtomicBool::new(false)),
+            visited,
             websites: DashMap::new(),
             queue_channel: (queue.0, Mutex::new(queue.1)),
         }
     }
 
-    fn load_visited_urls(db_pool: &DbPool) -> DashSet<String> {
-        let results = pages::table
-            .select(pages::url)
-            .load::<String>(&mut db_pool.get().unwrap())
-            .expect("Failed to load URLs");
+    /// Set the newline-separated domain patterns of the blocklist and the allowlist
+    pub fn set_domain_lists(&mut self, blocklist: &str, allowlist: &str) {
+        self.blocklist =
+            parse_domain_patterns(blocklist).expect("Invalid domain pattern in CRAWLER_BLOCKLIST");
+        self.allowlist =
+            parse_domain_patterns(allowlist).expect("Invalid domain pattern in CRAWLER_ALLOWLIST");
+
+        if !self.allowlist.is_empty() {
+            println!("Crawler allowlist mode: {} patterns", self.allowlist.len());
+        }
+    }
+
+    /// Set the newline-separated URL regex patterns of the blacklist
+    pub fn set_url_blacklist(&mut self, blacklist: &str) {
+        self.url_blacklist =
+            parse_url_patterns(blacklist).expect("Invalid regex in CRAWLER_URL_BLACKLIST");
+
+        if !self.url_blacklist.is_empty() {
+            println!(
+                "Crawler URL blacklist: {} patterns",
+                self.url_blacklist.len()
+            );
+        }
+    }
+
+    /// Queue the newline-separated URLs of a seed file, with `timestamp` as their queue timestamp.
+    /// The visited and blacklisted URLs are skipped, so the file can be imported at every start.
+    pub fn import_seed_file(&self, path: &str, timestamp: i64) {
+        let list = fs::read_to_string(path).expect("Cannot read CRAWLER_SEED_FILE");
+        let seeds = parse_seed_urls(&list)
+            .into_iter()
+            .filter(|(_, url)| blacklist_check(url, &self.url_blacklist))
+            .collect::<Vec<_>>();
+        let seed_count = seeds.len();
+
+        let elements = seeds
+            .into_iter()
+            // Marked as visited once queued, like the links of the crawled pages
+            .filter(|(_, url)| self.visited.insert(url))
+            .map(|(domain, url)| NewQueuedPage {
+                url,
+                registered_domain: get_registered_domain(&domain),
+                domain,
+                timestamp,
+                depth: 0,
+                redirect_depth: 0,
+                recrawl: false,
+            })
+            .collect::<Vec<_>>();
+
+        let imported = diesel::insert_into(queue::table)
+            .values(elements)
+            .on_conflict(queue::url)
+            .do_nothing()
+            .execute(&mut self.db_pool.get().unwrap())
+            .expect("Failed to queue the seed URLs");
+
+        println!(
+            "Crawler seed file: {imported} URLs imported, {} already present",
+            seed_count - imported
+        );
+    }
 
-        let visited_urls: DashSet<String> = results.into_iter().collect();
+    /// Check the crawl budget of a domain, so one website cannot fill the queue
+    pub fn is_domain_over_budget(&self, domain: &str) -> bool {
+        let page_count = self.domain_page_count.get(domain).map(|x| *x).unwrap_or(0);
 
-        visited_urls
+        is_over_budget(page_count, self.max_pages_per_domain)
+    }
+
+    /// Check that a found URL can be queued: not too long, not blacklisted,
+    /// and of a domain under its crawl budget
+    pub fn can_queue(&self, domain: &str, url: &str) -> bool {
+        url.len() <= 2048
+            && blacklist_check(url, &self.url_blacklist)
+            && !self.is_domain_over_budget(domain)
+    }
+
+    /// The redirects are not followed, they are queued so that `max_redirects` limits the chains
+    pub(crate) fn build_client(
+        user_agent: &str,
+        proxy: Option<&ProxyConfig>,
+    ) -> reqwest::Result<Client> {
+        let mut builder = Client::builder()
+            .user_agent(user_agent)
+            .pool_idle_timeout(Some(Duration::from_secs(30)))
+            .pool_max_idle_per_host(0)
+            .redirect(Policy::none());
+
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy.to_proxy()?);
+        }
+
+        builder.build()
+    }
+
+    /// Send the crawler requests through a proxy
+    pub fn set_proxy(&mut self, proxy: &ProxyConfig) -> reqwest::Result<()> {
+        self.web_client = Crawler::build_client(&self.user_agent, Some(proxy))?;
+        println!("Crawler proxy: {}", proxy.redacted_url());
+        Ok(())
+    }
+
+    fn load_domain_page_count(db_pool: &DbPool) -> DashMap<String, usize> {
+        use diesel::{dsl::count_star, query_dsl::methods::GroupByDsl};
+
+        let results = pages::table
+            .group_by(pages::domain)
+            .select((pages::domain, count_star()))
+            .load::<(String, i64)>(&mut db_pool.get().unwrap())
+            .expect("Failed to count the pages per domain");
+
+        results
+            .into_iter()
+            .map(|(domain, count)| (domain, count as usize))
+            .collect()
     }
 
     pub fn get_crawled_pages_count(&self) -> i64 {
@@ -104,6 +269,7 @@ impl Crawler {
             let manager = arc.clone();
             async move {
                 let mut count = 0;
+                let mut speed_average = SpeedAverage::default();
                 let delay = Duration::from_millis(1000);
                 loop {
                     sleep(delay).await;
@@ -114,6 +280,9 @@ impl Crawler {
                     let old_count = count;
                     count = new_count;
                     let per_min = per_sec * 60.0;
+                    if old_count != 0 {
+                        manager.update_metrics(speed_average.push(per_sec));
+                    }
                     let metrics = tokio::runtime::Handle::current().metrics();
                     let tasks = metrics.num_alive_tasks();
                     if old_count != 0 {
@@ -128,22 +297,55 @@ impl Crawler {
             task.await
                 .unwrap_or_else(|_| panic!("Crawler task panicked!"));
         }
+
+        if is_shutting_down(&self.shutdown) {
+            // The local queue was already removed from the db queue
+            let requeued = Worker::new(arc).requeue_local_queue().await;
+            println!("[Crawler] {requeued} pages of the local queue were queued again");
+
+            match self.visited.save(&get_visited_bloom_path()) {
+                Ok(()) => println!("[Crawler] The visited URLs were saved for the next start"),
+                Err(e) => eprintln!("[Crawler] Failed to save the visited URLs: {e}"),
+            }
+        }
         println!("Crawling finished");
     }
 
+    fn update_metrics(&self, pages_per_second: f32) {
+        let metrics = &self.metrics;
+
+        metrics.set_pages_per_second(pages_per_second);
+        metrics
+            .visited_count
+            .store(self.visited.len(), Ordering::Relaxed);
+        metrics
+            .websites_tracked
+            .store(self.websites.len(), Ordering::Relaxed);
+
+        let websites = self
+            .websites
+            .iter()
+            .map(|x| (x.key().clone(), x.last_crawl));
+        *metrics.active_domains.lock().unwrap() =
+            get_active_domains(websites, ACTIVE_DOMAINS_LIMIT);
+    }
+
     fn fill_queue(&self, arc: Arc<Crawler>) {
         let tx_clone = self.queue_channel.0.clone();
 
         tokio::spawn(async move {
-            loop {
+            while !is_shutting_down(&arc.shutdown) {
                 let p = &arc.clone().db_pool;
                 let tasks = Crawler::dequeue(p).await;
                 if tasks.is_empty() {
-                    sleep(Duration::from_secs(1)).await;
+                    sleep_or_shutdown(Duration::from_secs(1), &arc.shutdown).await;
                 } else {
                     for task in tasks {
-                        if let Some((url, domain)) = normalize_url(&task.url) {
-                            if !is_crawlable_url(&url.to_string()) {
+                        if let Some((url, domain)) = normalize_url(&task.url, true) {
+                            // Queued before a blacklist change, dropped from the queue
+                            if !is_crawlable_url(&url.to_string())
+                                || !blacklist_check(url.as_str(), &arc.url_blacklist)
+                            {
                                 continue;
                             }
 
@@ -151,8 +353,15 @@ impl Crawler {
                                 id: task.id,
                                 domain,
                                 url: url.to_string(),
+                                depth: task.depth,
+                                redirect_depth: u8::try_from(task.redirect_depth)
+                                    .unwrap_or(u8::MAX),
+                                recrawl: task.recrawl,
+                                max_depth: arc.max_depth,
                             };
 
+                            // Not in the queue table anymore, until the worker is done with it
+                            arc.visited.set_in_flight(&task.url);
                             if tx_clone.send(task).await.is_err() {
                                 break;
                             }
@@ -169,13 +378,18 @@ impl Crawler {
         let elements: Vec<QueuedPage> = diesel::sql_query(
             "DELETE FROM queue q
             USING (
-                SELECT DISTINCT ON (domain) id
-                FROM queue
-                ORDER BY domain, timestamp DESC
+                SELECT id
+                FROM (
+                    SELECT DISTINCT ON (domain) id, priority
+                    FROM queue
+                    ORDER BY domain, priority DESC, timestamp DESC
+                ) d
+                ORDER BY priority DESC
                 LIMIT 400
             ) s
             WHERE q.id = s.id
-            RETURNING q.id, q.domain, q.url, q.timestamp;",
+            RETURNING q.id, q.domain, q.url, q.timestamp, q.priority, q.depth, q.recrawl, q.redirect_depth,
+                q.registered_domain;",
         )
         .load::<QueuedPage>(&mut db_pool.get().unwrap())
         .unwrap();
@@ -1,5 +1,8 @@
 pub mod crawler;
+pub mod proxy;
 mod scraper;
+mod sitemap;
 mod utils;
+pub mod visited;
 mod website;
 mod worker;
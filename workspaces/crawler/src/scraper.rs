@@ -1,7 +1,14 @@
-use crate::utils::extract_words;
-use scraper::{Html, Selector};
-use std::{collections::HashSet, error::Error};
-use utils::{safe_slice, url::normalize_href};
+use crate::utils::{detect_language, extract_words};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use ego_tree::NodeId;
+use reqwest::header::HeaderMap;
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+use utils::{normalize_language_tag, safe_slice, url::normalize_href};
 
 const LINK_SELECTOR: &str = concat!(
     "a[href]",
@@ -85,14 +92,48 @@ const LINK_SELECTOR: &str = concat!(
     ":not([href$=\".dump\"])",
 );
 
+/// `rel` of the page icons, by priority
+const ICON_RELS: [&str; 4] = [
+    "icon",
+    "shortcut icon",
+    "apple-touch-icon",
+    "apple-touch-icon-precomposed",
+];
+
+/// Elements holding the main content of a page, by priority
+const MAIN_CONTENT_SELECTORS: [&str; 3] = ["main", "article", "[role=\"main\"]"];
+
+/// Elements whose text is never part of the main content
+const IGNORED_CONTENT_TAGS: [&str; 7] = [
+    "script", "style", "noscript", "nav", "header", "footer", "aside",
+];
+
+/// Fields of the JSON-LD items kept by `extract_jsonld`
+const JSONLD_FIELDS: [&str; 5] = ["name", "description", "author", "datePublished", "image"];
+
 type ScraperResult<T> = Result<T, Box<dyn Error>>;
 
+/// An icon of the page
+#[derive(Clone, Debug, PartialEq)]
+pub struct Icon {
+    pub url: String,
+    /// Largest size in pixels of its `sizes`, 0 if unknown
+    pub size: u32,
+}
+
 pub struct ScrapedPage {
     pub title: Option<String>,
-    pub favicon_url: Option<String>,
+    /// The largest icon of the `<link>` elements
+    pub favicon: Option<Icon>,
+    /// Absolute URL of the `<link rel="manifest">`, which can list larger icons
+    pub manifest_url: Option<String>,
     pub content: Option<String>,
+    /// Number of words of the main content, before it is truncated
+    pub word_count: usize,
     pub html: Option<String>,
     pub html_length: usize,
+    /// The body was larger than the crawler limit, only its start was scraped
+    pub truncated: bool,
     pub links: HashSet<String>,
     pub has_h1: bool,
 
@@ -100,25 +141,47 @@ pub struct ScrapedPage {
     pub meta_keywords: Option<String>,
     pub meta_theme_color: Option<String>,
     pub meta_og_image: Option<String>,
+    /// Type of the Twitter Card (e.g. `summary_large_image`)
+    pub meta_twitter_card: Option<String>,
+    /// Open Graph type of the page (e.g. `article`, `website`)
+    pub meta_og_type: Option<String>,
+    pub meta_og_site_name: Option<String>,
+    /// Open Graph locale of the page (e.g. `en_US`)
+    pub meta_og_locale: Option<String>,
+    /// Alternative texts of the images, except the icons
+    pub image_alts: Vec<String>,
+    /// Absolute URLs of the language variants of the page, by `hreflang`
+    pub alternates: HashMap<String, String>,
+    /// Target of a `<meta http-equiv="refresh">` redirect
+    pub meta_refresh_url: Option<String>,
+    /// URL of the RSS feed of the website
+    pub rss_url: Option<String>,
+    /// Primary BCP-47 language subtag (e.g. `en`)
+    pub language: Option<String>,
+    /// The page asks to not be indexed
+    pub noindex: bool,
+    /// The page asks to not follow any of its links
+    pub nofollow: bool,
+    /// Absolute URL of the `<link rel="canonical">`
+    pub canonical_url: Option<String>,
+    pub meta_author: Option<String>,
+    /// Timestamp of the `datePublished` of the JSON-LD data
+    pub published_at: Option<i64>,
+    /// Absolute URL of the next page of a paginated content (`rel="next"`)
+    pub next_url: Option<String>,
+    /// Absolute URL of the previous page of a paginated content (`rel="prev"`)
+    pub prev_url: Option<String>,
 }
 
-pub fn scrape_page(domain: String, url: String, page: String) -> ScraperResult<ScrapedPage> {
+pub fn scrape_page(
+    domain: String,
+    url: String,
+    page: String,
+    headers: &HeaderMap,
+) -> ScraperResult<ScrapedPage> {
     let document = Html::parse_document(&page);
     let html = document.root_element().html();
-    let selector = Selector::parse(LINK_SELECTOR)?;
-
-    let mut links = HashSet::new();
-    for element in document.select(&selector) {
-        if let Some(link) = element.value().attr("href") {
-            if let Ok(normalized_url) = normalize_href(&url, link) {
-                if links.contains(&normalized_url) {
-                    continue;
-                }
-
-                links.insert(normalized_url);
-            }
-        }
-    }
+    let links = extract_links(&document, &url)?;
 
     let title_selector = Selector::parse("title");
     let title = if let Ok(title_selector) = title_selector {
@@ -137,54 +200,182 @@ pub fn scrape_page(domain: String, url: String, page: String) -> ScraperResult<S
         false
     };
 
-    let favicon_url = extract_favicon_url(domain, &document)?;
-    let content = extract_text_content(&document)?;
-    let content = if let Some(content) = content {
-        if let Some(words) = extract_words(&content.to_lowercase()) {
-            let text = words.join(" ");
-            Some(safe_slice(&text, 1 << 7).to_string())
-        } else {
-            None
-        }
-    } else {
-        None
-    };
+    // The structured data fills the missing HTML metadata
+    let jsonld = extract_jsonld(&document);
+    let jsonld_text = |field: &str, key: &str| get_jsonld_text(jsonld.as_ref()?.get(field)?, key);
+    let title = title
+        .or_else(|| extract_meta_content(&document, "twitter:title"))
+        .or_else(|| jsonld_text("name", "name"));
+
+    let favicon = extract_favicon(&domain, &document)?;
+    let content = extract_main_content(&document);
+    let language = extract_language(&document).or_else(|| {
+        // Fallback on the detection of the whole text, before it is truncated
+        content.as_deref().and_then(detect_language)
+    });
+    let mut word_count = 0;
+    let content = content.map(|content| {
+        let words = extract_words(&content);
+        word_count = words.len();
+        let text = words.join(" ");
+        safe_slice(&text, 1 << 7).to_string()
+    });
 
     let scraped = ScrapedPage {
         title,
-        favicon_url,
+        favicon,
+        manifest_url: extract_manifest_url(&document, &url),
         content,
+        word_count,
         html: None,
         html_length: html.len(),
+        truncated: false,
         links,
         has_h1,
-        meta_description: extract_meta_content(&document, "description"),
+        meta_description: extract_meta_content(&document, "description")
+            .or_else(|| extract_meta_content(&document, "twitter:description"))
+            .or_else(|| jsonld_text("description", "description")),
         meta_keywords: extract_meta_content(&document, "keywords"),
         meta_theme_color: extract_meta_content(&document, "theme-color"),
-        meta_og_image: extract_meta_content(&document, "og:image"),
+        meta_og_image: extract_meta_content(&document, "og:image")
+            .or_else(|| extract_meta_content(&document, "twitter:image"))
+            .or_else(|| jsonld_text("image", "url")),
+        meta_twitter_card: extract_meta_content(&document, "twitter:card"),
+        meta_og_type: extract_meta_content(&document, "og:type"),
+        meta_og_site_name: extract_meta_content(&document, "og:site_name"),
+        meta_og_locale: extract_meta_content(&document, "og:locale"),
+        image_alts: extract_image_alts(&document),
+        alternates: extract_hreflang_links(&document, &url),
+        meta_refresh_url: extract_meta_refresh_url(&document, &url),
+        rss_url: extract_rss_url(&document, &url),
+        language,
+        noindex: check_noindex(&document, headers),
+        nofollow: check_nofollow(&document, headers),
+        canonical_url: extract_canonical_url(&document, &url),
+        meta_author: extract_meta_content(&document, "author")
+            .or_else(|| jsonld_text("author", "name")),
+        published_at: extract_published_date(&document),
+        next_url: extract_pagination_url(&document, &url, "next"),
+        prev_url: extract_pagination_url(&document, &url, "prev"),
     };
 
     Ok(scraped)
 }
 
-fn extract_favicon_url(domain: String, document: &Html) -> ScraperResult<Option<String>> {
-    let selector = Selector::parse(r#"link[rel="icon"], link[rel="shortcut icon"]"#)?;
+/// Extract the absolute URLs of the links, except the `rel="nofollow"` ones
+fn extract_links(document: &Html, url: &str) -> ScraperResult<HashSet<String>> {
+    let selector = Selector::parse(LINK_SELECTOR)?;
+
+    let mut links = HashSet::new();
+    for element in document.select(&selector) {
+        let is_nofollow = element.value().attr("rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|x| x.eq_ignore_ascii_case("nofollow"))
+        });
+        if is_nofollow {
+            continue;
+        }
 
-    if let Some(element) = document.select(&selector).next() {
-        if let Some(href) = element.value().attr("href") {
-            let favicon_url = if href.starts_with("http") {
+        if let Some(link) = element.value().attr("href") {
+            if let Ok(normalized_url) = normalize_href(url, link) {
+                if links.contains(&normalized_url) {
+                    continue;
+                }
+
+                links.insert(normalized_url);
+            }
+        }
+    }
+
+    Ok(links)
+}
+
+/// Get the largest size of a `sizes` attribute, e.g. `16x16 32x32` -> 32
+fn parse_icon_sizes(sizes: &str) -> u32 {
+    sizes
+        .split_ascii_whitespace()
+        .filter_map(|size| {
+            let (width, height) = size
+                .to_lowercase()
+                .split_once('x')
+                .map(|(w, h)| (w.parse::<u32>(), h.parse::<u32>()))?;
+            Some(width.ok()?.min(height.ok()?))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Pick the largest icon, the first one if they have the same size
+pub fn pick_largest_icon(icons: Vec<Icon>) -> Option<Icon> {
+    icons.into_iter().fold(None, |best, icon| match best {
+        Some(best) if best.size >= icon.size => Some(best),
+        _ => Some(icon),
+    })
+}
+
+/// Extract the largest icon of the `<link rel="icon">`, `"shortcut icon"`, `"apple-touch-icon"`
+/// and `"apple-touch-icon-precomposed"`, in this order of priority for the same size
+fn extract_favicon(domain: &str, document: &Html) -> ScraperResult<Option<Icon>> {
+    let mut icons = Vec::new();
+
+    for rel in ICON_RELS {
+        let selector =
+            Selector::parse(&format!(r#"link[rel="{rel}"][href]"#)).map_err(|e| e.to_string())?;
+
+        for element in document.select(&selector) {
+            let href = element.value().attr("href").unwrap_or_default();
+            let url = if href.starts_with("http") {
                 href.to_string()
             } else {
-                normalize_href(&format!("https://{domain}"), &href.trim_start_matches('/'))?
+                normalize_href(&format!("https://{domain}"), href.trim_start_matches('/'))?
             };
+            let size = element
+                .value()
+                .attr("sizes")
+                .map(parse_icon_sizes)
+                .unwrap_or(0);
 
-            return Ok(Some(favicon_url));
+            icons.push(Icon { url, size });
         }
-
-        Ok(None)
-    } else {
-        Ok(None)
     }
+
+    Ok(pick_largest_icon(icons))
+}
+
+/// Extract the absolute URL of a `<link rel="manifest">`
+fn extract_manifest_url(document: &Html, url: &str) -> Option<String> {
+    let selector = Selector::parse(r#"link[rel="manifest"][href]"#).ok()?;
+
+    document
+        .select(&selector)
+        .filter_map(|element| element.value().attr("href"))
+        .find_map(|href| normalize_href(url, href.trim()).ok())
+}
+
+/// Get the largest icon of the `icons` of a Web App Manifest
+pub fn parse_manifest_icon(manifest: &str, manifest_url: &str) -> Option<Icon> {
+    let manifest: serde_json::Value = serde_json::from_str(manifest).ok()?;
+
+    let icons = manifest
+        .get("icons")?
+        .as_array()?
+        .iter()
+        .filter_map(|icon| {
+            let src = icon.get("src")?.as_str()?;
+            let size = icon
+                .get("sizes")
+                .and_then(|sizes| sizes.as_str())
+                .map(parse_icon_sizes)
+                .unwrap_or(0);
+
+            Some(Icon {
+                url: normalize_href(manifest_url, src.trim()).ok()?,
+                size,
+            })
+        })
+        .collect();
+
+    pick_largest_icon(icons)
 }
 
 fn extract_meta_content(document: &Html, name: &str) -> Option<String> {
@@ -217,28 +408,1149 @@ fn extract_meta_content(document: &Html, name: &str) -> Option<String> {
     None
 }
 
-fn extract_text_content(document: &Html) -> ScraperResult<Option<String>> {
-    let body_selector = Selector::parse("body");
+/// Collect the common fields of the `<script type="application/ld+json">` items,
+/// the first item with a field wins
+pub fn extract_jsonld(document: &Html) -> Option<Value> {
+    let selector = Selector::parse(r#"script[type="application/ld+json"]"#).ok()?;
+    let mut fields = Map::new();
 
-    if let Ok(body_selector) = body_selector {
-        let mut text_content = String::new();
+    for script in document.select(&selector) {
+        let text = script.text().collect::<String>();
+        let Ok(value) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
 
-        if let Some(body) = document.select(&body_selector).next() {
-            let ignore_selector = Selector::parse("script, style, noscript")?;
+        for item in flatten_jsonld(value) {
+            for field in JSONLD_FIELDS {
+                if let Some(value) = item.get(field).filter(|x| !x.is_null()) {
+                    fields
+                        .entry(field.to_string())
+                        .or_insert_with(|| value.clone());
+                }
+            }
+        }
+    }
 
-            for node in body.select(&Selector::parse("*")?) {
-                if !ignore_selector.matches(&node) {
-                    let text = node.text().collect::<Vec<_>>().join(" ").trim().to_string();
-                    if !text.is_empty() {
-                        text_content.push_str(&text);
-                        text_content.push(' ');
-                    }
+    (!fields.is_empty()).then_some(Value::Object(fields))
+}
+
+/// Get the items of a JSON-LD document, including the ones of its arrays and `@graph`
+fn flatten_jsonld(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items.into_iter().flat_map(flatten_jsonld).collect(),
+        Value::Object(mut item) => {
+            let graph = item.remove("@graph");
+            let mut items = vec![Value::Object(item)];
+            items.extend(graph.map(flatten_jsonld).unwrap_or_default());
+            items
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Get the text of a JSON-LD value, which can be an object with the text in `key`,
+/// e.g. `{"@type": "Person", "name": "..."}`, or an array of values
+fn get_jsonld_text(value: &Value, key: &str) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.trim().to_string()).filter(|x| !x.is_empty()),
+        Value::Object(object) => get_jsonld_text(object.get(key)?, key),
+        Value::Array(values) => values.iter().find_map(|x| get_jsonld_text(x, key)),
+        _ => None,
+    }
+}
+
+/// Extract the publication timestamp of an article, from the JSON-LD `datePublished`,
+/// `<meta property="article:published_time">`, `<time datetime="...">` or `<meta name="date">`
+fn extract_published_date(document: &Html) -> Option<i64> {
+    let jsonld_date = extract_jsonld(document)
+        .and_then(|jsonld| get_jsonld_text(jsonld.get("datePublished")?, "@value"));
+    if let Some(date) = jsonld_date.as_deref().and_then(parse_iso_date) {
+        return Some(date);
+    }
+
+    if let Some(date) = extract_meta_content(document, "article:published_time")
+        .as_deref()
+        .and_then(parse_iso_date)
+    {
+        return Some(date);
+    }
+
+    let selector = Selector::parse("time[datetime]").ok()?;
+    let time_date = document
+        .select(&selector)
+        .filter_map(|element| element.value().attr("datetime"))
+        .find_map(|date| parse_iso_date(date.trim()));
+    if time_date.is_some() {
+        return time_date;
+    }
+
+    extract_meta_content(document, "date")
+        .as_deref()
+        .and_then(parse_iso_date)
+}
+
+/// Parse an ISO 8601 date to a timestamp in milliseconds,
+/// the dates without a time zone are in UTC
+fn parse_iso_date(date: &str) -> Option<i64> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.timestamp_millis());
+    }
+    if let Ok(date) = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S") {
+        return Some(date.and_utc().timestamp_millis());
+    }
+
+    let date = NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp_millis())
+}
+
+/// Extract the absolute target URL of a `<meta http-equiv="refresh" content="0; url=...">` tag
+fn extract_meta_refresh_url(document: &Html, url: &str) -> Option<String> {
+    let selector = Selector::parse("meta[http-equiv]").ok()?;
+
+    for element in document.select(&selector) {
+        let is_refresh = element
+            .value()
+            .attr("http-equiv")
+            .is_some_and(|x| x.trim().eq_ignore_ascii_case("refresh"));
+        if !is_refresh {
+            continue;
+        }
+
+        // The content is "<delay>" (reload) or "<delay>; url=<target>"
+        let (_, target) = element.value().attr("content")?.split_once(';')?;
+        let target = target.trim();
+        let target = match target.get(..4) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("url=") => &target[4..],
+            _ => target,
+        };
+        let target = target.trim().trim_matches(|c| c == '\'' || c == '"');
+
+        if target.is_empty() {
+            return None;
+        }
+
+        return normalize_href(url, target).ok();
+    }
+
+    None
+}
+
+/// Extract the absolute URL of a `<link rel="alternate" type="application/rss+xml">` feed
+fn extract_rss_url(document: &Html, url: &str) -> Option<String> {
+    let selector =
+        Selector::parse(r#"link[rel="alternate"][type="application/rss+xml"][href]"#).ok()?;
+
+    document
+        .select(&selector)
+        .filter_map(|element| element.value().attr("href"))
+        .find_map(|href| normalize_href(url, href.trim()).ok())
+}
+
+/// Extract the absolute URL of a `<link rel="canonical">`
+fn extract_canonical_url(document: &Html, base_url: &str) -> Option<String> {
+    let selector = Selector::parse("link[rel][href]").ok()?;
+
+    document
+        .select(&selector)
+        .filter(|element| {
+            element.value().attr("rel").is_some_and(|rel| {
+                rel.split_ascii_whitespace()
+                    .any(|x| x.eq_ignore_ascii_case("canonical"))
+            })
+        })
+        .filter_map(|element| element.value().attr("href"))
+        .find_map(|href| normalize_href(base_url, href.trim()).ok())
+}
+
+/// Extract the `<link rel="alternate" hreflang="...">` language variants, by lowercase `hreflang`
+fn extract_hreflang_links(document: &Html, base_url: &str) -> HashMap<String, String> {
+    let Ok(selector) = Selector::parse("link[rel][hreflang][href]") else {
+        return HashMap::new();
+    };
+
+    let mut alternates = HashMap::new();
+    for element in document.select(&selector) {
+        let is_alternate = element.value().attr("rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|x| x.eq_ignore_ascii_case("alternate"))
+        });
+        if !is_alternate {
+            continue;
+        }
+
+        let lang = element
+            .value()
+            .attr("hreflang")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        if lang.is_empty() || lang.len() > 10 {
+            continue;
+        }
+
+        let href = element.value().attr("href").unwrap_or_default().trim();
+        if let Ok(url) = normalize_href(base_url, href) {
+            alternates.entry(lang).or_insert(url);
+        }
+    }
+
+    alternates
+}
+
+/// Extract the non-empty `alt` of the `<img>`, the icons (`width` under 50 pixels) are ignored
+fn extract_image_alts(document: &Html) -> Vec<String> {
+    let Ok(selector) = Selector::parse("img[alt]") else {
+        return Vec::new();
+    };
+
+    document
+        .select(&selector)
+        .filter(|element| {
+            element
+                .value()
+                .attr("width")
+                .and_then(|width| width.trim().trim_end_matches("px").parse::<u32>().ok())
+                .is_none_or(|width| width >= 50)
+        })
+        .filter_map(|element| element.value().attr("alt"))
+        .map(|alt| alt.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|alt| !alt.is_empty())
+        .collect()
+}
+
+/// Extract the absolute URL of a `<link>` or `<a>` with the pagination `rel` (`next` or `prev`)
+fn extract_pagination_url(document: &Html, base_url: &str, rel: &str) -> Option<String> {
+    let selector = Selector::parse("link[rel][href], a[rel][href]").ok()?;
+
+    document
+        .select(&selector)
+        .filter(|element| {
+            element.value().attr("rel").is_some_and(|x| {
+                x.split_ascii_whitespace()
+                    .any(|x| x.eq_ignore_ascii_case(rel))
+            })
+        })
+        .filter_map(|element| element.value().attr("href"))
+        .find_map(|href| normalize_href(base_url, href.trim()).ok())
+}
+
+/// Extract the language declared by `<html lang="...">`, `<meta property="og:locale">`
+/// or `<meta http-equiv="content-language">`
+fn extract_language(document: &Html) -> Option<String> {
+    if let Some(lang) = document.root_element().value().attr("lang") {
+        if let Some(lang) = normalize_language_tag(lang) {
+            return Some(lang);
+        }
+    }
+
+    if let Some(locale) = extract_meta_content(document, "og:locale") {
+        if let Some(lang) = normalize_language_tag(&locale) {
+            return Some(lang);
+        }
+    }
+
+    let selector = Selector::parse("meta[http-equiv]").ok()?;
+
+    document
+        .select(&selector)
+        .filter(|element| {
+            element
+                .value()
+                .attr("http-equiv")
+                .is_some_and(|x| x.trim().eq_ignore_ascii_case("content-language"))
+        })
+        .filter_map(|element| element.value().attr("content"))
+        // The content can be a list of languages, keep the first one
+        .find_map(|content| normalize_language_tag(content.split(',').next()?))
+}
+
+/// Check if a list of robots directives (e.g. `noindex, nofollow`) contains `directive`
+fn has_robots_directive(directives: &str, directive: &str) -> bool {
+    directives.split(',').any(|x| {
+        // The X-Robots-Tag header can target a user agent: "googlebot: noindex"
+        let x = x.rsplit(':').next().unwrap_or_default().trim();
+        // "none" is the same as "noindex, nofollow"
+        x.eq_ignore_ascii_case(directive) || x.eq_ignore_ascii_case("none")
+    })
+}
+
+/// Get the `content` of the `<meta name="robots">` tags
+fn get_meta_robots(document: &Html) -> Vec<&str> {
+    match Selector::parse("meta[name][content]") {
+        Ok(selector) => document
+            .select(&selector)
+            .filter(|element| {
+                element
+                    .value()
+                    .attr("name")
+                    .is_some_and(|x| x.trim().eq_ignore_ascii_case("robots"))
+            })
+            .filter_map(|element| element.value().attr("content"))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Check the `noindex` directive of the `<meta name="robots">` tags and of the `X-Robots-Tag` header
+pub fn check_noindex(document: &Html, headers: &HeaderMap) -> bool {
+    let in_headers = headers
+        .get_all("x-robots-tag")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| has_robots_directive(value, "noindex"));
+
+    in_headers
+        || get_meta_robots(document)
+            .iter()
+            .any(|content| has_robots_directive(content, "noindex"))
+}
+
+/// Check the `nofollow` directive of the `<meta name="robots">` tags and of the `X-Robots-Tag` header
+pub fn check_nofollow(document: &Html, headers: &HeaderMap) -> bool {
+    let in_headers = headers
+        .get_all("x-robots-tag")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| has_robots_directive(value, "nofollow"));
+
+    in_headers
+        || get_meta_robots(document)
+            .iter()
+            .any(|content| has_robots_directive(content, "nofollow"))
+}
+
+/// Extract the text of the main content of the page, without its navigation, headers and footers
+///
+/// The `<main>`, `<article>` or `[role="main"]` element is used when the page has one,
+/// otherwise the block with the most text in its paragraphs, or the block with the
+/// highest text-to-tag ratio when the page has no paragraphs
+fn extract_main_content(document: &Html) -> Option<String> {
+    for selector in MAIN_CONTENT_SELECTORS {
+        let selector = Selector::parse(selector).ok()?;
+        let text = document
+            .select(&selector)
+            .map(|element| get_content_text(element).0)
+            .find(|text| !text.is_empty());
+
+        if text.is_some() {
+            return text;
+        }
+    }
+
+    let selector = Selector::parse("body, div, section, td").ok()?;
+    let stats = get_content_stats(document);
+    let get_stats = |element: &ElementRef| stats.get(&element.id()).copied().unwrap_or_default();
+
+    // Scored by their own paragraphs, so an article split in paragraphs
+    // is preferred to a short block without tags
+    let container = document
+        .select(&selector)
+        .map(|element| (element, get_paragraphs_length(element, &stats)))
+        .filter(|(_, length)| *length > 0)
+        .max_by_key(|(_, length)| *length);
+    if let Some((element, _)) = container {
+        return Some(get_content_text(element).0);
+    }
+
+    document
+        .select(&selector)
+        .max_by(|a, b| {
+            get_stats(a)
+                .text_ratio()
+                .total_cmp(&get_stats(b).text_ratio())
+        })
+        .map(|element| get_content_text(element).0)
+}
+
+/// Text of an element as counted by `get_content_text`, without building it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ContentStats {
+    /// Length of the words, without the spaces between them
+    words_length: usize,
+    words: usize,
+    tags: usize,
+}
+
+impl ContentStats {
+    /// Length of the text, the words are joined with a space
+    fn text_length(&self) -> usize {
+        self.words_length + self.words.saturating_sub(1)
+    }
+
+    fn text_ratio(&self) -> f64 {
+        self.text_length() as f64 / (self.tags + 1) as f64
+    }
+}
+
+/// Get the content stats of all the elements in one pass, the children are counted before their parent
+fn get_content_stats(document: &Html) -> HashMap<NodeId, ContentStats> {
+    let mut stats: HashMap<NodeId, ContentStats> = HashMap::new();
+    let nodes: Vec<_> = document.tree.root().descendants().collect();
+
+    // Reversed pre-order, the descendants of a node come before it
+    for node in nodes.into_iter().rev() {
+        if !node.value().is_element() {
+            continue;
+        }
+
+        let mut element_stats = ContentStats::default();
+        for child in node.children() {
+            if let Some(text) = child.value().as_text() {
+                for word in text.split_whitespace() {
+                    element_stats.words_length += word.len();
+                    element_stats.words += 1;
+                }
+            } else if let Some(child) = ElementRef::wrap(child) {
+                if !IGNORED_CONTENT_TAGS.contains(&child.value().name()) {
+                    let child_stats = stats.get(&child.id()).copied().unwrap_or_default();
+                    element_stats.words_length += child_stats.words_length;
+                    element_stats.words += child_stats.words;
+                    element_stats.tags += child_stats.tags + 1;
                 }
             }
         }
+        stats.insert(node.id(), element_stats);
+    }
 
-        return Ok(Some(text_content.trim().to_string()));
+    stats
+}
+
+/// Get the length of the text of the `<p>` children of an element
+fn get_paragraphs_length(element: ElementRef, stats: &HashMap<NodeId, ContentStats>) -> usize {
+    element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| child.value().name() == "p")
+        .filter_map(|child| stats.get(&child.id()))
+        .map(ContentStats::text_length)
+        .sum()
+}
+
+/// Get the text of an element and its number of descendant tags, without the ignored elements
+fn get_content_text(element: ElementRef) -> (String, usize) {
+    fn walk(element: ElementRef, words: &mut Vec<String>, tags: &mut usize) {
+        for child in element.children() {
+            if let Some(text) = child.value().as_text() {
+                words.extend(text.split_whitespace().map(str::to_string));
+            } else if let Some(child) = ElementRef::wrap(child) {
+                if !IGNORED_CONTENT_TAGS.contains(&child.value().name()) {
+                    *tags += 1;
+                    walk(child, words, tags);
+                }
+            }
+        }
     }
 
-    Ok(None)
+    let (mut words, mut tags) = (Vec::new(), 0);
+    walk(element, &mut words, &mut tags);
+
+    (words.join(" "), tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::calculate_seo_score;
+
+    #[test]
+    fn test_extract_meta_refresh_url() {
+        let url = "https://example.com/blog/post";
+
+        let document = Html::parse_document(
+            r#"<html><head><meta http-equiv="refresh" content="0; url=http://other.example.com"></head></html>"#,
+        );
+        assert_eq!(
+            extract_meta_refresh_url(&document, url),
+            Some("http://other.example.com/".into())
+        );
+
+        let document = Html::parse_document(
+            r#"<html><head><meta http-equiv="Refresh" content="5;URL='/new-post'"></head></html>"#,
+        );
+        assert_eq!(
+            extract_meta_refresh_url(&document, url),
+            Some("https://example.com/new-post".into())
+        );
+
+        let document = Html::parse_document(
+            r#"<html><head><meta http-equiv="refresh" content="0; url=next"></head></html>"#,
+        );
+        assert_eq!(
+            extract_meta_refresh_url(&document, url),
+            Some("https://example.com/blog/next".into())
+        );
+
+        // Simple reload, no target
+        let document = Html::parse_document(
+            r#"<html><head><meta http-equiv="refresh" content="30"></head></html>"#,
+        );
+        assert_eq!(extract_meta_refresh_url(&document, url), None);
+
+        let document = Html::parse_document(
+            r#"<html><head><meta http-equiv="content-type" content="text/html; charset=utf-8"></head></html>"#,
+        );
+        assert_eq!(extract_meta_refresh_url(&document, url), None);
+    }
+
+    #[test]
+    fn test_extract_jsonld() {
+        let document = Html::parse_document(
+            r#"<html><head>
+            <script type="application/ld+json">{ invalid</script>
+            <script type="application/ld+json">
+            {
+                "@context": "https://schema.org",
+                "@graph": [
+                    { "@type": "WebSite", "name": "Example" },
+                    {
+                        "@type": "Article",
+                        "name": "Ignored, the website has a name",
+                        "description": "An article",
+                        "author": [{ "@type": "Person", "name": "Jane Doe" }],
+                        "datePublished": "2024-03-01T10:00:00+01:00",
+                        "image": { "@type": "ImageObject", "url": "https://example.com/a.png" }
+                    }
+                ]
+            }
+            </script>
+            </head></html>"#,
+        );
+        let jsonld = extract_jsonld(&document).unwrap();
+
+        assert_eq!(jsonld["name"], "Example");
+        assert_eq!(jsonld["description"], "An article");
+        assert_eq!(
+            get_jsonld_text(&jsonld["author"], "name"),
+            Some("Jane Doe".into())
+        );
+        assert_eq!(
+            get_jsonld_text(&jsonld["image"], "url"),
+            Some("https://example.com/a.png".into())
+        );
+
+        let document = Html::parse_document("<html><head><title>Test</title></head></html>");
+        assert_eq!(extract_jsonld(&document), None);
+    }
+
+    #[test]
+    fn test_extract_published_date() {
+        let document = Html::parse_document(
+            r#"<html><head>
+            <meta property="article:published_time" content="2024-03-02T00:00:00Z">
+            <script type="application/ld+json">{"datePublished": "2024-03-01"}</script>
+            </head></html>"#,
+        );
+        assert_eq!(extract_published_date(&document), Some(1_709_251_200_000));
+
+        let document = Html::parse_document(
+            r#"<html><head>
+            <meta name="date" content="2024-03-03">
+            <meta property="article:published_time" content="2024-03-02T00:00:00Z">
+            </head></html>"#,
+        );
+        assert_eq!(extract_published_date(&document), Some(1_709_337_600_000));
+
+        let document = Html::parse_document(
+            r#"<html><head><meta name="date" content="2024-03-03"></head>
+            <body><time>Yesterday</time><time datetime="2024-03-01T09:00:00Z">March 1st</time></body></html>"#,
+        );
+        assert_eq!(extract_published_date(&document), Some(1_709_283_600_000));
+
+        let document = Html::parse_document(
+            r#"<html><head><meta name="date" content="2024-03-03"></head></html>"#,
+        );
+        assert_eq!(extract_published_date(&document), Some(1_709_424_000_000));
+
+        let document = Html::parse_document(r#"<html><head></head></html>"#);
+        assert_eq!(extract_published_date(&document), None);
+    }
+
+    #[test]
+    fn test_parse_iso_date() {
+        assert_eq!(
+            parse_iso_date("2024-03-01T10:00:00+01:00"),
+            Some(1_709_283_600_000)
+        );
+        assert_eq!(
+            parse_iso_date("2024-03-01T09:00:00Z"),
+            Some(1_709_283_600_000)
+        );
+        assert_eq!(
+            parse_iso_date("2024-03-01T09:00:00"),
+            Some(1_709_283_600_000)
+        );
+        assert_eq!(parse_iso_date("2024-03-01"), Some(1_709_251_200_000));
+        assert_eq!(parse_iso_date("March 1st"), None);
+    }
+
+    #[test]
+    fn test_jsonld_fallbacks() {
+        let html = r#"<html><head>
+            <meta name="author" content="John Smith">
+            <script type="application/ld+json">
+            {
+                "@type": "BlogPosting",
+                "name": "A post",
+                "description": "The description of the post",
+                "author": "Jane Doe",
+                "datePublished": "2024-03-01"
+            }
+            </script>
+            </head><body></body></html>"#;
+        let scraped = scrape_page(
+            "example.com".into(),
+            "https://example.com/post".into(),
+            html.into(),
+            &HeaderMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(scraped.title, Some("A post".into()));
+        assert_eq!(
+            scraped.meta_description,
+            Some("The description of the post".into())
+        );
+        // The HTML metadata comes first
+        assert_eq!(scraped.meta_author, Some("John Smith".into()));
+        assert_eq!(scraped.published_at, Some(1_709_251_200_000));
+    }
+
+    #[test]
+    fn test_twitter_card_fallbacks() {
+        let html = r#"<html><head>
+            <meta name="twitter:card" content="summary_large_image">
+            <meta name="twitter:title" content="A post">
+            <meta name="twitter:description" content="The description of the post">
+            <meta name="twitter:image" content="https://example.com/cover.png">
+            </head><body><h1>A post</h1></body></html>"#;
+        let scraped = scrape_page(
+            "example.com".into(),
+            "https://example.com/post".into(),
+            html.into(),
+            &HeaderMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(scraped.title, Some("A post".into()));
+        assert_eq!(
+            scraped.meta_description,
+            Some("The description of the post".into())
+        );
+        assert_eq!(
+            scraped.meta_og_image,
+            Some("https://example.com/cover.png".into())
+        );
+        assert_eq!(
+            scraped.meta_twitter_card,
+            Some("summary_large_image".into())
+        );
+        // title 25, description 20, image 10, h1 10, Twitter Card 5, thin content -10
+        assert_eq!(calculate_seo_score(&scraped), 60);
+
+        // The Open Graph and <title> metadata come first
+        let html = r#"<html><head>
+            <title>The title</title>
+            <meta property="og:image" content="https://example.com/og.png">
+            <meta name="twitter:title" content="A post">
+            <meta name="twitter:image" content="https://example.com/cover.png">
+            </head><body></body></html>"#;
+        let scraped = scrape_page(
+            "example.com".into(),
+            "https://example.com/post".into(),
+            html.into(),
+            &HeaderMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(scraped.title, Some("The title".into()));
+        assert_eq!(
+            scraped.meta_og_image,
+            Some("https://example.com/og.png".into())
+        );
+        assert_eq!(scraped.meta_twitter_card, None);
+    }
+
+    #[test]
+    fn test_open_graph_metadata() {
+        let html = r#"<html><head>
+            <meta property="og:type" content="article">
+            <meta property="og:site_name" content="The Blog">
+            <meta property="og:locale" content="fr_FR">
+            </head><body></body></html>"#;
+        let scraped = scrape_page(
+            "example.com".into(),
+            "https://example.com/post".into(),
+            html.into(),
+            &HeaderMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(scraped.meta_og_type, Some("article".into()));
+        assert_eq!(scraped.meta_og_site_name, Some("The Blog".into()));
+        assert_eq!(scraped.meta_og_locale, Some("fr_FR".into()));
+        assert_eq!(scraped.language, Some("fr".into()));
+        // Article 5, thin content -10
+        assert_eq!(calculate_seo_score(&scraped), 0);
+    }
+
+    #[test]
+    fn test_thin_content_seo_score() {
+        let html = format!(
+            r#"<html><head><title>A post</title></head><body><h1>A post</h1><p>{}</p></body></html>"#,
+            "word ".repeat(250)
+        );
+        let scraped = scrape_page(
+            "example.com".into(),
+            "https://example.com/post".into(),
+            html,
+            &HeaderMap::new(),
+        )
+        .unwrap();
+
+        // title 25, h1 10
+        assert_eq!(calculate_seo_score(&scraped), 35);
+    }
+
+    #[test]
+    fn test_check_noindex() {
+        let empty_headers = HeaderMap::new();
+
+        let document = Html::parse_document(
+            r#"<html><head><meta name="robots" content="noindex"></head></html>"#,
+        );
+        assert_eq!(check_noindex(&document, &empty_headers), true);
+
+        let document = Html::parse_document(
+            r#"<html><head><meta name="ROBOTS" content="NoIndex, nofollow"></head></html>"#,
+        );
+        assert_eq!(check_noindex(&document, &empty_headers), true);
+
+        let document = Html::parse_document(
+            r#"<html><head><meta name="robots" content="none"></head></html>"#,
+        );
+        assert_eq!(check_noindex(&document, &empty_headers), true);
+
+        let document = Html::parse_document(
+            r#"<html><head><meta name="robots" content="index, nofollow"><meta name="description" content="noindex"></head></html>"#,
+        );
+        assert_eq!(check_noindex(&document, &empty_headers), false);
+
+        // Headers
+        let document = Html::parse_document(r#"<html><head></head></html>"#);
+        assert_eq!(check_noindex(&document, &empty_headers), false);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Robots-Tag", "noindex,nofollow".parse().unwrap());
+        assert_eq!(check_noindex(&document, &headers), true);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-robots-tag", "googlebot: noindex".parse().unwrap());
+        assert_eq!(check_noindex(&document, &headers), true);
+
+        let mut headers = HeaderMap::new();
+        headers.append("x-robots-tag", "nofollow".parse().unwrap());
+        headers.append("x-robots-tag", "noarchive".parse().unwrap());
+        assert_eq!(check_noindex(&document, &headers), false);
+    }
+
+    #[test]
+    fn test_extract_links_nofollow() {
+        let document = Html::parse_document(
+            r#"<html><body>
+                <a href="/followed">Followed</a>
+                <a href="/nofollow" rel="nofollow">Nofollow</a>
+                <a href="https://ads.example.org/" rel="sponsored NoFollow noopener">Ad</a>
+                <a href="/external" rel="noopener noreferrer">External</a>
+            </body></html>"#,
+        );
+        let links = extract_links(&document, "https://example.com/").unwrap();
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links.contains("https://example.com/followed"), true);
+        assert_eq!(links.contains("https://example.com/external"), true);
+        assert_eq!(links.contains("https://example.com/nofollow"), false);
+    }
+
+    #[test]
+    fn test_check_nofollow() {
+        let empty_headers = HeaderMap::new();
+
+        let document = Html::parse_document(
+            r#"<html><head><meta name="robots" content="nofollow"></head><body><a href="/a">A</a></body></html>"#,
+        );
+        assert_eq!(check_nofollow(&document, &empty_headers), true);
+        assert_eq!(check_noindex(&document, &empty_headers), false);
+
+        let document = Html::parse_document(
+            r#"<html><head><meta name="robots" content="noindex"></head></html>"#,
+        );
+        assert_eq!(check_nofollow(&document, &empty_headers), false);
+
+        let document = Html::parse_document(r#"<html><head></head></html>"#);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-robots-tag", "noindex, nofollow".parse().unwrap());
+        assert_eq!(check_nofollow(&document, &headers), true);
+    }
+
+    #[test]
+    fn test_extract_main_content() {
+        let document = Html::parse_document(
+            r#"<html><body>
+                <nav><a href="/">Home</a><a href="/blog">Blog</a></nav>
+                <main>
+                    <header><h1>Title</h1></header>
+                    <p>The main  content</p>
+                    <aside>Related posts</aside>
+                    <script>var x = 1;</script>
+                </main>
+                <footer>Copyright</footer>
+            </body></html>"#,
+        );
+        assert_eq!(
+            extract_main_content(&document),
+            Some("The main content".into())
+        );
+
+        let document = Html::parse_document(
+            r#"<html><body>
+                <div role="main"><p>Some text</p></div>
+                <footer>Copyright</footer>
+            </body></html>"#,
+        );
+        assert_eq!(extract_main_content(&document), Some("Some text".into()));
+
+        // Without a semantic element, the densest block is used
+        let document = Html::parse_document(
+            r#"<html><body>
+                <div class="menu"><ul><li><a href="/">Home</a></li><li><a href="/about">About</a></li></ul></div>
+                <div class="post"><p>A long paragraph of the post, with most of the text of the page.</p></div>
+            </body></html>"#,
+        );
+        assert_eq!(
+            extract_main_content(&document),
+            Some("A long paragraph of the post, with most of the text of the page.".into())
+        );
+
+        let document = Html::parse_document(r#"<html><body></body></html>"#);
+        assert_eq!(extract_main_content(&document), Some(String::new()));
+    }
+
+    #[test]
+    fn test_content_stats() {
+        let document = Html::parse_document(
+            r#"<html><body>
+                <div><section><p>The <em>first</em>  paragraph</p><script>var x;</script></section>
+                <div><div><p>A <a href="/a">nested <b>link</b></a></p></div></div></div>
+                <td>Cell</td>
+            </body></html>"#,
+        );
+        let stats = get_content_stats(&document);
+
+        for element in document.select(&Selector::parse("*").unwrap()) {
+            let (text, tags) = get_content_text(element);
+            let element_stats = stats[&element.id()];
+            assert_eq!(element_stats.text_length(), text.len());
+            assert_eq!(element_stats.tags, tags);
+        }
+    }
+
+    #[test]
+    fn test_extract_main_content_paragraphs() {
+        // The note is denser than the article, whose paragraphs have links and emphasis
+        let document = Html::parse_document(
+            r#"<html><body>
+                <div class="note">Subscribe to the newsletter</div>
+                <div class="post">
+                    <h1>Title</h1>
+                    <p>The <em>first</em> paragraph of the <a href="/a">post</a>.</p>
+                    <p>The <em>second</em> paragraph of the <a href="/b">post</a>.</p>
+                    <p>The <em>third</em> paragraph of the <a href="/c">post</a>.</p>
+                </div>
+            </body></html>"#,
+        );
+        assert_eq!(
+            extract_main_content(&document),
+            Some(
+                "Title The first paragraph of the post . The second paragraph of the post . \
+                 The third paragraph of the post ."
+                    .into()
+            )
+        );
+
+        // Without paragraphs, the densest block is used
+        let document = Html::parse_document(
+            r#"<html><body>
+                <div class="menu"><a href="/">Home</a><a href="/about">About</a></div>
+                <div class="post">All the text of the post, without a paragraph.</div>
+            </body></html>"#,
+        );
+        assert_eq!(
+            extract_main_content(&document),
+            Some("All the text of the post, without a paragraph.".into())
+        );
+    }
+
+    #[test]
+    fn test_extract_hreflang_links() {
+        let url = "https://example.com/en/post";
+        let document = Html::parse_document(
+            r#"<html><head>
+                <link rel="alternate" hreflang="FR" href="/fr/post">
+                <link rel="alternate" hreflang="de-DE" href="https://example.de/post">
+                <link rel="alternate" hreflang="x-default" href="https://example.com/post">
+                <link rel="alternate" hreflang="fr" href="/fr/other-post">
+                <link rel="alternate" type="application/rss+xml" href="/feed.xml">
+                <link rel="canonical" hreflang="it" href="/it/post">
+                <link rel="alternate" hreflang="" href="/post">
+            </head></html>"#,
+        );
+
+        assert_eq!(
+            extract_hreflang_links(&document, url),
+            HashMap::from([
+                ("fr".to_string(), "https://example.com/fr/post".to_string()),
+                ("de-de".to_string(), "https://example.de/post".to_string()),
+                (
+                    "x-default".to_string(),
+                    "https://example.com/post".to_string()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_extract_image_alts() {
+        let document = Html::parse_document(
+            r#"<html><body>
+                <img src="/logo.png" alt="Logo" width="32">
+                <img src="/cat.jpg" alt=" A  sleeping cat ">
+                <img src="/dog.jpg" alt="A dog" width="400px">
+                <img src="/spacer.gif" alt="">
+                <img src="/no-alt.jpg">
+            </body></html>"#,
+        );
+
+        assert_eq!(
+            extract_image_alts(&document),
+            vec!["A sleeping cat".to_string(), "A dog".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_extract_pagination_url() {
+        let url = "https://example.com/blog/page/2";
+        let document = Html::parse_document(include_str!("../tests/fixtures/paginated.html"));
+        assert_eq!(
+            extract_pagination_url(&document, url, "next"),
+            Some("https://example.com/blog/page/3".into())
+        );
+        assert_eq!(
+            extract_pagination_url(&document, url, "prev"),
+            Some("https://example.com/blog/page/1".into())
+        );
+
+        let url = "https://example.com/thread/";
+        let document =
+            Html::parse_document(include_str!("../tests/fixtures/paginated_anchors.html"));
+        assert_eq!(
+            extract_pagination_url(&document, url, "next"),
+            Some("https://example.com/thread/2".into())
+        );
+        assert_eq!(
+            extract_pagination_url(&document, url, "prev"),
+            Some("https://example.com/thread/1".into())
+        );
+
+        let document = Html::parse_document(r#"<html><head></head></html>"#);
+        assert_eq!(extract_pagination_url(&document, url, "next"), None);
+    }
+
+    #[test]
+    fn test_scrape_paginated_page() {
+        let scraped = scrape_page(
+            "example.com".into(),
+            "https://example.com/blog/page/2".into(),
+            include_str!("../tests/fixtures/paginated.html").into(),
+            &HeaderMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            scraped.next_url,
+            Some("https://example.com/blog/page/3".into())
+        );
+        assert_eq!(
+            scraped.prev_url,
+            Some("https://example.com/blog/page/1".into())
+        );
+    }
+
+    #[test]
+    fn test_extract_canonical_url() {
+        let url = "https://example.com/blog/post?utm_source=feed";
+
+        let document = Html::parse_document(
+            r#"<html><head><link rel="canonical" href="https://example.com/blog/post"></head></html>"#,
+        );
+        assert_eq!(
+            extract_canonical_url(&document, url),
+            Some("https://example.com/blog/post".into())
+        );
+
+        let document = Html::parse_document(
+            r#"<html><head><link rel="Canonical" href="/posts/1"></head></html>"#,
+        );
+        assert_eq!(
+            extract_canonical_url(&document, url),
+            Some("https://example.com/posts/1".into())
+        );
+
+        let document = Html::parse_document(
+            r#"<html><head><link rel="canonical" href="other-post"></head></html>"#,
+        );
+        assert_eq!(
+            extract_canonical_url(&document, url),
+            Some("https://example.com/blog/other-post".into())
+        );
+
+        let document = Html::parse_document(
+            r#"<html><head><link rel="alternate" href="https://example.com/fr/"></head></html>"#,
+        );
+        assert_eq!(extract_canonical_url(&document, url), None);
+    }
+
+    #[test]
+    fn test_extract_language() {
+        let document = Html::parse_document(r#"<html lang="en-US"><head></head></html>"#);
+        assert_eq!(extract_language(&document), Some("en".into()));
+
+        let document = Html::parse_document(
+            r#"<html><head><meta http-equiv="Content-Language" content="fr, en"></head></html>"#,
+        );
+        assert_eq!(extract_language(&document), Some("fr".into()));
+
+        let document = Html::parse_document(
+            r#"<html><head><meta property="og:locale" content="de_DE"></head></html>"#,
+        );
+        assert_eq!(extract_language(&document), Some("de".into()));
+
+        // <html lang> comes first
+        let document = Html::parse_document(
+            r#"<html lang="en"><head><meta property="og:locale" content="de_DE"></head></html>"#,
+        );
+        assert_eq!(extract_language(&document), Some("en".into()));
+
+        let document = Html::parse_document(r#"<html><head></head><body>a</body></html>"#);
+        assert_eq!(extract_language(&document), None);
+    }
+
+    #[test]
+    fn test_extract_rss_url() {
+        let url = "https://example.com/blog/";
+
+        let document = Html::parse_document(
+            r#"<html><head><link rel="alternate" type="application/rss+xml" title="Blog" href="/feed.xml"></head></html>"#,
+        );
+        assert_eq!(
+            extract_rss_url(&document, url),
+            Some("https://example.com/feed.xml".into())
+        );
+
+        let document = Html::parse_document(
+            r#"<html><head><link rel="alternate" type="application/rss+xml" href="https://feeds.example.org/rss"></head></html>"#,
+        );
+        assert_eq!(
+            extract_rss_url(&document, url),
+            Some("https://feeds.example.org/rss".into())
+        );
+
+        // Other alternate links are ignored
+        let document = Html::parse_document(
+            r#"<html><head><link rel="alternate" hreflang="fr" href="/fr/"></head></html>"#,
+        );
+        assert_eq!(extract_rss_url(&document, url), None);
+    }
+
+    #[test]
+    fn test_extract_favicon() {
+        let document = Html::parse_document(
+            r#"<html><head>
+                <link rel="icon" href="/favicon-32.png" sizes="32x32">
+                <link rel="apple-touch-icon" href="/apple.png" sizes="180x180">
+                <link rel="icon" href="https://cdn.example.com/favicon-16.png" sizes="16x16">
+            </head></html>"#,
+        );
+        assert_eq!(
+            extract_favicon("example.com", &document).unwrap(),
+            Some(Icon {
+                url: "https://example.com/apple.png".into(),
+                size: 180
+            })
+        );
+
+        // Same unknown size, the priority order is used
+        let document = Html::parse_document(
+            r#"<html><head>
+                <link rel="apple-touch-icon-precomposed" href="/precomposed.png">
+                <link rel="shortcut icon" href="/favicon.ico">
+            </head></html>"#,
+        );
+        assert_eq!(
+            extract_favicon("example.com", &document).unwrap(),
+            Some(Icon {
+                url: "https://example.com/favicon.ico".into(),
+                size: 0
+            })
+        );
+
+        let document = Html::parse_document("<html><head></head></html>");
+        assert_eq!(extract_favicon("example.com", &document).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_icon_sizes() {
+        assert_eq!(parse_icon_sizes("16x16 32x32"), 32);
+        assert_eq!(parse_icon_sizes("192X192"), 192);
+        assert_eq!(parse_icon_sizes("any"), 0);
+        assert_eq!(parse_icon_sizes("48x24 invalid"), 24);
+    }
+
+    #[test]
+    fn test_extract_manifest_url() {
+        let document = Html::parse_document(
+            r#"<html><head><link rel="manifest" href="site.webmanifest"></head></html>"#,
+        );
+        assert_eq!(
+            extract_manifest_url(&document, "https://example.com/blog/post"),
+            Some("https://example.com/blog/site.webmanifest".into())
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest_icon() {
+        let manifest = r#"{
+            "name": "Example",
+            "icons": [
+                { "src": "icons/192.png", "sizes": "192x192", "type": "image/png" },
+                { "src": "/icons/512.png", "sizes": "512x512", "type": "image/png" },
+                { "sizes": "1024x1024" }
+            ]
+        }"#;
+        assert_eq!(
+            parse_manifest_icon(manifest, "https://example.com/static/manifest.json"),
+            Some(Icon {
+                url: "https://example.com/icons/512.png".into(),
+                size: 512
+            })
+        );
+
+        assert_eq!(
+            parse_manifest_icon(r#"{ "name": "Example" }"#, "https://example.com/"),
+            None
+        );
+        assert_eq!(
+            parse_manifest_icon("not json", "https://example.com/"),
+            None
+        );
+    }
 }
@@ -0,0 +1,230 @@
+use crate::utils::is_crawlable_url;
+use quick_xml::{events::Event, Reader};
+use reqwest::Client;
+use utils::url::normalize_url;
+
+/// Maximum number of nested sitemaps fetched from a sitemap index
+pub const MAX_NESTED_SITEMAPS: usize = 50;
+
+/// Maximum number of URLs kept from a sitemap (the limit of the sitemaps protocol)
+pub const MAX_SITEMAP_URLS: usize = 50_000;
+
+#[derive(Debug, PartialEq)]
+pub enum Sitemap {
+    /// `<urlset>`, the URLs of the pages
+    UrlSet(Vec<String>),
+    /// `<sitemapindex>`, the URLs of other sitemaps
+    Index(Vec<String>),
+}
+
+/// Get the URLs of the `Sitemap:` directives of a robots.txt
+pub fn get_robots_sitemaps(robots: &str) -> Vec<String> {
+    robots
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            if !key.trim().eq_ignore_ascii_case("sitemap") {
+                return None;
+            }
+
+            let value = value.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        })
+        .collect()
+}
+
+/// Parse a `<urlset>` or a `<sitemapindex>`, returns None if the XML is malformed
+pub fn parse_sitemap(xml: &str) -> Option<Sitemap> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut root = None;
+    let mut in_loc = false;
+    let mut locs = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = e.local_name();
+                if root.is_none() {
+                    root = Some(String::from_utf8_lossy(name.as_ref()).to_string());
+                } else if name.as_ref() == b"loc" {
+                    in_loc = true;
+                }
+            }
+            Ok(Event::End(e)) if e.local_name().as_ref() == b"loc" => in_loc = false,
+            Ok(Event::Text(e)) if in_loc && locs.len() < MAX_SITEMAP_URLS => {
+                locs.push(e.unescape().ok()?.trim().to_string());
+            }
+            Ok(Event::CData(e)) if in_loc && locs.len() < MAX_SITEMAP_URLS => {
+                let text = String::from_utf8_lossy(&e.into_inner()).trim().to_string();
+                locs.push(text);
+            }
+            Ok(Event::Eof) => break,
+            Err(_) => return None,
+            _ => {}
+        }
+    }
+
+    match root.as_deref() {
+        Some("urlset") => Some(Sitemap::UrlSet(locs)),
+        Some("sitemapindex") => Some(Sitemap::Index(locs)),
+        _ => None,
+    }
+}
+
+/// Keep the normalized URLs that can be crawled
+pub fn filter_sitemap_urls(urls: Vec<String>) -> Vec<(String, String)> {
+    urls.iter()
+        .filter_map(|url| normalize_url(url, true))
+        .map(|(url, domain)| (url.to_string(), domain))
+        .filter(|(url, _)| is_crawlable_url(url))
+        .collect()
+}
+
+async fn fetch_sitemap_text(url: &str, client: &Client) -> Result<Option<String>, reqwest::Error> {
+    let response = client.get(url).send().await?;
+
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+
+    Ok(Some(response.text().await?))
+}
+
+/// Fetch the page URLs of a sitemap.
+/// The nested sitemaps of a sitemap index are fetched too, but only one level deep.
+pub async fn fetch_sitemap(url: &str, client: &Client) -> Result<Vec<String>, reqwest::Error> {
+    let text = match fetch_sitemap_text(url, client).await? {
+        Some(text) => text,
+        None => return Ok(Vec::new()),
+    };
+
+    match parse_sitemap(&text) {
+        Some(Sitemap::UrlSet(urls)) => Ok(urls),
+        Some(Sitemap::Index(sitemaps)) => {
+            let mut urls = Vec::new();
+
+            for sitemap in sitemaps.iter().take(MAX_NESTED_SITEMAPS) {
+                // A broken nested sitemap should not prevent reading the others
+                if let Ok(Some(text)) = fetch_sitemap_text(sitemap, client).await {
+                    if let Some(Sitemap::UrlSet(nested)) = parse_sitemap(&text) {
+                        urls.extend(nested);
+                    }
+                }
+
+                if urls.len() >= MAX_SITEMAP_URLS {
+                    urls.truncate(MAX_SITEMAP_URLS);
+                    break;
+                }
+            }
+
+            Ok(urls)
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URLSET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+    <url>
+        <loc>https://example.com/</loc>
+        <lastmod>2024-01-01</lastmod>
+    </url>
+    <url>
+        <loc> https://example.com/a?b=1&amp;c=2 </loc>
+    </url>
+    <url>
+        <loc><![CDATA[https://example.com/cdata]]></loc>
+    </url>
+</urlset>"#;
+
+    const SITEMAP_INDEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+    <sitemap>
+        <loc>https://example.com/sitemap-1.xml</loc>
+    </sitemap>
+    <sitemap>
+        <loc>https://example.com/sitemap-2.xml</loc>
+        <lastmod>2024-01-01</lastmod>
+    </sitemap>
+</sitemapindex>"#;
+
+    #[test]
+    fn test_parse_urlset() {
+        assert_eq!(
+            parse_sitemap(URLSET),
+            Some(Sitemap::UrlSet(vec![
+                "https://example.com/".to_string(),
+                "https://example.com/a?b=1&c=2".to_string(),
+                "https://example.com/cdata".to_string(),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_sitemap_index() {
+        assert_eq!(
+            parse_sitemap(SITEMAP_INDEX),
+            Some(Sitemap::Index(vec![
+                "https://example.com/sitemap-1.xml".to_string(),
+                "https://example.com/sitemap-2.xml".to_string(),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_sitemap() {
+        // Mismatched tags
+        assert_eq!(
+            parse_sitemap("<urlset><url><loc>https://example.com/</url></loc></urlset>"),
+            None
+        );
+        // Unknown root
+        assert_eq!(
+            parse_sitemap("<rss><loc>https://example.com/</loc></rss>"),
+            None
+        );
+        // Not XML
+        assert_eq!(parse_sitemap("User-agent: *\nDisallow: /"), None);
+        assert_eq!(parse_sitemap(""), None);
+        // Empty
+        assert_eq!(
+            parse_sitemap("<urlset></urlset>"),
+            Some(Sitemap::UrlSet(Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_get_robots_sitemaps() {
+        let robots = "User-agent: *\nDisallow: /api\n\nSitemap: https://example.com/sitemap.xml\nsitemap:https://example.com/news.xml\nSitemap:\n";
+
+        assert_eq!(
+            get_robots_sitemaps(robots),
+            vec![
+                "https://example.com/sitemap.xml".to_string(),
+                "https://example.com/news.xml".to_string(),
+            ]
+        );
+        assert_eq!(
+            get_robots_sitemaps("User-agent: *\nDisallow: /").is_empty(),
+            true
+        );
+    }
+
+    #[test]
+    fn test_filter_sitemap_urls() {
+        let urls = filter_sitemap_urls(vec![
+            "https://example.com/".to_string(),
+            "ftp://example.com/file".to_string(),
+            "not a url".to_string(),
+        ]);
+
+        assert_eq!(urls.len(), 1);
+        assert_eq!(urls[0].1, "example.com");
+    }
+}
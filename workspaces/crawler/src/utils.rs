@@ -1,7 +1,20 @@
 use crate::scraper::ScrapedPage;
+use encoding_rs::{Encoding, UTF_8};
+use glob::{MatchOptions, Pattern, PatternError};
+use mime::Mime;
 use regex::Regex;
-use reqwest::header::HeaderMap;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE, LOCATION};
+use reqwest::Response;
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::time::Instant;
 use url::Url;
+use utils::{split_words, url::normalize_url};
+use whatlang::Lang;
+
+/// Pages with fewer words are penalized in the SEO score
+pub const THIN_CONTENT_WORD_COUNT: usize = 200;
 
 /// Validate that a link is a valid URL and starts with http/https
 pub fn is_crawlable_url(link: &str) -> bool {
@@ -15,18 +28,15 @@ pub fn is_crawlable_url(link: &str) -> bool {
     false
 }
 
-pub fn extract_words(text: &str) -> Option<Vec<String>> {
-    let mut words = Vec::new();
-    if let Ok(regex) = Regex::new(r"\b[a-zä-ÿ]{2,}\b") {
-        for cap in regex.captures_iter(text) {
-            let word = cap[0].to_lowercase();
-            words.push(word);
-        }
-
-        Some(words)
-    } else {
-        None
-    }
+/// Get the lowercase words of a text, split like the indexer does.
+/// A word is only made of letters and apostrophes (e.g. `l'école`) and has at least 2 characters,
+/// except the CJK characters, which are words on their own.
+pub fn extract_words(text: &str) -> Vec<String> {
+    split_words(text)
+        .filter(|word| word.chars().all(|c| c.is_alphabetic() || c == '\''))
+        .filter(|word| word.chars().nth(1).is_some() || word.chars().all(|c| c >= '\u{2E80}'))
+        .map(str::to_lowercase)
+        .collect()
 }
 
 pub fn get_content_type<'a>(headers: &HeaderMap, url: &str) -> Option<&'a str> {
@@ -69,18 +79,282 @@ pub fn calculate_seo_score(scraped: &ScrapedPage) -> i32 {
     if scraped.meta_og_image.is_some() {
         seo_score += 10
     }
+    if scraped.meta_twitter_card.is_some() {
+        seo_score += 5;
+    }
+    if scraped.meta_og_type.as_deref() == Some("article") {
+        seo_score += 5;
+    }
+    if scraped.image_alts.len() >= 3 {
+        seo_score += 5;
+    }
     if scraped.has_h1 {
         seo_score += 10;
     }
     if scraped.links.len() >= 5 {
         seo_score += 10;
     }
+    if scraped.word_count < THIN_CONTENT_WORD_COUNT {
+        seo_score -= 10;
+    }
     seo_score.clamp(0, 100)
 }
 
+/// Get the `limit` domains crawled most recently, the last one first
+pub fn get_active_domains(
+    websites: impl Iterator<Item = (String, Option<Instant>)>,
+    limit: usize,
+) -> Vec<String> {
+    let mut crawled: Vec<(String, Instant)> = websites
+        .filter_map(|(domain, last_crawl)| Some((domain, last_crawl?)))
+        .collect();
+    crawled.sort_unstable_by_key(|x| Reverse(x.1));
+
+    crawled
+        .into_iter()
+        .take(limit)
+        .map(|(domain, _)| domain)
+        .collect()
+}
+
+/// Get the hex encoded SHA-256 hash of a string
+pub fn sha256_hex(input: &str) -> String {
+    format!("{:x}", Sha256::digest(input.as_bytes()))
+}
+
+/// Get the new `content_changed_at` of a crawled page
+///
+/// `stored` holds the `(body_hash, content_changed_at)` of the page if it was already crawled.
+/// The timestamp is only moved to `now` when the body hash changed.
+pub fn get_content_changed_at(
+    stored: Option<(Option<String>, Option<i64>)>,
+    new_hash: &str,
+    now: i64,
+) -> i64 {
+    if let Some((Some(hash), Some(changed_at))) = stored {
+        if hash == new_hash {
+            return changed_at;
+        }
+    }
+
+    now
+}
+
+/// Detect the language of a text, returns its ISO 639-1 code if the detection is reliable
+pub fn detect_language(text: &str) -> Option<String> {
+    let info = whatlang::detect(text)?;
+
+    if info.is_reliable() {
+        Some(get_iso_639_1(info.lang()).to_string())
+    } else {
+        None
+    }
+}
+
+/// Get the ISO 639-1 code of a language, or its ISO 639-3 code if it has none
+fn get_iso_639_1(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Epo => "eo",
+        Lang::Eng => "en",
+        Lang::Rus => "ru",
+        Lang::Cmn => "zh",
+        Lang::Spa => "es",
+        Lang::Por => "pt",
+        Lang::Ita => "it",
+        Lang::Ben => "bn",
+        Lang::Fra => "fr",
+        Lang::Deu => "de",
+        Lang::Ukr => "uk",
+        Lang::Kat => "ka",
+        Lang::Ara => "ar",
+        Lang::Hin => "hi",
+        Lang::Jpn => "ja",
+        Lang::Heb => "he",
+        Lang::Yid => "yi",
+        Lang::Pol => "pl",
+        Lang::Amh => "am",
+        Lang::Jav => "jv",
+        Lang::Kor => "ko",
+        Lang::Nob => "nb",
+        Lang::Dan => "da",
+        Lang::Swe => "sv",
+        Lang::Fin => "fi",
+        Lang::Tur => "tr",
+        Lang::Nld => "nl",
+        Lang::Hun => "hu",
+        Lang::Ces => "cs",
+        Lang::Ell => "el",
+        Lang::Bul => "bg",
+        Lang::Bel => "be",
+        Lang::Mar => "mr",
+        Lang::Kan => "kn",
+        Lang::Ron => "ro",
+        Lang::Slv => "sl",
+        Lang::Hrv => "hr",
+        Lang::Srp => "sr",
+        Lang::Mkd => "mk",
+        Lang::Lit => "lt",
+        Lang::Lav => "lv",
+        Lang::Est => "et",
+        Lang::Tam => "ta",
+        Lang::Vie => "vi",
+        Lang::Urd => "ur",
+        Lang::Tha => "th",
+        Lang::Guj => "gu",
+        Lang::Uzb => "uz",
+        Lang::Pan => "pa",
+        Lang::Aze => "az",
+        Lang::Ind => "id",
+        Lang::Tel => "te",
+        Lang::Pes => "fa",
+        Lang::Mal => "ml",
+        Lang::Ori => "or",
+        Lang::Mya => "my",
+        Lang::Nep => "ne",
+        Lang::Sin => "si",
+        Lang::Khm => "km",
+        Lang::Tuk => "tk",
+        Lang::Aka => "ak",
+        Lang::Zul => "zu",
+        Lang::Sna => "sn",
+        Lang::Afr => "af",
+        Lang::Lat => "la",
+        Lang::Slk => "sk",
+        Lang::Cat => "ca",
+        Lang::Tgl => "tl",
+        Lang::Hye => "hy",
+    }
+}
+
+/// Whether the `Content-Length` header announces a body larger than `max_bytes`
+pub fn is_content_length_over(headers: &HeaderMap, max_bytes: usize) -> bool {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.trim().parse::<u64>().ok())
+        .is_some_and(|length| length > max_bytes as u64)
+}
+
+/// Read the body of a response up to `max_bytes`, returns it with whether it was truncated.
+/// The rest of the body is not downloaded.
+pub async fn read_body_limited(
+    mut response: Response,
+    max_bytes: usize,
+) -> reqwest::Result<(String, bool)> {
+    let encoding = get_charset_encoding(response.headers());
+    let mut body = Vec::new();
+
+    while let Some(chunk) = response.chunk().await? {
+        let remaining = max_bytes - body.len();
+        if chunk.len() > remaining {
+            body.extend_from_slice(&chunk[..remaining]);
+            return Ok((encoding.decode(&body).0.into_owned(), true));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok((encoding.decode(&body).0.into_owned(), false))
+}
+
+/// Encoding of the `charset` of the `Content-Type` header, UTF-8 by default like `Response::text`
+fn get_charset_encoding(headers: &HeaderMap) -> &'static Encoding {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.parse::<Mime>().ok())
+        .and_then(|mime| Encoding::for_label(mime.get_param("charset")?.as_str().as_bytes()))
+        .unwrap_or(UTF_8)
+}
+
+/// Target of a redirect response, its `Location` resolved against the URL of the response
+pub fn get_redirect_location(headers: &HeaderMap, url: &Url) -> Option<Url> {
+    let location = headers.get(LOCATION)?.to_str().ok()?;
+    url.join(location).ok()
+}
+
+/// Parse a newline-separated list of domain glob patterns (e.g. `*.example.com`)
+pub fn parse_domain_patterns(list: &str) -> Result<Vec<Pattern>, PatternError> {
+    list.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| Pattern::new(&line.to_lowercase()))
+        .collect()
+}
+
+/// Check the domain against the blocklist, and the allowlist if it is not empty.
+///
+/// Patterns follow the glob syntax: `*.example.com` matches the subdomains of `example.com`, but not `example.com` itself.
+pub fn crawlability_check(domain: &str, blocklist: &[Pattern], allowlist: &[Pattern]) -> bool {
+    let options = MatchOptions {
+        case_sensitive: false,
+        ..MatchOptions::new()
+    };
+
+    if blocklist.iter().any(|p| p.matches_with(domain, options)) {
+        return false;
+    }
+
+    allowlist.is_empty() || allowlist.iter().any(|p| p.matches_with(domain, options))
+}
+
+/// Parse a newline-separated list of URL regex patterns (e.g. `/calendar/\d{4}/\d{2}`)
+pub fn parse_url_patterns(list: &str) -> Result<Vec<Regex>, regex::Error> {
+    list.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(Regex::new)
+        .collect()
+}
+
+/// Check that the URL matches none of the blacklist patterns.
+/// The patterns are not anchored, they can match any part of the URL.
+pub fn blacklist_check(url: &str, patterns: &[Regex]) -> bool {
+    !patterns.iter().any(|p| p.is_match(url))
+}
+
+/// Parse a newline-separated list of seed URLs, returns their `(domain, url)` once each.
+/// The empty lines, the `#` comments and the URLs which cannot be crawled are skipped.
+pub fn parse_seed_urls(list: &str) -> Vec<(String, String)> {
+    let mut seen = HashSet::new();
+
+    list.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| normalize_url(line, true))
+        .map(|(url, domain)| (domain, url.to_string()))
+        .filter(|(_, url)| is_crawlable_url(url) && url.len() <= 2048)
+        .filter(|(_, url)| seen.insert(url.clone()))
+        .collect()
+}
+
+/// Check if a domain with `page_count` crawled pages reached its crawl budget
+pub fn is_over_budget(page_count: usize, max_pages_per_domain: Option<usize>) -> bool {
+    max_pages_per_domain.is_some_and(|max| page_count >= max)
+}
+
+/// Get the depth of the links found on a page at `depth`,
+/// or None if they are deeper than `max_depth` and should not be queued
+pub fn get_link_depth(depth: i32, max_depth: Option<usize>) -> Option<i32> {
+    let link_depth = depth + 1;
+
+    match max_depth {
+        Some(max_depth) if link_depth as usize > max_depth => None,
+        _ => Some(link_depth),
+    }
+}
+
+/// Get the redirect depth of the target of a redirect,
+/// or None if the chain is longer than `max_redirects` and the target should be dropped
+pub fn get_redirect_depth(redirect_depth: u8, max_redirects: u8) -> Option<u8> {
+    redirect_depth
+        .checked_add(1)
+        .filter(|&target_depth| target_depth <= max_redirects)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
 
     #[test]
     fn test_is_crawlable_url() {
@@ -94,24 +368,358 @@ mod tests {
 
     #[test]
     fn test_extract_words() {
-        assert_eq!(extract_words("a b c 1 2 3").unwrap(), Vec::<String>::new());
-        assert_eq!(extract_words("aa b c 1 2 3456").unwrap(), vec!["aa"]);
+        assert_eq!(extract_words("a b c 1 2 3"), Vec::<String>::new());
+        assert_eq!(extract_words("aa b c 1 2 3456"), vec!["aa"]);
+        assert_eq!(
+            extract_words("Hello my friend!"),
+            vec!["hello", "my", "friend"]
+        );
+        assert_eq!(extract_words("t1a2b3"), Vec::<String>::new());
+        assert_eq!(extract_words("This1 is2 very strange3"), vec!["very"]);
+        assert_eq!(
+            extract_words("Wii sports resort is the BEST game ever!"),
+            vec!["wii", "sports", "resort", "is", "the", "best", "game", "ever"]
+        );
+        assert_eq!(
+            extract_words("This, should. work, i. think!"),
+            vec!["this", "should", "work", "think"]
+        );
+        assert_eq!(extract_words("Élève à l'école"), vec!["élève", "l'école"]);
+        assert_eq!(extract_words("مرحبا بالعالم"), vec!["مرحبا", "بالعالم"]);
+        assert_eq!(extract_words("東京タワー"), vec!["東", "京", "タワー"]);
+    }
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("Le renard brun rapide saute par-dessus le chien paresseux, puis il repart dans la forêt."),
+            Some("fr".into())
+        );
+        assert_eq!(
+            detect_language(
+                "This search engine crawls the web, indexes the pages it finds and ranks them when you are looking for something."
+            ),
+            Some("en".into())
+        );
+        assert_eq!(detect_language(""), None);
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(sha256_hex("<html></html>").len(), 64);
+        assert_ne!(sha256_hex("<html></html>"), sha256_hex("<html> </html>"));
+    }
+
+    #[test]
+    fn test_get_content_changed_at() {
+        let hash = sha256_hex("<html>v1</html>");
+
+        // First crawl
+        assert_eq!(get_content_changed_at(None, &hash, 2000), 2000);
+
+        // Re-crawl with the same content
+        assert_eq!(
+            get_content_changed_at(Some((Some(hash.clone()), Some(1000))), &hash, 2000),
+            1000
+        );
+
+        // Re-crawl with a different content
         assert_eq!(
-            extract_words("Hello my friend!").unwrap(),
-            vec!["my", "friend"]
+            get_content_changed_at(
+                Some((Some(hash.clone()), Some(1000))),
+                &sha256_hex("<html>v2</html>"),
+                2000
+            ),
+            2000
         );
-        assert_eq!(extract_words("t1a2b3").unwrap(), Vec::<String>::new());
+
+        // Pages crawled before the hash was stored
+        assert_eq!(
+            get_content_changed_at(Some((None, None)), &hash, 2000),
+            2000
+        );
+    }
+
+    #[test]
+    fn test_is_content_length_over() {
+        let headers = |length: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_LENGTH, length.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(is_content_length_over(&headers("6000000"), 5 << 20), true);
+        assert_eq!(is_content_length_over(&headers("5242880"), 5 << 20), false);
+        assert_eq!(is_content_length_over(&headers("1024"), 5 << 20), false);
+        // Without a valid length, the body is read up to the limit
+        assert_eq!(is_content_length_over(&headers("abc"), 5 << 20), false);
+        assert_eq!(is_content_length_over(&HeaderMap::new(), 5 << 20), false);
+    }
+
+    #[tokio::test]
+    async fn test_read_body_limited() {
+        let response = |body: &'static str| Response::from(http::Response::new(body));
+
+        assert_eq!(
+            read_body_limited(response("<html></html>"), 1024)
+                .await
+                .unwrap(),
+            ("<html></html>".to_string(), false)
+        );
+        assert_eq!(
+            read_body_limited(response("<html></html>"), 13)
+                .await
+                .unwrap(),
+            ("<html></html>".to_string(), false)
+        );
+        assert_eq!(
+            read_body_limited(response("<html><body>Large page"), 12)
+                .await
+                .unwrap(),
+            ("<html><body>".to_string(), true)
+        );
+        assert_eq!(
+            read_body_limited(response(""), 12).await.unwrap(),
+            (String::new(), false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_body_limited_charset() {
+        let response = |content_type: &str, body: &[u8]| {
+            Response::from(
+                http::Response::builder()
+                    .header(CONTENT_TYPE, content_type)
+                    .body(body.to_vec())
+                    .unwrap(),
+            )
+        };
+
+        // "Café" in Latin-1
+        assert_eq!(
+            read_body_limited(response("text/html; charset=ISO-8859-1", b"Caf\xe9"), 1024)
+                .await
+                .unwrap(),
+            ("Café".to_string(), false)
+        );
+        assert_eq!(
+            read_body_limited(
+                response("text/html; charset=\"utf-8\"", "Café".as_bytes()),
+                1024
+            )
+            .await
+            .unwrap(),
+            ("Café".to_string(), false)
+        );
+        // Unknown charsets are decoded as UTF-8
+        assert_eq!(
+            read_body_limited(
+                response("text/html; charset=unknown", "Café".as_bytes()),
+                1024
+            )
+            .await
+            .unwrap(),
+            ("Café".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn test_parse_seed_urls() {
+        let seeds = parse_seed_urls(
+            "# News websites\n\
+            https://www.example.com/news\n\
+            \n\
+            \thttps://blog.example.org/  \n\
+            ftp://files.example.com/\n\
+            not a url\n\
+            https://example.com/news#top\n\
+            HTTP://Example.net\n",
+        );
+
+        assert_eq!(
+            seeds,
+            vec![
+                (
+                    "example.com".to_string(),
+                    "https://www.example.com/news".to_string()
+                ),
+                (
+                    "blog.example.org".to_string(),
+                    "https://blog.example.org/".to_string()
+                ),
+                (
+                    "example.com".to_string(),
+                    "https://example.com/news".to_string()
+                ),
+                ("example.net".to_string(), "http://example.net/".to_string()),
+            ]
+        );
+        assert_eq!(parse_seed_urls(""), vec![]);
+        assert_eq!(parse_seed_urls("# Nothing yet\n\n"), vec![]);
+    }
+
+    #[test]
+    fn test_blacklist_check() {
+        let patterns = parse_url_patterns(
+            "/calendar/\\d{4}/\\d{2}\n\n  [?&]sort=  \n[?&](color|size)=.*[?&](color|size)=\n",
+        )
+        .unwrap();
+        assert_eq!(patterns.len(), 3);
+
+        // Calendars
+        assert_eq!(
+            blacklist_check("https://example.com/calendar/2024/05", &patterns),
+            false
+        );
+        assert_eq!(
+            blacklist_check("https://example.com/events/calendar/2024/05/12", &patterns),
+            false
+        );
+        assert_eq!(
+            blacklist_check("https://example.com/calendar/", &patterns),
+            true
+        );
+        // Sorted and filtered listings
+        assert_eq!(
+            blacklist_check("https://shop.com/shoes?sort=price", &patterns),
+            false
+        );
+        assert_eq!(
+            blacklist_check("https://shop.com/shoes?page=2&sort=price", &patterns),
+            false
+        );
+        assert_eq!(
+            blacklist_check("https://shop.com/shoes?color=red&size=42", &patterns),
+            false
+        );
+        assert_eq!(
+            blacklist_check("https://shop.com/shoes?color=red", &patterns),
+            true
+        );
+        assert_eq!(blacklist_check("https://shop.com/resort", &patterns), true);
+
+        assert_eq!(
+            blacklist_check("https://example.com/calendar/2024/05", &[]),
+            true
+        );
+        assert!(parse_url_patterns("/calendar/(\\d{4}").is_err());
+        assert!(parse_url_patterns("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_crawlability_check() {
+        let blocklist = parse_domain_patterns("*.blocked.com\n\n  spam.net  \n").unwrap();
+        let allowlist = parse_domain_patterns("*.example.com\nexample.com").unwrap();
+
+        // Empty lists
+        assert_eq!(crawlability_check("example.org", &[], &[]), true);
+
+        // Blocklist
+        assert_eq!(crawlability_check("a.blocked.com", &blocklist, &[]), false);
+        assert_eq!(
+            crawlability_check("a.b.blocked.com", &blocklist, &[]),
+            false
+        );
+        assert_eq!(crawlability_check("blocked.com", &blocklist, &[]), true);
+        assert_eq!(crawlability_check("notblocked.com", &blocklist, &[]), true);
+        assert_eq!(crawlability_check("spam.net", &blocklist, &[]), false);
+        assert_eq!(crawlability_check("SPAM.net", &blocklist, &[]), false);
+        assert_eq!(crawlability_check("www.spam.net", &blocklist, &[]), true);
+
+        // Allowlist
+        assert_eq!(crawlability_check("example.com", &[], &allowlist), true);
+        assert_eq!(crawlability_check("www.example.com", &[], &allowlist), true);
+        assert_eq!(crawlability_check("example.org", &[], &allowlist), false);
+        assert_eq!(crawlability_check("badexample.com", &[], &allowlist), false);
+
+        // The blocklist wins
+        let blocklist = parse_domain_patterns("private.example.com").unwrap();
+        assert_eq!(
+            crawlability_check("private.example.com", &blocklist, &allowlist),
+            false
+        );
+
+        assert_eq!(parse_domain_patterns("[example.com").is_err(), true);
+        assert_eq!(parse_domain_patterns("").unwrap().is_empty(), true);
+    }
+
+    #[test]
+    fn test_is_over_budget() {
+        assert_eq!(is_over_budget(0, Some(2)), false);
+        assert_eq!(is_over_budget(1, Some(2)), false);
+        assert_eq!(is_over_budget(2, Some(2)), true);
+        assert_eq!(is_over_budget(3, Some(2)), true);
+
+        // No budget
+        assert_eq!(is_over_budget(1_000_000, None), false);
+        // Nothing is queued with a 0 budget
+        assert_eq!(is_over_budget(0, Some(0)), true);
+    }
+
+    #[test]
+    fn test_get_link_depth() {
+        // A seed page, its links are direct children
+        let children = get_link_depth(0, Some(1));
+        assert_eq!(children, Some(1));
+
+        // The links of the children are 2 levels deep and are not queued
+        assert_eq!(get_link_depth(children.unwrap(), Some(1)), None);
+
+        // Unlimited
+        assert_eq!(get_link_depth(1, None), Some(2));
+        assert_eq!(get_link_depth(100, None), Some(101));
+
+        // Nothing is queued with a 0 depth limit
+        assert_eq!(get_link_depth(0, Some(0)), None);
+    }
+
+    #[test]
+    fn test_redirect_chain_limit() {
+        // A chain of 6 redirects from a queued URL
+        let mut redirect_depth = 0;
+        let mut followed = 0;
+        for _ in 0..6 {
+            match get_redirect_depth(redirect_depth, 5) {
+                Some(depth) => {
+                    redirect_depth = depth;
+                    followed += 1;
+                }
+                None => break,
+            }
+        }
+        assert_eq!(followed, 5);
+        assert_eq!(get_redirect_depth(redirect_depth, 5), None);
+
+        // Redirects are never followed with a 0 limit
+        assert_eq!(get_redirect_depth(0, 0), None);
+        assert_eq!(get_redirect_depth(u8::MAX, u8::MAX), None);
+    }
+
+    #[test]
+    fn test_get_active_domains() {
+        let now = Instant::now();
+        let websites = vec![
+            ("a.com".to_string(), Some(now)),
+            ("b.com".to_string(), None),
+            ("c.com".to_string(), Some(now + Duration::from_secs(2))),
+            ("d.com".to_string(), Some(now + Duration::from_secs(1))),
+        ];
+
         assert_eq!(
-            extract_words("This1 is2 very strange3").unwrap(),
-            vec!["very"]
+            get_active_domains(websites.clone().into_iter(), 10),
+            vec!["c.com", "d.com", "a.com"]
         );
         assert_eq!(
-            extract_words("Wii sports resort is the BEST game ever!").unwrap(),
-            vec!["sports", "resort", "is", "the", "game", "ever"]
+            get_active_domains(websites.into_iter(), 2),
+            vec!["c.com", "d.com"]
         );
         assert_eq!(
-            extract_words("This, should. work, i. think!").unwrap(),
-            vec!["should", "work", "think"]
+            get_active_domains(std::iter::empty(), 2),
+            Vec::<String>::new()
         );
     }
 }
@@ -0,0 +1,452 @@
+use dashmap::DashSet;
+use database::schema::{pages, queue, visited_extra};
+use database::{DbConn, DbPool};
+use diesel::dsl::exists;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::LoadQuery;
+use siphasher::sip::SipHasher13;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{env, fs, io};
+use utils::sql::get_sql_timestamp;
+
+/// File of the visited URLs filter, saved on shutdown
+pub const VISITED_BLOOM_FILE: &str = "visited.bloom";
+
+/// Minimum number of URLs the filter is sized for (about 12 MB)
+pub const DEFAULT_BLOOM_CAPACITY: usize = 10_000_000;
+
+/// Rate of the unvisited URLs confirmed with a db call
+pub const BLOOM_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// URLs loaded per db call when building the filter
+pub const VISITED_LOAD_CHUNK_SIZE: i64 = 100_000;
+
+/// Keep the URLs which will not be loaded back from the pages or the queue.
+/// They are purged by the monitor after a while, so the failed URLs can be queued again.
+pub(crate) const SAVE_VISITED_EXTRA_SQL: &str = "INSERT INTO visited_extra (url, timestamp)
+    SELECT u, $2 FROM unnest($1::text[]) AS u
+    WHERE NOT EXISTS (SELECT 1 FROM pages WHERE url = u)
+        AND NOT EXISTS (SELECT 1 FROM queue WHERE url = u)
+    ON CONFLICT (url) DO UPDATE SET timestamp = EXCLUDED.timestamp";
+
+pub fn get_visited_bloom_path() -> PathBuf {
+    env::current_dir().unwrap().join(VISITED_BLOOM_FILE)
+}
+
+/// A Bloom filter of strings, the hashes are stable so it can be saved to a file
+#[derive(Debug, PartialEq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// Size the filter for `capacity` items at `false_positive_rate`
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let capacity = capacity.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+
+        let num_bits = (-capacity * false_positive_rate.ln() / (ln2 * ln2)).ceil() as u64;
+        let num_bits = num_bits.max(64);
+        let num_hashes = ((num_bits as f64 / capacity) * ln2).round().max(1.0) as u32;
+
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    /// The bit positions of an item, from two hashes (Kirsch-Mitzenmacher)
+    fn get_positions(&self, item: &str) -> impl Iterator<Item = u64> + '_ {
+        let hash = |key: u64| {
+            let mut hasher = SipHasher13::new_with_keys(key, !key);
+            hasher.write(item.as_bytes());
+            hasher.finish()
+        };
+        let (h1, h2) = (hash(0), hash(1));
+
+        (0..self.num_hashes as u64)
+            .map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.get_positions(item)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Add an item, returns whether it was maybe already added
+    pub fn check_and_set(&mut self, item: &str) -> bool {
+        let positions = self.get_positions(item).collect::<Vec<_>>();
+        let mut present = true;
+
+        for bit in positions {
+            let word = &mut self.bits[(bit / 64) as usize];
+            present &= *word & (1 << (bit % 64)) != 0;
+            *word |= 1 << (bit % 64);
+        }
+
+        present
+    }
+
+    /// Estimate the number of added items from the number of set bits
+    pub fn estimate_len(&self) -> usize {
+        let set_bits = self.bits.iter().map(|x| x.count_ones() as u64).sum::<u64>();
+        if set_bits >= self.num_bits {
+            return usize::MAX;
+        }
+
+        let (m, k) = (self.num_bits as f64, self.num_hashes as f64);
+        (-(m / k) * (1.0 - set_bits as f64 / m).ln()).round() as usize
+    }
+
+    /// `num_hashes` (u32), `num_bits` (u64) then the bits, in little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + self.bits.len() * 8);
+        bytes.extend(self.num_hashes.to_le_bytes());
+        bytes.extend(self.num_bits.to_le_bytes());
+        for word in &self.bits {
+            bytes.extend(word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// `None` if the bytes are not a filter saved by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let num_hashes = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?);
+        let num_bits = u64::from_le_bytes(bytes.get(4..12)?.try_into().ok()?);
+        let words = bytes.get(12..)?;
+
+        if num_hashes == 0 || num_bits == 0 || words.len() as u64 != num_bits.div_ceil(64) * 8 {
+            return None;
+        }
+
+        Some(Self {
+            bits: words
+                .chunks_exact(8)
+                .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+                .collect(),
+            num_bits,
+            num_hashes,
+        })
+    }
+}
+
+/// Whether a URL is crawled, queued or saved as visited
+fn get_is_visited_query(url: &str) -> impl LoadQuery<'_, DbConn, bool> + QueryFragment<Pg> {
+    diesel::select(
+        exists(pages::table.filter(pages::url.eq(url)))
+            .or(exists(queue::table.filter(queue::url.eq(url))))
+            .or(exists(
+                visited_extra::table.filter(visited_extra::url.eq(url)),
+            )),
+    )
+}
+
+/// Whether a URL is crawled and not deleted
+fn get_is_crawled_query(url: &str) -> impl LoadQuery<'_, DbConn, bool> + QueryFragment<Pg> {
+    diesel::select(exists(
+        pages::table
+            .filter(pages::url.eq(url))
+            .filter(pages::deleted_at.is_null()),
+    ))
+}
+
+/// The crawled, queued and failed URLs, which are not queued again.
+/// A Bloom filter answers for the unvisited URLs, the db confirms the others.
+pub struct VisitedUrls {
+    bloom: Mutex<BloomFilter>,
+    /// The dequeued URLs, in none of the tables until their page is saved or they are dropped
+    in_flight: DashSet<String>,
+    db_pool: DbPool,
+}
+
+/// Removes its URL from the in-flight URLs when dropped, once the task is done
+pub struct InFlightGuard<'a> {
+    visited: &'a VisitedUrls,
+    url: String,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.visited.in_flight.remove(&self.url);
+    }
+}
+
+impl VisitedUrls {
+    /// Restore the filter saved on shutdown, or build it from the db
+    pub fn load(db_pool: DbPool, path: &Path) -> Self {
+        let restored = fs::read(path)
+            .ok()
+            .and_then(|x| BloomFilter::from_bytes(&x));
+
+        // Removed until the next shutdown, a crash must not leave an outdated filter
+        let _ = fs::remove_file(path);
+
+        let bloom = match restored {
+            Some(bloom) => bloom,
+            None => VisitedUrls::build_bloom(&db_pool),
+        };
+
+        Self {
+            bloom: Mutex::new(bloom),
+            in_flight: DashSet::new(),
+            db_pool,
+        }
+    }
+
+    fn build_bloom(db_pool: &DbPool) -> BloomFilter {
+        let db_conn = &mut db_pool.get().unwrap();
+
+        let count = pages::table
+            .count()
+            .get_result::<i64>(db_conn)
+            .expect("Failed to count the pages");
+        let capacity = (count as usize * 2).max(DEFAULT_BLOOM_CAPACITY);
+        let mut bloom = BloomFilter::new(capacity, BLOOM_FALSE_POSITIVE_RATE);
+
+        // Paginate on the ids, so all the URLs are never in memory at once
+        let mut last_id = 0;
+        loop {
+            let chunk = pages::table
+                .select((pages::id, pages::url))
+                .filter(pages::id.gt(last_id))
+                .order(pages::id)
+                .limit(VISITED_LOAD_CHUNK_SIZE)
+                .load::<(i32, String)>(db_conn)
+                .expect("Failed to load URLs");
+
+            match chunk.last() {
+                Some((id, _)) => last_id = *id,
+                None => break,
+            }
+            for (_, url) in chunk {
+                bloom.check_and_set(&url);
+            }
+        }
+
+        let queued = queue::table
+            .select(queue::url)
+            .load::<String>(db_conn)
+            .expect("Failed to load the queued URLs");
+        let extra = visited_extra::table
+            .select(visited_extra::url)
+            .load::<String>(db_conn)
+            .expect("Failed to load the extra visited URLs");
+
+        for url in queued.iter().chain(&extra) {
+            bloom.check_and_set(url);
+        }
+
+        bloom
+    }
+
+    /// Mark a URL as visited, returns whether it was not visited yet
+    pub fn insert(&self, url: &str) -> bool {
+        let maybe_visited = self.bloom.lock().unwrap().check_and_set(url);
+        if !maybe_visited {
+            return true;
+        }
+        if self.in_flight.contains(url) {
+            return false;
+        }
+
+        let db_conn = &mut self.db_pool.get().unwrap();
+        !get_is_visited_query(url)
+            .get_result::<bool>(db_conn)
+            .unwrap()
+    }
+
+    /// Whether a URL is crawled, the filter avoids the db call for most of the others
+    pub fn is_crawled(&self, url: &str) -> bool {
+        if !self.bloom.lock().unwrap().contains(url) {
+            return false;
+        }
+
+        let db_conn = &mut self.db_pool.get().unwrap();
+        get_is_crawled_query(url)
+            .get_result::<bool>(db_conn)
+            .unwrap()
+    }
+
+    /// Approximate number of visited URLs
+    pub fn len(&self) -> usize {
+        self.bloom.lock().unwrap().estimate_len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keep a dequeued URL as visited until the guard of its task is dropped
+    pub fn set_in_flight(&self, url: &str) {
+        self.in_flight.insert(url.to_string());
+    }
+
+    /// Get the guard of a dequeued URL, to drop once its page is saved, or it is queued again or dropped
+    pub fn get_in_flight_guard(&self, url: &str) -> InFlightGuard<'_> {
+        InFlightGuard {
+            visited: self,
+            url: url.to_string(),
+        }
+    }
+
+    /// Mark a URL as visited, without checking it
+    pub fn mark(&self, url: &str) {
+        self.bloom.lock().unwrap().check_and_set(url);
+    }
+
+    /// Keep a visited URL which is neither crawled nor queued, e.g. a failed one
+    pub fn save_extra(&self, url: &str) {
+        let db_conn = &mut self.db_pool.get().unwrap();
+
+        diesel::sql_query(SAVE_VISITED_EXTRA_SQL)
+            .bind::<diesel::sql_types::Array<diesel::sql_types::Text>, _>(&[url][..])
+            .bind::<diesel::sql_types::BigInt, _>(get_sql_timestamp())
+            .execute(db_conn)
+            .unwrap();
+    }
+
+    /// Save the filter for the next start
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes = self.bloom.lock().unwrap().to_bytes();
+        fs::write(path, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::debug_query;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut bloom = BloomFilter::new(1000, BLOOM_FALSE_POSITIVE_RATE);
+
+        assert_eq!(bloom.check_and_set("https://example.com/"), false);
+        assert_eq!(bloom.check_and_set("https://example.com/"), true);
+        assert_eq!(bloom.contains("https://example.com/"), true);
+        assert_eq!(bloom.contains("https://example.com/about"), false);
+
+        // Never a false negative, few false positives
+        for i in 0..1000 {
+            bloom.check_and_set(&format!("https://example.com/{i}"));
+        }
+        assert_eq!(
+            (0..1000).all(|i| bloom.contains(&format!("https://example.com/{i}"))),
+            true
+        );
+        let false_positives = (0..10_000)
+            .filter(|i| bloom.contains(&format!("https://example.org/{i}")))
+            .count();
+        assert_eq!(false_positives < 300, true);
+    }
+
+    #[test]
+    fn test_bloom_filter_estimate_len() {
+        let mut bloom = BloomFilter::new(10_000, BLOOM_FALSE_POSITIVE_RATE);
+        assert_eq!(bloom.estimate_len(), 0);
+
+        for i in 0..1000 {
+            bloom.check_and_set(&format!("https://example.com/{i}"));
+        }
+        let estimate = bloom.estimate_len();
+        assert_eq!((950..=1050).contains(&estimate), true);
+    }
+
+    #[test]
+    fn test_bloom_filter_size() {
+        let bloom = BloomFilter::new(DEFAULT_BLOOM_CAPACITY, BLOOM_FALSE_POSITIVE_RATE);
+
+        assert_eq!(bloom.num_hashes, 7);
+        assert_eq!(bloom.to_bytes().len() / 1_000_000, 11);
+    }
+
+    #[test]
+    fn test_bloom_filter_bytes() {
+        let mut bloom = BloomFilter::new(100, BLOOM_FALSE_POSITIVE_RATE);
+        bloom.check_and_set("https://example.com/");
+
+        let bytes = bloom.to_bytes();
+        let restored = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(restored, bloom);
+        assert_eq!(restored.contains("https://example.com/"), true);
+
+        assert_eq!(BloomFilter::from_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(BloomFilter::from_bytes(&[]), None);
+        assert_eq!(BloomFilter::from_bytes(&[0; 20]), None);
+    }
+
+    #[test]
+    fn test_in_flight_urls() {
+        use diesel::r2d2::ConnectionManager;
+
+        // A pool that never connects, the in-flight URLs are found without a db call
+        let manager = ConnectionManager::<PgConnection>::new("postgres://localhost/epsilon_tests");
+        let visited = VisitedUrls {
+            bloom: Mutex::new(BloomFilter::new(100, BLOOM_FALSE_POSITIVE_RATE)),
+            in_flight: DashSet::new(),
+            db_pool: DbPool::builder().build_unchecked(manager),
+        };
+
+        assert_eq!(visited.insert("https://example.com/"), true);
+        visited.set_in_flight("https://example.com/");
+        assert_eq!(visited.insert("https://example.com/"), false);
+
+        let guard = visited.get_in_flight_guard("https://example.com/");
+        assert_eq!(visited.in_flight.contains("https://example.com/"), true);
+        drop(guard);
+        assert_eq!(visited.in_flight.is_empty(), true);
+    }
+
+    #[test]
+    fn test_is_visited_query() {
+        let sql = debug_query::<Pg, _>(&get_is_visited_query("https://example.com/")).to_string();
+
+        assert_eq!(sql.contains(r#"EXISTS (SELECT "pages"."#), true);
+        assert_eq!(
+            sql.contains(r#"FROM "queue" WHERE ("queue"."url" = $2)"#),
+            true
+        );
+        assert_eq!(
+            sql.contains(r#"FROM "visited_extra" WHERE ("visited_extra"."url" = $3)"#),
+            true
+        );
+    }
+
+    #[test]
+    fn test_is_crawled_query() {
+        let sql =
+            debug_query::<Pg, _>(&get_is_crawled_query("https://www.example.com/")).to_string();
+
+        assert_eq!(sql.contains(r#"WHERE (("pages"."url" = $1)"#), true);
+        assert_eq!(sql.contains(r#""pages"."deleted_at" IS NULL"#), true);
+        assert_eq!(sql.contains("queue"), false);
+    }
+
+    #[test]
+    fn test_save_visited_extra_query() {
+        let urls = [
+            "https://example.com/".to_string(),
+            "https://example.com/404".to_string(),
+        ];
+        let query = diesel::sql_query(SAVE_VISITED_EXTRA_SQL)
+            .bind::<diesel::sql_types::Array<diesel::sql_types::Text>, _>(&urls[..])
+            .bind::<diesel::sql_types::BigInt, _>(1_700_000_000_000);
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        // The crawled and queued URLs are already loaded on start
+        assert!(sql.contains("NOT EXISTS (SELECT 1 FROM pages WHERE url = u)"));
+        assert!(sql.contains("NOT EXISTS (SELECT 1 FROM queue WHERE url = u)"));
+        // A URL failing again is kept longer
+        assert!(sql.contains("DO UPDATE SET timestamp = EXCLUDED.timestamp"));
+        assert!(sql.contains(
+            r#"binds: [["https://example.com/", "https://example.com/404"], 1700000000000]"#
+        ));
+    }
+}
@@ -1,15 +1,24 @@
+use crate::scraper::Icon;
+use regex::Regex;
 use reqwest::Client;
 use robotstxt::DefaultMatcher;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 /// Cooldown before crawling the robots again
 pub const ROBOTS_FETCH_COOLDOWN: u128 = 86_400_000;
 
+/// Longer crawl delays are lowered to this one (1 minute)
+pub const MAX_CRAWL_DELAY: u64 = 60_000;
+
 pub struct Website {
     pub domain: String,
     pub robots: Option<String>,
     pub last_robots_fetch: Option<Instant>,
     pub last_crawl: Option<Instant>,
+    /// The `Crawl-delay` of the robots, if any
+    pub crawl_delay: Option<Duration>,
+    /// The largest icon of the Web App Manifest, with the manifest URL, fetched once per website
+    pub manifest_icon: Option<(String, Option<Icon>)>,
 }
 
 impl Website {
@@ -19,9 +28,19 @@ impl Website {
             robots: None,
             last_robots_fetch: None,
             last_crawl: None,
+            crawl_delay: None,
+            manifest_icon: None,
         }
     }
 
+    /// Get the cached icon of a manifest, `None` when this manifest is not fetched yet
+    pub fn get_manifest_icon(&self, manifest_url: &str) -> Option<Option<Icon>> {
+        self.manifest_icon
+            .as_ref()
+            .filter(|(url, _)| url == manifest_url)
+            .map(|(_, icon)| icon.clone())
+    }
+
     pub fn should_fetch_robots(&self) -> bool {
         if let Some(last_fetch) = self.last_robots_fetch {
             if last_fetch.elapsed().as_millis() >= ROBOTS_FETCH_COOLDOWN {
@@ -55,8 +74,12 @@ impl Website {
         Ok(Some(text))
     }
 
-    pub fn set_robots(&mut self, text: Option<String>) {
+    pub fn set_robots(&mut self, text: Option<String>, user_agent: &str) {
         self.last_robots_fetch = Some(Instant::now());
+        self.crawl_delay = text
+            .as_deref()
+            .and_then(|robots| crawl_delay_ms(robots, user_agent))
+            .map(|delay| Duration::from_millis(delay.min(MAX_CRAWL_DELAY)));
         self.robots = text;
     }
 
@@ -70,10 +93,94 @@ impl Website {
     }
 }
 
+/// Get the `Crawl-delay` in milliseconds of the group of `user_agent`, or of the `*` group
+pub fn crawl_delay_ms(robots: &str, user_agent: &str) -> Option<u64> {
+    let regex = Regex::new(r"^\s*([A-Za-z-]+)\s*:\s*([^#]*)").unwrap();
+
+    // "MyCrawler/1.0 (https://example.com)" -> "mycrawler"
+    let product = user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let mut agents: Vec<String> = Vec::new();
+    let mut is_group_start = false;
+    let mut agent_delay = None;
+    let mut wildcard_delay = None;
+
+    for line in robots.lines() {
+        if let Some(caps) = regex.captures(line) {
+            let key = caps[1].to_lowercase();
+            let value = caps[2].trim();
+
+            if key == "user-agent" {
+                // Consecutive user agents share the same group
+                if !is_group_start {
+                    agents.clear();
+                }
+                agents.push(value.to_lowercase());
+                is_group_start = true;
+                continue;
+            }
+
+            is_group_start = false;
+
+            if key != "crawl-delay" {
+                continue;
+            }
+
+            if let Ok(seconds) = value.parse::<f64>() {
+                if !seconds.is_finite() || seconds < 0.0 {
+                    continue;
+                }
+
+                let delay = (seconds * 1000.0) as u64;
+                if agents.iter().any(|a| a == &product) {
+                    agent_delay.get_or_insert(delay);
+                } else if agents.iter().any(|a| a == "*") {
+                    wildcard_delay.get_or_insert(delay);
+                }
+            }
+        }
+    }
+
+    agent_delay.or(wildcard_delay)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_get_manifest_icon() {
+        let mut website = Website::new("example.com".into());
+        let manifest_url = "https://example.com/manifest.json";
+        assert_eq!(website.get_manifest_icon(manifest_url), None);
+
+        let icon = Icon {
+            url: "https://example.com/icon-512.png".into(),
+            size: 512,
+        };
+        website.manifest_icon = Some((manifest_url.into(), Some(icon)));
+        assert_eq!(
+            website
+                .get_manifest_icon(manifest_url)
+                .flatten()
+                .map(|x| x.size),
+            Some(512)
+        );
+        // Another manifest is fetched again
+        assert_eq!(
+            website.get_manifest_icon("https://example.com/app.webmanifest"),
+            None
+        );
+
+        // A manifest without icons is not fetched again
+        website.manifest_icon = Some((manifest_url.into(), None));
+        assert_eq!(website.get_manifest_icon(manifest_url), Some(None));
+    }
+
     #[test]
     fn test_is_crawlable() {
         let mut website = Website::new("google.com".into());
@@ -93,4 +200,51 @@ mod tests {
         assert_eq!(website.is_crawlable("Epsilon", "/home"), false);
         assert_eq!(website.is_crawlable("Epsilon", "/api"), true);
     }
+
+    #[test]
+    fn test_crawl_delay() {
+        let robots = "User-agent: *\nCrawl-delay: 5\n\nUser-agent: Other\nUser-agent: Epsilon\nDisallow: /api\nCrawl-delay: 0.5 # half a second\n";
+
+        assert_eq!(crawl_delay_ms(robots, "Epsilon"), Some(500));
+        assert_eq!(
+            crawl_delay_ms(robots, "epsilon/1.0 (https://example.com)"),
+            Some(500)
+        );
+        assert_eq!(crawl_delay_ms(robots, "Another"), Some(5_000));
+
+        // Agent-specific group without delay
+        let robots = "User-agent: Epsilon\nDisallow: /api\n\nUser-agent: *\ncrawl-delay: 2";
+        assert_eq!(crawl_delay_ms(robots, "Epsilon"), Some(2_000));
+
+        assert_eq!(
+            crawl_delay_ms("User-agent: *\nDisallow: /", "Epsilon"),
+            None
+        );
+        assert_eq!(
+            crawl_delay_ms("User-agent: *\nCrawl-delay: soon", "Epsilon"),
+            None
+        );
+        assert_eq!(
+            crawl_delay_ms("User-agent: Other\nCrawl-delay: 3", "Epsilon"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_set_robots_crawl_delay() {
+        let mut website = Website::new("google.com".into());
+
+        website.set_robots(Some("User-agent: *\nCrawl-delay: 3".into()), "Epsilon");
+        assert_eq!(website.crawl_delay, Some(Duration::from_secs(3)));
+
+        website.set_robots(Some("User-agent: *\nCrawl-delay: 86400".into()), "Epsilon");
+        assert_eq!(
+            website.crawl_delay,
+            Some(Duration::from_millis(MAX_CRAWL_DELAY))
+        );
+
+        // Without a delay, the default cooldown is used
+        website.set_robots(None, "Epsilon");
+        assert_eq!(website.crawl_delay, None);
+    }
 }
@@ -1,18 +1,61 @@
 use crate::crawler::Crawler;
-use crate::utils::{calculate_seo_score, get_content_type};
+use crate::sitemap::{fetch_sitemap, filter_sitemap_urls, get_robots_sitemaps};
+use crate::utils::{
+    calculate_seo_score, crawlability_check, get_content_changed_at, get_content_type,
+    get_link_depth, get_redirect_depth, get_redirect_location, is_content_length_over,
+    read_body_limited, sha256_hex,
+};
 use crate::website::Website;
-use crate::{crawler::Task, scraper::scrape_page};
+use crate::{
+    crawler::Task,
+    scraper::{parse_manifest_icon, pick_largest_icon, scrape_page, Icon},
+};
 use dashmap::mapref::one::RefMut;
-use database::models::{NewFavicon, NewPage, NewQueuedPage};
-use database::schema::{favicons, pages, queue};
+use database::models::{
+    NewCrawlError, NewDomainStat, NewFavicon, NewLink, NewPage, NewPageAlternate, NewPageLink,
+    NewQueuedPage,
+};
+use database::schema::{
+    crawl_errors, domain_stats, favicons, links, page_alternates, page_links, pages, queue,
+};
+use database::DbConn;
+use diesel::dsl::sql;
+use diesel::pg::Pg;
 use diesel::prelude::*;
-use std::{collections::HashSet, sync::Arc, time::Instant};
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::methods::ExecuteDsl;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::sql_query;
+use diesel::sql_types::{Integer, Text};
+use diesel::upsert::excluded;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 use url::Url;
 use utils::safe_slice;
+use utils::shutdown::{is_shutting_down, sleep_or_shutdown, wait_for_shutdown};
 use utils::sql::get_sql_timestamp;
-use utils::url::normalize_url;
+use utils::url::{get_registered_domain, get_www_alternate_url, normalize_url, url_fingerprint};
 
-pub const DOMAIN_CRAWL_COOLDOWN: u128 = 10_000;
+/// Delay between two crawls of the same domain, used when the robots has no `Crawl-delay`
+pub const DOMAIN_CRAWL_COOLDOWN: u64 = 10_000;
+
+/// Queue priority given to the URLs of websites with a RSS feed
+pub const RSS_QUEUE_PRIORITY: i32 = 10;
+
+/// Queue priority given to the next page of a paginated content, so it is crawled in sequence
+pub const PAGINATION_QUEUE_PRIORITY: i32 = 5;
+
+/// Manifests are small JSON files, larger responses are ignored
+const MAX_MANIFEST_BYTES: usize = 64 << 10;
+
+/// Delay between two checks of the pause flag
+pub const PAUSE_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 // CrawlError //
 
@@ -22,6 +65,8 @@ enum CrawlError {
     InvalidContentType,
     Reqwest(reqwest::Error),
     NotCrawlable,
+    /// The page asks to not be indexed
+    NoIndex,
     Redirect(String, Url),
     ParseError,
 }
@@ -32,8 +77,170 @@ impl From<reqwest::Error> for CrawlError {
     }
 }
 
+// CrawledPage //
+
+/// The data of a crawled page, to save
+struct CrawledPage {
+    page: NewPage,
+    favicon: NewFavicon,
+    /// Links to queue, none for a nofollow page
+    links: HashSet<String>,
+    /// Next page of a paginated content, crawled before the other links
+    next_url: Option<String>,
+    /// Language variants of the page, by `hreflang`
+    alternates: HashMap<String, String>,
+}
+
 // Worker //
 
+/// Get the URL of another page with the same body hash
+fn get_duplicate_page_query<'a>(
+    body_hash: &'a str,
+    url: &'a str,
+) -> pages::BoxedQuery<'a, Pg, Text> {
+    pages::table
+        .filter(pages::body_hash.eq(body_hash))
+        .filter(pages::url.ne(url))
+        .select(pages::url)
+        .into_boxed()
+}
+
+/// Get the URL of another page with the same URL fingerprint
+fn get_fingerprint_duplicate_query<'a>(
+    fingerprint: &'a str,
+    url: &'a str,
+) -> pages::BoxedQuery<'a, Pg, Text> {
+    pages::table
+        .filter(pages::url_fingerprint.eq(fingerprint))
+        .filter(pages::url.ne(url))
+        .select(pages::url)
+        .into_boxed()
+}
+
+/// Turn the pending links to a newly crawled page (`$1`, `$2` its URL) into links
+const RESOLVE_PAGE_LINKS_SQL: &str = "WITH resolved AS (
+        DELETE FROM page_links WHERE to_url = $2 RETURNING from_page_id
+    )
+    INSERT INTO links (from_page_id, to_page_id)
+    SELECT DISTINCT from_page_id, $1 FROM resolved WHERE from_page_id <> $1";
+
+/// Split the links of a page into the links to the crawled pages and the pending ones,
+/// `crawled` holds the `(url, id)` of the crawled pages among `urls`
+fn split_links(
+    page_id: i32,
+    urls: &[&str],
+    crawled: &[(String, i32)],
+    timestamp: i64,
+) -> (Vec<NewLink>, Vec<NewPageLink>) {
+    let crawled: HashMap<&str, i32> = crawled
+        .iter()
+        .map(|(url, id)| (url.as_str(), *id))
+        .collect();
+    let mut links = Vec::new();
+    let mut pending = Vec::new();
+
+    for url in urls.iter().copied().collect::<HashSet<_>>() {
+        match crawled.get(url) {
+            Some(&to_page_id) if to_page_id != page_id => links.push(NewLink {
+                from_page_id: page_id,
+                to_page_id,
+            }),
+            Some(_) => {}
+            None if url.len() <= 2048 => pending.push(NewPageLink {
+                from_page_id: page_id,
+                to_url: url.to_string(),
+                timestamp,
+            }),
+            None => {}
+        }
+    }
+
+    (links, pending)
+}
+
+/// Cumulative average of the response times, including the new page
+const DOMAIN_AVG_RESPONSE_TIME_SQL: &str =
+    "((domain_stats.avg_response_time::int8 * domain_stats.pages_crawled \
+    + excluded.avg_response_time) / (domain_stats.pages_crawled + 1))::int4";
+
+/// Add a crawled page to the statistics of its domain.
+/// The domain keeps its RSS flag once one of its pages had a feed.
+fn get_domain_stats_upsert(
+    domain: String,
+    last_crawled: i64,
+    response_time: i32,
+    has_rss: bool,
+) -> impl RunQueryDsl<DbConn> + ExecuteDsl<DbConn> + QueryFragment<Pg> {
+    diesel::insert_into(domain_stats::table)
+        .values(NewDomainStat {
+            domain,
+            pages_crawled: 1,
+            pages_indexed: 0,
+            last_crawled: Some(last_crawled),
+            avg_response_time: response_time,
+            error_count: 0,
+            has_rss,
+        })
+        .on_conflict(domain_stats::domain)
+        .do_update()
+        .set((
+            domain_stats::pages_crawled.eq(domain_stats::pages_crawled + 1),
+            domain_stats::last_crawled.eq(excluded(domain_stats::last_crawled)),
+            domain_stats::avg_response_time.eq(sql::<Integer>(DOMAIN_AVG_RESPONSE_TIME_SQL)),
+            domain_stats::has_rss.eq(domain_stats::has_rss.or(excluded(domain_stats::has_rss))),
+        ))
+}
+
+/// Add a non-recoverable crawl error to the statistics of its domain
+fn get_domain_error_upsert(
+    domain: String,
+) -> impl RunQueryDsl<DbConn> + ExecuteDsl<DbConn> + QueryFragment<Pg> {
+    diesel::insert_into(domain_stats::table)
+        .values(NewDomainStat {
+            domain,
+            pages_crawled: 0,
+            pages_indexed: 0,
+            last_crawled: None,
+            avg_response_time: 0,
+            error_count: 1,
+            has_rss: false,
+        })
+        .on_conflict(domain_stats::domain)
+        .do_update()
+        .set(domain_stats::error_count.eq(domain_stats::error_count + 1))
+}
+
+/// Point the page of `url` and the pages redirecting to it at the redirect target
+fn get_final_url_update<'a>(
+    url: &'a str,
+    target: &'a str,
+) -> impl RunQueryDsl<DbConn> + ExecuteDsl<DbConn> + QueryFragment<Pg> + 'a {
+    diesel::update(pages::table)
+        .filter(pages::url.eq(url).or(pages::final_url.eq(url)))
+        .set(pages::final_url.eq(target))
+}
+
+/// Crawl the queued next page of a paginated content before the other pages of its domain
+fn get_next_page_priority_update(
+    url: &str,
+) -> impl RunQueryDsl<DbConn> + ExecuteDsl<DbConn> + QueryFragment<Pg> + '_ {
+    diesel::update(queue::table)
+        .filter(queue::url.eq(url))
+        .filter(queue::priority.lt(PAGINATION_QUEUE_PRIORITY))
+        .set(queue::priority.eq(queue::priority + PAGINATION_QUEUE_PRIORITY))
+}
+
+/// Wait while the crawler is paused, returns `false` if the shutdown flag is set meanwhile
+async fn wait_while_paused(paused: &AtomicBool, shutdown: &AtomicBool) -> bool {
+    while paused.load(Ordering::SeqCst) {
+        if !sleep_or_shutdown(PAUSE_POLL_INTERVAL, shutdown).await {
+            return false;
+        }
+    }
+
+    true
+}
+
 pub struct Worker {
     manager: Arc<Crawler>,
 }
@@ -43,21 +250,67 @@ impl Worker {
         Self { manager }
     }
 
+    /// Get the next task of the local queue, `None` once the crawler is shutting down
     async fn dequeue(&mut self) -> Option<Task> {
+        tokio::select! {
+            task = async { self.manager.queue_channel.1.lock().await.recv().await } => task,
+            _ = wait_for_shutdown(&self.manager.shutdown) => None,
+        }
+    }
+
+    /// Save the tasks left in the local queue to the db queue, returns their count
+    pub async fn requeue_local_queue(&self) -> usize {
         let mut rx = self.manager.queue_channel.1.lock().await;
-        rx.recv().await
+        let mut count = 0;
+
+        while let Ok(task) = rx.try_recv() {
+            self.save_to_queue(
+                task.domain,
+                task.url,
+                task.depth,
+                task.redirect_depth,
+                task.recrawl,
+            );
+            count += 1;
+        }
+
+        count
     }
 
+    /// Get the website of a domain, the subdomains of a registered domain share the same one
+    /// so the rate limits apply to all of them (e.g. `www.example.com` and `example.com`)
     fn get_website(&self, domain: String) -> RefMut<'_, String, Website> {
+        let key = get_registered_domain(&domain).unwrap_or(domain);
+
         let website = self
             .manager
             .websites
-            .entry(domain.clone())
-            .or_insert(Website::new(domain));
+            .entry(key.clone())
+            .or_insert(Website::new(key));
         website
     }
 
+    /// Whether the `www.` or non-`www.` variant of the URL is not crawled yet,
+    /// they are the same page on most websites
+    fn www_redirect_check(&self, url: &str) -> bool {
+        match get_www_alternate_url(url) {
+            Some(alternate) => !self.manager.visited.is_crawled(&alternate),
+            None => true,
+        }
+    }
+
     async fn can_crawl(&self, task: Task) -> bool {
+        // A recrawled page is already saved, its variant does not make it a duplicate
+        if !crawlability_check(
+            &task.domain,
+            &self.manager.blocklist,
+            &self.manager.allowlist,
+        ) || (!task.recrawl && !self.www_redirect_check(&task.url))
+        {
+            self.manager.visited.save_extra(&task.url);
+            return false;
+        }
+
         let should_fetch_robots = {
             let website = self.get_website(task.domain.clone());
             website.should_fetch_robots()
@@ -66,25 +319,39 @@ impl Worker {
 
         let mut website;
         if should_fetch_robots {
-            let robots = Website::fetch_robots(task.domain.clone(), &self.manager.web_client).await;
+            // The domain has no `www.` prefix, but the website can be served only with it
+            let host = Url::parse(&task.url)
+                .ok()
+                .and_then(|x| x.host_str().map(String::from))
+                .unwrap_or_else(|| task.domain.clone());
+            let robots = Website::fetch_robots(host, &self.manager.web_client).await;
+
+            if let Ok(Some(robots)) = &robots {
+                self.queue_sitemaps(get_robots_sitemaps(robots));
+            }
 
             website = self.get_website(task.domain.clone());
             if robots.is_ok() {
-                website.set_robots(robots.unwrap());
+                website.set_robots(robots.unwrap(), &self.manager.user_agent);
             }
         } else {
             website = self.get_website(task.domain.clone());
         }
 
         if !website.is_crawlable(&self.manager.user_agent, &task.url) {
+            drop(website);
+            self.manager.visited.save_extra(&task.url);
             return false;
         }
 
         // Rate limits
         if let Some(last_crawl) = &website.last_crawl {
-            let elapsed = last_crawl.elapsed().as_millis();
+            let elapsed = last_crawl.elapsed();
+            let cooldown = website
+                .crawl_delay
+                .unwrap_or(Duration::from_millis(DOMAIN_CRAWL_COOLDOWN));
 
-            if elapsed < DOMAIN_CRAWL_COOLDOWN {
+            if elapsed < cooldown {
                 // println!("cooldown: {} / {}", task.url.clone(), website.domain);
 
                 // Drop the website as soon as possible to drop the lock
@@ -94,7 +361,13 @@ impl Worker {
 
                 // This domain cannot be crawled for now, send it back in the queue
                 // TODO: currently this push the url to the back of the queue, fix that
-                self.save_to_queue(task.domain, task.url);
+                self.save_to_queue(
+                    task.domain,
+                    task.url,
+                    task.depth,
+                    task.redirect_depth,
+                    task.recrawl,
+                );
                 return false;
             }
         }
@@ -104,92 +377,141 @@ impl Worker {
     }
 
     pub async fn crawl(&mut self) {
-        while let Some(task) = self.dequeue().await {
-            if self.manager.visited.contains(&task.url) {
-                continue;
+        loop {
+            // Nothing is dequeued while paused, the local queue is kept
+            if !wait_while_paused(&self.manager.paused, &self.manager.shutdown).await {
+                break;
+            }
+            let task = match self.dequeue().await {
+                Some(task) => task,
+                None => break,
+            };
+
+            let _in_flight = self.manager.visited.get_in_flight_guard(&task.url);
+
+            if is_shutting_down(&self.manager.shutdown) {
+                // Dequeued while shutting down, crawl it on the next start
+                self.save_to_queue(
+                    task.domain,
+                    task.url,
+                    task.depth,
+                    task.redirect_depth,
+                    task.recrawl,
+                );
+                break;
             }
 
             if !self.can_crawl(task.clone()).await {
                 continue;
             }
 
-            self.manager.visited.insert(task.url.clone());
+            self.manager.visited.mark(&task.url);
 
             match self.crawl_page(&task).await {
-                Ok((page, favicon, links)) => {
-                    let mut new_links = HashSet::new();
-
-                    for l in links {
-                        if let Some((url, domain)) = normalize_url(&l) {
-                            let stringified_url = url.to_string();
-                            if self.manager.visited.contains(&stringified_url) {
-                                continue;
-                            }
-                            new_links.insert((domain, stringified_url));
+                Ok(crawled) => {
+                    let mut normalized_links = HashSet::new();
+
+                    for l in crawled.links {
+                        if let Some((url, domain)) = normalize_url(&l, true) {
+                            normalized_links.insert((domain, url.to_string()));
                         }
                     }
 
-                    self.save_page(page, favicon, new_links);
+                    let next_url = crawled
+                        .next_url
+                        .as_deref()
+                        .and_then(|x| normalize_url(x, true))
+                        .map(|(url, _)| url.to_string());
+
+                    let link_depth = get_link_depth(task.depth, task.max_depth);
+                    self.save_page(
+                        crawled.page,
+                        crawled.favicon,
+                        normalized_links,
+                        next_url,
+                        crawled.alternates,
+                        link_depth,
+                    );
                 }
                 Err(CrawlError::Reqwest(e)) => {
                     if e.is_timeout() {
-                        self.save_to_queue(task.domain, task.url);
-                        continue;
-                    } else if e.is_redirect() {
-                        continue;
-                    }
-                    if e.is_connect() {
-                        continue;
-                    }
-                    if e.is_request() {
+                        // Transient, try again later
+                        self.save_to_queue(
+                            task.domain,
+                            task.url,
+                            task.depth,
+                            task.redirect_depth,
+                            task.recrawl,
+                        );
                         continue;
-                        // TODO: errors should be logged in a file, not in the console.
-                        /*if let Some(std_error) = e.source() {
-                            let error_string = format!("{std_error}");
-                            // unknown host
-                            if error_string.contains("Os { code: 11001, ") {
-                                continue;
-                            }
-                            // connection reset
-                            if error_string.contains("Os { code: 10054, ") {
-                                continue;
-                            }
-                            // invalid certificate error
-                            if error_string.contains("Os { code: -2146762481, ") {
-                                continue;
-                            }
-                            // invalid certificate error
-                            if error_string.contains("Os { code: -2146762495, ") {
-                                continue;
-                            }
-                        }*/
                     }
-                    eprintln!("reqwest error when crawling {}: {:?}", task.url, e);
+
+                    self.manager.visited.save_extra(&task.url);
+
+                    let error_type = if e.is_connect() {
+                        "connect"
+                    } else if e.is_request() {
+                        "request"
+                    } else {
+                        eprintln!("reqwest error when crawling {}: {:?}", task.url, e);
+                        "reqwest"
+                    };
+                    self.save_crawl_error(&task, error_type, Some(e.to_string()));
+
+                    let db_conn = &mut self.manager.db_pool.get().unwrap();
+                    get_domain_error_upsert(task.domain.clone())
+                        .execute(db_conn)
+                        .unwrap();
+                }
+                Err(CrawlError::ParseError) => {
+                    self.save_crawl_error(&task, "parse", None);
+                    self.save_to_queue(
+                        task.domain,
+                        task.url,
+                        task.depth,
+                        task.redirect_depth,
+                        task.recrawl,
+                    );
                 }
-                Err(CrawlError::ParseError) | Err(CrawlError::ServerError) => {
-                    self.save_to_queue(task.domain, task.url);
+                Err(CrawlError::ServerError) => {
+                    self.save_crawl_error(&task, "server", None);
+                    self.save_to_queue(
+                        task.domain,
+                        task.url,
+                        task.depth,
+                        task.redirect_depth,
+                        task.recrawl,
+                    );
                 }
                 Err(CrawlError::Redirect(domain, url)) => {
-                    if self.manager.visited.contains(&url.to_string()) {
+                    self.save_final_url(&task.url, url.as_str());
+
+                    let redirect_depth =
+                        get_redirect_depth(task.redirect_depth, self.manager.max_redirects);
+                    let Some(redirect_depth) = redirect_depth else {
+                        self.save_crawl_error(&task, "redirects", Some(url.to_string()));
+                        self.manager.visited.save_extra(&task.url);
+                        continue;
+                    };
+
+                    if !self.manager.visited.insert(url.as_str()) {
                         continue;
                     }
-                    self.save_to_queue(domain, url.to_string());
+                    self.save_to_queue(domain, url.to_string(), task.depth, redirect_depth, false);
                 }
-                Err(CrawlError::NotCrawlable) => {
-                    // Ignore
+                Err(CrawlError::NotCrawlable) | Err(CrawlError::NoIndex) => {
+                    self.manager.visited.save_extra(&task.url);
                 }
                 Err(e) => {
                     eprintln!("Error when crawling {}: {:?}", task.url, e);
+                    self.manager.visited.save_extra(&task.url);
                 }
             }
         }
     }
 
-    /// Crawl a page and returns the links present on the page
-    async fn crawl_page(
-        &self,
-        task: &Task,
-    ) -> Result<(NewPage, NewFavicon, HashSet<String>), CrawlError> {
+    /// Crawl a page and returns its data, with the links present on the page
+    async fn crawl_page(&self, task: &Task) -> Result<CrawledPage, CrawlError> {
         // println!("Crawling {}", &task.url);
 
         let start_at = Instant::now();
@@ -202,11 +524,21 @@ impl Worker {
             return Err(CrawlError::ServerError);
         }
 
+        if status_code.is_redirection() {
+            // Queued like the other redirects, the chain length is counted by `redirect_depth`
+            let target = get_redirect_location(response.headers(), response.url())
+                .and_then(|x| normalize_url(x.as_str(), true));
+            return match target {
+                Some((url, domain)) => Err(CrawlError::Redirect(domain, url)),
+                None => Err(CrawlError::NotCrawlable),
+            };
+        }
+
         if !status_code.is_success() {
             return Err(CrawlError::NotCrawlable);
         }
 
-        if let Some((new_url, domain)) = normalize_url(&response.url().to_string()) {
+        if let Some((new_url, domain)) = normalize_url(&response.url().to_string(), true) {
             if new_url.to_string() != task.url.to_string() {
                 // The URL changed, so the task infos are invalid
                 return Err(CrawlError::Redirect(domain, new_url));
@@ -215,8 +547,8 @@ impl Worker {
             return Err(CrawlError::NotCrawlable);
         }
 
-        let headers = response.headers();
-        let content_type = get_content_type(headers, &task.url);
+        let headers = response.headers().clone();
+        let content_type = get_content_type(&headers, &task.url);
 
         if let Some(content_type) = content_type {
             if content_type != "text/html" {
@@ -224,10 +556,51 @@ impl Worker {
             }
         }
 
-        let text_result = response.text().await?;
+        let max_body_bytes = self.manager.max_body_bytes;
+        if is_content_length_over(&headers, max_body_bytes) {
+            return Err(CrawlError::NotCrawlable);
+        }
 
-        match scrape_page(task.domain.clone(), task.url.clone(), text_result) {
+        let (text_result, truncated) = read_body_limited(response, max_body_bytes).await?;
+        let body_hash = sha256_hex(&text_result);
+
+        // The scraper error is not `Send`, it cannot be kept while the manifest is fetched
+        let scraped = scrape_page(task.domain.clone(), task.url.clone(), text_result, &headers)
+            .map_err(|e| e.to_string());
+
+        match scraped {
             Ok(mut scraped) => {
+                scraped.truncated = truncated;
+                if scraped.truncated {
+                    eprintln!(
+                        "[Crawler] Body of {} truncated to {max_body_bytes} bytes",
+                        task.url
+                    );
+                }
+
+                if let Some(target) = &scraped.meta_refresh_url {
+                    if let Some((target_url, domain)) = normalize_url(target, true) {
+                        if target_url.to_string() != task.url {
+                            // HTML redirect, the target is crawled instead of this page
+                            return Err(CrawlError::Redirect(domain, target_url));
+                        }
+                    }
+                }
+
+                if let Some(canonical) = &scraped.canonical_url {
+                    if let Some((canonical_url, domain)) = normalize_url(canonical, true) {
+                        if canonical_url.to_string() != task.url {
+                            // Duplicate of the canonical page, crawl it instead
+                            return Err(CrawlError::Redirect(domain, canonical_url));
+                        }
+                    }
+                }
+
+                if scraped.noindex {
+                    // Neither saved nor queued again, its links are not followed either
+                    return Err(CrawlError::NoIndex);
+                }
+
                 let seo_score = calculate_seo_score(&scraped);
 
                 let page = NewPage {
@@ -261,28 +634,147 @@ impl Worker {
                     meta_og_image: scraped
                         .meta_og_image
                         .map(|x| safe_slice(&x, 512).to_string()),
+                    body_hash: Some(body_hash),
+                    content_changed_at: None,
+                    has_rss: scraped.rss_url.is_some(),
+                    language: scraped.language,
+                    canonical_url: scraped.canonical_url.take_if(|x| x.len() <= 2048),
+                    meta_author: scraped.meta_author.map(|x| safe_slice(&x, 100).to_string()),
+                    published_at: scraped.published_at,
+                    meta_twitter_card: scraped
+                        .meta_twitter_card
+                        .map(|x| safe_slice(&x, 30).to_string()),
+                    meta_og_type: scraped.meta_og_type.map(|x| safe_slice(&x, 50).to_string()),
+                    meta_og_site_name: scraped
+                        .meta_og_site_name
+                        .map(|x| safe_slice(&x, 100).to_string()),
+                    meta_og_locale: scraped
+                        .meta_og_locale
+                        .map(|x| safe_slice(&x, 20).to_string()),
+                    image_alts: (!scraped.image_alts.is_empty())
+                        .then(|| safe_slice(&scraped.image_alts.join("\n"), 1 << 12).to_string()),
+                    // Counted by the indexer, kept on the recrawls until then
+                    word_count: 0,
+                    url_fingerprint: Some(url_fingerprint(&task.url)),
+                    registered_domain: get_registered_domain(&task.domain),
                 };
 
+                // The manifest icons are often larger than the <link> ones
+                let mut icons: Vec<Icon> = scraped.favicon.take().into_iter().collect();
+                if let Some(manifest_url) = &scraped.manifest_url {
+                    if let Some(icon) = self.get_manifest_icon(&task.domain, manifest_url).await {
+                        icons.push(icon);
+                    }
+                }
+
                 let favicon = NewFavicon {
-                    url: scraped
-                        .favicon_url
-                        .take_if(|x| x.len() <= 2048)
+                    url: pick_largest_icon(icons)
+                        .map(|icon| icon.url)
+                        .filter(|x| x.len() <= 2048)
                         .unwrap_or(format!("https://{}/favicon.ico", task.domain)),
                 };
 
-                Ok((page, favicon, scraped.links))
+                // A nofollow page is saved, but none of its links is queued
+                let (links, next_url) = if scraped.nofollow {
+                    (HashSet::new(), None)
+                } else {
+                    let mut links = scraped.links;
+                    links.extend(scraped.prev_url);
+                    links.extend(scraped.next_url.clone());
+                    // The language variants are crawled like the other links
+                    links.extend(scraped.alternates.values().cloned());
+                    (links, scraped.next_url)
+                };
+
+                Ok(CrawledPage {
+                    page,
+                    favicon,
+                    links,
+                    next_url,
+                    alternates: scraped.alternates,
+                })
             }
             Err(e) => {
-                eprintln!("Failed to scrape page: {e:?}");
+                eprintln!("Failed to scrape page: {e}");
                 Err(CrawlError::ParseError)
             }
         }
     }
 
+    /// Get the largest icon of the Web App Manifest of a website, fetched once per manifest URL
+    async fn get_manifest_icon(&self, domain: &str, manifest_url: &str) -> Option<Icon> {
+        if let Some(icon) = self
+            .get_website(domain.to_string())
+            .get_manifest_icon(manifest_url)
+        {
+            return icon;
+        }
+
+        let icon = self.fetch_manifest_icon(manifest_url).await;
+        self.get_website(domain.to_string()).manifest_icon =
+            Some((manifest_url.to_string(), icon.clone()));
+        icon
+    }
+
+    /// Get the largest icon of a Web App Manifest, errors are ignored
+    async fn fetch_manifest_icon(&self, manifest_url: &str) -> Option<Icon> {
+        let response = self
+            .manager
+            .web_client
+            .get(manifest_url)
+            .send()
+            .await
+            .ok()?;
+
+        if !response.status().is_success() {
+            return None;
+        }
+
+        let (manifest, truncated) = read_body_limited(response, MAX_MANIFEST_BYTES).await.ok()?;
+        if truncated {
+            return None;
+        }
+        parse_manifest_icon(&manifest, manifest_url)
+    }
+
     /// Save the collected page data
-    fn save_page(&self, mut page: NewPage, favicon: NewFavicon, links: HashSet<(String, String)>) {
+    fn save_page(
+        &self,
+        mut page: NewPage,
+        favicon: NewFavicon,
+        links: HashSet<(String, String)>,
+        next_url: Option<String>,
+        alternates: HashMap<String, String>,
+        link_depth: Option<i32>,
+    ) {
         let db_conn = &mut self.manager.db_pool.get().unwrap();
 
+        // Skip the pages with the same body as another one (mirrors, tracking URLs...)
+        if let Some(body_hash) = &page.body_hash {
+            let duplicate = get_duplicate_page_query(body_hash, &page.url)
+                .first::<String>(db_conn)
+                .optional()
+                .unwrap();
+
+            if let Some(duplicate) = duplicate {
+                println!("Skipping {}: duplicate of {duplicate}", page.url);
+                return;
+            }
+        }
+
+        // Skip the near-duplicate URLs of a saved page (trailing slash, encoding, port...)
+        if let Some(fingerprint) = &page.url_fingerprint {
+            let duplicate = get_fingerprint_duplicate_query(fingerprint, &page.url)
+                .first::<String>(db_conn)
+                .optional()
+                .unwrap();
+
+            if let Some(duplicate) = duplicate {
+                println!("Skipping {}: same URL as {duplicate}", page.url);
+                return;
+            }
+        }
+
         let favicon_url = favicon.url.clone();
 
         // Insert the new favicon
@@ -297,43 +789,288 @@ impl Worker {
 
         page.favicon_id = favicon_id;
 
-        // Insert the page
-        diesel::insert_into(pages::table)
+        // Only move content_changed_at when the body changed since the last crawl
+        let stored = pages::table
+            .filter(pages::url.eq(&page.url))
+            .select((pages::body_hash, pages::content_changed_at))
+            .first::<(Option<String>, Option<i64>)>(db_conn)
+            .optional()
+            .unwrap();
+        let is_new_page = stored.is_none();
+
+        page.content_changed_at = Some(get_content_changed_at(
+            stored,
+            page.body_hash.as_deref().unwrap_or(""),
+            page.last_crawled,
+        ));
+
+        let domain = page.domain.clone();
+        let page_url = page.url.clone();
+        let has_rss = page.has_rss;
+        let (last_crawled, response_time) = (page.last_crawled, page.response_time);
+
+        // Insert the page, or update it if it is re-crawled
+        let page_id = diesel::insert_into(pages::table)
             .values(page)
+            .on_conflict(pages::url)
+            .do_update()
+            .set((
+                pages::title.eq(excluded(pages::title)),
+                pages::favicon_id.eq(excluded(pages::favicon_id)),
+                pages::content.eq(excluded(pages::content)),
+                pages::body.eq(excluded(pages::body)),
+                pages::body_length.eq(excluded(pages::body_length)),
+                pages::content_type.eq(excluded(pages::content_type)),
+                pages::response_time.eq(excluded(pages::response_time)),
+                pages::status_code.eq(excluded(pages::status_code)),
+                pages::last_crawled.eq(excluded(pages::last_crawled)),
+                pages::seo_score.eq(excluded(pages::seo_score)),
+                pages::meta_description.eq(excluded(pages::meta_description)),
+                pages::meta_keywords.eq(excluded(pages::meta_keywords)),
+                pages::meta_theme_color.eq(excluded(pages::meta_theme_color)),
+                pages::meta_og_image.eq(excluded(pages::meta_og_image)),
+                pages::body_hash.eq(excluded(pages::body_hash)),
+                pages::content_changed_at.eq(excluded(pages::content_changed_at)),
+                pages::has_rss.eq(excluded(pages::has_rss)),
+                pages::language.eq(excluded(pages::language)),
+                pages::canonical_url.eq(excluded(pages::canonical_url)),
+                pages::meta_author.eq(excluded(pages::meta_author)),
+                pages::published_at.eq(excluded(pages::published_at)),
+                pages::meta_twitter_card.eq(excluded(pages::meta_twitter_card)),
+                pages::meta_og_type.eq(excluded(pages::meta_og_type)),
+                pages::meta_og_site_name.eq(excluded(pages::meta_og_site_name)),
+                pages::meta_og_locale.eq(excluded(pages::meta_og_locale)),
+                pages::image_alts.eq(excluded(pages::image_alts)),
+                pages::url_fingerprint.eq(excluded(pages::url_fingerprint)),
+                pages::registered_domain.eq(excluded(pages::registered_domain)),
+                // It does not redirect anymore
+                pages::final_url.eq(None::<String>),
+            ))
+            .returning(pages::id)
+            .get_result::<i32>(db_conn);
+
+        let page_id = match page_id {
+            // Another worker saved a near-duplicate URL since the check
+            Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                println!("Skipping {page_url}: same URL fingerprint as another page");
+                return;
+            }
+            result => result.unwrap(),
+        };
+
+        self.save_links(db_conn, page_id, &links);
+        if is_new_page {
+            sql_query(RESOLVE_PAGE_LINKS_SQL)
+                .bind::<Integer, _>(page_id)
+                .bind::<Text, _>(&page_url)
+                .execute(db_conn)
+                .unwrap();
+        }
+        self.save_alternates(db_conn, page_id, alternates);
+
+        get_domain_stats_upsert(domain.clone(), last_crawled, response_time, has_rss)
             .execute(db_conn)
             .unwrap();
 
-        let elements = links
-            .iter()
-            .filter(|x| x.1.len() <= 2048)
-            .map(|x| NewQueuedPage {
-                url: x.1.clone(),
-                domain: x.0.clone(),
-                timestamp: get_sql_timestamp(),
-            })
+        if is_new_page {
+            *self
+                .manager
+                .domain_page_count
+                .entry(domain.clone())
+                .or_insert(0) += 1;
+        }
+
+        // The links are still saved above when they are too deep to be crawled
+        if let Some(link_depth) = link_depth {
+            let elements = links
+                .iter()
+                .filter(|x| self.manager.can_queue(&x.0, &x.1))
+                // Marked as visited once queued, so they are queued only once
+                .filter(|x| self.manager.visited.insert(&x.1))
+                .map(|x| NewQueuedPage {
+                    url: x.1.clone(),
+                    domain: x.0.clone(),
+                    registered_domain: get_registered_domain(&x.0),
+                    timestamp: get_sql_timestamp(),
+                    depth: link_depth,
+                    redirect_depth: 0,
+                    recrawl: false,
+                })
+                .collect::<Vec<_>>();
+
+            // Insert the new urls to the queue
+            diesel::insert_into(queue::table)
+                .values(elements)
+                .on_conflict(queue::url)
+                .do_nothing()
+                .execute(db_conn)
+                .unwrap();
+
+            if let Some(next_url) = &next_url {
+                get_next_page_priority_update(next_url)
+                    .execute(db_conn)
+                    .unwrap();
+            }
+        }
+
+        if has_rss {
+            // Websites with a RSS feed tend to have regularly updated content, crawl them first
+            diesel::update(queue::table)
+                .filter(queue::domain.eq(&domain))
+                .filter(queue::priority.lt(RSS_QUEUE_PRIORITY))
+                .set(queue::priority.eq(queue::priority + RSS_QUEUE_PRIORITY))
+                .execute(db_conn)
+                .unwrap();
+        }
+    }
+
+    /// Replace the links of a page, the ones to pages which are not crawled yet are pending
+    fn save_links(&self, db_conn: &mut DbConn, page_id: i32, links: &HashSet<(String, String)>) {
+        diesel::delete(links::table)
+            .filter(links::from_page_id.eq(page_id))
+            .execute(db_conn)
+            .unwrap();
+        diesel::delete(page_links::table)
+            .filter(page_links::from_page_id.eq(page_id))
+            .execute(db_conn)
+            .unwrap();
+
+        let urls = links.iter().map(|x| x.1.as_str()).collect::<Vec<_>>();
+
+        let crawled = pages::table
+            .filter(pages::url.eq_any(&urls))
+            .select((pages::url, pages::id))
+            .load::<(String, i32)>(db_conn)
+            .unwrap();
+        let (elements, pending) = split_links(page_id, &urls, &crawled, get_sql_timestamp());
+
+        diesel::insert_into(links::table)
+            .values(elements)
+            .execute(db_conn)
+            .unwrap();
+        diesel::insert_into(page_links::table)
+            .values(pending)
+            .on_conflict_do_nothing()
+            .execute(db_conn)
+            .unwrap();
+    }
+
+    /// Replace the language variants of a page
+    fn save_alternates(
+        &self,
+        db_conn: &mut DbConn,
+        page_id: i32,
+        alternates: HashMap<String, String>,
+    ) {
+        diesel::delete(page_alternates::table)
+            .filter(page_alternates::page_id.eq(page_id))
+            .execute(db_conn)
+            .unwrap();
+
+        let elements = alternates
+            .into_iter()
+            .filter(|(_, url)| url.len() <= 2048)
+            .map(|(lang, url)| NewPageAlternate { page_id, lang, url })
             .collect::<Vec<_>>();
 
-        // Insert the new urls to the queue
-        diesel::insert_into(queue::table)
+        diesel::insert_into(page_alternates::table)
             .values(elements)
-            .on_conflict(queue::url)
-            .do_nothing()
             .execute(db_conn)
             .unwrap();
     }
 
-    /// Put back a URL in the database queue
-    fn save_to_queue(&self, domain: String, url: String) {
-        // Remove it from the visited so it can be crawled again
-        self.manager.visited.remove(&url);
+    /// Fetch the sitemaps in the background and add their URLs to the queue
+    fn queue_sitemaps(&self, sitemaps: Vec<String>) {
+        if sitemaps.is_empty() {
+            return;
+        }
+
+        let manager = self.manager.clone();
+
+        tokio::spawn(async move {
+            for sitemap in sitemaps {
+                let urls = match fetch_sitemap(&sitemap, &manager.web_client).await {
+                    Ok(urls) => filter_sitemap_urls(urls),
+                    Err(e) => {
+                        eprintln!("Error when fetching the sitemap {sitemap}: {e:?}");
+                        continue;
+                    }
+                };
+
+                let elements = urls
+                    .into_iter()
+                    .filter(|(url, domain)| manager.can_queue(domain, url))
+                    .filter(|(url, _)| manager.visited.insert(url))
+                    .map(|(url, domain)| NewQueuedPage {
+                        url,
+                        registered_domain: get_registered_domain(&domain),
+                        domain,
+                        timestamp: get_sql_timestamp(),
+                        // Listed by the website itself, like a seed URL
+                        depth: 0,
+                        redirect_depth: 0,
+                        recrawl: false,
+                    })
+                    .collect::<Vec<_>>();
+
+                let db_conn = &mut manager.db_pool.get().unwrap();
+
+                // 6 parameters per row, stay under the 65535 parameters limit
+                for chunk in elements.chunks(10_000) {
+                    diesel::insert_into(queue::table)
+                        .values(chunk)
+                        .on_conflict(queue::url)
+                        .do_nothing()
+                        .execute(db_conn)
+                        .unwrap();
+                }
+            }
+        });
+    }
+
+    /// Save the redirect target of a crawled URL, and of the URLs redirecting to it
+    fn save_final_url(&self, url: &str, target: &str) {
+        let db_conn = &mut self.manager.db_pool.get().unwrap();
+
+        get_final_url_update(url, target).execute(db_conn).unwrap();
+    }
+
+    /// Save a crawl error so it can be listed by the API
+    fn save_crawl_error(&self, task: &Task, error_type: &str, error_detail: Option<String>) {
+        let db_conn = &mut self.manager.db_pool.get().unwrap();
+
+        diesel::insert_into(crawl_errors::table)
+            .values(NewCrawlError {
+                url: task.url.clone(),
+                domain: task.domain.clone(),
+                error_type: error_type.to_string(),
+                error_detail,
+                timestamp: get_sql_timestamp(),
+            })
+            .execute(db_conn)
+            .unwrap();
+    }
 
+    /// Put back a URL in the database queue, a recrawl stays one
+    fn save_to_queue(
+        &self,
+        domain: String,
+        url: String,
+        depth: i32,
+        redirect_depth: u8,
+        recrawl: bool,
+    ) {
         let db_conn = &mut self.manager.db_pool.get().unwrap();
 
         diesel::insert_into(queue::table)
             .values(NewQueuedPage {
+                registered_domain: get_registered_domain(&domain),
                 domain,
                 url,
                 timestamp: get_sql_timestamp(),
+                depth,
+                redirect_depth: redirect_depth.into(),
+                recrawl,
             })
             .on_conflict(queue::url)
             .do_nothing()
@@ -341,3 +1078,268 @@ impl Worker {
             .unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::debug_query;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve the `/0` -> `/1` -> ... -> `/{hops}` redirect chain, the last page is a 200
+    async fn serve_redirect_chain(hops: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buffer = [0; 1024];
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    let request = String::from_utf8_lossy(&buffer[..read]);
+                    let step: usize = request
+                        .split_whitespace()
+                        .nth(1)
+                        .and_then(|path| path[1..].parse().ok())
+                        .unwrap_or(0);
+
+                    let response = if step < hops {
+                        format!(
+                            "HTTP/1.1 301 Moved Permanently\r\nLocation: /{}\r\n\
+                            Content-Length: 0\r\nConnection: close\r\n\r\n",
+                            step + 1
+                        )
+                    } else {
+                        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok"
+                            .to_string()
+                    };
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        address
+    }
+
+    /// Follow a redirect chain like the worker does through the queue.
+    /// Returns the crawled URL, or the dropped target if the chain is too long.
+    async fn follow_redirects(
+        client: &reqwest::Client,
+        url: &str,
+        max_redirects: u8,
+    ) -> Result<Url, Url> {
+        let mut url = Url::parse(url).unwrap();
+        let mut redirect_depth = 0;
+
+        loop {
+            let response = client.get(url.clone()).send().await.unwrap();
+            if !response.status().is_redirection() {
+                return Ok(url);
+            }
+
+            let target = get_redirect_location(response.headers(), response.url()).unwrap();
+            match get_redirect_depth(redirect_depth, max_redirects) {
+                Some(target_depth) => redirect_depth = target_depth,
+                None => return Err(target),
+            }
+            url = target;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_chain() {
+        let address = serve_redirect_chain(3).await;
+        let client = Crawler::build_client("Epsilon", None).unwrap();
+
+        // The client returns the redirects instead of following them
+        let response = client.get(format!("{address}/0")).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 301);
+        assert_eq!(
+            get_redirect_location(response.headers(), response.url()),
+            Some(Url::parse(&format!("{address}/1")).unwrap())
+        );
+
+        assert_eq!(
+            follow_redirects(&client, &format!("{address}/0"), 3).await,
+            Ok(Url::parse(&format!("{address}/3")).unwrap())
+        );
+        assert_eq!(
+            follow_redirects(&client, &format!("{address}/0"), 2).await,
+            Err(Url::parse(&format!("{address}/3")).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_while_paused() {
+        let paused = Arc::new(AtomicBool::new(false));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        // Not paused, the worker goes on at once
+        assert_eq!(wait_while_paused(&paused, &shutdown).await, true);
+
+        for _ in 0..2 {
+            paused.store(true, Ordering::SeqCst);
+            let waiting = tokio::spawn({
+                let (paused, shutdown) = (paused.clone(), shutdown.clone());
+                async move { wait_while_paused(&paused, &shutdown).await }
+            });
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(waiting.is_finished(), false);
+
+            paused.store(false, Ordering::SeqCst);
+            assert_eq!(waiting.await.unwrap(), true);
+        }
+
+        // Paused until the shutdown
+        paused.store(true, Ordering::SeqCst);
+        shutdown.store(true, Ordering::SeqCst);
+        assert_eq!(wait_while_paused(&paused, &shutdown).await, false);
+    }
+
+    #[test]
+    fn test_fingerprint_duplicate_query() {
+        let fingerprint = url_fingerprint("https://example.com/about/");
+        let query = get_fingerprint_duplicate_query(&fingerprint, "https://example.com/about/");
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert_eq!(
+            sql.contains(r#"WHERE (("pages"."url_fingerprint" = $1) AND ("pages"."url" != $2))"#),
+            true
+        );
+        assert_eq!(
+            sql.contains(&url_fingerprint("https://example.com/about")),
+            true
+        );
+    }
+
+    #[test]
+    fn test_duplicate_page_query() {
+        let body_hash = sha256_hex("<html>Hello</html>");
+        let query = get_duplicate_page_query(&body_hash, "https://example.com/");
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert_eq!(
+            sql.contains(r#"WHERE (("pages"."body_hash" = $1) AND ("pages"."url" != $2))"#),
+            true
+        );
+        assert_eq!(sql.contains(&body_hash), true);
+
+        // The same body always gives the same hash
+        assert_eq!(body_hash, sha256_hex("<html>Hello</html>"));
+    }
+
+    #[test]
+    fn test_split_links() {
+        let urls = [
+            "https://example.com/",
+            "https://example.com/a",
+            "https://example.com/b",
+            "https://example.com/b",
+        ];
+        let crawled = [
+            ("https://example.com/".to_string(), 1),
+            ("https://example.com/a".to_string(), 2),
+        ];
+
+        let (links, pending) = split_links(1, &urls, &crawled, 1_700_000_000_000);
+
+        // No link to itself, the pending link is saved once
+        assert_eq!(
+            links,
+            vec![NewLink {
+                from_page_id: 1,
+                to_page_id: 2
+            }]
+        );
+        assert_eq!(
+            pending,
+            vec![NewPageLink {
+                from_page_id: 1,
+                to_url: "https://example.com/b".to_string(),
+                timestamp: 1_700_000_000_000,
+            }]
+        );
+
+        let long_url = format!("https://example.com/{}", "a".repeat(2048));
+        let (links, pending) = split_links(1, &[long_url.as_str()], &[], 1_700_000_000_000);
+        assert_eq!((links.len(), pending.len()), (0, 0));
+    }
+
+    #[test]
+    fn test_resolve_page_links_sql() {
+        let query = sql_query(RESOLVE_PAGE_LINKS_SQL)
+            .bind::<Integer, _>(7)
+            .bind::<Text, _>("https://example.com/b");
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert_eq!(
+            sql.contains("DELETE FROM page_links WHERE to_url = $2"),
+            true
+        );
+        assert_eq!(sql.contains("SELECT DISTINCT from_page_id, $1"), true);
+        assert_eq!(sql.contains(r#"binds: [7, "https://example.com/b"]"#), true);
+    }
+
+    #[test]
+    fn test_next_page_priority_update() {
+        let query = get_next_page_priority_update("https://example.com/blog/page/2");
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert_eq!(
+            sql.contains(r#"WHERE (("queue"."url" = $2) AND ("queue"."priority" < $3))"#),
+            true
+        );
+        assert_eq!(
+            sql.contains(r#"binds: [5, "https://example.com/blog/page/2", 5]"#),
+            true
+        );
+    }
+
+    #[test]
+    fn test_final_url_update() {
+        let query = get_final_url_update("https://example.com/a", "https://example.com/c");
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        // The start of a chain follows it to the end
+        assert_eq!(
+            sql.contains(r#"WHERE (("pages"."url" = $2) OR ("pages"."final_url" = $3))"#),
+            true
+        );
+        assert_eq!(
+            sql.contains(
+                r#"binds: ["https://example.com/c", "https://example.com/a", "https://example.com/a"]"#
+            ),
+            true
+        );
+    }
+
+    #[test]
+    fn test_domain_stats_upserts() {
+        let query = get_domain_stats_upsert("example.com".to_string(), 1_000, 250, true);
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert_eq!(sql.contains(r#"ON CONFLICT ("domain") DO UPDATE"#), true);
+        assert_eq!(
+            sql.contains(r#""pages_crawled" = ("domain_stats"."pages_crawled" + $"#),
+            true
+        );
+        assert_eq!(sql.contains(DOMAIN_AVG_RESPONSE_TIME_SQL), true);
+        assert_eq!(
+            sql.contains(r#""has_rss" = ("domain_stats"."has_rss" OR excluded."has_rss")"#),
+            true
+        );
+
+        let query = get_domain_error_upsert("example.com".to_string());
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert_eq!(
+            sql.contains(r#""error_count" = ("domain_stats"."error_count" + $"#),
+            true
+        );
+        // The crawl stats of the domain are left untouched
+        assert_eq!(sql.contains(r#""pages_crawled" = "#), false);
+        assert_eq!(sql.contains(r#""has_rss" = "#), false);
+    }
+}
@@ -1,7 +1,12 @@
-use diesel::pg::PgConnection;
+use diesel::alias;
+use diesel::dsl::{exists, not};
+use diesel::pg::{Pg, PgConnection};
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::sql_query;
+use diesel::sql_types::{Bool, Nullable};
+use schema::pages;
+use std::time::Duration;
 
 pub mod models;
 pub mod schema;
@@ -10,16 +15,89 @@ pub mod types;
 pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 pub type DbConn = PooledConnection<ConnectionManager<PgConnection>>;
 
+/// Default maximum number of connections of a pool
 pub const MAX_POOL_SIZE: u32 = 40;
 
-pub fn create_pool(db_url: &str) -> DbPool {
+/// Upper limit of the configurable pool size
+pub const POOL_SIZE_LIMIT: u32 = 1000;
+
+/// Default time to wait for a connection of the pool
+pub const DEFAULT_POOL_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upper limit of the configurable connection timeout, in seconds
+pub const POOL_CONNECTION_TIMEOUT_LIMIT_SECS: u64 = 3600;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolOptions {
+    pub max_size: u32,
+    /// Idle connections kept open, `None` keeps `max_size` connections
+    pub min_idle: Option<u32>,
+    pub connection_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_size: MAX_POOL_SIZE,
+            min_idle: None,
+            connection_timeout: DEFAULT_POOL_CONNECTION_TIMEOUT,
+        }
+    }
+}
+
+pub fn create_pool(db_url: &str, options: &PoolOptions) -> DbPool {
     let manager = ConnectionManager::<PgConnection>::new(db_url);
     Pool::builder()
-        .max_size(MAX_POOL_SIZE)
+        .max_size(options.max_size)
+        .min_idle(options.min_idle)
+        .connection_timeout(options.connection_timeout)
         .build(manager)
         .expect("Failed to create DB pool")
 }
 
+/// Connections of a pool which can be used without waiting, the idle ones and the ones not opened yet
+pub fn count_available_connections(max_size: u32, connections: u32, idle_connections: u32) -> u32 {
+    max_size.saturating_sub(connections) + idle_connections
+}
+
+/// Connections of the pool which can be used without waiting
+pub fn get_available_connections(pool: &DbPool) -> u32 {
+    let state = pool.state();
+    count_available_connections(pool.max_size(), state.connections, state.idle_connections)
+}
+
+/// A filter of the pages, nullable as it compares nullable columns
+pub type PagesFilter = Box<dyn BoxableExpression<pages::table, Pg, SqlType = Nullable<Bool>>>;
+
+/// Pages the indexer has to index: crawled since their last indexing and not deleted.
+/// Pages with the same body hash as an already indexed page are skipped, they are never indexed.
+pub fn get_pages_to_index_filter() -> PagesFilter {
+    let indexed_pages = alias!(pages as indexed_pages);
+
+    Box::new(
+        pages::last_indexed
+            .is_null()
+            .or(pages::last_crawled.nullable().gt(pages::last_indexed))
+            .and(pages::deleted_at.is_null())
+            .and(not(exists(
+                indexed_pages
+                    .filter(indexed_pages.field(pages::body_hash).eq(pages::body_hash))
+                    .filter(indexed_pages.field(pages::id).ne(pages::id))
+                    .filter(indexed_pages.field(pages::last_indexed).is_not_null()),
+            ))),
+    )
+}
+
+/// Indexed pages which are not deleted
+pub fn get_indexed_pages_filter() -> PagesFilter {
+    Box::new(
+        pages::last_indexed
+            .is_not_null()
+            .and(pages::deleted_at.is_null())
+            .nullable(),
+    )
+}
+
 #[derive(QueryableByName)]
 pub struct TableSize {
     #[diesel(sql_type = diesel::sql_types::Text)]
@@ -11,6 +11,15 @@ pub struct QueuedPage {
     pub domain: String,
     pub url: String,
     pub timestamp: i64,
+    pub priority: i32,
+    /// Number of links followed from a seed URL to reach this one
+    pub depth: i32,
+    /// Queued again by the monitor because the page is stale
+    pub recrawl: bool,
+    /// Number of redirects followed to reach this URL
+    pub redirect_depth: i16,
+    /// Domain bought from a registrar, e.g. `example.co.uk` for `blog.example.co.uk`
+    pub registered_domain: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -19,6 +28,62 @@ pub struct NewQueuedPage {
     pub domain: String,
     pub url: String,
     pub timestamp: i64,
+    pub depth: i32,
+    pub redirect_depth: i16,
+    pub registered_domain: Option<String>,
+    pub recrawl: bool,
+}
+
+// Crawl errors //
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::crawl_errors)]
+pub struct NewCrawlError {
+    pub url: String,
+    pub domain: String,
+    pub error_type: String,
+    pub error_detail: Option<String>,
+    pub timestamp: i64,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::crawl_errors)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CrawlError {
+    pub id: i32,
+    pub url: String,
+    pub domain: String,
+    pub error_type: String,
+    pub error_detail: Option<String>,
+    pub timestamp: i64,
+}
+
+// Domain stats //
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::domain_stats)]
+pub struct NewDomainStat {
+    pub domain: String,
+    pub pages_crawled: i64,
+    pub pages_indexed: i64,
+    pub last_crawled: Option<i64>,
+    pub avg_response_time: i32,
+    pub error_count: i32,
+    pub has_rss: bool,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::domain_stats)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DomainStat {
+    pub domain: String,
+    pub pages_crawled: i64,
+    pub pages_indexed: i64,
+    pub last_crawled: Option<i64>,
+    pub avg_response_time: i32,
+    pub error_count: i32,
+    /// A crawled page of the domain has a RSS feed, used by the search ranking
+    pub has_rss: bool,
 }
 
 // Links //
@@ -32,6 +97,43 @@ pub struct Link {
     pub to_page_id: i32,
 }
 
+#[derive(Insertable, Debug, PartialEq)]
+#[diesel(table_name = crate::schema::links)]
+pub struct NewLink {
+    pub from_page_id: i32,
+    pub to_page_id: i32,
+}
+
+/// A link to a page which is not crawled yet, turned into a `Link` once it is
+#[derive(Insertable, Debug, PartialEq)]
+#[diesel(table_name = crate::schema::page_links)]
+pub struct NewPageLink {
+    pub from_page_id: i32,
+    pub to_url: String,
+    /// The pending links to URLs which are not queued are purged by the monitor after a while
+    pub timestamp: i64,
+}
+
+// Page Alternates //
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::page_alternates)]
+pub struct NewPageAlternate {
+    pub page_id: i32,
+    /// `hreflang` of the variant, e.g. `fr` or `x-default`
+    pub lang: String,
+    pub url: String,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::page_alternates)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PageAlternate {
+    pub page_id: i32,
+    pub lang: String,
+    pub url: String,
+}
+
 // Favicons //
 
 #[derive(Insertable)]
@@ -50,7 +152,7 @@ pub struct Favicon {
 
 // Pages //
 
-#[derive(Queryable, Selectable)]
+#[derive(Queryable, Selectable, Default)]
 #[diesel(table_name = crate::schema::pages)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Page {
@@ -72,6 +174,32 @@ pub struct Page {
     pub meta_keywords: Option<String>,
     pub meta_theme_color: Option<String>,
     pub meta_og_image: Option<String>,
+    pub body_hash: Option<String>,
+    pub content_changed_at: Option<i64>,
+    pub has_rss: bool,
+    pub page_rank: f64,
+    pub language: Option<String>,
+    pub canonical_url: Option<String>,
+    pub previous_hash: Option<String>,
+    /// Where the URL redirects to, when it redirected at its last crawl
+    pub final_url: Option<String>,
+    pub meta_author: Option<String>,
+    /// Publication timestamp of the structured data
+    pub published_at: Option<i64>,
+    pub meta_twitter_card: Option<String>,
+    pub meta_og_type: Option<String>,
+    pub meta_og_site_name: Option<String>,
+    pub meta_og_locale: Option<String>,
+    /// Alternative texts of the images, one per line
+    pub image_alts: Option<String>,
+    /// When the page was deleted, it is purged by the monitor later
+    pub deleted_at: Option<i64>,
+    /// Total number of words, counted by the indexer
+    pub word_count: i32,
+    /// MD5 of the canonical URL, unique so the near-duplicate URLs are saved once
+    pub url_fingerprint: Option<String>,
+    /// Domain bought from a registrar, e.g. `example.co.uk` for `blog.example.co.uk`
+    pub registered_domain: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -94,6 +222,21 @@ pub struct NewPage {
     pub meta_keywords: Option<String>,
     pub meta_theme_color: Option<String>,
     pub meta_og_image: Option<String>,
+    pub body_hash: Option<String>,
+    pub content_changed_at: Option<i64>,
+    pub has_rss: bool,
+    pub language: Option<String>,
+    pub canonical_url: Option<String>,
+    pub meta_author: Option<String>,
+    pub published_at: Option<i64>,
+    pub meta_twitter_card: Option<String>,
+    pub meta_og_type: Option<String>,
+    pub meta_og_site_name: Option<String>,
+    pub meta_og_locale: Option<String>,
+    pub image_alts: Option<String>,
+    pub word_count: i32,
+    pub url_fingerprint: Option<String>,
+    pub registered_domain: Option<String>,
 }
 
 // Pages Analytics //
@@ -116,6 +259,31 @@ pub struct PageAnalytics {
     pub impressions: i32,
 }
 
+// Pages Analytics History //
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::pages_analytics_history)]
+pub struct NewPageAnalyticsHistory {
+    pub page_id: i32,
+    pub clicks_delta: i32,
+    pub impressions_delta: i32,
+    pub timestamp: i64,
+    /// The search which led to the impression or click, if known
+    pub search_id: Option<i32>,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::pages_analytics_history)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PageAnalyticsHistory {
+    pub id: i32,
+    pub page_id: i32,
+    pub clicks_delta: i32,
+    pub impressions_delta: i32,
+    pub timestamp: i64,
+    pub search_id: Option<i32>,
+}
+
 // Queries //
 
 #[derive(Insertable)]
@@ -123,9 +291,11 @@ pub struct PageAnalytics {
 pub struct NewQuery {
     pub query: String,
     pub timestamp: i64,
-    pub search_time: i32,
+    /// In milliseconds
+    pub search_time: i64,
     pub result_count: i32,
     pub user_agent: Option<String>,
+    pub zero_results: bool,
 }
 
 #[derive(Queryable, Selectable)]
@@ -135,9 +305,10 @@ pub struct Query {
     pub id: i32,
     pub query: String,
     pub timestamp: i64,
-    pub search_time: i32,
+    pub search_time: i64,
     pub result_count: i32,
     pub user_agent: Option<String>,
+    pub zero_results: bool,
 }
 
 // Votes //
@@ -177,6 +348,26 @@ pub struct Index {
     pub count: i32,
 }
 
+// Positions //
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::positions)]
+pub struct NewPosition {
+    pub word_id: i32,
+    pub page_id: i32,
+    /// Index of the word in the page content
+    pub position: i32,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::positions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Position {
+    pub word_id: i32,
+    pub page_id: i32,
+    pub position: i32,
+}
+
 // Words //
 
 #[derive(Insertable)]
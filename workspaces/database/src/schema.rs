@@ -1,10 +1,39 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    crawl_errors (id) {
+        id -> Int4,
+        #[max_length = 2048]
+        url -> Varchar,
+        #[max_length = 100]
+        domain -> Varchar,
+        #[max_length = 50]
+        error_type -> Varchar,
+        error_detail -> Nullable<Text>,
+        timestamp -> Int8,
+    }
+}
+
+diesel::table! {
+    domain_stats (domain) {
+        #[max_length = 100]
+        domain -> Varchar,
+        pages_crawled -> Int8,
+        pages_indexed -> Int8,
+        last_crawled -> Nullable<Int8>,
+        avg_response_time -> Int4,
+        error_count -> Int4,
+        has_rss -> Bool,
+    }
+}
+
 diesel::table! {
     favicons (id) {
         id -> Int4,
         #[max_length = 2048]
         url -> Varchar,
+        #[max_length = 255]
+        local_path -> Nullable<Varchar>,
     }
 }
 
@@ -24,6 +53,25 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    page_alternates (page_id, lang) {
+        page_id -> Int4,
+        #[max_length = 10]
+        lang -> Varchar,
+        #[max_length = 2048]
+        url -> Varchar,
+    }
+}
+
+diesel::table! {
+    page_links (from_page_id, to_url) {
+        from_page_id -> Int4,
+        #[max_length = 2048]
+        to_url -> Varchar,
+        timestamp -> Int8,
+    }
+}
+
 diesel::table! {
     pages (id) {
         id -> Int4,
@@ -54,6 +102,36 @@ diesel::table! {
         meta_theme_color -> Nullable<Varchar>,
         #[max_length = 512]
         meta_og_image -> Nullable<Varchar>,
+        #[max_length = 64]
+        body_hash -> Nullable<Varchar>,
+        content_changed_at -> Nullable<Int8>,
+        has_rss -> Bool,
+        page_rank -> Float8,
+        #[max_length = 10]
+        language -> Nullable<Varchar>,
+        #[max_length = 2048]
+        canonical_url -> Nullable<Varchar>,
+        previous_hash -> Nullable<Varchar>,
+        #[max_length = 2048]
+        final_url -> Nullable<Varchar>,
+        #[max_length = 100]
+        meta_author -> Nullable<Varchar>,
+        published_at -> Nullable<Int8>,
+        #[max_length = 30]
+        meta_twitter_card -> Nullable<Varchar>,
+        #[max_length = 50]
+        meta_og_type -> Nullable<Varchar>,
+        #[max_length = 100]
+        meta_og_site_name -> Nullable<Varchar>,
+        #[max_length = 20]
+        meta_og_locale -> Nullable<Varchar>,
+        image_alts -> Nullable<Text>,
+        deleted_at -> Nullable<Int8>,
+        word_count -> Int4,
+        #[max_length = 32]
+        url_fingerprint -> Nullable<Varchar>,
+        #[max_length = 100]
+        registered_domain -> Nullable<Varchar>,
     }
 }
 
@@ -66,16 +144,36 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    pages_analytics_history (id) {
+        id -> Int4,
+        page_id -> Int4,
+        clicks_delta -> Int4,
+        impressions_delta -> Int4,
+        timestamp -> Int8,
+        search_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    positions (page_id, position) {
+        word_id -> Int4,
+        page_id -> Int4,
+        position -> Int4,
+    }
+}
+
 diesel::table! {
     queries (id) {
         id -> Int4,
         #[max_length = 512]
         query -> Varchar,
         timestamp -> Int8,
-        search_time -> Int4,
+        search_time -> Int8,
         result_count -> Int4,
         #[max_length = 255]
         user_agent -> Nullable<Varchar>,
+        zero_results -> Bool,
     }
 }
 
@@ -87,6 +185,12 @@ diesel::table! {
         #[max_length = 2048]
         url -> Varchar,
         timestamp -> Int8,
+        priority -> Int4,
+        depth -> Int4,
+        recrawl -> Bool,
+        redirect_depth -> Int2,
+        #[max_length = 100]
+        registered_domain -> Nullable<Varchar>,
     }
 }
 
@@ -113,6 +217,14 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    visited_extra (url) {
+        #[max_length = 2048]
+        url -> Varchar,
+        timestamp -> Int8,
+    }
+}
+
 diesel::table! {
     words (id) {
         id -> Int4,
@@ -123,19 +235,32 @@ diesel::table! {
 
 diesel::joinable!(indexes -> pages (page_id));
 diesel::joinable!(indexes -> words (word_id));
+diesel::joinable!(page_alternates -> pages (page_id));
+diesel::joinable!(page_links -> pages (from_page_id));
 diesel::joinable!(pages -> favicons (favicon_id));
 diesel::joinable!(pages_analytics -> pages (page_id));
+diesel::joinable!(pages_analytics_history -> pages (page_id));
+diesel::joinable!(pages_analytics_history -> queries (search_id));
+diesel::joinable!(positions -> pages (page_id));
+diesel::joinable!(positions -> words (word_id));
 diesel::joinable!(votes -> pages (page_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    crawl_errors,
+    domain_stats,
     favicons,
     indexes,
     links,
+    page_alternates,
+    page_links,
     pages,
     pages_analytics,
+    pages_analytics_history,
+    positions,
     queries,
     queue,
     statistics,
+    visited_extra,
     votes,
     words,
 );
@@ -70,6 +70,25 @@ pub enum StatisticType {
     WordCount = 9,
     IndexesCount = 10,
     FaviconsCount = 11,
+    RecrawlCount = 12,
+    /// Pages indexed per minute by an indexer run
+    IndexerThroughput = 13,
+    /// Unchanged pages skipped by an indexer run
+    IndexerSkippedCount = 14,
+    /// Stale favicons downloaded again since the last save
+    FaviconRefreshCount = 15,
+    /// Used bytes of the filesystem of the working directory
+    DiskUsage = 16,
+    /// Size in bytes of the downloaded favicons
+    FaviconDirSize = 17,
+    /// Alerts fired by the monitor since the last save
+    AlertFiredCount = 18,
+    /// Domains with crawl statistics
+    DomainCount = 19,
+    /// Deleted pages waiting to be purged
+    DeletedPageCount = 20,
+    /// Median response time of the crawled pages, in milliseconds
+    AverageResponseTime = 21,
 }
 
 impl<DB> FromSql<Integer, DB> for StatisticType
@@ -90,6 +109,16 @@ where
             9 => Ok(StatisticType::WordCount),
             10 => Ok(StatisticType::IndexesCount),
             11 => Ok(StatisticType::FaviconsCount),
+            12 => Ok(StatisticType::RecrawlCount),
+            13 => Ok(StatisticType::IndexerThroughput),
+            14 => Ok(StatisticType::IndexerSkippedCount),
+            15 => Ok(StatisticType::FaviconRefreshCount),
+            16 => Ok(StatisticType::DiskUsage),
+            17 => Ok(StatisticType::FaviconDirSize),
+            18 => Ok(StatisticType::AlertFiredCount),
+            19 => Ok(StatisticType::DomainCount),
+            20 => Ok(StatisticType::DeletedPageCount),
+            21 => Ok(StatisticType::AverageResponseTime),
             x => Err(format!("Unrecognized StatisticType variant {}", x).into()),
         }
     }
@@ -113,6 +142,16 @@ where
             StatisticType::WordCount => 9.to_sql(out),
             StatisticType::IndexesCount => 10.to_sql(out),
             StatisticType::FaviconsCount => 11.to_sql(out),
+            StatisticType::RecrawlCount => 12.to_sql(out),
+            StatisticType::IndexerThroughput => 13.to_sql(out),
+            StatisticType::IndexerSkippedCount => 14.to_sql(out),
+            StatisticType::FaviconRefreshCount => 15.to_sql(out),
+            StatisticType::DiskUsage => 16.to_sql(out),
+            StatisticType::FaviconDirSize => 17.to_sql(out),
+            StatisticType::AlertFiredCount => 18.to_sql(out),
+            StatisticType::DomainCount => 19.to_sql(out),
+            StatisticType::DeletedPageCount => 20.to_sql(out),
+            StatisticType::AverageResponseTime => 21.to_sql(out),
         }
     }
 }
@@ -1,8 +1,15 @@
 use crate::utils::get_favicons_directory;
-use image::{imageops::FilterType, ImageError, ImageFormat};
-use reqwest::Client;
+use database::{schema::favicons, DbPool};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use image::{
+    error::{DecodingError, ImageFormatHint},
+    imageops::FilterType,
+    DynamicImage, ImageError, ImageFormat, RgbaImage,
+};
+use reqwest::{header::CONTENT_TYPE, Client, Url};
+use resvg::usvg;
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{self, BufWriter},
     path::PathBuf,
     time::Duration,
@@ -41,17 +48,19 @@ impl From<io::Error> for FaviconDownloadError {
 
 pub struct Downloader {
     client: Client,
+    db_pool: DbPool,
     favicon_directory: PathBuf,
 }
 
 impl Downloader {
-    pub fn new(user_agent: String) -> Self {
+    pub fn new(db_pool: DbPool, user_agent: String) -> Self {
         Self {
             client: Client::builder()
                 .timeout(Duration::from_secs(10))
                 .user_agent(user_agent)
                 .build()
                 .expect("Failed to build the reqwest Client"),
+            db_pool,
             favicon_directory: get_favicons_directory(),
         }
     }
@@ -62,8 +71,9 @@ impl Downloader {
         let mut i = 0;
 
         for (fav_id, fav_url) in favicons {
-            if let Err(e) = self.download_favicon(fav_id, fav_url.clone()).await {
-                match e {
+            match self.download_favicon(fav_id, fav_url.clone()).await {
+                Ok(file_name) => self.save_local_path(fav_id, &file_name),
+                Err(e) => match e {
                     FaviconDownloadError::Reqwest(err) => {
                         let _ = err; // temp remove the warn
                                      // eprintln!("Failed to download favicon {fav_id}: {err:?}");
@@ -75,7 +85,7 @@ impl Downloader {
                     FaviconDownloadError::File(err) => {
                         eprintln!("Failed to write file of favicon {fav_id}: {err:?}");
                     }
-                };
+                },
             }
 
             if (i + 1) < len {
@@ -86,24 +96,282 @@ impl Downloader {
         }
     }
 
+    /// Download a favicon as a PNG file, returns the name of the file
     async fn download_favicon(
         &self,
         fav_id: i32,
         fav_url: String,
-    ) -> Result<(), FaviconDownloadError> {
+    ) -> Result<String, FaviconDownloadError> {
+        let (mut bytes, mut content_type) = self.fetch_favicon(&fav_url).await?;
+        let mut fav_url = fav_url;
+        let file_name = get_output_file_name(fav_id);
+
+        if is_svg(&fav_url, &bytes) {
+            if let Some(png) = render_svg(&bytes) {
+                fs::write(self.favicon_directory.join(&file_name), png)?;
+                return Ok(file_name);
+            }
+
+            // Fall back to the default favicon of the website
+            match get_default_favicon_url(&fav_url) {
+                Some(default_url) if default_url != fav_url => {
+                    (bytes, content_type) = self.fetch_favicon(&default_url).await?;
+                    fav_url = default_url;
+                }
+                _ => {
+                    return Err(FaviconDownloadError::Image(ImageError::Decoding(
+                        DecodingError::new(
+                            ImageFormatHint::Name("svg".to_string()),
+                            "Failed to render the SVG",
+                        ),
+                    )))
+                }
+            }
+        }
+
+        let img = if is_ico(&fav_url, content_type.as_deref()) {
+            decode_ico(&bytes)?
+        } else {
+            image::load_from_memory(&bytes)?
+        };
+        let resized = img.resize_exact(FAVICON_SIZE, FAVICON_SIZE, FilterType::Lanczos3);
+
+        let file = File::create(self.favicon_directory.join(&file_name))?;
+        let writer = &mut BufWriter::new(file);
+
+        resized.write_to(writer, ImageFormat::Png)?;
+        Ok(file_name)
+    }
+
+    /// Save the file of a downloaded favicon, so the API does not search the favicons directory
+    fn save_local_path(&self, fav_id: i32, file_name: &str) {
+        let conn = &mut self.db_pool.get().unwrap();
+
+        let result = diesel::update(favicons::table.find(fav_id))
+            .set(favicons::local_path.eq(file_name))
+            .execute(conn);
+        if let Err(e) = result {
+            eprintln!("Failed to save the file of favicon {fav_id}: {e}");
+        }
+    }
+
+    /// Returns the favicon bytes and its `Content-Type`
+    async fn fetch_favicon(
+        &self,
+        fav_url: &str,
+    ) -> Result<(Vec<u8>, Option<String>), reqwest::Error> {
         let response = self.client.get(fav_url).send().await?;
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
         let bytes = response.bytes().await?;
 
-        let img = image::load_from_memory(&bytes)?;
+        Ok((bytes.to_vec(), content_type))
+    }
+}
+
+/// Get the `{favicon_id}-{timestamp}.png` name of a new favicon file
+fn get_output_file_name(fav_id: i32) -> String {
+    let now = get_timestamp().as_millis().to_string();
+    format!("{}-{}.png", fav_id, now)
+}
+
+/// Returns `true` if the favicon is a SVG file, from its URL extension or its content
+fn is_svg(fav_url: &str, bytes: &[u8]) -> bool {
+    let path = fav_url.split(['?', '#']).next().unwrap_or(fav_url);
+    let start = String::from_utf8_lossy(&bytes[..bytes.len().min(256)]);
+    let start = start.trim_start();
+
+    path.to_lowercase().ends_with(".svg") || start.starts_with("<svg") || start.starts_with("<?xml")
+}
+
+/// Render a SVG at `FAVICON_SIZE`, returns the PNG bytes
+fn render_svg(bytes: &[u8]) -> Option<Vec<u8>> {
+    let tree = usvg::Tree::from_data(bytes, &usvg::Options::default()).ok()?;
+    let mut pixmap = tiny_skia::Pixmap::new(FAVICON_SIZE, FAVICON_SIZE)?;
+
+    let size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        FAVICON_SIZE as f32 / size.width(),
+        FAVICON_SIZE as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    pixmap.encode_png().ok()
+}
+
+/// The `/favicon.ico` of the favicon website
+fn get_default_favicon_url(fav_url: &str) -> Option<String> {
+    let url = Url::parse(fav_url).ok()?.join("/favicon.ico").ok()?;
+    Some(url.to_string())
+}
+
+/// Returns `true` if the favicon is an ICO file, from its URL extension or its `Content-Type`
+fn is_ico(fav_url: &str, content_type: Option<&str>) -> bool {
+    let path = fav_url.split(['?', '#']).next().unwrap_or(fav_url);
+
+    path.to_lowercase().ends_with(".ico")
+        || matches!(
+            content_type.map(|t| t.trim().to_lowercase()).as_deref(),
+            Some("image/x-icon" | "image/vnd.microsoft.icon")
+        )
+}
+
+/// Decode the largest image of an ICO file.
+/// ICO files can contain several images, which `image::load_from_memory` may fail to read.
+fn decode_ico(bytes: &[u8]) -> Result<DynamicImage, ImageError> {
+    let to_image_error = |e: io::Error| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Exact(ImageFormat::Ico),
+            e,
+        ))
+    };
+
+    let icon_dir = ico::IconDir::read(io::Cursor::new(bytes)).map_err(to_image_error)?;
+
+    // Even a 1x1 image is better than nothing
+    let entry = match icon_dir
+        .entries()
+        .iter()
+        .max_by_key(|e| e.width() * e.height())
+    {
+        Some(entry) => entry,
+        None => {
+            return Err(to_image_error(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "ICO file without images",
+            )))
+        }
+    };
+
+    let icon = entry.decode().map_err(to_image_error)?;
+
+    match RgbaImage::from_raw(icon.width(), icon.height(), icon.rgba_data().to_vec()) {
+        Some(img) => Ok(DynamicImage::ImageRgba8(img)),
+        None => Err(to_image_error(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid ICO image size",
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1x1 ICO file with a single red 32 bits BMP image
+    #[rustfmt::skip]
+    const ICO_1X1: &[u8] = &[
+        // ICONDIR: reserved, type (1 = icon), images count
+        0, 0, 1, 0, 1, 0,
+        // ICONDIRENTRY: width, height, colors, reserved, planes, bits per pixel, size, offset
+        1, 1, 0, 0, 1, 0, 32, 0, 48, 0, 0, 0, 22, 0, 0, 0,
+        // BITMAPINFOHEADER: size, width, height (x2 for the mask), planes, bits per pixel
+        40, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1, 0, 32, 0,
+        // compression, image size, resolution, colors
+        0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        // BGRA pixel
+        0, 0, 255, 255,
+        // AND mask, padded to 4 bytes
+        0, 0, 0, 0,
+    ];
+
+    fn encode_ico(sizes: &[u32]) -> Vec<u8> {
+        let mut icon_dir = ico::IconDir::new(ico::ResourceType::Icon);
+        for size in sizes {
+            let rgba = vec![255; (size * size * 4) as usize];
+            let image = ico::IconImage::from_rgba_data(*size, *size, rgba);
+            icon_dir.add_entry(ico::IconDirEntry::encode(&image).unwrap());
+        }
+
+        let mut bytes = Vec::new();
+        icon_dir.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_decode_ico() {
+        let img = decode_ico(ICO_1X1).unwrap();
+        assert_eq!((img.width(), img.height()), (1, 1));
+        assert_eq!(img.to_rgba8().get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+        // The resize pipeline works with the smallest images
         let resized = img.resize_exact(FAVICON_SIZE, FAVICON_SIZE, FilterType::Lanczos3);
+        assert_eq!(resized.width(), FAVICON_SIZE);
+    }
+
+    #[test]
+    fn test_decode_ico_largest_image() {
+        let img = decode_ico(&encode_ico(&[16, 48, 32])).unwrap();
+        assert_eq!((img.width(), img.height()), (48, 48));
+    }
 
-        let now = get_timestamp().as_millis().to_string();
-        let output_path = format!("{}-{}.png", fav_id, now);
+    #[test]
+    fn test_decode_invalid_ico() {
+        assert_eq!(
+            matches!(decode_ico(b"not an icon"), Err(ImageError::Decoding(_))),
+            true
+        );
+        assert_eq!(decode_ico(&encode_ico(&[])).is_err(), true);
+    }
 
-        let file = File::create(&self.favicon_directory.join(output_path))?;
-        let writer = &mut BufWriter::new(file);
+    const SVG_SQUARE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16">
+    <rect width="16" height="16" fill="#ff0000"/>
+</svg>"##;
 
-        resized.write_to(writer, ImageFormat::Png)?;
-        Ok(())
+    #[test]
+    fn test_render_svg() {
+        let png = render_svg(SVG_SQUARE.as_bytes()).unwrap();
+        let img = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+
+        // Scaled to the favicon size
+        assert_eq!((img.width(), img.height()), (FAVICON_SIZE, FAVICON_SIZE));
+        assert_eq!(img.to_rgba8().get_pixel(16, 16).0, [255, 0, 0, 255]);
+
+        assert_eq!(render_svg(b"<svg><rect").is_none(), true);
+        assert_eq!(render_svg(b"not a svg").is_none(), true);
+    }
+
+    #[test]
+    fn test_is_svg() {
+        assert_eq!(is_svg("https://example.com/icon.svg", b""), true);
+        assert_eq!(
+            is_svg("https://example.com/icon", SVG_SQUARE.as_bytes()),
+            true
+        );
+        assert_eq!(
+            is_svg(
+                "https://example.com/icon",
+                b"  <?xml version=\"1.0\"?><svg/>"
+            ),
+            true
+        );
+        assert_eq!(is_svg("https://example.com/favicon.ico", ICO_1X1), false);
+    }
+
+    #[test]
+    fn test_get_default_favicon_url() {
+        assert_eq!(
+            get_default_favicon_url("https://example.com/assets/icon.svg?v=1"),
+            Some("https://example.com/favicon.ico".to_string())
+        );
+        assert_eq!(get_default_favicon_url("not a url"), None);
+    }
+
+    #[test]
+    fn test_is_ico() {
+        assert_eq!(is_ico("https://example.com/favicon.ico", None), true);
+        assert_eq!(is_ico("https://example.com/favicon.ICO?v=2", None), true);
+        assert_eq!(
+            is_ico("https://example.com/favicon", Some("image/x-icon")),
+            true
+        );
+        assert_eq!(
+            is_ico("https://example.com/favicon.png", Some("image/png")),
+            false
+        );
+        assert_eq!(is_ico("https://example.com/favicon.png", None), false);
     }
 }
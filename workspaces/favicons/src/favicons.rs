@@ -1,15 +1,37 @@
 use crate::{downloader::Downloader, utils::get_favicons_directory};
-use database::{models::Favicon, schema::favicons, DbPool};
-use diesel::{query_dsl::QueryDsl, RunQueryDsl};
+use database::{models::Favicon, schema::favicons, DbConn, DbPool};
+use diesel::{
+    query_dsl::QueryDsl,
+    sql_query,
+    sql_types::{Array, Integer, Text},
+    QueryResult, RunQueryDsl,
+};
 use std::{
     collections::HashMap,
     fs::{self},
     io::{self, ErrorKind},
     path::PathBuf,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 use tokio::sync::Mutex;
-use utils::url::normalize_url;
+use utils::{get_timestamp, url::normalize_url};
+
+/// Default age in days after which the favicons are downloaded again
+pub const DEFAULT_FAVICON_REFRESH_DAYS: i64 = 30;
+
+const DAY_MS: i64 = 86_400_000;
+
+/// Point the favicons at their last downloaded file
+const SYNC_LOCAL_PATHS_SQL: &str = "UPDATE favicons f SET local_path = v.local_path
+    FROM unnest($1::int4[], $2::text[]) AS v(id, local_path)
+    WHERE f.id = v.id AND f.local_path IS DISTINCT FROM v.local_path";
+
+/// Forget the files of the favicons which are not in the favicons directory anymore
+const CLEAR_LOCAL_PATHS_SQL: &str =
+    "UPDATE favicons SET local_path = NULL WHERE local_path IS NOT NULL AND NOT (id = ANY($1))";
 
 /// Manage the download of the pages favicons
 pub struct Favicons {
@@ -17,24 +39,30 @@ pub struct Favicons {
     parallel_tasks: usize,
     downloader: Arc<Downloader>,
     favicon_directory: PathBuf,
+    /// Age in days after which the favicons are downloaded again
+    pub refresh_days: i64,
+    /// Number of stale favicons downloaded again, saved by the monitor
+    pub refresh_count: Arc<AtomicU64>,
 }
 
 impl Favicons {
     pub fn new(db_pool: DbPool, parallel_tasks: usize, user_agent: String) -> Self {
         Self {
+            downloader: Arc::new(Downloader::new(db_pool.clone(), user_agent)),
             db_pool,
             parallel_tasks,
-            downloader: Arc::new(Downloader::new(user_agent)),
             favicon_directory: get_favicons_directory(),
+            refresh_days: DEFAULT_FAVICON_REFRESH_DAYS,
+            refresh_count: Arc::new(AtomicU64::new(0)),
         }
     }
 
     pub async fn download_missing_favicons(&self) -> usize {
-        let favicons_map = self.find_favicons_to_download();
+        let (favicons_map, stale_count) = self.find_favicons_to_download();
 
         let count: usize = favicons_map.values().map(|v| v.len()).sum();
         println!(
-            "Downloading {count} favicons with {} task(s)...",
+            "Downloading {count} favicons ({stale_count} stale) with {} task(s)...",
             self.parallel_tasks
         );
 
@@ -74,42 +102,58 @@ impl Favicons {
             t.await.expect("A favicon task panicked");
         }
 
+        self.refresh_count
+            .fetch_add(stale_count as u64, Ordering::Relaxed);
+
         println!("Favicons download ended");
         count
     }
 
-    /// Determines the favicons that are missing from the favicons directory
+    /// Determines the favicons that are missing from the favicons directory,
+    /// or downloaded more than `refresh_days` ago
     ///
-    /// Returns HashMap<domain, Vec<(favicon_id, favicon_url)>>
-    fn find_favicons_to_download(&self) -> HashMap<String, Vec<(i32, String)>> {
+    /// Returns (HashMap<domain, Vec<(favicon_id, favicon_url)>>, stale favicons count)
+    fn find_favicons_to_download(&self) -> (HashMap<String, Vec<(i32, String)>>, usize) {
         let db_favicons = self.get_db_favicons_list();
         let downloaded_favicons = self
             .get_downloaded_favicons_list()
             .expect("Failed to get the downloaded favicons list");
 
+        // The files downloaded before the local_path column, or removed by hand
+        let conn = &mut self.db_pool.get().unwrap();
+        if let Err(e) = sync_local_paths(conn, &downloaded_favicons) {
+            eprintln!("Failed to save the files of the favicons: {e}");
+        }
+
+        let now = get_timestamp().as_millis() as i64;
         let mut missing_favicons = HashMap::new();
+        let mut stale_count = 0;
 
         for fav in db_favicons {
-            if let Some((favicon_url, domain)) = normalize_url(&fav.url) {
-                if let Some(_) = downloaded_favicons.get(&fav.id) {
-                    // favicon already downloaded, continue
-                    continue;
-                } else {
-                    // mark the favicon as missing
-                    missing_favicons
-                        .entry(domain)
-                        .or_insert(Vec::new())
-                        .push((fav.id, favicon_url.to_string()));
+            if let Some((favicon_url, domain)) = normalize_url(&fav.url, true) {
+                if let Some(downloaded_at) = downloaded_favicons.get(&fav.id) {
+                    if !is_favicon_stale(*downloaded_at, now, self.refresh_days) {
+                        // favicon already downloaded, continue
+                        continue;
+                    }
+                    stale_count += 1;
                 }
+
+                // mark the favicon as missing
+                missing_favicons
+                    .entry(domain)
+                    .or_insert(Vec::new())
+                    .push((fav.id, favicon_url.to_string()));
             }
         }
 
-        missing_favicons
+        (missing_favicons, stale_count)
     }
 
-    /// Get the files list of the downloaded favicons and their download timestamp
-    ///  
-    /// Returns HashMap<page_id, favicon_download_timestamp>
+    /// Get the files list of the downloaded favicons and their download timestamp.
+    /// The older files of the refreshed favicons are removed.
+    ///
+    /// Returns HashMap<favicon_id, favicon_download_timestamp>
     fn get_downloaded_favicons_list(&self) -> Result<HashMap<i32, i64>, io::Error> {
         if let Err(e) = fs::create_dir(&self.favicon_directory) {
             if e.kind() != ErrorKind::AlreadyExists {
@@ -123,8 +167,17 @@ impl Favicons {
         for path in paths {
             let file_name = path.unwrap().file_name().into_string().unwrap();
 
-            if let Some((id, timestamp)) = file_name.split_once('-') {
-                favicons.insert(id.parse().unwrap(), timestamp[..4].parse().unwrap());
+            if let Some((id, timestamp)) = parse_favicon_file_name(&file_name) {
+                if let Some(previous) = favicons.insert(id, timestamp) {
+                    // Keep the last download
+                    let (old, last) = (previous.min(timestamp), previous.max(timestamp));
+                    favicons.insert(id, last);
+
+                    let old_file = self.favicon_directory.join(get_favicon_file_name(id, old));
+                    if let Err(e) = fs::remove_file(old_file) {
+                        eprintln!("Failed to remove the old file of favicon {id}: {e}");
+                    }
+                }
             } else {
                 eprintln!("The favicon file '{file_name}' is not in the correct format");
             }
@@ -145,3 +198,84 @@ impl Favicons {
         results
     }
 }
+
+/// Save the last downloaded file of each favicon, as `HashMap<favicon_id, favicon_download_timestamp>`
+fn sync_local_paths(conn: &mut DbConn, downloaded: &HashMap<i32, i64>) -> QueryResult<()> {
+    let (ids, paths): (Vec<i32>, Vec<String>) = downloaded
+        .iter()
+        .map(|(id, timestamp)| (*id, get_favicon_file_name(*id, *timestamp)))
+        .unzip();
+
+    sql_query(CLEAR_LOCAL_PATHS_SQL)
+        .bind::<Array<Integer>, _>(&ids)
+        .execute(conn)?;
+    sql_query(SYNC_LOCAL_PATHS_SQL)
+        .bind::<Array<Integer>, _>(&ids)
+        .bind::<Array<Text>, _>(&paths)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn get_favicon_file_name(id: i32, timestamp: i64) -> String {
+    format!("{id}-{timestamp}.png")
+}
+
+/// Parse a `{favicon_id}-{timestamp}.png` file name
+fn parse_favicon_file_name(file_name: &str) -> Option<(i32, i64)> {
+    let (id, timestamp) = file_name.strip_suffix(".png")?.split_once('-')?;
+    Some((id.parse().ok()?, timestamp.parse().ok()?))
+}
+
+/// Returns `true` if the favicon was downloaded more than `refresh_days` before `now`
+fn is_favicon_stale(downloaded_at: i64, now: i64, refresh_days: i64) -> bool {
+    now - downloaded_at > refresh_days * DAY_MS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    #[test]
+    fn test_is_favicon_stale() {
+        assert_eq!(is_favicon_stale(NOW - DAY_MS, NOW, 30), false);
+        assert_eq!(is_favicon_stale(NOW - 30 * DAY_MS, NOW, 30), false);
+        assert_eq!(is_favicon_stale(NOW - 30 * DAY_MS - 1, NOW, 30), true);
+        assert_eq!(is_favicon_stale(NOW - 2 * DAY_MS, NOW, 1), true);
+        // Refresh at every run
+        assert_eq!(is_favicon_stale(NOW - 1, NOW, 0), true);
+    }
+
+    #[test]
+    fn test_favicon_file_name() {
+        let file_name = get_favicon_file_name(42, NOW);
+        assert_eq!(file_name, "42-1700000000000.png");
+        assert_eq!(parse_favicon_file_name(&file_name), Some((42, NOW)));
+    }
+
+    #[test]
+    fn test_local_paths_sql() {
+        let query = sql_query(SYNC_LOCAL_PATHS_SQL)
+            .bind::<Array<Integer>, _>(vec![42])
+            .bind::<Array<Text>, _>(vec![get_favicon_file_name(42, NOW)]);
+        let sql = diesel::debug_query::<diesel::pg::Pg, _>(&query).to_string();
+        assert_eq!(sql.contains("IS DISTINCT FROM v.local_path"), true);
+        assert_eq!(
+            sql.contains(r#"binds: [[42], ["42-1700000000000.png"]]"#),
+            true
+        );
+    }
+
+    #[test]
+    fn test_parse_favicon_file_name() {
+        assert_eq!(
+            parse_favicon_file_name("42-1700000000000.png"),
+            Some((42, NOW))
+        );
+        assert_eq!(parse_favicon_file_name("42.png"), None);
+        assert_eq!(parse_favicon_file_name("42-abc.png"), None);
+        assert_eq!(parse_favicon_file_name("42-1700000000000.ico"), None);
+    }
+}
@@ -1,3 +1,5 @@
 mod downloader;
 pub mod favicons;
 mod utils;
+
+pub use utils::get_favicons_directory;
@@ -1,41 +1,103 @@
-use database::DbPool;
+use database::{get_pages_to_index_filter, DbPool};
 use database::{
-    models::Page,
-    schema::{indexes, pages, words},
+    models::{NewPosition, NewStatistic, Page},
+    schema::{domain_stats, indexes, pages, positions, statistics, words},
+    types::StatisticType,
+};
+use diesel::{
+    dsl::sql, pg::Pg, upsert::excluded, Connection, ExpressionMethods, OptionalExtension, QueryDsl,
+    QueryResult, RunQueryDsl,
 };
-use diesel::{dsl::sql, upsert::excluded, ExpressionMethods, QueryDsl, RunQueryDsl};
 use diesel::{BoolExpressionMethods, NullableExpressionMethods};
-use std::collections::HashMap;
-use utils::sql::get_sql_timestamp;
+use futures_util::{stream::FuturesUnordered, StreamExt};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::task;
+use utils::{
+    normalize_text, split_words, sql::get_sql_timestamp, stem_word, stopwords::get_stop_words,
+};
 
 pub const INDEXING_BATCH_SIZE: i64 = 1000;
 
-/// When a page is indexed, all words indexes are created in one db call.
-/// To not exceed the PostgreSQL limit, this is the max.
-///
-/// TODO: make multiple requests to always index all words
+/// Pages with more unique words than this are logged, they are still fully indexed
 pub const MAX_WORD_COUNT: usize = (1 << 16) - 1;
 
-/// TODO: should we add multi-threading?
+/// Number of words and indexes inserted per db call, to stay under the PostgreSQL parameters limit
+pub const WORDS_CHUNK_SIZE: usize = 1000;
+
+/// A word in the title counts as this many words in the body
+pub const TITLE_WORD_WEIGHT: i32 = 5;
+
+/// Number of occurrences of a word in the image alt texts counting as one in the body
+pub const IMAGE_ALT_WORD_DIVISOR: i32 = 2;
+
+/// Number of word positions inserted per db call (3 parameters per position)
+pub const POSITIONS_CHUNK_SIZE: usize = 20_000;
+
+/// Get the pages crawled since their last indexing.
+/// Pages with the same body hash as an already indexed page are skipped.
+fn get_pages_query() -> pages::BoxedQuery<'static, Pg> {
+    pages::table
+        .select(pages::all_columns)
+        .filter(get_pages_to_index_filter())
+        .limit(INDEXING_BATCH_SIZE)
+        .into_boxed()
+}
+
+/// Result of `Indexer::index_page`
+#[derive(Debug, PartialEq)]
+enum IndexOutcome {
+    Indexed,
+    /// The content did not change since the last indexing, only `last_indexed` was updated
+    Unchanged,
+    /// Already being indexed by another task
+    Locked,
+}
+
+/// Counts of an indexer run
+#[derive(Debug, Default, PartialEq)]
+pub struct IndexRun {
+    /// Pages to index
+    pub pages: usize,
+    pub indexed: usize,
+    /// Unchanged pages, their words were not indexed again
+    pub skipped: usize,
+}
+
+/// Default number of pages indexed in parallel
+pub const DEFAULT_INDEXER_CONCURRENCY: usize = 4;
+
 pub struct Indexer {
     db_pool: DbPool,
+    /// Number of pages indexed in parallel
+    pub concurrency: usize,
+    /// Words which are not indexed, they are still saved in the positions for the phrase search
+    stop_words: Arc<HashSet<String>>,
+    /// Index the stem of the words, so "running" and "runs" are the same word
+    pub stemming: bool,
 }
 
 impl Indexer {
     pub fn new(db_pool: DbPool) -> Self {
-        Self { db_pool }
+        Self {
+            db_pool,
+            concurrency: DEFAULT_INDEXER_CONCURRENCY,
+            stop_words: Arc::new(get_stop_words(None)),
+            stemming: true,
+        }
+    }
+
+    /// Add a newline-separated list of stop words to the default ones
+    pub fn set_extra_stop_words(&mut self, extra: &str) {
+        self.stop_words = Arc::new(get_stop_words(Some(extra)));
     }
 
     /// Get pages to index
     async fn get_pages(&self) -> Vec<Page> {
-        let results = pages::table
-            .select(pages::all_columns)
-            .filter(
-                pages::last_indexed
-                    .is_null()
-                    .or(pages::last_crawled.nullable().gt(pages::last_indexed)),
-            )
-            .limit(INDEXING_BATCH_SIZE)
+        let results = get_pages_query()
             .load::<Page>(&mut self.db_pool.get().unwrap())
             .unwrap();
 
@@ -43,101 +105,644 @@ impl Indexer {
     }
 
     /// Start indexing all pages
-    pub async fn index(&self) -> usize {
+    pub async fn index(&self) -> IndexRun {
         let pages = self.get_pages().await;
         let len = pages.len();
 
         println!("Indexing {len} pages...");
+        let start = Instant::now();
+
+        // The stop words are compared to the indexed form of the words
+        let stop_words = if self.stemming {
+            Arc::new(self.stop_words.iter().map(|w| stem_word(w)).collect())
+        } else {
+            self.stop_words.clone()
+        };
+
+        let mut pages = pages.into_iter();
+        let mut tasks = FuturesUnordered::new();
+        let mut indexed = 0;
+        let mut skipped = 0;
+        let mut errors = Vec::new();
 
-        for page in pages {
-            self.index_page(page).await;
+        loop {
+            // Keep `concurrency` pages being indexed
+            while tasks.len() < self.concurrency.max(1) {
+                match pages.next() {
+                    Some(page) => tasks.push(task::spawn(Indexer::index_page(
+                        self.db_pool.clone(),
+                        stop_words.clone(),
+                        self.stemming,
+                        page,
+                    ))),
+                    None => break,
+                }
+            }
+
+            match tasks.next().await {
+                Some(Ok(Ok(IndexOutcome::Indexed))) => indexed += 1,
+                Some(Ok(Ok(IndexOutcome::Unchanged))) => skipped += 1,
+                Some(Ok(Ok(IndexOutcome::Locked))) => {}
+                Some(Ok(Err(e))) => errors.push(e.to_string()),
+                Some(Err(e)) => errors.push(e.to_string()),
+                None => break,
+            }
+        }
+
+        for e in &errors {
+            eprintln!("[Indexer] Failed to index a page: {e}");
         }
 
-        println!("Indexed {len} pages");
-
-        len
-    }
-
-    async fn index_page(&self, page: Page) {
-        let db_conn = &mut self.db_pool.get().unwrap();
-
-        // Index the words
-        if let Some(content) = page.content {
-            let words_count = self.tokenize(&content);
-            let words_list: Vec<String> = words_count.keys().cloned().collect();
-
-            if words_count.len() > 0 && words_count.len() < MAX_WORD_COUNT {
-                // Insert the new words (if some) and return them
-                let inserted_words: Vec<(i32, String)> = diesel::insert_into(words::table)
-                    .values(
-                        words_list
-                            .iter()
-                            .map(|w| words::word.eq(w))
-                            .collect::<Vec<_>>(),
-                    )
-                    .on_conflict(words::word)
-                    .do_update()
-                    .set(words::word.eq(excluded(words::word)))
-                    .returning((words::id, words::word))
-                    .load(db_conn)
-                    .unwrap();
-
-                // Update the indexes
-
-                let word_ids: HashMap<String, i32> = inserted_words
-                    .into_iter()
-                    .map(|(id, word)| (word, id))
-                    .collect();
-
-                let new_indexes: Vec<_> = words_count
-                    .into_iter()
-                    .map(|(word, count)| {
-                        let word_id = *word_ids.get(&word).unwrap();
-                        (
-                            indexes::word_id.eq(word_id),
-                            indexes::page_id.eq(page.id),
-                            indexes::count.eq(count),
-                        )
-                    })
-                    .collect();
-
-                // Insert the new indexes
-                diesel::insert_into(indexes::table)
-                    .values(new_indexes)
-                    .on_conflict((indexes::word_id, indexes::page_id))
-                    .do_update()
-                    .set(indexes::count.eq(sql("excluded.count")))
-                    .execute(db_conn)
-                    .unwrap();
+        let elapsed = start.elapsed();
+        println!(
+            "Indexed {indexed}/{len} pages in {}ms, {skipped} unchanged",
+            elapsed.as_millis()
+        );
+
+        if indexed > 0 {
+            if let Err(e) = self.save_throughput(indexed, elapsed) {
+                eprintln!("[Indexer] Failed to save the throughput: {e}");
+            }
+        }
+        if len > 0 {
+            if let Err(e) = self.save_skipped_count(skipped) {
+                eprintln!("[Indexer] Failed to save the skipped pages count: {e}");
             }
         }
 
-        // Mark the table as indexed
-        diesel::update(pages::table)
-            .filter(pages::id.eq(page.id))
-            .set(pages::last_indexed.eq(get_sql_timestamp()))
-            .execute(db_conn)
-            .unwrap();
+        IndexRun {
+            pages: len,
+            indexed,
+            skipped,
+        }
     }
 
-    /// Divides the content into lowercase words
-    /// A word length is `>= 1 && <= 100`
+    /// Save the pages indexed per minute by this run, for the monitor analytics
+    fn save_throughput(&self, indexed: usize, elapsed: Duration) -> QueryResult<()> {
+        diesel::insert_into(statistics::table)
+            .values(NewStatistic {
+                statistic_type: StatisticType::IndexerThroughput,
+                value: get_throughput(indexed, elapsed),
+                timestamp: get_sql_timestamp(),
+            })
+            .execute(&mut self.db_pool.get().unwrap())?;
+
+        Ok(())
+    }
+
+    /// Save the unchanged pages skipped by this run, for `GET /api/index/status`
+    fn save_skipped_count(&self, skipped: usize) -> QueryResult<()> {
+        diesel::insert_into(statistics::table)
+            .values(NewStatistic {
+                statistic_type: StatisticType::IndexerSkippedCount,
+                value: skipped as i64,
+                timestamp: get_sql_timestamp(),
+            })
+            .execute(&mut self.db_pool.get().unwrap())?;
+
+        Ok(())
+    }
+
+    /// Index a page, returns `Locked` if it is being indexed by another task.
     ///
-    /// Returns HashMap<word, count>
-    fn tokenize(&self, content: &str) -> HashMap<String, i32> {
-        let mut word_count = HashMap::new();
-
-        for word in content.split_whitespace() {
-            let clean_word = word
-                .to_lowercase()
-                .trim_matches(|c: char| !c.is_alphabetic())
-                .to_string();
-
-            if !clean_word.is_empty() && clean_word.len() <= 100 {
-                *word_count.entry(clean_word.to_string()).or_insert(0) += 1;
+    /// The page row is locked until it is indexed, so concurrent indexers skip it.
+    async fn index_page(
+        db_pool: DbPool,
+        stop_words: Arc<HashSet<String>>,
+        stemming: bool,
+        page: Page,
+    ) -> QueryResult<IndexOutcome> {
+        let db_conn = &mut db_pool.get().unwrap();
+
+        db_conn.transaction(|db_conn| {
+            let claimed = pages::table
+                .filter(pages::id.eq(page.id))
+                .filter(
+                    pages::last_indexed
+                        .is_null()
+                        .or(pages::last_crawled.nullable().gt(pages::last_indexed)),
+                )
+                .select(pages::id)
+                .for_update()
+                .skip_locked()
+                .first::<i32>(db_conn)
+                .optional()?;
+
+            if claimed.is_none() {
+                return Ok(IndexOutcome::Locked);
+            }
+
+            // Same content as the last indexing, the indexes are up to date
+            if is_content_unchanged(&page) {
+                diesel::update(pages::table)
+                    .filter(pages::id.eq(page.id))
+                    .set(pages::last_indexed.eq(get_sql_timestamp()))
+                    .execute(db_conn)?;
+
+                return Ok(IndexOutcome::Unchanged);
+            }
+
+            // Index the words
+            let mut word_count = 0;
+            if let Some(content) = &page.content {
+                let PageWords {
+                    words_list,
+                    words_count,
+                    words,
+                } = get_page_words(
+                    content,
+                    page.title.as_deref(),
+                    page.image_alts.as_deref(),
+                    &stop_words,
+                    stemming,
+                );
+                word_count = get_total_word_count(&words_count);
+
+                diesel::delete(positions::table)
+                    .filter(positions::page_id.eq(page.id))
+                    .execute(db_conn)?;
+
+                if words_list.len() > MAX_WORD_COUNT {
+                    eprintln!(
+                        "[Indexer] Warning: page {} has {} unique words",
+                        page.id,
+                        words_list.len()
+                    );
+                }
+
+                if !words_count.is_empty() {
+                    // Insert the new words (if some) and return them
+                    let mut inserted_words: Vec<(i32, String)> = Vec::new();
+                    for chunk in words_list.chunks(WORDS_CHUNK_SIZE) {
+                        inserted_words.extend(
+                            diesel::insert_into(words::table)
+                                .values(chunk.iter().map(|w| words::word.eq(w)).collect::<Vec<_>>())
+                                .on_conflict(words::word)
+                                .do_update()
+                                .set(words::word.eq(excluded(words::word)))
+                                .returning((words::id, words::word))
+                                .load::<(i32, String)>(db_conn)?,
+                        );
+                    }
+
+                    // Update the indexes
+
+                    let word_ids: HashMap<String, i32> = inserted_words
+                        .into_iter()
+                        .map(|(id, word)| (word, id))
+                        .collect();
+
+                    let new_indexes: Vec<_> = words_count
+                        .into_iter()
+                        .map(|(word, count)| {
+                            let word_id = *word_ids.get(&word).unwrap();
+                            (
+                                indexes::word_id.eq(word_id),
+                                indexes::page_id.eq(page.id),
+                                indexes::count.eq(count),
+                            )
+                        })
+                        .collect();
+
+                    // Insert the new indexes
+                    for chunk in new_indexes.chunks(WORDS_CHUNK_SIZE) {
+                        diesel::insert_into(indexes::table)
+                            .values(chunk)
+                            .on_conflict((indexes::word_id, indexes::page_id))
+                            .do_update()
+                            .set(indexes::count.eq(sql("excluded.count")))
+                            .execute(db_conn)?;
+                    }
+
+                    // Insert the words positions, used by the phrase search
+                    let new_positions: Vec<NewPosition> = words
+                        .iter()
+                        .enumerate()
+                        .map(|(i, word)| NewPosition {
+                            word_id: word_ids[word],
+                            page_id: page.id,
+                            position: i as i32,
+                        })
+                        .collect();
+
+                    for chunk in new_positions.chunks(POSITIONS_CHUNK_SIZE) {
+                        diesel::insert_into(positions::table)
+                            .values(chunk)
+                            .execute(db_conn)?;
+                    }
+                }
+            }
+
+            // Mark the table as indexed
+            diesel::update(pages::table)
+                .filter(pages::id.eq(page.id))
+                .set((
+                    pages::last_indexed.eq(get_sql_timestamp()),
+                    pages::previous_hash.eq(&page.body_hash),
+                    pages::word_count.eq(word_count),
+                ))
+                .execute(db_conn)?;
+
+            // The row is created by the crawler when it saves the page
+            if page.last_indexed.is_none() {
+                diesel::update(domain_stats::table)
+                    .filter(domain_stats::domain.eq(&page.domain))
+                    .set(domain_stats::pages_indexed.eq(domain_stats::pages_indexed + 1))
+                    .execute(db_conn)?;
+            }
+
+            Ok(IndexOutcome::Indexed)
+        })
+    }
+}
+
+/// The rows of a page inserted by the indexer
+#[derive(Debug, Default, PartialEq)]
+struct PageWords {
+    /// Every word of the page, sorted so concurrent transactions lock them in the same order
+    words_list: Vec<String>,
+    /// The weighted count of the indexed words, without the stop words
+    words_count: HashMap<String, i32>,
+    /// The words of the content in order, their positions are used by the phrase search
+    words: Vec<String>,
+}
+
+/// Get the words of a page, nothing is inserted for a page with only stop words
+fn get_page_words(
+    content: &str,
+    title: Option<&str>,
+    image_alts: Option<&str>,
+    stop_words: &HashSet<String>,
+    stemming: bool,
+) -> PageWords {
+    let words = tokenize(content, stemming);
+    let mut words_count = count_words(&words);
+
+    // The title words count more than the body ones
+    if let Some(title) = title {
+        let title_count = tokenize_weighted(title, TITLE_WORD_WEIGHT, stemming);
+        words_count = merge_word_counts(title_count, words_count);
+    }
+    // The image alt texts help the pages with little text, but count less than the body
+    if let Some(image_alts) = image_alts {
+        let alts_count = tokenize_divided(image_alts, IMAGE_ALT_WORD_DIVISOR, stemming);
+        words_count = merge_word_counts(alts_count, words_count);
+    }
+    // The stop words are kept in the words, for the positions of the phrase search
+    let mut words_list: Vec<String> = words_count.keys().cloned().collect();
+    words_list.sort_unstable();
+
+    remove_stop_words(&mut words_count, stop_words);
+    if words_count.is_empty() {
+        return PageWords::default();
+    }
+
+    PageWords {
+        words_list,
+        words_count,
+        words,
+    }
+}
+
+/// Returns `true` if the page was indexed with its current body hash
+fn is_content_unchanged(page: &Page) -> bool {
+    page.last_indexed.is_some() && page.body_hash.is_some() && page.body_hash == page.previous_hash
+}
+
+/// Divides the content into lowercase words without diacritics, in order, stemmed if `stemming` is set
+/// A word length is `>= 1 && <= 100`
+fn tokenize(content: &str, stemming: bool) -> Vec<String> {
+    let mut words = Vec::new();
+    let content = normalize_text(content);
+
+    for word in split_words(&content) {
+        let clean_word = word
+            .to_lowercase()
+            .trim_matches(|c: char| !c.is_alphabetic())
+            .to_string();
+
+        if !clean_word.is_empty() && clean_word.len() <= 100 {
+            if stemming {
+                words.push(stem_word(&clean_word));
+            } else {
+                words.push(clean_word);
             }
         }
+    }
+
+    words
+}
+
+/// Remove the stop words of the word counts, so they are not indexed
+fn remove_stop_words(word_count: &mut HashMap<String, i32>, stop_words: &HashSet<String>) {
+    word_count.retain(|word, _| !stop_words.contains(word));
+}
+
+/// Sum the occurrences of the indexed words, capped at `i32::MAX`
+fn get_total_word_count(word_count: &HashMap<String, i32>) -> i32 {
+    word_count
+        .values()
+        .fold(0, |total: i32, count| total.saturating_add(*count))
+}
+
+/// Returns HashMap<word, count * weight>, the counts are capped at `i32::MAX`
+fn tokenize_weighted(content: &str, weight: i32, stemming: bool) -> HashMap<String, i32> {
+    count_words(&tokenize(content, stemming))
+        .into_iter()
+        .map(|(word, count)| (word, count.saturating_mul(weight)))
+        .collect()
+}
+
+/// Count the words of a content which count less than the body ones,
+/// rounded up so a single occurrence is still indexed
+fn tokenize_divided(content: &str, divisor: i32, stemming: bool) -> HashMap<String, i32> {
+    count_words(&tokenize(content, stemming))
+        .into_iter()
+        .map(|(word, count)| (word, count.saturating_add(divisor - 1) / divisor))
+        .collect()
+}
+
+/// Add the body word counts to the title ones
+fn merge_word_counts(
+    mut title_count: HashMap<String, i32>,
+    body_count: HashMap<String, i32>,
+) -> HashMap<String, i32> {
+    for (word, count) in body_count {
+        title_count
+            .entry(word)
+            .and_modify(|v| *v = v.saturating_add(count))
+            .or_insert(count);
+    }
+
+    title_count
+}
+
+/// Get the number of pages indexed per minute
+fn get_throughput(indexed: usize, elapsed: Duration) -> i64 {
+    (indexed as f64 * 60.0 / elapsed.as_secs_f64().max(0.001)) as i64
+}
+
+/// Returns HashMap<word, count>
+fn count_words(words: &[String]) -> HashMap<String, i32> {
+    let mut word_count = HashMap::new();
+
+    for word in words {
+        *word_count.entry(word.clone()).or_insert(0) += 1;
+    }
+
+    word_count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_pages_query_skips_duplicates() {
+        let sql = diesel::debug_query::<Pg, _>(&get_pages_query()).to_string();
+
+        assert_eq!(
+            sql.contains(r#"NOT (EXISTS (SELECT "indexed_pages"."#),
+            true
+        );
+        assert_eq!(
+            sql.contains(r#"("indexed_pages"."body_hash" = "pages"."body_hash")"#),
+            true
+        );
+        assert_eq!(
+            sql.contains(r#"("indexed_pages"."id" != "pages"."id")"#),
+            true
+        );
+    }
+
+    #[test]
+    fn test_get_pages_query_skips_deleted() {
+        let sql = diesel::debug_query::<Pg, _>(&get_pages_query()).to_string();
+
+        assert_eq!(sql.contains(r#"("pages"."deleted_at" IS NULL)"#), true);
+    }
+
+    #[test]
+    fn test_remove_stop_words() {
+        let mut count = count_words(&tokenize(
+            "The crawler is a program, it visits the web",
+            false,
+        ));
+        remove_stop_words(&mut count, &get_stop_words(None));
+
+        assert_eq!(count.contains_key("the"), false);
+        assert_eq!(count.contains_key("a"), false);
+        assert_eq!(count.contains_key("is"), false);
+        assert_eq!(count.contains_key("it"), false);
+        assert_eq!(count["crawler"], 1);
+        assert_eq!(count["program"], 1);
+        assert_eq!(count["visits"], 1);
+        assert_eq!(count["web"], 1);
+    }
+
+    #[test]
+    fn test_tokenize_weighted() {
+        let count = tokenize_weighted("Rust: the Rust book", 5, false);
+
+        assert_eq!(count["rust"], 10);
+        assert_eq!(count["the"], 5);
+        assert_eq!(count["book"], 5);
+
+        // Overflow guard
+        let count = tokenize_weighted("rust rust", i32::MAX, false);
+        assert_eq!(count["rust"], i32::MAX);
+    }
+
+    #[test]
+    fn test_get_total_word_count() {
+        let mut count = count_words(&tokenize("The crawler visits the web, the web", false));
+        remove_stop_words(&mut count, &get_stop_words(None));
+
+        // The occurrences, not the unique words
+        assert_eq!(get_total_word_count(&count), 4);
+        assert_eq!(get_total_word_count(&HashMap::new()), 0);
+        assert_eq!(
+            get_total_word_count(&HashMap::from([
+                ("rust".to_string(), i32::MAX),
+                ("book".to_string(), 1)
+            ])),
+            i32::MAX
+        );
+    }
+
+    #[test]
+    fn test_merge_word_counts() {
+        let title_count = tokenize_weighted("Rust book", TITLE_WORD_WEIGHT, false);
+        let body_count = count_words(&tokenize("rust rust rust rust rust programming", false));
+        let count = merge_word_counts(title_count, body_count);
+
+        // Once in the title is worth 5 times in the body
+        assert_eq!(count["book"], 5);
+        assert_eq!(count["rust"], 10);
+        assert_eq!(count["programming"], 1);
+
+        let count = merge_word_counts(
+            HashMap::from([("rust".to_string(), i32::MAX)]),
+            HashMap::from([("rust".to_string(), 1)]),
+        );
+        assert_eq!(count["rust"], i32::MAX);
+    }
+
+    #[test]
+    fn test_tokenize_divided() {
+        let alts_count = tokenize_divided(
+            "A sleeping cat\nA cat on a sofa\nAnother cat",
+            IMAGE_ALT_WORD_DIVISOR,
+            false,
+        );
+        assert_eq!(alts_count["cat"], 2);
+        assert_eq!(alts_count["sofa"], 1);
+
+        // Twice in the alt texts is worth once in the body
+        let body_count = count_words(&tokenize("cat cat", false));
+        let count = merge_word_counts(tokenize_divided("cat cat cat cat", 2, false), body_count);
+        assert_eq!(count["cat"], 4);
+    }
+
+    #[test]
+    fn test_get_throughput() {
+        assert_eq!(get_throughput(100, Duration::from_secs(30)), 200);
+        assert_eq!(get_throughput(1, Duration::from_secs(60)), 1);
+        // Very fast runs do not divide by zero
+        assert_eq!(get_throughput(1, Duration::ZERO), 60_000);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Open source, (software) 42 open-source!", false),
+            vec!["open", "source", "software", "open", "source"]
+        );
+        assert_eq!(
+            tokenize("Le café de l'école", false),
+            vec!["le", "cafe", "de", "l'ecole"]
+        );
+        assert_eq!(tokenize("Café cafe CAFÉ", false), vec!["cafe"; 3]);
+        assert_eq!(tokenize("東京タワー", false), vec!["東", "京", "タワー"]);
+    }
+
+    fn page_with_hashes(
+        last_indexed: Option<i64>,
+        body_hash: Option<&str>,
+        previous_hash: Option<&str>,
+    ) -> Page {
+        Page {
+            last_indexed,
+            body_hash: body_hash.map(String::from),
+            previous_hash: previous_hash.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_content_unchanged() {
+        let page = page_with_hashes(Some(1), Some("hash"), Some("hash"));
+        assert_eq!(is_content_unchanged(&page), true);
+
+        let page = page_with_hashes(Some(1), Some("new hash"), Some("hash"));
+        assert_eq!(is_content_unchanged(&page), false);
+
+        // Never indexed
+        let page = page_with_hashes(None, Some("hash"), Some("hash"));
+        assert_eq!(is_content_unchanged(&page), false);
+
+        // Pages crawled before the hashes were saved
+        let page = page_with_hashes(Some(1), None, None);
+        assert_eq!(is_content_unchanged(&page), false);
+    }
+
+    #[test]
+    fn test_words_chunks() {
+        let content: Vec<String> = (0..2000)
+            .map(|i| format!("word{}", to_letters(i)))
+            .collect();
+        let page_words = get_page_words(
+            &format!("the {}", content.join(" ")),
+            Some("The title"),
+            Some("An image"),
+            &get_stop_words(None),
+            false,
+        );
+
+        let chunks: Vec<&[String]> = page_words.words_list.chunks(WORDS_CHUNK_SIZE).collect();
+        assert_eq!(chunks.len(), 3);
+        // The words are locked in order
+        assert_eq!(page_words.words_list.is_sorted(), true);
+
+        // Every index and every position has its word inserted
+        let inserted: HashSet<&String> = chunks.iter().flat_map(|x| x.iter()).collect();
+        assert_eq!(page_words.words_count.len(), 2002);
+        assert_eq!(
+            page_words.words_count.keys().all(|x| inserted.contains(x)),
+            true
+        );
+        assert_eq!(page_words.words.iter().all(|x| inserted.contains(x)), true);
+
+        // The stop words have a position but no index
+        assert_eq!(page_words.words.len(), 2001);
+        assert_eq!(page_words.words[0], "the");
+        assert_eq!(page_words.words_count.contains_key("the"), false);
+        assert_eq!(page_words.words_count["title"], TITLE_WORD_WEIGHT);
+        assert_eq!(page_words.words_count["image"], 1);
+    }
+
+    #[test]
+    fn test_stop_words_page() {
+        let page_words = get_page_words(
+            "It is what it is",
+            Some("The"),
+            None,
+            &get_stop_words(None),
+            false,
+        );
+
+        // Neither words, indexes nor positions
+        assert_eq!(page_words, PageWords::default());
+        assert_eq!(get_total_word_count(&page_words.words_count), 0);
+    }
+
+    /// Words cannot contain digits, so the numbers are written with letters
+    fn to_letters(mut n: usize) -> String {
+        let mut letters = String::new();
+        loop {
+            letters.push((b'a' + (n % 26) as u8) as char);
+            n /= 26;
+            if n == 0 {
+                return letters;
+            }
+        }
+    }
+
+    #[test]
+    fn test_tokenize_stemming() {
+        let counts = count_words(&tokenize("Running runs", true));
+
+        // Same index entry
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts["run"], 2);
+        assert_eq!(tokenize("Running runs", false), vec!["running", "runs"]);
+    }
+
+    #[test]
+    fn test_word_positions_are_consecutive() {
+        let words = tokenize("open source software", false);
+
+        let position = |w: &str| words.iter().position(|x| x == w).unwrap();
+        assert_eq!(position("source"), position("open") + 1);
+        assert_eq!(position("software"), position("source") + 1);
+    }
+
+    #[test]
+    fn test_count_words() {
+        let words = vec!["a".to_string(), "b".to_string(), "a".to_string()];
+        let counts = count_words(&words);
 
-        word_count
+        assert_eq!(counts["a"], 2);
+        assert_eq!(counts["b"], 1);
     }
 }
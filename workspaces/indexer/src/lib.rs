@@ -1 +1,2 @@
 pub mod indexer;
+pub mod page_rank;
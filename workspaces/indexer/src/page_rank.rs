@@ -0,0 +1,200 @@
+use database::{
+    schema::{links, pages},
+    DbPool,
+};
+use diesel::{
+    sql_query,
+    sql_types::{Array, Float8, Int4},
+    QueryDsl, QueryResult, RunQueryDsl,
+};
+use std::{collections::HashMap, time::Duration};
+use tokio::{task, time::sleep};
+
+pub const PAGE_RANK_DAMPING_FACTOR: f64 = 0.85;
+
+pub const PAGE_RANK_ITERATIONS: usize = 20;
+
+/// Delay between two computations of the pages rank (1 hour)
+pub const PAGE_RANK_INTERVAL: Duration = Duration::from_secs(3_600);
+
+/// Number of pages updated per db call (2 parameters per page)
+pub const PAGE_RANK_CHUNK_SIZE: usize = 10_000;
+
+/// The pages whose rank changed less than this are not updated, to not rewrite every row
+pub const PAGE_RANK_EPSILON: f64 = 1e-4;
+
+/// Periodically compute the rank of the pages from the links between them
+#[derive(Clone)]
+pub struct PageRank {
+    db_pool: DbPool,
+}
+
+impl PageRank {
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn run(self) {
+        loop {
+            // The computation and the db calls are blocking
+            let page_rank = self.clone();
+            match task::spawn_blocking(move || page_rank.update()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("[PageRank] Failed to update the pages rank: {e}"),
+                Err(e) => eprintln!("[PageRank] The update of the pages rank panicked: {e}"),
+            }
+            sleep(PAGE_RANK_INTERVAL).await;
+        }
+    }
+
+    /// Compute the rank of all pages and save the ones which changed
+    fn update(&self) -> QueryResult<()> {
+        let conn = &mut self.db_pool.get().unwrap();
+
+        let current_ranks = pages::table
+            .select((pages::id, pages::page_rank))
+            .load::<(i32, f64)>(conn)?;
+        let page_ids: Vec<i32> = current_ranks.iter().map(|(id, _)| *id).collect();
+        let links = links::table
+            .select((links::from_page_id, links::to_page_id))
+            .load::<(i32, i32)>(conn)?;
+
+        println!("Computing the rank of {} pages...", page_ids.len());
+
+        let ranks = compute_page_rank(
+            &page_ids,
+            &links,
+            PAGE_RANK_DAMPING_FACTOR,
+            PAGE_RANK_ITERATIONS,
+        );
+        let ranks = get_changed_ranks(&current_ranks, &ranks, PAGE_RANK_EPSILON);
+
+        for chunk in ranks.chunks(PAGE_RANK_CHUNK_SIZE) {
+            let (ids, values): (Vec<i32>, Vec<f64>) = chunk.iter().copied().unzip();
+
+            sql_query(
+                "UPDATE pages SET page_rank = ranks.page_rank
+                FROM UNNEST($1, $2) AS ranks(id, page_rank)
+                WHERE pages.id = ranks.id",
+            )
+            .bind::<Array<Int4>, _>(ids)
+            .bind::<Array<Float8>, _>(values)
+            .execute(conn)?;
+        }
+
+        println!(
+            "Computed the rank of {} pages, {} changed",
+            page_ids.len(),
+            ranks.len()
+        );
+
+        Ok(())
+    }
+}
+
+/// The `(page_id, rank)` of the pages whose rank changed by more than `epsilon`
+pub fn get_changed_ranks(
+    current_ranks: &[(i32, f64)],
+    ranks: &HashMap<i32, f64>,
+    epsilon: f64,
+) -> Vec<(i32, f64)> {
+    current_ranks
+        .iter()
+        .filter_map(|(id, current)| {
+            let rank = *ranks.get(id)?;
+            ((rank - current).abs() > epsilon).then_some((*id, rank))
+        })
+        .collect()
+}
+
+/// Simplified PageRank, returns HashMap<page_id, rank>
+///
+/// Ranks are not divided by the pages count, so the average rank is 1.
+/// The rank of pages without outgoing links is shared between all pages.
+pub fn compute_page_rank(
+    page_ids: &[i32],
+    links: &[(i32, i32)],
+    damping_factor: f64,
+    iterations: usize,
+) -> HashMap<i32, f64> {
+    let n = page_ids.len();
+    if n == 0 {
+        return HashMap::new();
+    }
+
+    let indexes: HashMap<i32, usize> = page_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (*id, i))
+        .collect();
+
+    let mut outgoing = vec![Vec::new(); n];
+    for (from, to) in links {
+        if let (Some(from), Some(to)) = (indexes.get(from), indexes.get(to)) {
+            outgoing[*from].push(*to);
+        }
+    }
+
+    let mut ranks = vec![1.0; n];
+
+    for _ in 0..iterations {
+        let dangling: f64 = (0..n)
+            .filter(|i| outgoing[*i].is_empty())
+            .map(|i| ranks[i])
+            .sum();
+
+        let mut next = vec![(1.0 - damping_factor) + damping_factor * dangling / n as f64; n];
+
+        for (from, targets) in outgoing.iter().enumerate() {
+            let share = damping_factor * ranks[from] / targets.len().max(1) as f64;
+            for to in targets {
+                next[*to] += share;
+            }
+        }
+
+        ranks = next;
+    }
+
+    page_ids.iter().copied().zip(ranks).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_rank_favors_linked_pages() {
+        let ranks = compute_page_rank(&[1, 2, 3], &[(1, 3), (2, 3), (3, 1)], 0.85, 20);
+
+        assert_eq!(ranks[&3] > ranks[&1], true);
+        assert_eq!(ranks[&1] > ranks[&2], true);
+    }
+
+    #[test]
+    fn test_page_rank_average_is_one() {
+        let ranks = compute_page_rank(&[1, 2, 3, 4], &[(1, 2), (2, 3), (3, 1)], 0.85, 20);
+        let total: f64 = ranks.values().sum();
+
+        assert!((total - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_changed_ranks() {
+        let ranks = HashMap::from([(1, 1.5), (2, 1.00001), (3, 0.5)]);
+        let current = [(1, 1.0), (2, 1.0), (3, 0.5), (4, 1.0)];
+
+        assert_eq!(
+            get_changed_ranks(&current, &ranks, PAGE_RANK_EPSILON),
+            vec![(1, 1.5)]
+        );
+    }
+
+    #[test]
+    fn test_page_rank_without_links() {
+        let ranks = compute_page_rank(&[1, 2], &[(1, 42)], 0.85, 20);
+
+        assert!((ranks[&1] - 1.0).abs() < 1e-9);
+        assert!((ranks[&2] - 1.0).abs() < 1e-9);
+        assert_eq!(compute_page_rank(&[], &[], 0.85, 20).is_empty(), true);
+    }
+}
@@ -0,0 +1,275 @@
+use database::types::StatisticType;
+use reqwest::Client;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+/// Attempts to call the webhook for an alert
+pub const ALERT_WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry, doubled after each attempt
+pub const ALERT_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Timeout of a webhook call
+pub const ALERT_WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The pool alert is fired when fewer connections are available
+pub const POOL_AVAILABLE_MIN: u32 = 5;
+
+/// Time the pool must stay under `POOL_AVAILABLE_MIN` before the alert is fired
+pub const POOL_ALERT_DELAY: Duration = Duration::from_secs(60);
+
+/// Delay between the checks of the pool
+pub const POOL_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+const BYTES_PER_GB: i64 = 1 << 30;
+
+/// The CPU usage statistic is saved as `percent * 10000`
+const CPU_USAGE_SCALE: i64 = 10_000;
+
+/// Metric name, checked statistic, threshold and conversion of the statistic value to the threshold unit
+type AlertCheck = (&'static str, StatisticType, Option<i64>, fn(i64) -> i64);
+
+/// Limits of the statistics, `None` to disable an alert
+#[derive(Clone, Default)]
+pub struct AlertThresholds {
+    pub queue_size: Option<i64>,
+    pub db_size_gb: Option<i64>,
+    /// Percent of a CPU core
+    pub cpu_usage: Option<i64>,
+}
+
+impl AlertThresholds {
+    pub fn is_empty(&self) -> bool {
+        self.queue_size.is_none() && self.db_size_gb.is_none() && self.cpu_usage.is_none()
+    }
+}
+
+/// The JSON body sent to the webhook
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Alert {
+    pub metric: &'static str,
+    pub value: i64,
+    pub threshold: i64,
+    pub timestamp: i64,
+}
+
+/// Compare the last statistic values to the thresholds, returns the exceeded ones
+pub fn get_exceeded_alerts(
+    values: &HashMap<StatisticType, i64>,
+    thresholds: &AlertThresholds,
+    timestamp: i64,
+) -> Vec<Alert> {
+    let checks: [AlertCheck; 3] = [
+        (
+            "queue_size",
+            StatisticType::QueueSize,
+            thresholds.queue_size,
+            |v| v,
+        ),
+        (
+            "database_size",
+            StatisticType::DatabaseSize,
+            thresholds
+                .db_size_gb
+                .map(|gb| gb.saturating_mul(BYTES_PER_GB)),
+            |v| v,
+        ),
+        (
+            "cpu_usage",
+            StatisticType::CpuUsage,
+            thresholds.cpu_usage,
+            |v| v / CPU_USAGE_SCALE,
+        ),
+    ];
+
+    checks
+        .into_iter()
+        .filter_map(|(metric, statistic_type, threshold, convert)| {
+            let threshold = threshold?;
+            let value = convert(*values.get(&statistic_type)?);
+
+            (value > threshold).then_some(Alert {
+                metric,
+                value,
+                threshold,
+                timestamp,
+            })
+        })
+        .collect()
+}
+
+/// Track the available connections of the db pool, the alert is fired once per exhaustion
+#[derive(Default)]
+pub struct PoolAlertTracker {
+    /// Since when fewer than `POOL_AVAILABLE_MIN` connections are available
+    low_since: Option<Instant>,
+    fired: bool,
+}
+
+impl PoolAlertTracker {
+    /// Returns whether the alert must be fired
+    pub fn update(&mut self, available: u32, now: Instant) -> bool {
+        if available >= POOL_AVAILABLE_MIN {
+            self.low_since = None;
+            self.fired = false;
+            return false;
+        }
+
+        let low_since = *self.low_since.get_or_insert(now);
+        if self.fired || now.duration_since(low_since) <= POOL_ALERT_DELAY {
+            return false;
+        }
+
+        self.fired = true;
+        true
+    }
+}
+
+/// POST an alert to the webhook, retried with an exponential backoff
+pub async fn send_alert(client: &Client, webhook_url: &str, alert: &Alert) -> bool {
+    for attempt in 0..ALERT_WEBHOOK_ATTEMPTS {
+        if attempt > 0 {
+            sleep(ALERT_RETRY_BASE_DELAY * 2u32.pow(attempt - 1)).await;
+        }
+
+        match client.post(webhook_url).json(alert).send().await {
+            Ok(response) if response.status().is_success() => return true,
+            Ok(response) => eprintln!(
+                "[Monitor] The alert webhook responded {} (attempt {})",
+                response.status(),
+                attempt + 1
+            ),
+            Err(e) => eprintln!(
+                "[Monitor] Failed to call the alert webhook (attempt {}): {e}",
+                attempt + 1
+            ),
+        }
+    }
+
+    false
+}
+
+/// Send an alert in the background, the caller does not wait for the retries
+pub fn spawn_send_alert(client: &Client, webhook_url: &str, alert: Alert) {
+    let client = client.clone();
+    let webhook_url = webhook_url.to_string();
+
+    tokio::spawn(async move {
+        if !send_alert(&client, &webhook_url, &alert).await {
+            eprintln!("[Monitor] Failed to send the {} alert", alert.metric);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds() -> AlertThresholds {
+        AlertThresholds {
+            queue_size: Some(500_000),
+            db_size_gb: Some(100),
+            cpu_usage: Some(90),
+        }
+    }
+
+    #[test]
+    fn test_exceeded_alerts() {
+        let values = HashMap::from([
+            (StatisticType::QueueSize, 600_000),
+            (StatisticType::DatabaseSize, 50 * BYTES_PER_GB),
+            (StatisticType::CpuUsage, 95 * CPU_USAGE_SCALE),
+        ]);
+
+        assert_eq!(
+            get_exceeded_alerts(&values, &thresholds(), 42),
+            vec![
+                Alert {
+                    metric: "queue_size",
+                    value: 600_000,
+                    threshold: 500_000,
+                    timestamp: 42,
+                },
+                Alert {
+                    metric: "cpu_usage",
+                    value: 95,
+                    threshold: 90,
+                    timestamp: 42,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thresholds_are_inclusive() {
+        let values = HashMap::from([
+            (StatisticType::QueueSize, 500_000),
+            (StatisticType::DatabaseSize, 100 * BYTES_PER_GB),
+            (StatisticType::CpuUsage, 90 * CPU_USAGE_SCALE),
+        ]);
+        assert_eq!(
+            get_exceeded_alerts(&values, &thresholds(), 0).is_empty(),
+            true
+        );
+
+        let values = HashMap::from([(StatisticType::DatabaseSize, 100 * BYTES_PER_GB + 1)]);
+        assert_eq!(
+            get_exceeded_alerts(&values, &thresholds(), 0)[0].metric,
+            "database_size"
+        );
+    }
+
+    #[test]
+    fn test_disabled_alerts() {
+        let values = HashMap::from([(StatisticType::QueueSize, i64::MAX)]);
+
+        assert_eq!(
+            get_exceeded_alerts(&values, &AlertThresholds::default(), 0).is_empty(),
+            true
+        );
+        assert_eq!(AlertThresholds::default().is_empty(), true);
+        assert_eq!(thresholds().is_empty(), false);
+        // Missing statistics are ignored
+        assert_eq!(
+            get_exceeded_alerts(&HashMap::new(), &thresholds(), 0).is_empty(),
+            true
+        );
+    }
+
+    #[test]
+    fn test_alert_payload() {
+        let alert = Alert {
+            metric: "queue_size",
+            value: 600_000,
+            threshold: 500_000,
+            timestamp: 1_700_000_000_000,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&alert).unwrap(),
+            r#"{"metric":"queue_size","value":600000,"threshold":500000,"timestamp":1700000000000}"#
+        );
+    }
+
+    #[test]
+    fn test_pool_alert_tracker() {
+        let start = Instant::now();
+        let mut tracker = PoolAlertTracker::default();
+
+        assert_eq!(tracker.update(10, start), false);
+        assert_eq!(tracker.update(2, start), false);
+        assert_eq!(tracker.update(0, start + Duration::from_secs(60)), false);
+        assert_eq!(tracker.update(4, start + Duration::from_secs(61)), true);
+        // Fired once while the pool stays exhausted
+        assert_eq!(tracker.update(0, start + Duration::from_secs(120)), false);
+
+        // A recovery starts a new delay
+        assert_eq!(tracker.update(5, start + Duration::from_secs(125)), false);
+        assert_eq!(tracker.update(0, start + Duration::from_secs(130)), false);
+        assert_eq!(tracker.update(0, start + Duration::from_secs(191)), true);
+    }
+}
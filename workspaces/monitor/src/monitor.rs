@@ -1,13 +1,42 @@
+use crate::alerts::{
+    get_exceeded_alerts, spawn_send_alert, Alert, AlertThresholds, PoolAlertTracker,
+    ALERT_WEBHOOK_TIMEOUT, POOL_AVAILABLE_MIN, POOL_CHECK_INTERVAL,
+};
+use ::favicons::get_favicons_directory;
 use database::{
-    get_database_size,
+    get_available_connections, get_database_size,
     models::NewStatistic,
-    schema::{favicons, indexes, pages, queries, queue, statistics, words},
+    schema::{
+        crawl_errors, domain_stats, favicons, indexes, page_links, pages, pages_analytics_history,
+        queries, queue, statistics, visited_extra, words,
+    },
     types::StatisticType,
-    DbPool,
+    DbConn, DbPool,
+};
+use diesel::{
+    dsl::{exists, not, sql},
+    pg::Pg,
+    query_builder::QueryFragment,
+    query_dsl::{methods::ExecuteDsl, LoadQuery},
+    sql_query,
+    sql_types::{BigInt, Integer, Nullable},
+    BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, QueryResult, QueryableByName,
+    RunQueryDsl,
+};
+use reqwest::Client;
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs, io,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
-use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
-use std::{error::Error, sync::Arc, time::Duration};
-use sysinfo::{Pid, System};
+use sysinfo::{Disks, Pid, System};
 use tokio::{sync::Mutex, time::sleep};
 use utils::sql::get_sql_timestamp;
 
@@ -15,32 +44,130 @@ pub const MAX_ANALYTICS_AGE: i64 = 86_400_000 * 3;
 
 pub const MAX_SYSTEM_ANALYTICS_AGE: i64 = 86_400_000;
 
+pub const MAX_PAGES_HISTORY_AGE: i64 = 86_400_000 * 30;
+
+pub const MAX_CRAWL_ERRORS_AGE: i64 = 86_400_000 * 7;
+
+/// The failed URLs can be queued again after this delay (7 days)
+pub const MAX_VISITED_EXTRA_AGE: i64 = 86_400_000 * 7;
+
+/// The pending links to URLs which are not queued are purged after this delay (1 day),
+/// their URL is being crawled when they are younger
+pub const MAX_PENDING_LINKS_AGE: i64 = 86_400_000;
+
+/// Deleted pages are purged after this delay (30 days)
+pub const MAX_DELETED_PAGES_AGE: i64 = 86_400_000 * 30;
+
+/// Pages history entries are aggregated into buckets of this size (1 hour)
+pub const PAGES_HISTORY_BUCKET: i64 = 3_600_000;
+
+/// Pages crawled before this delay are crawled again (7 days)
+pub const DEFAULT_RECRAWL_TTL: i64 = 86_400_000 * 7;
+
+/// Number of stale pages queued per db call
+pub const RECRAWL_BATCH_SIZE: i64 = 1000;
+
+/// Minimum of the configurable intervals, in seconds
+pub const MIN_MONITOR_INTERVAL_SECS: u64 = 5;
+
+/// Delays between the monitor tasks
+#[derive(Clone, Copy)]
+pub struct MonitorIntervals {
+    /// System analytics
+    pub sys: Duration,
+    /// Database analytics
+    pub db: Duration,
+    /// Deletion of the old analytics
+    pub cleanup: Duration,
+    /// Delay before the first database analytics, cleanup and re-crawl
+    pub initial_delay: Duration,
+}
+
+impl Default for MonitorIntervals {
+    fn default() -> Self {
+        Self {
+            sys: Duration::from_secs(60),
+            db: Duration::from_secs(600),
+            cleanup: Duration::from_secs(3_600),
+            initial_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct RecrawlBatch {
+    /// The last page id of the batch, `None` when there are no more stale pages
+    #[diesel(sql_type = Nullable<Integer>)]
+    last_id: Option<i32>,
+    #[diesel(sql_type = BigInt)]
+    queued: i64,
+}
+
 /// Monitor the process and save analytics
 pub struct Monitor {
     db_pool: DbPool,
     system: System,
     current_pid: Pid,
+    /// Requests counter of the API, if it runs in the same process
+    api_request_count: Option<Arc<AtomicU64>>,
+    /// Refreshed favicons counter, if the favicons downloader runs in the same process
+    pub favicon_refresh_count: Option<Arc<AtomicU64>>,
+    /// Age of the pages to crawl again, in milliseconds
+    pub recrawl_ttl: i64,
+    pub intervals: MonitorIntervals,
+    pub alert_thresholds: AlertThresholds,
+    /// Receives a POST request for each fired alert
+    pub alert_webhook_url: Option<String>,
+    http_client: Client,
 }
 
 impl Monitor {
-    pub fn new(db_pool: DbPool) -> Self {
+    pub fn new(db_pool: DbPool, api_request_count: Option<Arc<AtomicU64>>) -> Self {
         let pid = sysinfo::get_current_pid().expect("Failed to get the current PID");
 
         Self {
             db_pool,
             system: System::new_all(),
             current_pid: pid,
+            api_request_count,
+            favicon_refresh_count: None,
+            recrawl_ttl: DEFAULT_RECRAWL_TTL,
+            intervals: MonitorIntervals::default(),
+            alert_thresholds: AlertThresholds::default(),
+            alert_webhook_url: None,
+            // A slow webhook must not delay the alerts
+            http_client: Client::builder()
+                .timeout(ALERT_WEBHOOK_TIMEOUT)
+                .build()
+                .expect("Failed to build the HTTP client"),
         }
     }
 
     pub async fn run(monitor: Monitor) {
         let monitor = Arc::new(Mutex::new(monitor));
 
-        // Run the system analytics each 60s
+        let MonitorIntervals {
+            sys,
+            db,
+            cleanup,
+            initial_delay,
+        } = monitor.lock().await.intervals;
+
+        // The alerts are sent without the monitor lock
+        let (db_pool, http_client, webhook_url) = {
+            let guard = monitor.lock().await;
+            (
+                guard.db_pool.clone(),
+                guard.http_client.clone(),
+                guard.alert_webhook_url.clone(),
+            )
+        };
+
+        // Run the system analytics each `sys` interval (60s)
         let monitor_clone = monitor.clone();
         let t1 = tokio::spawn(async move {
             loop {
-                sleep(Duration::from_secs(60)).await;
+                sleep(sys).await;
                 {
                     let guard = &mut monitor_clone.lock().await;
                     if let Err(e) = guard.save_sys_analytics() {
@@ -50,26 +177,37 @@ impl Monitor {
             }
         });
 
-        // Run the database analytics at start after 60s and every 10min
+        // Run the database analytics at start after the initial delay (60s) and every `db` interval (10min)
         let monitor_clone = monitor.clone();
+        let (t2_http_client, t2_webhook_url) = (http_client.clone(), webhook_url.clone());
         let t2 = tokio::spawn(async move {
-            sleep(Duration::from_secs(60)).await;
+            sleep(initial_delay).await;
 
             loop {
-                {
+                let alerts = {
                     let guard = monitor_clone.lock().await;
                     if let Err(e) = guard.save_db_analytics() {
                         eprintln!("[Monitor] Failed to monitor database: {e}");
                     }
+                    guard.check_alerts().unwrap_or_else(|e| {
+                        eprintln!("[Monitor] Failed to check the alerts: {e}");
+                        Vec::new()
+                    })
+                };
+                if let Some(webhook_url) = &t2_webhook_url {
+                    for alert in alerts {
+                        spawn_send_alert(&t2_http_client, webhook_url, alert);
+                    }
                 }
-                sleep(Duration::from_secs(600)).await;
+                sleep(db).await;
             }
         });
 
-        // Delete the old analytics at start after 60s and every hour
+        // Delete the old analytics and purge the deleted pages at start after the initial delay
+        // and every `cleanup` interval (1 hour)
         let monitor_clone = monitor.clone();
         let t3 = tokio::spawn(async move {
-            sleep(Duration::from_secs(60)).await;
+            sleep(initial_delay).await;
 
             loop {
                 {
@@ -77,12 +215,56 @@ impl Monitor {
                     if let Err(e) = guard.delete_old_analytics() {
                         eprintln!("[Monitor] Failed to delete old analytics: {e}");
                     }
+                    if let Err(e) = guard.purge_deleted_pages() {
+                        eprintln!("[Monitor] Failed to purge the deleted pages: {e}");
+                    }
+                }
+                sleep(cleanup).await;
+            }
+        });
+
+        // Queue the stale pages at start after the initial delay and every hour
+        let monitor_clone = monitor.clone();
+        let t4 = tokio::spawn(async move {
+            sleep(initial_delay).await;
+
+            loop {
+                {
+                    let guard = monitor_clone.lock().await;
+                    if let Err(e) = guard.queue_stale_pages() {
+                        eprintln!("[Monitor] Failed to queue the stale pages: {e}");
+                    }
                 }
                 sleep(Duration::from_secs(3_600)).await;
             }
         });
 
-        let _ = tokio::join!(t1, t2, t3);
+        // Check the available connections of the db pool every 5 seconds, without the monitor lock
+        let t5 = tokio::spawn(async move {
+            let mut tracker = PoolAlertTracker::default();
+
+            loop {
+                sleep(POOL_CHECK_INTERVAL).await;
+
+                let available = get_available_connections(&db_pool);
+                if !tracker.update(available, Instant::now()) {
+                    continue;
+                }
+
+                eprintln!("[Monitor] Alert: only {available} db connections are available");
+                let alert = Alert {
+                    metric: "db_pool_available",
+                    value: available as i64,
+                    threshold: POOL_AVAILABLE_MIN as i64,
+                    timestamp: get_sql_timestamp(),
+                };
+                if let Some(webhook_url) = &webhook_url {
+                    spawn_send_alert(&http_client, webhook_url, alert);
+                }
+            }
+        });
+
+        let _ = tokio::join!(t1, t2, t3, t4, t5);
     }
 
     fn save_sys_analytics(&mut self) -> QueryResult<()> {
@@ -114,22 +296,77 @@ impl Monitor {
         Ok(())
     }
 
+    /// Compare the last statistics to the alert thresholds, returns the exceeded ones to send
+    fn check_alerts(&self) -> QueryResult<Vec<Alert>> {
+        if self.alert_thresholds.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = &mut self.db_pool.get().unwrap();
+
+        // The last value of each checked statistic
+        let values = statistics::table
+            .filter(statistics::statistic_type.eq_any([
+                StatisticType::QueueSize,
+                StatisticType::DatabaseSize,
+                StatisticType::CpuUsage,
+            ]))
+            .distinct_on(statistics::statistic_type)
+            .select((statistics::statistic_type, statistics::value))
+            .order((statistics::statistic_type, statistics::timestamp.desc()))
+            .load::<(StatisticType, i64)>(conn)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        let now = get_sql_timestamp();
+        let alerts = get_exceeded_alerts(&values, &self.alert_thresholds, now);
+
+        for alert in &alerts {
+            eprintln!(
+                "[Monitor] Alert: {} is {} (threshold {})",
+                alert.metric, alert.value, alert.threshold
+            );
+        }
+
+        diesel::insert_into(statistics::table)
+            .values(NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::AlertFiredCount,
+                value: alerts.len() as i64,
+            })
+            .execute(conn)?;
+
+        Ok(alerts)
+    }
+
     fn save_db_analytics(&self) -> Result<(), Box<dyn Error>> {
         let conn = &mut self.db_pool.get().unwrap();
 
         let now = get_sql_timestamp();
 
-        let new_statistics = vec![
+        let mut new_statistics = vec![
             NewStatistic {
                 timestamp: now,
                 statistic_type: StatisticType::CrawledPageCount,
-                value: pages::table.count().get_result::<i64>(conn)?,
+                value: pages::table
+                    .filter(pages::deleted_at.is_null())
+                    .count()
+                    .get_result::<i64>(conn)?,
             },
             NewStatistic {
                 timestamp: now,
                 statistic_type: StatisticType::IndexedPageCount,
                 value: pages::table
                     .filter(pages::last_indexed.is_not_null())
+                    .filter(pages::deleted_at.is_null())
+                    .count()
+                    .get_result::<i64>(conn)?,
+            },
+            NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::DeletedPageCount,
+                value: pages::table
+                    .filter(pages::deleted_at.is_not_null())
                     .count()
                     .get_result::<i64>(conn)?,
             },
@@ -163,8 +400,61 @@ impl Monitor {
                 statistic_type: StatisticType::FaviconsCount,
                 value: favicons::table.count().get_result::<i64>(conn)?,
             },
+            NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::DomainCount,
+                value: domain_stats::table.count().get_result::<i64>(conn)?,
+            },
         ];
 
+        // No statistic without crawled pages
+        if let Some(median) = get_median_response_time_query().get_result::<Option<i32>>(conn)? {
+            new_statistics.push(NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::AverageResponseTime,
+                value: median.into(),
+            });
+        }
+
+        if let Some(api_request_count) = &self.api_request_count {
+            // Requests received since the last save
+            let count = api_request_count.swap(0, Ordering::Relaxed);
+
+            new_statistics.push(NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::ApiRequestCount,
+                value: count as i64,
+            });
+        }
+
+        if let Some(disk_usage) = get_disk_usage() {
+            new_statistics.push(NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::DiskUsage,
+                value: disk_usage as i64,
+            });
+        }
+
+        match get_directory_size(&get_favicons_directory()) {
+            Ok(size) => new_statistics.push(NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::FaviconDirSize,
+                value: size as i64,
+            }),
+            Err(e) => eprintln!("[Monitor] Failed to get the favicons directory size: {e}"),
+        }
+
+        if let Some(favicon_refresh_count) = &self.favicon_refresh_count {
+            // Favicons refreshed since the last save
+            let count = favicon_refresh_count.swap(0, Ordering::Relaxed);
+
+            new_statistics.push(NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::FaviconRefreshCount,
+                value: count as i64,
+            });
+        }
+
         diesel::insert_into(statistics::table)
             .values(new_statistics)
             .execute(conn)?;
@@ -172,6 +462,59 @@ impl Monitor {
         Ok(())
     }
 
+    /// Queue again the pages crawled before `recrawl_ttl`, and save their count
+    fn queue_stale_pages(&self) -> QueryResult<()> {
+        let now = get_sql_timestamp();
+        let conn = &mut self.db_pool.get().unwrap();
+
+        let mut last_id = 0;
+        let mut total = 0;
+
+        // Paginate on the id, pages dequeued by the crawler meanwhile are not queued twice
+        loop {
+            let batch = sql_query(
+                "WITH batch AS (
+                    SELECT id, domain, url, registered_domain
+                    FROM pages
+                    WHERE id > $1 AND last_crawled < $2 AND deleted_at IS NULL
+                    ORDER BY id
+                    LIMIT $3
+                ), queued AS (
+                    INSERT INTO queue (domain, url, timestamp, recrawl, registered_domain)
+                    SELECT domain, url, $4, true, registered_domain
+                    FROM batch b
+                    WHERE NOT EXISTS (SELECT 1 FROM queue q WHERE q.url = b.url)
+                    ON CONFLICT (url) DO NOTHING
+                    RETURNING 1
+                )
+                SELECT (SELECT MAX(id) FROM batch) AS last_id,
+                    (SELECT COUNT(*) FROM queued) AS queued",
+            )
+            .bind::<Integer, _>(last_id)
+            .bind::<BigInt, _>(now - self.recrawl_ttl)
+            .bind::<BigInt, _>(RECRAWL_BATCH_SIZE)
+            .bind::<BigInt, _>(now)
+            .get_result::<RecrawlBatch>(conn)?;
+
+            total += batch.queued;
+
+            match batch.last_id {
+                Some(id) => last_id = id,
+                None => break,
+            }
+        }
+
+        diesel::insert_into(statistics::table)
+            .values(NewStatistic {
+                timestamp: now,
+                statistic_type: StatisticType::RecrawlCount,
+                value: total,
+            })
+            .execute(conn)?;
+
+        Ok(())
+    }
+
     fn delete_old_analytics(&self) -> QueryResult<()> {
         let now = get_sql_timestamp();
         let conn = &mut self.db_pool.get().unwrap();
@@ -188,6 +531,311 @@ impl Monitor {
             .filter(statistics::timestamp.le(now - MAX_ANALYTICS_AGE))
             .execute(conn)?;
 
+        diesel::delete(pages_analytics_history::table)
+            .filter(pages_analytics_history::timestamp.le(now - MAX_PAGES_HISTORY_AGE))
+            .execute(conn)?;
+
+        diesel::delete(crawl_errors::table)
+            .filter(crawl_errors::timestamp.le(now - MAX_CRAWL_ERRORS_AGE))
+            .execute(conn)?;
+
+        diesel::delete(visited_extra::table)
+            .filter(visited_extra::timestamp.le(now - MAX_VISITED_EXTRA_AGE))
+            .execute(conn)?;
+
+        get_purge_pending_links_query(now - MAX_PENDING_LINKS_AGE).execute(conn)?;
+
+        // Merge the entries of each finished hour into a single entry per page and search.
+        // Merged entries are aligned on the hour, so they are not merged again
+        sql_query(
+            "WITH merged AS (
+                DELETE FROM pages_analytics_history
+                WHERE timestamp < $1 AND timestamp % $2 <> 0
+                RETURNING page_id, search_id, clicks_delta, impressions_delta, timestamp
+            )
+            INSERT INTO pages_analytics_history
+                (page_id, search_id, clicks_delta, impressions_delta, timestamp)
+            SELECT page_id, search_id, SUM(clicks_delta)::int4, SUM(impressions_delta)::int4,
+                (timestamp / $2) * $2
+            FROM merged
+            GROUP BY page_id, search_id, 5",
+        )
+        .bind::<diesel::sql_types::BigInt, _>(now - now % PAGES_HISTORY_BUCKET)
+        .bind::<diesel::sql_types::BigInt, _>(PAGES_HISTORY_BUCKET)
+        .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Delete the pages deleted before `MAX_DELETED_PAGES_AGE`, with their unused favicons
+    fn purge_deleted_pages(&self) -> QueryResult<()> {
+        let now = get_sql_timestamp();
+        let conn = &mut self.db_pool.get().unwrap();
+
+        // The rows referencing the pages are deleted by cascade
+        let (page_count, local_paths) = conn.transaction(|conn| {
+            let favicon_ids = get_purge_deleted_pages_query(now - MAX_DELETED_PAGES_AGE)
+                .get_results::<i32>(conn)?;
+            let local_paths = get_delete_unused_favicons_query(&favicon_ids)
+                .get_results::<Option<String>>(conn)?;
+
+            QueryResult::Ok((favicon_ids.len(), local_paths))
+        })?;
+
+        remove_favicon_files(&get_favicons_directory(), local_paths);
+
+        if page_count > 0 {
+            println!("[Monitor] Purged {page_count} deleted pages");
+        }
+
         Ok(())
     }
 }
+
+/// Get the median response time of the pages, in milliseconds.
+/// The median is not skewed by the few websites which time out.
+fn get_median_response_time_query(
+) -> impl LoadQuery<'static, DbConn, Option<i32>> + QueryFragment<Pg> {
+    pages::table
+        .filter(pages::deleted_at.is_null())
+        .select(sql::<Nullable<Integer>>(
+            "percentile_disc(0.5) WITHIN GROUP (ORDER BY response_time)",
+        ))
+}
+
+/// Delete the pages deleted before `before`, returns their favicon ids
+fn get_purge_deleted_pages_query(
+    before: i64,
+) -> impl LoadQuery<'static, DbConn, i32> + QueryFragment<Pg> {
+    diesel::delete(pages::table)
+        .filter(pages::deleted_at.lt(before))
+        .returning(pages::favicon_id)
+}
+
+/// Delete the pending links saved before `before` to URLs which are not queued,
+/// the blacklisted, failed or too deep URLs are never crawled so their links never resolve
+fn get_purge_pending_links_query(
+    before: i64,
+) -> impl RunQueryDsl<DbConn> + ExecuteDsl<DbConn> + QueryFragment<Pg> {
+    diesel::delete(page_links::table)
+        .filter(page_links::timestamp.lt(before))
+        .filter(not(exists(
+            queue::table.filter(queue::url.eq(page_links::to_url)),
+        )))
+}
+
+/// Delete the favicons no page uses anymore, returns their downloaded files.
+/// Deleting a favicon cascades to its pages.
+fn get_delete_unused_favicons_query(
+    favicon_ids: &[i32],
+) -> impl LoadQuery<'_, DbConn, Option<String>> + QueryFragment<Pg> {
+    diesel::delete(favicons::table)
+        .filter(favicons::id.eq_any(favicon_ids))
+        .filter(not(exists(
+            pages::table.filter(pages::favicon_id.eq(favicons::id)),
+        )))
+        .returning(favicons::local_path)
+}
+
+/// Remove the downloaded files of the deleted favicons, only from the favicons directory
+fn remove_favicon_files(directory: &Path, local_paths: Vec<Option<String>>) {
+    for local_path in local_paths.into_iter().flatten() {
+        if let Some(file_name) = Path::new(&local_path).file_name() {
+            let _ = fs::remove_file(directory.join(file_name));
+        }
+    }
+}
+
+/// Get the used bytes of the filesystem of the working directory
+fn get_disk_usage() -> Option<u64> {
+    let cwd = env::current_dir().ok()?;
+    let disks = Disks::new_with_refreshed_list();
+
+    // The disk with the most specific mount point
+    let disk = disks
+        .list()
+        .iter()
+        .filter(|disk| cwd.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())?;
+
+    Some(disk.total_space().saturating_sub(disk.available_space()))
+}
+
+/// Sum the size of the files of a directory and its subdirectories.
+/// A missing directory is empty.
+fn get_directory_size(path: &Path) -> io::Result<u64> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let mut size = 0;
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            size += get_directory_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::debug_query;
+
+    #[test]
+    fn test_default_intervals() {
+        let intervals = MonitorIntervals::default();
+        let min = Duration::from_secs(MIN_MONITOR_INTERVAL_SECS);
+
+        assert_eq!(intervals.sys >= min, true);
+        assert_eq!(intervals.db, Duration::from_secs(600));
+        assert_eq!(intervals.cleanup, Duration::from_secs(3_600));
+        assert_eq!(intervals.initial_delay, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_median_response_time_query() {
+        let sql = debug_query::<Pg, _>(&get_median_response_time_query()).to_string();
+
+        assert_eq!(
+            sql.contains(
+                "SELECT percentile_disc(0.5) WITHIN GROUP (ORDER BY response_time) FROM \"pages\""
+            ),
+            true
+        );
+        assert_eq!(sql.contains(r#"("pages"."deleted_at" IS NULL)"#), true);
+    }
+
+    #[test]
+    fn test_purge_deleted_pages_queries() {
+        let sql =
+            debug_query::<Pg, _>(&get_purge_deleted_pages_query(1_700_000_000_000)).to_string();
+        assert_eq!(
+            sql.contains(r#"DELETE FROM "pages" WHERE ("pages"."deleted_at" < $1)"#),
+            true
+        );
+        assert_eq!(sql.contains(r#"RETURNING "pages"."favicon_id""#), true);
+        assert_eq!(sql.contains("binds: [1700000000000]"), true);
+
+        let favicon_ids = [3, 4];
+        let sql = debug_query::<Pg, _>(&get_delete_unused_favicons_query(&favicon_ids)).to_string();
+        // Never cascade to the pages still using the favicon
+        assert_eq!(sql.contains(r#"NOT (EXISTS (SELECT "pages"."#), true);
+        assert_eq!(
+            sql.contains(r#"("pages"."favicon_id" = "favicons"."id")"#),
+            true
+        );
+        assert_eq!(sql.contains(r#"RETURNING "favicons"."local_path""#), true);
+    }
+
+    #[test]
+    fn test_purge_pending_links_query() {
+        let sql =
+            debug_query::<Pg, _>(&get_purge_pending_links_query(1_700_000_000_000)).to_string();
+
+        assert_eq!(
+            sql.contains(r#"DELETE FROM "page_links" WHERE (("page_links"."timestamp" < $1)"#),
+            true
+        );
+        // The links to the queued URLs are resolved once they are crawled
+        assert_eq!(sql.contains(r#"NOT (EXISTS (SELECT "queue"."#), true);
+        assert_eq!(
+            sql.contains(r#"WHERE ("queue"."url" = "page_links"."to_url")"#),
+            true
+        );
+        assert_eq!(sql.contains("binds: [1700000000000]"), true);
+    }
+
+    #[test]
+    fn test_purged_pages_cascade() {
+        let migrations = [
+            include_str!("../../../migrations/2025-03-16-005145_create_epsilon_tables/up.sql"),
+            include_str!("../../../migrations/2026-10-16-102200_create_page_alternates/up.sql"),
+            include_str!("../../../migrations/2026-10-16-102500_create_page_links/up.sql"),
+        ];
+        let references = migrations
+            .iter()
+            .flat_map(|sql| sql.split("CREATE TABLE ").skip(1))
+            .flat_map(|table| {
+                let name = table.split_whitespace().next().unwrap();
+                table
+                    .lines()
+                    .filter(|line| line.contains("REFERENCES pages(id)"))
+                    .map(move |line| (name, line.trim()))
+            })
+            .collect::<Vec<_>>();
+
+        // The links from and to the page, its language variants and its pending links
+        for (table, column) in [
+            ("links", "from_page_id"),
+            ("links", "to_page_id"),
+            ("page_alternates", "page_id"),
+            ("page_links", "from_page_id"),
+        ] {
+            assert_eq!(
+                references
+                    .iter()
+                    .any(|(name, line)| *name == table && line.starts_with(column)),
+                true
+            );
+        }
+        assert_eq!(
+            references
+                .iter()
+                .all(|(_, line)| line.contains("ON DELETE CASCADE")),
+            true
+        );
+    }
+
+    #[test]
+    fn test_remove_favicon_files() {
+        let directory = env::temp_dir().join(format!("epsilon-favicons-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("1-1700000000000.png"), [0; 10]).unwrap();
+        fs::write(directory.join("2-1700000000000.png"), [0; 10]).unwrap();
+
+        remove_favicon_files(
+            &directory,
+            vec![
+                Some("favicons/1-1700000000000.png".to_string()),
+                // Never downloaded
+                None,
+                Some("favicons/3-1700000000000.png".to_string()),
+            ],
+        );
+
+        // The favicon still used by a page is kept
+        assert_eq!(directory.join("1-1700000000000.png").exists(), false);
+        assert_eq!(directory.join("2-1700000000000.png").exists(), true);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_get_directory_size() {
+        let directory = env::temp_dir().join(format!("epsilon-monitor-{}", std::process::id()));
+        fs::create_dir_all(directory.join("nested")).unwrap();
+        fs::write(directory.join("1-1700000000000.png"), [0; 100]).unwrap();
+        fs::write(directory.join("2-1700000000000.png"), [0; 50]).unwrap();
+        fs::write(directory.join("nested").join("file"), [0; 10]).unwrap();
+
+        assert_eq!(get_directory_size(&directory).unwrap(), 160);
+        assert_eq!(get_directory_size(&directory.join("nested")).unwrap(), 10);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_get_directory_size_missing() {
+        let directory = env::temp_dir().join("epsilon-monitor-missing-directory");
+        assert_eq!(get_directory_size(&directory).unwrap(), 0);
+    }
+}
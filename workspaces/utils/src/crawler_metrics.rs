@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicI64, AtomicUsize, Ordering},
+    Mutex,
+};
+
+/// Number of samples of the crawl speed average, one per second
+pub const SPEED_AVERAGE_WINDOW: usize = 10;
+
+/// Number of domains listed in the active domains
+pub const ACTIVE_DOMAINS_LIMIT: usize = 20;
+
+/// Live state of the crawler, shared with the API when both services run in the same process
+#[derive(Default)]
+pub struct CrawlerMetrics {
+    /// Approximate number of visited URLs
+    pub visited_count: AtomicUsize,
+    /// Crawled pages per second, averaged on the last seconds, in thousandths
+    pub pages_per_second_milli: AtomicI64,
+    /// Number of websites kept in memory, with their robots and rate limits
+    pub websites_tracked: AtomicUsize,
+    /// The domains crawled most recently, the last one first
+    pub active_domains: Mutex<Vec<String>>,
+}
+
+impl CrawlerMetrics {
+    pub fn set_pages_per_second(&self, pages_per_second: f32) {
+        self.pages_per_second_milli.store(
+            (pages_per_second * 1000.0).round() as i64,
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn get_pages_per_second(&self) -> f32 {
+        self.pages_per_second_milli.load(Ordering::Relaxed) as f32 / 1000.0
+    }
+}
+
+/// Rolling average of the last `SPEED_AVERAGE_WINDOW` crawl speeds
+#[derive(Default)]
+pub struct SpeedAverage {
+    samples: VecDeque<f32>,
+}
+
+impl SpeedAverage {
+    /// Add a sample, returns the new average
+    pub fn push(&mut self, pages_per_second: f32) -> f32 {
+        if self.samples.len() == SPEED_AVERAGE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(pages_per_second);
+
+        self.samples.iter().sum::<f32>() / self.samples.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_per_second() {
+        let metrics = CrawlerMetrics::default();
+        assert_eq!(metrics.get_pages_per_second(), 0.0);
+
+        metrics.set_pages_per_second(12.5);
+        assert_eq!(metrics.get_pages_per_second(), 12.5);
+        metrics.set_pages_per_second(0.0004);
+        assert_eq!(metrics.get_pages_per_second(), 0.0);
+    }
+
+    #[test]
+    fn test_speed_average() {
+        let mut average = SpeedAverage::default();
+
+        assert_eq!(average.push(10.0), 10.0);
+        assert_eq!(average.push(20.0), 15.0);
+
+        // Only the last samples are kept
+        for _ in 0..SPEED_AVERAGE_WINDOW {
+            average.push(4.0);
+        }
+        assert_eq!(average.push(4.0), 4.0);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::sql::get_sql_timestamp;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
+use tokio::sync::Notify;
+
+/// Live state of the indexer, shared with the API when both services run in the same process
+#[derive(Default)]
+pub struct IndexerState {
+    /// Wakes up the indexer when it waits for new pages to index
+    pub trigger: Notify,
+    /// Timestamp of the end of the last run, 0 before the first one
+    pub last_run_at: AtomicI64,
+    /// Pages indexed by the last run
+    pub last_indexed_count: AtomicUsize,
+    pub is_running: AtomicBool,
+}
+
+impl IndexerState {
+    pub fn start_run(&self) {
+        self.is_running.store(true, Ordering::Relaxed);
+    }
+
+    pub fn finish_run(&self, indexed_count: usize) {
+        self.last_indexed_count
+            .store(indexed_count, Ordering::Relaxed);
+        self.last_run_at
+            .store(get_sql_timestamp(), Ordering::Relaxed);
+        self.is_running.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_indexer_run() {
+        let state = IndexerState::default();
+        assert_eq!(state.last_run_at.load(Ordering::Relaxed), 0);
+
+        state.start_run();
+        assert!(state.is_running.load(Ordering::Relaxed));
+
+        state.finish_run(12);
+        assert!(!state.is_running.load(Ordering::Relaxed));
+        assert_eq!(state.last_indexed_count.load(Ordering::Relaxed), 12);
+        assert!(state.last_run_at.load(Ordering::Relaxed) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_before_wait() {
+        let state = IndexerState::default();
+
+        // Triggered during a run, the next wait returns at once
+        state.trigger.notify_one();
+        tokio::time::timeout(Duration::from_secs(1), state.trigger.notified())
+            .await
+            .unwrap();
+    }
+}
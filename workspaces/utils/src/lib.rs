@@ -1,8 +1,51 @@
+use rust_stemmers::{Algorithm, Stemmer};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use unicode_normalization::UnicodeNormalization;
+use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
+use unicode_segmentation::UnicodeSegmentation;
 
+pub mod crawler_metrics;
+pub mod indexer_state;
+pub mod shutdown;
 pub mod sql;
+pub mod stopwords;
 pub mod url;
 
+/// Reduce an English word to its stem with the Porter algorithm, e.g. "running" -> "run".
+/// Used by the indexer and the search, so both store and look up the same form.
+pub fn stem_word(word: &str) -> String {
+    Stemmer::create(Algorithm::English).stem(word).to_string()
+}
+
+/// Remove the diacritics of a text, e.g. "café" -> "cafe".
+/// The text is decomposed (NFD) without its nonspacing marks (`Mn`), then recomposed (NFC).
+/// Used by the indexer and the search, so "café" and "cafe" are the same word.
+pub fn normalize_text(input: &str) -> String {
+    input
+        .nfd()
+        .filter(|c| c.general_category() != GeneralCategory::NonspacingMark)
+        .nfc()
+        .collect()
+}
+
+/// Split a text at the Unicode word boundaries, which also separates the words of the scripts
+/// without spaces (e.g. CJK). Used by the indexer and the search, so both split the same words.
+pub fn split_words(text: &str) -> impl Iterator<Item = &str> {
+    text.unicode_words()
+}
+
+/// Get the primary subtag of a BCP-47 language tag (e.g. `en-US` => `en`).
+/// Used by the crawler and the search, so both store and filter the same language.
+pub fn normalize_language_tag(tag: &str) -> Option<String> {
+    let primary = tag.trim().split(['-', '_']).next()?;
+
+    if (2..=8).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(primary.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
 pub fn get_timestamp() -> Duration {
     let start = SystemTime::now();
     let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap();
@@ -23,10 +66,149 @@ pub fn safe_slice(s: &str, max_bytes: usize) -> &str {
     &s[..end]
 }
 
+/// Levenshtein distance between two strings, in characters
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+    let mut current = vec![0; b_chars.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b_chars.len()]
+}
+
+/// Excerpt of the first sentence of `content` containing one of the lowercase `query_words`,
+/// with the query words wrapped in `**...**`.
+/// Long sentences are cut around the first match to `max_chars` characters (without the markers).
+pub fn generate_snippet(content: &str, query_words: &[&str], max_chars: usize) -> Option<String> {
+    let is_query_word = |token: &str| {
+        let word =
+            normalize_text(token.trim_matches(|c: char| !c.is_alphanumeric())).to_lowercase();
+        query_words.contains(&word.as_str())
+    };
+
+    let sentence = content
+        .split_inclusive(['.', '!', '?', '\n'])
+        .find(|s| s.split_whitespace().any(is_query_word))?;
+
+    let tokens: Vec<&str> = sentence.split_whitespace().collect();
+    let first_match = tokens.iter().position(|t| is_query_word(t))?;
+
+    // Keep some context before the first match
+    let mut start = first_match;
+    let mut len = tokens[first_match].chars().count();
+    while start > 0 && len + tokens[start - 1].chars().count() < max_chars / 2 {
+        start -= 1;
+        len += tokens[start].chars().count() + 1;
+    }
+
+    let mut end = first_match + 1;
+    while end < tokens.len() && len + tokens[end].chars().count() < max_chars {
+        len += tokens[end].chars().count() + 1;
+        end += 1;
+    }
+
+    let mut snippet = tokens[start..end]
+        .iter()
+        .map(|token| {
+            if !is_query_word(token) {
+                return token.to_string();
+            }
+
+            // Keep the punctuation out of the markers
+            let word_start = token.find(char::is_alphanumeric).unwrap_or(0);
+            let word_end = token
+                .rfind(char::is_alphanumeric)
+                .map(|i| i + token[i..].chars().next().map_or(0, char::len_utf8))
+                .unwrap_or(token.len());
+            format!(
+                "{}**{}**{}",
+                &token[..word_start],
+                &token[word_start..word_end],
+                &token[word_end..]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if start > 0 {
+        snippet.insert_str(0, "... ");
+    }
+    if end < tokens.len() {
+        snippet.push_str(" ...");
+    }
+
+    Some(snippet)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_stem_word() {
+        assert_eq!(stem_word("running"), "run");
+        assert_eq!(stem_word("runs"), "run");
+        assert_eq!(stem_word("rust"), "rust");
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        // French
+        assert_eq!(normalize_text("café"), "cafe");
+        assert_eq!(
+            normalize_text("Élève à l'école, garçon"),
+            "Eleve a l'ecole, garcon"
+        );
+        assert_eq!(normalize_text("cafe\u{301}"), "cafe");
+        // German, ß is a letter and not a diacritic
+        assert_eq!(normalize_text("Über die Größe"), "Uber die Große");
+        // Spanish
+        assert_eq!(normalize_text("¿Cómo está el niño?"), "¿Como esta el nino?");
+        // Arabic, the short vowels and the hamza are removed
+        assert_eq!(normalize_text("مَرْحَبًا"), "مرحبا");
+        assert_eq!(normalize_text("أحمد"), "احمد");
+        // The Hangul syllables are recomposed
+        assert_eq!(normalize_text("한국어"), "한국어");
+        assert_eq!(normalize_text("epsilon"), "epsilon");
+        assert_eq!(normalize_text(""), "");
+    }
+
+    #[test]
+    fn test_split_words() {
+        let words = |text| split_words(text).collect::<Vec<_>>();
+
+        assert_eq!(
+            words("The web, the crawler."),
+            ["The", "web", "the", "crawler"]
+        );
+        assert_eq!(words("open-source l'ecole"), ["open", "source", "l'ecole"]);
+        assert_eq!(words("مرحبا بالعالم"), ["مرحبا", "بالعالم"]);
+        // No spaces between the CJK words
+        assert_eq!(words("東京タワー"), ["東", "京", "タワー"]);
+        assert_eq!(words("  "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_normalize_language_tag() {
+        assert_eq!(normalize_language_tag("en"), Some("en".into()));
+        assert_eq!(normalize_language_tag(" en-US "), Some("en".into()));
+        assert_eq!(normalize_language_tag("FR_ca"), Some("fr".into()));
+        assert_eq!(normalize_language_tag(""), None);
+        assert_eq!(normalize_language_tag("e"), None);
+        assert_eq!(normalize_language_tag("e1"), None);
+        assert_eq!(normalize_language_tag("{{ lang }}"), None);
+    }
+
     #[test]
     fn test_safe_slice() {
         assert_eq!(safe_slice("abc123", 6), "abc123");
@@ -39,4 +221,52 @@ mod tests {
         assert_eq!(safe_slice("abc12é", 6), "abc12");
         assert_eq!(safe_slice("éééééé", 6), "ééé");
     }
+
+    #[test]
+    fn test_generate_snippet() {
+        let content = "Welcome to the blog. Rust is a systems language! Learn Rust and Go today.";
+
+        assert_eq!(
+            generate_snippet(content, &["rust"], 100),
+            Some("**Rust** is a systems language!".to_string())
+        );
+        assert_eq!(
+            generate_snippet(content, &["go", "learn"], 100),
+            Some("**Learn** Rust and **Go** today.".to_string())
+        );
+        assert_eq!(
+            generate_snippet(content, &["systems", "blog"], 100),
+            Some("Welcome to the **blog**.".to_string())
+        );
+        assert_eq!(generate_snippet(content, &["python"], 100), None);
+        assert_eq!(generate_snippet("", &["rust"], 100), None);
+    }
+
+    #[test]
+    fn test_generate_snippet_max_chars() {
+        let content =
+            "one two three four five six seven eight nine ten rust eleven twelve thirteen";
+
+        assert_eq!(
+            generate_snippet(content, &["rust"], 30),
+            Some("... nine ten **rust** eleven twelve ...".to_string())
+        );
+        // Shorter than max_chars
+        assert_eq!(
+            generate_snippet("rust", &["rust"], 30),
+            Some("**rust**".to_string())
+        );
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("rust", "rust"), 0);
+        assert_eq!(levenshtein("", "rust"), 4);
+        assert_eq!(levenshtein("rust", ""), 4);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("rsut", "rust"), 2);
+        assert_eq!(levenshtein("rustt", "rust"), 1);
+        assert_eq!(levenshtein("café", "cafe"), 1);
+    }
 }
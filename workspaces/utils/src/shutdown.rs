@@ -0,0 +1,53 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tokio::time::sleep;
+
+/// Delay between two checks of the shutdown flag
+pub const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub fn is_shutting_down(shutdown: &AtomicBool) -> bool {
+    shutdown.load(Ordering::Relaxed)
+}
+
+/// Wait until the shutdown flag is set
+pub async fn wait_for_shutdown(shutdown: &AtomicBool) {
+    while !is_shutting_down(shutdown) {
+        sleep(SHUTDOWN_POLL_INTERVAL).await;
+    }
+}
+
+/// Sleep for `duration`, returns `false` if the shutdown flag is set before the end
+pub async fn sleep_or_shutdown(duration: Duration, shutdown: &AtomicBool) -> bool {
+    tokio::select! {
+        _ = sleep(duration) => true,
+        _ = wait_for_shutdown(shutdown) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_sleep_or_shutdown() {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        assert_eq!(
+            sleep_or_shutdown(Duration::from_millis(10), &shutdown).await,
+            true
+        );
+
+        let shutdown_clone = shutdown.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            shutdown_clone.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(
+            sleep_or_shutdown(Duration::from_secs(60), &shutdown).await,
+            false
+        );
+        assert_eq!(is_shutting_down(&shutdown), true);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+
+/// The most common English stop words
+pub const STOP_WORDS: &[&str] = &[
+    "a",
+    "about",
+    "above",
+    "after",
+    "again",
+    "against",
+    "all",
+    "also",
+    "although",
+    "am",
+    "an",
+    "and",
+    "another",
+    "any",
+    "are",
+    "as",
+    "at",
+    "be",
+    "because",
+    "been",
+    "before",
+    "being",
+    "below",
+    "between",
+    "both",
+    "but",
+    "by",
+    "can",
+    "could",
+    "did",
+    "do",
+    "does",
+    "doing",
+    "down",
+    "during",
+    "each",
+    "either",
+    "every",
+    "few",
+    "for",
+    "from",
+    "further",
+    "had",
+    "has",
+    "have",
+    "having",
+    "he",
+    "her",
+    "here",
+    "hers",
+    "herself",
+    "him",
+    "himself",
+    "his",
+    "how",
+    "however",
+    "i",
+    "if",
+    "in",
+    "into",
+    "is",
+    "it",
+    "its",
+    "itself",
+    "just",
+    "may",
+    "me",
+    "might",
+    "more",
+    "most",
+    "much",
+    "must",
+    "my",
+    "myself",
+    "neither",
+    "no",
+    "nor",
+    "not",
+    "now",
+    "of",
+    "off",
+    "on",
+    "once",
+    "only",
+    "onto",
+    "or",
+    "other",
+    "our",
+    "ours",
+    "ourselves",
+    "out",
+    "over",
+    "own",
+    "per",
+    "same",
+    "shall",
+    "she",
+    "should",
+    "since",
+    "so",
+    "some",
+    "such",
+    "than",
+    "that",
+    "the",
+    "their",
+    "theirs",
+    "them",
+    "themselves",
+    "then",
+    "there",
+    "these",
+    "they",
+    "this",
+    "those",
+    "though",
+    "through",
+    "to",
+    "too",
+    "under",
+    "until",
+    "up",
+    "upon",
+    "us",
+    "very",
+    "via",
+    "was",
+    "we",
+    "were",
+    "what",
+    "when",
+    "where",
+    "whether",
+    "which",
+    "while",
+    "who",
+    "whom",
+    "whose",
+    "why",
+    "will",
+    "with",
+    "within",
+    "without",
+    "would",
+    "yet",
+    "you",
+    "your",
+    "yours",
+    "yourself",
+    "yourselves",
+];
+
+/// Check if a lowercase word is an English stop word
+pub fn is_stopword(word: &str) -> bool {
+    STOP_WORDS.contains(&word)
+}
+
+/// Get the default stop words, and the ones of a newline-separated list
+pub fn get_stop_words(extra: Option<&str>) -> HashSet<String> {
+    let mut stop_words: HashSet<String> = STOP_WORDS.iter().map(|w| w.to_string()).collect();
+
+    if let Some(extra) = extra {
+        stop_words.extend(
+            extra
+                .lines()
+                .map(|w| w.trim().to_lowercase())
+                .filter(|w| !w.is_empty() && !w.starts_with('#')),
+        );
+    }
+
+    stop_words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_words() {
+        assert_eq!(STOP_WORDS.len(), 150);
+
+        let unique: HashSet<&&str> = STOP_WORDS.iter().collect();
+        assert_eq!(unique.len(), STOP_WORDS.len());
+    }
+
+    #[test]
+    fn test_is_stopword() {
+        assert_eq!(is_stopword("the"), true);
+        assert_eq!(is_stopword("a"), true);
+        assert_eq!(is_stopword("is"), true);
+        assert_eq!(is_stopword("rust"), false);
+        assert_eq!(is_stopword("crawler"), false);
+        assert_eq!(is_stopword("The"), false);
+    }
+
+    #[test]
+    fn test_get_stop_words() {
+        let stop_words = get_stop_words(Some("# Custom words\nLorem\n\n ipsum \n"));
+
+        assert_eq!(stop_words.contains("the"), true);
+        assert_eq!(stop_words.contains("lorem"), true);
+        assert_eq!(stop_words.contains("ipsum"), true);
+        assert_eq!(stop_words.contains("# custom words"), false);
+        assert_eq!(stop_words.contains("rust"), false);
+        assert_eq!(get_stop_words(None).len(), STOP_WORDS.len());
+    }
+}
@@ -1,10 +1,45 @@
+use md5::{Digest, Md5};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use publicsuffix::{List, Psl};
+use std::sync::LazyLock;
 use url::{ParseError, Url};
 
-pub fn normalize_url(url: &str) -> Option<(Url, String)> {
+/// Mozilla's Public Suffix List, from https://publicsuffix.org/list/public_suffix_list.dat
+static PUBLIC_SUFFIX_LIST: LazyLock<List> = LazyLock::new(|| {
+    include_str!("../data/public_suffix_list.dat")
+        .parse()
+        .expect("Invalid public suffix list")
+});
+
+/// Characters encoded in a path segment of a fingerprinted URL
+const PATH_SEGMENT_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Normalize a URL and get its domain, `None` if the URL has no domain
+///
+/// With `strip_www`, the `www.` prefix is removed from the domain, so a website is stored under
+/// a single domain. Callers pass `true` unless they need the raw domain.
+/// The URL keeps its host, some websites are only served with the `www.` one.
+pub fn normalize_url(url: &str, strip_www: bool) -> Option<(Url, String)> {
     if let Ok(mut normalized_url) = Url::parse(url) {
         normalized_url.set_query(None);
         normalized_url.set_fragment(None);
         if let Some(domain) = normalized_url.clone().domain() {
+            let domain = if strip_www {
+                strip_www_prefix(domain)
+            } else {
+                domain
+            };
             Some((normalized_url, domain.to_string()))
         } else {
             None
@@ -14,6 +49,120 @@ pub fn normalize_url(url: &str) -> Option<(Url, String)> {
     }
 }
 
+/// Remove the `www.` prefix of a domain, unless only a TLD would be left (e.g. `www.com`)
+pub fn strip_www_prefix(domain: &str) -> &str {
+    match domain.strip_prefix("www.") {
+        Some(stripped) if stripped.contains('.') => stripped,
+        _ => domain,
+    }
+}
+
+/// Get the same URL with the `www.` prefix added to or removed from its host
+pub fn get_www_alternate_url(url: &str) -> Option<String> {
+    let mut url = Url::parse(url).ok()?;
+    let domain = url.domain()?;
+
+    let stripped = strip_www_prefix(domain);
+    let alternate = if stripped != domain {
+        stripped.to_string()
+    } else if domain.contains('.') && !domain.starts_with("www.") {
+        format!("www.{domain}")
+    } else {
+        return None;
+    };
+
+    url.set_host(Some(&alternate)).ok()?;
+    Some(url.to_string())
+}
+
+/// Parts of a domain, e.g. `blog.example.co.uk`
+#[derive(Debug, PartialEq, Clone)]
+pub struct DomainParts {
+    /// `blog`, `None` for the registered domain itself
+    pub subdomain: Option<String>,
+    /// Second-level domain, `example`
+    pub sld: String,
+    /// Public suffix, `co.uk`
+    pub tld: String,
+    /// `example.co.uk`, the domain bought from a registrar
+    pub registered_domain: String,
+}
+
+/// Split a domain with the public suffix list, `None` if it has no registered domain
+/// (e.g. `localhost`, `co.uk` or an IP address)
+pub fn extract_domain_parts(domain: &str) -> Option<DomainParts> {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    if domain.parse::<std::net::IpAddr>().is_ok() {
+        return None;
+    }
+
+    let registered = PUBLIC_SUFFIX_LIST.domain(domain.as_bytes())?;
+    let registered_domain = std::str::from_utf8(registered.as_bytes()).ok()?;
+    let tld = std::str::from_utf8(registered.suffix().as_bytes()).ok()?;
+
+    let sld = registered_domain.strip_suffix(tld)?.trim_end_matches('.');
+    let subdomain = domain
+        .strip_suffix(registered_domain)?
+        .strip_suffix('.')
+        .map(String::from);
+
+    Some(DomainParts {
+        subdomain,
+        sld: sld.to_string(),
+        tld: tld.to_string(),
+        registered_domain: registered_domain.to_string(),
+    })
+}
+
+/// Get the registered domain of a domain, e.g. `blog.example.co.uk` => `example.co.uk`
+pub fn get_registered_domain(domain: &str) -> Option<String> {
+    extract_domain_parts(domain).map(|x| x.registered_domain)
+}
+
+/// Get the canonical form of a URL, the same for the URLs which are the same page:
+/// lowercase host, no default port, sorted query parameters, consistent percent-encoding
+/// and no trailing slash
+fn canonicalize_url(url: &str) -> Option<String> {
+    // The parser lowercases the host and removes the default ports
+    let url = Url::parse(url.trim()).ok()?;
+    let host = url.host_str()?;
+
+    let path = url
+        .path()
+        .split('/')
+        .map(|segment| {
+            let decoded = percent_decode_str(segment).decode_utf8_lossy();
+            utf8_percent_encode(&decoded, PATH_SEGMENT_ENCODE_SET).to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let mut canonical = format!("{}://{host}", url.scheme());
+    if let Some(port) = url.port() {
+        canonical.push_str(&format!(":{port}"));
+    }
+    canonical.push_str(path.trim_end_matches('/'));
+
+    let mut params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    if !params.is_empty() {
+        params.sort();
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        canonical.push('?');
+        canonical.push_str(&query);
+    }
+
+    Some(canonical)
+}
+
+/// Get the MD5 hex fingerprint of a URL, the same for the URLs which are the same page
+/// (see `canonicalize_url`). The URLs which cannot be parsed are hashed as is.
+pub fn url_fingerprint(url: &str) -> String {
+    let canonical = canonicalize_url(url).unwrap_or_else(|| url.to_string());
+    format!("{:x}", Md5::digest(canonical.as_bytes()))
+}
+
 /// Normalize a website href link
 ///
 /// `base` is the page url, and `link` the string inside the `href` attribute of an `a` element.
@@ -44,23 +193,26 @@ mod tests {
 
     #[test]
     fn test_normalize_url() {
-        assert_eq!(normalize_url("google").is_none(), true);
-        assert_eq!(normalize_url("google.com").is_none(), true);
-        assert_eq!(normalize_url("/google.com").is_none(), true);
-        assert_eq!(normalize_url("//google.com").is_none(), true);
+        assert_eq!(normalize_url("google", true).is_none(), true);
+        assert_eq!(normalize_url("google.com", true).is_none(), true);
+        assert_eq!(normalize_url("/google.com", true).is_none(), true);
+        assert_eq!(normalize_url("//google.com", true).is_none(), true);
         assert_eq!(
-            normalize_url("https://google.com").unwrap().0.to_string(),
+            normalize_url("https://google.com", true)
+                .unwrap()
+                .0
+                .to_string(),
             "https://google.com/"
         );
         assert_eq!(
-            normalize_url("https://google.com/about#cc?a=0")
+            normalize_url("https://google.com/about#cc?a=0", true)
                 .unwrap()
                 .0
                 .to_string(),
             "https://google.com/about"
         );
         assert_eq!(
-            normalize_url("https://google.com/about?a")
+            normalize_url("https://google.com/about?a", true)
                 .unwrap()
                 .0
                 .to_string(),
@@ -68,6 +220,180 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_normalize_url_www() {
+        let normalize = |url, strip_www| {
+            normalize_url(url, strip_www).map(|(url, domain)| (url.to_string(), domain))
+        };
+
+        // Same domain with and without www., the URL keeps its host
+        assert_eq!(
+            normalize("https://www.google.com", true),
+            Some(("https://www.google.com/".into(), "google.com".into()))
+        );
+        assert_eq!(
+            normalize("https://google.com", true),
+            Some(("https://google.com/".into(), "google.com".into()))
+        );
+        assert_eq!(
+            normalize("https://WWW.Google.com/about", true),
+            Some(("https://www.google.com/about".into(), "google.com".into()))
+        );
+        assert_eq!(
+            normalize("https://www.google.com", false),
+            Some(("https://www.google.com/".into(), "www.google.com".into()))
+        );
+
+        // Only the www. label of the start is removed, once
+        assert_eq!(
+            normalize("https://www.www.example.com", true).unwrap().1,
+            "www.example.com"
+        );
+        assert_eq!(
+            normalize("https://www2.example.com", true).unwrap().1,
+            "www2.example.com"
+        );
+        assert_eq!(
+            normalize("https://wwwexample.com", true).unwrap().1,
+            "wwwexample.com"
+        );
+        assert_eq!(
+            normalize("https://sub.www.example.com", true).unwrap().1,
+            "sub.www.example.com"
+        );
+        assert_eq!(normalize("https://www.com", true).unwrap().1, "www.com");
+        assert_eq!(
+            normalize("http://www.localhost:8080/", true).unwrap().1,
+            "www.localhost"
+        );
+        assert_eq!(normalize("http://127.0.0.1/", true), None);
+    }
+
+    #[test]
+    fn test_get_www_alternate_url() {
+        assert_eq!(
+            get_www_alternate_url("https://www.example.com/about"),
+            Some("https://example.com/about".into())
+        );
+        assert_eq!(
+            get_www_alternate_url("https://example.com/about"),
+            Some("https://www.example.com/about".into())
+        );
+        assert_eq!(
+            get_www_alternate_url("http://blog.example.com:8080/"),
+            Some("http://www.blog.example.com:8080/".into())
+        );
+        assert_eq!(get_www_alternate_url("https://www.com/"), None);
+        assert_eq!(get_www_alternate_url("http://localhost/"), None);
+        assert_eq!(get_www_alternate_url("http://127.0.0.1/"), None);
+        assert_eq!(get_www_alternate_url("example.com"), None);
+    }
+
+    #[test]
+    fn test_extract_domain_parts() {
+        assert_eq!(
+            extract_domain_parts("blog.example.co.uk"),
+            Some(DomainParts {
+                subdomain: Some("blog".into()),
+                sld: "example".into(),
+                tld: "co.uk".into(),
+                registered_domain: "example.co.uk".into(),
+            })
+        );
+        assert_eq!(
+            extract_domain_parts("example.com"),
+            Some(DomainParts {
+                subdomain: None,
+                sld: "example".into(),
+                tld: "com".into(),
+                registered_domain: "example.com".into(),
+            })
+        );
+
+        let registered = |domain| get_registered_domain(domain);
+        assert_eq!(registered("www.example.com"), Some("example.com".into()));
+        assert_eq!(registered("a.b.Example.COM."), Some("example.com".into()));
+        assert_eq!(
+            extract_domain_parts("a.b.example.com").unwrap().subdomain,
+            Some("a.b".into())
+        );
+        // The private suffixes are separate websites
+        assert_eq!(registered("user.github.io"), Some("user.github.io".into()));
+        assert_eq!(registered("co.uk"), None);
+        assert_eq!(registered("com"), None);
+        assert_eq!(registered("localhost"), None);
+        assert_eq!(registered("127.0.0.1"), None);
+        assert_eq!(registered(""), None);
+    }
+
+    #[test]
+    fn test_canonicalize_url() {
+        assert_eq!(
+            canonicalize_url("HTTPS://Example.COM:443/About/?b=2&a=1#top"),
+            Some("https://example.com/About?a=1&b=2".into())
+        );
+        assert_eq!(
+            canonicalize_url("http://example.com:8080/"),
+            Some("http://example.com:8080".into())
+        );
+        assert_eq!(
+            canonicalize_url("https://example.com/caf%c3%a9/a%2Fb/%7euser"),
+            Some("https://example.com/caf%C3%A9/a%2Fb/~user".into())
+        );
+        assert_eq!(canonicalize_url("example.com"), None);
+    }
+
+    #[test]
+    fn test_url_fingerprint() {
+        let same_page = [
+            ("https://example.com/about", "https://example.com/about/"),
+            ("https://example.com/about", "https://EXAMPLE.com/about"),
+            ("https://example.com/about", "https://example.com:443/about"),
+            ("http://example.com/about", "http://example.com:80/about"),
+            (
+                "https://example.com/about",
+                "https://example.com/about#team",
+            ),
+            ("https://example.com/", "https://example.com"),
+            (
+                "https://example.com/?a=1&b=2",
+                "https://example.com/?b=2&a=1",
+            ),
+            ("https://example.com/a%20b", "https://example.com/a b"),
+            ("https://example.com/%7Euser", "https://example.com/~user"),
+            ("https://example.com/caf%C3%A9", "https://example.com/café"),
+            (
+                "https://example.com/caf%c3%a9",
+                "https://example.com/caf%C3%A9",
+            ),
+        ];
+        for (a, b) in same_page {
+            assert_eq!(url_fingerprint(a), url_fingerprint(b), "{a} and {b}");
+        }
+
+        let other_pages = [
+            ("https://example.com/about", "https://example.com/About"),
+            ("https://example.com/about", "http://example.com/about"),
+            (
+                "https://example.com/about",
+                "https://example.com:8443/about",
+            ),
+            ("https://example.com/about", "https://example.org/about"),
+            ("https://example.com/a%2Fb", "https://example.com/a/b"),
+            ("https://example.com/?a=1", "https://example.com/?a=2"),
+        ];
+        for (a, b) in other_pages {
+            assert_ne!(url_fingerprint(a), url_fingerprint(b), "{a} and {b}");
+        }
+
+        // Fits in the VARCHAR(32) column
+        assert_eq!(url_fingerprint("https://example.com/").len(), 32);
+        assert_eq!(
+            url_fingerprint("not a url"),
+            format!("{:x}", Md5::digest(b"not a url"))
+        );
+    }
+
     #[test]
     fn test_normalize_href() {
         assert_eq!(